@@ -0,0 +1,216 @@
+// グリーンスレッドとOSスレッド(std::thread)の、生成、メッセージのやり取り、メモリの使用量を比べるベンチマーク
+// NOTE: switch.rsと同じくcriterionは使わず、std::time::Instantで測る
+//       cargo bench --bench compare で実行する
+//       メモリの使用量は/proc/self/statmのRSSの増え方から求めるので、Linuxでしか測れない
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use greenthreads::sync::mpsc as green_mpsc;
+use greenthreads::sync::Barrier as GreenBarrier;
+use greenthreads::Runtime;
+
+// 生成してjoinする回数
+const SPAWNS: usize = 3_000;
+// ピンポンで往復する回数
+const ROUND_TRIPS: u64 = 100_000;
+// 何回か測って一番速かったものを使う
+const ROUNDS: usize = 5;
+
+struct Row {
+    name: &'static str,
+    unit: &'static str,
+    green: Option<f64>,
+    os: Option<f64>,
+}
+
+fn main() {
+    // 同時に動かすタスクの数は、Runtimeが同時に動かせるスレッドの数に合わせる
+    // NOTE: GREENTHREADS_MAX_THREADS=1000 cargo bench --bench compare のように環境変数で増やせる
+    let tasks = Runtime::new().stats().available;
+    // NOTE: 一度作ったスタックはプールやglibcに残って次に使い回されるので、メモリは他より先に測る
+    let memory = (green_memory(tasks), os_memory(tasks));
+    let rows = [
+        Row {
+            name: "spawn + join",
+            unit: "us/task",
+            green: Some(best(|| green_spawn(tasks))),
+            os: Some(best(|| os_spawn(tasks))),
+        },
+        Row {
+            name: "ping-pong message",
+            unit: "ns/message",
+            green: Some(best(green_ping_pong)),
+            os: Some(best(os_ping_pong)),
+        },
+        Row {
+            name: "memory",
+            unit: "KiB/task",
+            green: memory.0,
+            os: memory.1,
+        },
+    ];
+
+    println!("{} tasks at a time", tasks);
+    println!(
+        "{:<20} {:<12} {:>14} {:>14}",
+        "", "", "green threads", "std::thread"
+    );
+    for row in &rows {
+        println!(
+            "{:<20} {:<12} {:>14} {:>14}",
+            row.name,
+            row.unit,
+            format_value(row.green),
+            format_value(row.os)
+        );
+    }
+}
+
+fn best(f: impl Fn() -> f64) -> f64 {
+    (0..ROUNDS).map(|_| f()).fold(f64::INFINITY, f64::min)
+}
+
+fn format_value(value: Option<f64>) -> String {
+    match value {
+        Some(value) => format!("{:.2}", value),
+        None => "-".to_string(),
+    }
+}
+
+// tasks個ずつ生成してすべて終わるまで待つのを繰り返したときの、1つあたりの時間(マイクロ秒)
+// NOTE: ベーススレッドはjoinでブロックできないので、runで終わるのを待つ
+fn green_spawn(tasks: usize) -> f64 {
+    let mut runtime = Runtime::new();
+    let start = Instant::now();
+    for _ in 0..SPAWNS / tasks {
+        for i in 0..tasks {
+            runtime
+                .as_mut()
+                .spawn(move || {
+                    black_box(i);
+                })
+                .unwrap();
+        }
+        runtime.as_mut().run();
+    }
+    per_task(start.elapsed(), SPAWNS / tasks * tasks) / 1000.0
+}
+
+fn os_spawn(tasks: usize) -> f64 {
+    let start = Instant::now();
+    for _ in 0..SPAWNS / tasks {
+        let handles: Vec<_> = (0..tasks)
+            .map(|i| std::thread::spawn(move || black_box(i)))
+            .collect();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+    }
+    per_task(start.elapsed(), SPAWNS / tasks * tasks) / 1000.0
+}
+
+// 2つのタスクがチャネルで値を送り返し合うときの、1回送って受け取られるまでの時間(ナノ秒)
+fn green_ping_pong() -> f64 {
+    let mut runtime = Runtime::new();
+    let (ping_tx, ping_rx) = green_mpsc::channel();
+    let (pong_tx, pong_rx) = green_mpsc::channel();
+    runtime
+        .as_mut()
+        .spawn(move || {
+            for i in 0..ROUND_TRIPS {
+                ping_tx.send(i).unwrap();
+                pong_rx.recv().unwrap();
+            }
+        })
+        .unwrap();
+    runtime
+        .as_mut()
+        .spawn(move || {
+            while let Ok(i) = ping_rx.recv() {
+                pong_tx.send(i).unwrap();
+            }
+        })
+        .unwrap();
+    let start = Instant::now();
+    runtime.as_mut().run();
+    per_task(start.elapsed(), ROUND_TRIPS as usize * 2)
+}
+
+fn os_ping_pong() -> f64 {
+    let (ping_tx, ping_rx) = std::sync::mpsc::channel();
+    let (pong_tx, pong_rx) = std::sync::mpsc::channel();
+    let start = Instant::now();
+    let echo = std::thread::spawn(move || {
+        while let Ok(i) = ping_rx.recv() {
+            pong_tx.send(i).unwrap();
+        }
+    });
+    for i in 0..ROUND_TRIPS {
+        ping_tx.send(i).unwrap();
+        pong_rx.recv().unwrap();
+    }
+    drop(ping_tx);
+    echo.join().unwrap();
+    per_task(start.elapsed(), ROUND_TRIPS as usize * 2)
+}
+
+// tasks個のタスクが同時に生きているときの、1つあたりのRSSの増え方(KiB)
+// NOTE: すべてのタスクがバリアに着いたところで、最後に着いたタスクが測る
+fn green_memory(tasks: usize) -> Option<f64> {
+    let before = rss()?;
+    let mut runtime = Runtime::new();
+    let barrier = std::rc::Rc::new(GreenBarrier::new(tasks));
+    let grown = std::rc::Rc::new(std::cell::Cell::new(0));
+    for _ in 0..tasks {
+        let (barrier, grown) = (barrier.clone(), grown.clone());
+        runtime
+            .as_mut()
+            .spawn(move || {
+                if barrier.wait().is_leader() {
+                    grown.set(rss().unwrap_or(before).saturating_sub(before));
+                }
+            })
+            .unwrap();
+    }
+    runtime.as_mut().run();
+    Some(grown.get() as f64 / tasks as f64 / 1024.0)
+}
+
+fn os_memory(tasks: usize) -> Option<f64> {
+    let before = rss()?;
+    let barrier = std::sync::Arc::new(std::sync::Barrier::new(tasks));
+    let handles: Vec<_> = (0..tasks)
+        .map(|_| {
+            let barrier = barrier.clone();
+            std::thread::spawn(move || {
+                barrier
+                    .wait()
+                    .is_leader()
+                    .then(|| rss().unwrap_or(before).saturating_sub(before))
+            })
+        })
+        .collect();
+    let grown: usize = handles
+        .into_iter()
+        .filter_map(|handle| handle.join().unwrap())
+        .sum();
+    Some(grown as f64 / tasks as f64 / 1024.0)
+}
+
+fn per_task(elapsed: Duration, count: usize) -> f64 {
+    elapsed.as_nanos() as f64 / count as f64
+}
+
+// プロセスのRSS(バイト)
+#[cfg(target_os = "linux")]
+fn rss() -> Option<usize> {
+    let statm = std::fs::read_to_string("/proc/self/statm").ok()?;
+    let pages: usize = statm.split_whitespace().nth(1)?.parse().ok()?;
+    // NOTE: x86のLinuxのページの大きさは4KiB
+    Some(pages * 4096)
+}
+
+#[cfg(not(target_os = "linux"))]
+fn rss() -> Option<usize> {
+    None
+}
@@ -0,0 +1,62 @@
+// スレッドの切り替え1回あたりの時間を測るベンチマーク
+// NOTE: 依存を増やさないようにcriterionは使わず、std::time::Instantで測る
+//       cargo bench --bench switch で実行する
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use greenthreads::{yield_thread, Coroutine, CoroutineState, Runtime};
+
+const ITERATIONS: u64 = 1_000_000;
+// 何回か測って一番速かったものを使う
+const ROUNDS: usize = 5;
+
+fn main() {
+    report("coroutine resume/yield", bench_coroutine);
+    report("runtime yield_thread", bench_runtime);
+}
+
+fn report(name: &str, f: fn() -> (Duration, u64)) {
+    let best = (0..ROUNDS)
+        .map(|_| {
+            let (elapsed, switches) = f();
+            elapsed.as_nanos() as f64 / switches as f64
+        })
+        .fold(f64::INFINITY, f64::min);
+    println!("{:<24} {:>8.2} ns/switch", name, best);
+}
+
+// スケジューラを通さない、切り替えそのものの時間
+// NOTE: resumeとyield_withでそれぞれ1回ずつ切り替わる
+fn bench_coroutine() -> (Duration, u64) {
+    let mut co = Coroutine::new(|y| loop {
+        y.yield_with(());
+    })
+    .unwrap();
+    let start = Instant::now();
+    for _ in 0..ITERATIONS {
+        match black_box(co.resume()) {
+            CoroutineState::Yielded(()) => {}
+            CoroutineState::Complete(()) => unreachable!(),
+        }
+    }
+    (start.elapsed(), ITERATIONS * 2)
+}
+
+// 2つのスレッドがyield_threadで交互に切り替わるときの、スケジューラも含めた時間
+fn bench_runtime() -> (Duration, u64) {
+    let mut runtime = Runtime::new();
+    runtime.as_mut().init();
+    for _ in 0..2 {
+        runtime
+            .as_mut()
+            .spawn(|| {
+                for _ in 0..ITERATIONS / 2 {
+                    yield_thread();
+                }
+            })
+            .unwrap();
+    }
+    let start = Instant::now();
+    runtime.as_mut().run();
+    (start.elapsed(), ITERATIONS)
+}
@@ -0,0 +1,81 @@
+use std::os::raw::{c_char, c_int};
+
+use greenthreads::{current, debug_verify_stack, yield_thread, Runtime, ThreadId};
+
+extern "C" {
+    fn snprintf(buf: *mut c_char, len: usize, fmt: *const c_char, ...) -> c_int;
+}
+
+// 16byte境界に揃える必要がある値
+#[repr(align(16))]
+struct Aligned([f64; 2]);
+
+// 可変長引数にdoubleを渡すと、glibcのsnprintfはxmmレジスタをmovapsでスタックに退避するので、
+// スタックポインタが16byte境界からずれていると落ちる
+fn format_float(value: f64) -> String {
+    let mut buf = [0u8; 32];
+    let len = unsafe {
+        snprintf(
+            buf.as_mut_ptr() as *mut c_char,
+            buf.len(),
+            c"%.3f".as_ptr(),
+            value,
+        )
+    };
+    String::from_utf8_lossy(&buf[..len as usize]).into_owned()
+}
+
+// スタック上の値が16byte境界に揃っているかを確かめる
+// NOTE: コンパイラはスタックポインタが揃っている前提で配置するので、ずれていれば値もずれる
+#[inline(never)]
+extern "C" fn aligned_local(value: f64) -> bool {
+    let local = std::hint::black_box(Aligned([value, value]));
+    (&local as *const Aligned as usize).is_multiple_of(16) && local.0[1] == value
+}
+
+fn main() {
+    // スレッドを作るたびにスタックの形を確かめる
+    let mut runtime = Runtime::builder().strict_abi(true).build();
+
+    let mut ids: Vec<ThreadId> = Vec::new();
+    for i in 0..2 {
+        let handle = runtime
+            .as_mut()
+            .spawn(move || {
+                let value = i as f64 * 1.5;
+                println!(
+                    "thread {}: snprintf={} aligned={}",
+                    i,
+                    format_float(value),
+                    aligned_local(value)
+                );
+                yield_thread();
+                // 実行中のスレッドは、今のスタックポインタがスタックの中にあるかを確かめる
+                let me = current().thread_id();
+                println!(
+                    "thread {}: verified itself: {:?}",
+                    i,
+                    debug_verify_stack(me)
+                );
+            })
+            .unwrap();
+        ids.push(handle.thread().thread_id());
+    }
+
+    // yieldして止まっているスレッドと、終わったスレッドのスタックを確かめる
+    // NOTE: まだ始まっていないスレッドのスタックは、strict_abiで作ったときに確かめている
+    runtime
+        .as_mut()
+        .spawn(move || {
+            for id in &ids {
+                println!("checker: thread {} -> {:?}", id, debug_verify_stack(*id));
+            }
+            yield_thread();
+            for id in &ids {
+                println!("checker: thread {} -> {:?}", id, debug_verify_stack(*id));
+            }
+        })
+        .unwrap();
+
+    runtime.as_mut().run();
+}
@@ -0,0 +1,86 @@
+use greenthreads::actor::Actor;
+use greenthreads::sync::oneshot;
+use greenthreads::{yield_thread, Runtime};
+
+// 数を数えるアクター
+struct Counter {
+    count: u64,
+}
+
+enum CounterMessage {
+    Add(u64),
+    Get(oneshot::Sender<u64>),
+}
+
+impl Actor for Counter {
+    type Message = CounterMessage;
+
+    fn handle(&mut self, msg: CounterMessage) {
+        match msg {
+            CounterMessage::Add(n) => self.count += n,
+            CounterMessage::Get(reply) => {
+                let _ = reply.send(self.count);
+            }
+        }
+    }
+}
+
+// 受け取った行を1つずつゆっくり表示するアクター
+struct Logger;
+
+impl Actor for Logger {
+    type Message = String;
+
+    fn started(&mut self) {
+        println!("logger: started");
+    }
+
+    fn handle(&mut self, line: String) {
+        println!("logger: {}", line);
+        // 処理が遅いアクターの代わり
+        yield_thread();
+        yield_thread();
+    }
+
+    fn stopped(&mut self) {
+        println!("logger: stopped");
+    }
+}
+
+fn main() {
+    let mut runtime = Runtime::new();
+    runtime.as_mut().init();
+
+    let (counter, counter_handle) = runtime.as_mut().spawn_actor(Counter { count: 0 }).unwrap();
+    // メールボックスが小さいので、送る側はloggerが処理するのを待ちながら送ることになる
+    let (logger, logger_handle) = runtime
+        .as_mut()
+        .spawn_actor_with_capacity(2, Logger)
+        .unwrap();
+
+    let client = {
+        let (counter, logger) = (counter.clone(), logger.clone());
+        runtime
+            .as_mut()
+            .spawn(move || {
+                for i in 1..=5 {
+                    counter.send(CounterMessage::Add(i)).unwrap();
+                    logger.send(format!("client: added {}", i)).unwrap();
+                    println!("client: sent {}", i);
+                }
+            })
+            .unwrap()
+    };
+    client.join().unwrap();
+
+    // 返事が届くまで待つ
+    let count = counter.call(CounterMessage::Get).unwrap();
+    logger.send(format!("count = {}", count)).unwrap();
+
+    // アドレスをすべてドロップするとアクターは止まる
+    drop(counter);
+    drop(logger);
+    logger_handle.join().unwrap();
+    let counter = counter_handle.join().unwrap();
+    println!("final count: {}", counter.count);
+}
@@ -0,0 +1,42 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use greenthreads::multi::MultiRuntime;
+use greenthreads::yield_thread;
+
+extern "C" {
+    fn sched_getcpu() -> i32;
+}
+
+fn main() {
+    let cores = thread::available_parallelism()
+        .map_or(1, |n| n.get())
+        .min(2);
+    // ワーカーをそれぞれ別のCPUに固定する
+    let mut runtime = MultiRuntime::new(cores).pin_workers((0..cores).collect());
+
+    let seen = Arc::new(Mutex::new(Vec::new()));
+    let done = Arc::new(AtomicUsize::new(0));
+    for worker in 0..cores {
+        for task in 0..2 {
+            let (seen, done) = (seen.clone(), done.clone());
+            // 固定したタスクは盗まれず、必ずworkerのワーカー(=そのCPU)で動く
+            runtime.spawn_on(worker, move || {
+                for _ in 0..3 {
+                    let cpu = unsafe { sched_getcpu() };
+                    seen.lock().unwrap().push((worker, task, cpu));
+                    yield_thread();
+                }
+                done.fetch_add(1, Ordering::SeqCst);
+            });
+        }
+    }
+    runtime.run();
+
+    for (worker, task, cpu) in seen.lock().unwrap().iter() {
+        println!("worker: {} task: {} cpu: {}", worker, task, cpu);
+        assert_eq!(*worker as i32, *cpu);
+    }
+    println!("done: {}", done.load(Ordering::SeqCst));
+}
@@ -0,0 +1,50 @@
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use greenthreads::Runtime;
+
+// 一度だけPendingを返して他のスレッドに実行を譲るFuture
+struct YieldNow {
+    yielded: bool,
+}
+
+impl Future for YieldNow {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        if self.yielded {
+            return Poll::Ready(());
+        }
+        self.yielded = true;
+        // すぐに起こしてもらうので、他のスレッドを一周した後にもう一度pollされる
+        cx.waker().wake_by_ref();
+        Poll::Pending
+    }
+}
+
+fn yield_now() -> YieldNow {
+    YieldNow { yielded: false }
+}
+
+async fn count(id: usize, n: usize) -> usize {
+    for i in 0..n {
+        println!("task: {} counter: {}", id, i);
+        yield_now().await;
+    }
+    id * n
+}
+
+fn main() {
+    let mut runtime = Runtime::new();
+    runtime.as_mut().init();
+
+    let task = runtime.as_mut().spawn_async(count(1, 3)).unwrap();
+    let result = runtime.as_mut().block_on(async {
+        let result = count(2, 5).await;
+        println!("task: 2 finished");
+        result
+    });
+    println!("block_on: {}", result);
+    println!("spawn_async: {:?}", task.join().unwrap());
+}
@@ -0,0 +1,20 @@
+use std::backtrace::Backtrace;
+
+use greenthreads::Runtime;
+
+#[inline(never)]
+fn inner() {
+    // スレッドのスタックで取ったバックトレースは、タスク関数を呼び出したところで終わる
+    println!("{}", Backtrace::force_capture());
+}
+
+#[inline(never)]
+fn task() {
+    inner();
+}
+
+fn main() {
+    let mut runtime = Runtime::new();
+    runtime.as_mut().spawn(task).unwrap();
+    runtime.as_mut().run();
+}
@@ -0,0 +1,43 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use greenthreads::bare::{self, Executor};
+
+// カーネルではアロケータから確保したメモリや、静的な配列をスタックに使う
+fn stack() -> Box<[u8]> {
+    vec![0u8; 64 * 1024].into_boxed_slice()
+}
+
+fn main() {
+    let log = Rc::new(RefCell::new(Vec::new()));
+    let mut executor = Executor::new();
+
+    let l = log.clone();
+    let waiter = executor.spawn(stack(), move || {
+        l.borrow_mut().push("waiter: parking".to_string());
+        bare::park();
+        l.borrow_mut().push("waiter: unparked".to_string());
+    });
+
+    for name in ["a", "b"] {
+        let l = log.clone();
+        executor.spawn(stack(), move || {
+            for i in 0..3 {
+                l.borrow_mut().push(format!("{}: {}", name, i));
+                bare::yield_thread();
+            }
+        });
+    }
+
+    // 再開可能なスレッドがなくなると戻る
+    executor.run();
+    log.borrow_mut().push("run returned".to_string());
+
+    // 割り込みなどで起こしたことにして、続きを実行する
+    executor.unpark(waiter);
+    executor.run();
+
+    for line in log.borrow().iter() {
+        println!("{}", line);
+    }
+}
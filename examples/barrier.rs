@@ -0,0 +1,31 @@
+use std::rc::Rc;
+use std::time::Duration;
+
+use greenthreads::sync::Barrier;
+use greenthreads::{current, sleep, Runtime};
+
+fn main() {
+    let mut runtime = Runtime::new();
+    runtime.as_mut().init();
+
+    // 3つのスレッドが各フェーズの終わりで待ち合わせる
+    let barrier = Rc::new(Barrier::new(3));
+    for _ in 0..3 {
+        let barrier = barrier.clone();
+        runtime
+            .as_mut()
+            .spawn(move || {
+                let id = current().id();
+                for phase in 0..2 {
+                    sleep(Duration::from_millis(20 * id as u64)).unwrap();
+                    println!("thread: {} finished phase {}", id, phase);
+                    if barrier.wait().is_leader() {
+                        println!("--- phase {} done ---", phase);
+                    }
+                }
+            })
+            .unwrap();
+    }
+
+    runtime.as_mut().run();
+}
@@ -0,0 +1,49 @@
+use std::time::{Duration, Instant};
+
+use greenthreads::sync::mpsc;
+use greenthreads::{consume_budget, Runtime};
+
+fn main() {
+    // チャネルやロックなどを切り替えずに16回使うか、5ms続けて実行したら他のスレッドに切り替える
+    let mut runtime = Runtime::builder()
+        .budget(16)
+        .time_budget(Duration::from_millis(5))
+        .build();
+    runtime.as_mut().init();
+
+    // 容量のないチャネルは送信がブロックしないので、予算がなければ送り終わるまで他のスレッドは動かない
+    let (tx, rx) = mpsc::channel();
+    runtime
+        .as_mut()
+        .spawn(move || {
+            for i in 0..64 {
+                tx.send(i).unwrap();
+            }
+            println!("producer: done");
+        })
+        .unwrap();
+
+    runtime
+        .as_mut()
+        .spawn(move || {
+            let mut received = 0;
+            while rx.recv().is_ok() {
+                received += 1;
+            }
+            println!("consumer: received {}", received);
+        })
+        .unwrap();
+
+    // 予算を使う操作をしないまま長く実行すると、次に切り替えたときに警告が出る
+    runtime
+        .as_mut()
+        .spawn(|| {
+            let begin = Instant::now();
+            while begin.elapsed() < Duration::from_millis(20) {}
+            consume_budget();
+            println!("hog: done");
+        })
+        .unwrap();
+
+    runtime.as_mut().run();
+}
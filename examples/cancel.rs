@@ -0,0 +1,52 @@
+use std::time::{Duration, Instant};
+
+use greenthreads::sync::mpsc::{self, RecvError};
+use greenthreads::{cancellation_token, sleep, Runtime};
+
+fn main() {
+    let mut runtime = Runtime::new();
+    let start = Instant::now();
+
+    // キャンセルされるまでスリープを繰り返すスレッド
+    let sleeper = runtime
+        .as_mut()
+        .spawn(move || {
+            let mut count = 0;
+            while sleep(Duration::from_secs(1)).is_ok() {
+                count += 1;
+            }
+            println!("sleeper: cancelled after {:?}", start.elapsed());
+            count
+        })
+        .unwrap();
+
+    // 値が届かないチャネルで受信を待つスレッド
+    let (tx, rx) = mpsc::channel::<i32>();
+    let receiver = runtime
+        .as_mut()
+        .spawn(move || {
+            let result = rx.recv();
+            println!(
+                "receiver: {:?} cancelled: {} after {:?}",
+                result,
+                cancellation_token().is_cancelled(),
+                start.elapsed()
+            );
+            drop(tx);
+            result
+        })
+        .unwrap();
+
+    runtime
+        .as_mut()
+        .spawn(move || {
+            sleep(Duration::from_millis(100)).unwrap();
+            sleeper.cancel();
+            receiver.cancel();
+            println!("sleeper slept {} times", sleeper.join().unwrap());
+            assert_eq!(receiver.join().unwrap(), Err(RecvError::Cancelled));
+        })
+        .unwrap();
+
+    runtime.as_mut().run();
+}
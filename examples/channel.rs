@@ -0,0 +1,38 @@
+use greenthreads::sync::mpsc;
+use greenthreads::Runtime;
+
+fn main() {
+    let mut runtime = Runtime::new();
+    runtime.as_mut().init();
+
+    // 2つまでしか溜められないチャネル
+    let (tx, rx) = mpsc::sync_channel(2);
+
+    for id in 1..=2 {
+        let tx = tx.clone();
+        runtime
+            .as_mut()
+            .spawn(move || {
+                for i in 0..5 {
+                    // 満杯の場合は受信側が取り出すまでブロックする
+                    tx.send((id, i)).unwrap();
+                    println!("producer {} sent: {}", id, i);
+                }
+            })
+            .unwrap();
+    }
+    // 送信側がすべてドロップされると受信側のイテレータが終了する
+    drop(tx);
+
+    runtime
+        .as_mut()
+        .spawn(move || {
+            for (id, i) in &rx {
+                println!("consumer received: {} from producer {}", i, id);
+            }
+            println!("all producers finished");
+        })
+        .unwrap();
+
+    runtime.as_mut().run();
+}
@@ -0,0 +1,43 @@
+use std::time::Instant;
+
+use greenthreads::{checkpoint, current, Runtime};
+
+// nまでの素数を数える、CPUだけを使う重い計算
+fn count_primes(n: u64) -> usize {
+    (2..n)
+        .filter(|&i| {
+            // 時間を使いすぎていれば他のスレッドに切り替える
+            // NOTE: ほとんどの場合は時刻を比べるだけで戻るので、ループのたびに呼んでよい
+            checkpoint!();
+            (2..).take_while(|d| d * d <= i).all(|d| i % d != 0)
+        })
+        .count()
+}
+
+fn main() {
+    let mut runtime = Runtime::new();
+    runtime.as_mut().init();
+
+    let start = Instant::now();
+    for n in [200_000, 300_000, 400_000] {
+        runtime
+            .as_mut()
+            .spawn(move || {
+                let id = current().id();
+                println!("thread {}: started at {:?}", id, start.elapsed());
+                let primes = count_primes(n);
+                println!(
+                    "thread {}: {} primes below {} at {:?}",
+                    id,
+                    primes,
+                    n,
+                    start.elapsed()
+                );
+            })
+            .unwrap();
+    }
+
+    runtime.as_mut().run();
+    // 3つのスレッドが交互に進むので、切り替えが何度も起きている
+    println!("switches: {}", runtime.stats().switches);
+}
@@ -0,0 +1,126 @@
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::future::{poll_fn, Future};
+use std::pin::pin;
+use std::rc::Rc;
+use std::task::{Context, Poll, Waker};
+
+use greenthreads::{spawner, yield_thread, Runtime, Spawner, ThreadWaker};
+
+// グリーンスレッドのことを知らない、Wakerだけを使う非同期のチャネル(既存のライブラリの代わり)
+struct Shared<T> {
+    queue: VecDeque<T>,
+    closed: bool,
+    waker: Option<Waker>,
+}
+
+struct Sender<T>(Rc<RefCell<Shared<T>>>);
+struct Receiver<T>(Rc<RefCell<Shared<T>>>);
+
+fn channel<T>() -> (Sender<T>, Receiver<T>) {
+    let shared = Rc::new(RefCell::new(Shared {
+        queue: VecDeque::new(),
+        closed: false,
+        waker: None,
+    }));
+    (Sender(shared.clone()), Receiver(shared))
+}
+
+impl<T> Sender<T> {
+    fn send(&self, t: T) {
+        let mut shared = self.0.borrow_mut();
+        shared.queue.push_back(t);
+        if let Some(waker) = shared.waker.take() {
+            waker.wake();
+        }
+    }
+}
+
+impl<T> Drop for Sender<T> {
+    fn drop(&mut self) {
+        let mut shared = self.0.borrow_mut();
+        shared.closed = true;
+        if let Some(waker) = shared.waker.take() {
+            waker.wake();
+        }
+    }
+}
+
+impl<T> Receiver<T> {
+    async fn recv(&self) -> Option<T> {
+        poll_fn(|cx| {
+            let mut shared = self.0.borrow_mut();
+            match shared.queue.pop_front() {
+                Some(t) => Poll::Ready(Some(t)),
+                None if shared.closed => Poll::Ready(None),
+                None => {
+                    shared.waker = Some(cx.waker().clone());
+                    Poll::Pending
+                }
+            }
+        })
+        .await
+    }
+}
+
+// Executorを受け取ってタスクを生成するライブラリの関数
+fn start_printer(spawner: &Spawner, rx: Receiver<String>) {
+    spawner
+        .spawn(async move {
+            while let Some(line) = rx.recv().await {
+                println!("printer: {}", line);
+            }
+            println!("printer: closed");
+        })
+        .unwrap();
+}
+
+fn main() {
+    let mut runtime = Runtime::new();
+    runtime.as_mut().init();
+
+    let (tx, rx) = channel();
+    start_printer(&spawner(), rx);
+
+    // asyncではない普通のスレッドから、Wakerを使うチャネルに送る
+    runtime
+        .as_mut()
+        .spawn(move || {
+            for i in 0..3 {
+                tx.send(format!("message {}", i));
+                yield_thread();
+            }
+        })
+        .unwrap();
+
+    // ThreadWakerを使って、普通のスレッドの中でFutureを自分でpollする
+    let (tx, rx) = channel();
+    runtime
+        .as_mut()
+        .spawn(move || {
+            let waker = ThreadWaker::current();
+            let std_waker = Waker::from(waker.clone());
+            let mut cx = Context::from_waker(&std_waker);
+            let mut recv = pin!(rx.recv());
+            loop {
+                match recv.as_mut().poll(&mut cx) {
+                    Poll::Ready(value) => {
+                        println!("poller: received {:?}", value);
+                        break;
+                    }
+                    // 送られるまでこのスレッドをブロックする
+                    Poll::Pending => waker.wait(),
+                }
+            }
+        })
+        .unwrap();
+    runtime
+        .as_mut()
+        .spawn(move || {
+            yield_thread();
+            tx.send(42);
+        })
+        .unwrap();
+
+    runtime.as_mut().run();
+}
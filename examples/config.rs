@@ -0,0 +1,40 @@
+use greenthreads::{current, yield_thread, Runtime};
+
+// スレッドの数とスタックの大きさを実行時に決める
+// NOTE: GREENTHREADS_MAX_THREADS=8 GREENTHREADS_STACK_SIZE=64K cargo run --example config のように
+//       環境変数でも指定できる(Builderで指定した値が優先される)
+fn main() {
+    let mut runtime = Runtime::builder()
+        .max_threads(8)
+        .stack_size(64 * 1024)
+        .build();
+    println!("available: {}", runtime.stats().available);
+    for _ in 0..8 {
+        runtime
+            .as_mut()
+            .spawn(|| {
+                yield_thread();
+                println!("thread {} done", current().id());
+            })
+            .unwrap();
+    }
+    runtime.as_mut().run();
+
+    // 環境変数だけで決める
+    let runtime = Runtime::new();
+    println!(
+        "from the environment: {} threads",
+        runtime.stats().available
+    );
+
+    // 正しくない値はエラーになる
+    for builder in [
+        Runtime::builder().max_threads(0),
+        Runtime::builder().stack_size(100),
+    ] {
+        match builder.try_build() {
+            Ok(_) => println!("built"),
+            Err(e) => println!("error: {}", e),
+        }
+    }
+}
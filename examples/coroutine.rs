@@ -0,0 +1,30 @@
+use greenthreads::{Coroutine, CoroutineState};
+
+fn main() {
+    // フィボナッチ数列を順に返すジェネレータ
+    let mut fib = Coroutine::new(|y| {
+        let (mut a, mut b) = (0u64, 1u64);
+        for _ in 0..10 {
+            y.yield_with(a);
+            (a, b) = (b, a + b);
+        }
+        "done"
+    })
+    .unwrap();
+
+    loop {
+        match fib.resume() {
+            CoroutineState::Yielded(n) => println!("yielded: {}", n),
+            CoroutineState::Complete(r) => {
+                println!("complete: {}", r);
+                break;
+            }
+        }
+    }
+    assert!(fib.is_finished());
+
+    // コルーチンの中のパニックはresumeを呼んだ側に伝わる
+    let mut co: Coroutine<(), ()> = Coroutine::new(|_| panic!("boom")).unwrap();
+    let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| co.resume()));
+    println!("panicked: {}", result.is_err());
+}
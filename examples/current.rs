@@ -0,0 +1,40 @@
+use greenthreads::{current, park, yield_thread, Runtime};
+
+fn main() {
+    let mut runtime = Runtime::new();
+    runtime.as_mut().init();
+
+    let worker = runtime
+        .as_mut()
+        .spawn_named("worker", || {
+            let me = current();
+            println!("id: {} name: {:?}", me.id(), me.name());
+            // unparkされるまで止まる
+            park();
+            println!("id: {} unparked", me.id());
+        })
+        .unwrap();
+    let thread = worker.thread().clone();
+
+    runtime
+        .as_mut()
+        .spawn(move || {
+            let me = current();
+            println!(
+                "id: {} name: {:?} state: {:?}",
+                me.id(),
+                me.name(),
+                me.state()
+            );
+            yield_thread();
+            println!("worker state: {:?}", thread.state());
+            thread.unpark().unwrap();
+            yield_thread();
+            yield_thread();
+            println!("worker state: {:?}", thread.state());
+        })
+        .unwrap();
+
+    println!("name: {:?}", current().name());
+    runtime.as_mut().run();
+}
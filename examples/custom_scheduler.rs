@@ -0,0 +1,36 @@
+use greenthreads::scheduler::Scheduler;
+use greenthreads::{yield_thread, Runtime};
+
+// 最後に再開可能になったスレッドから実行するスケジューラ
+struct LifoScheduler {
+    stack: Vec<usize>,
+}
+
+impl Scheduler for LifoScheduler {
+    fn ready(&mut self, thread_id: usize) {
+        self.stack.push(thread_id);
+    }
+
+    fn pick_next(&mut self) -> Option<usize> {
+        self.stack.pop()
+    }
+}
+
+fn main() {
+    let mut runtime = Runtime::with_custom_scheduler(Box::new(LifoScheduler { stack: Vec::new() }));
+    runtime.as_mut().init();
+
+    for id in 1..=3 {
+        runtime
+            .as_mut()
+            .spawn(move || {
+                for i in 0..3 {
+                    println!("thread: {} counter: {}", id, i);
+                    yield_thread();
+                }
+            })
+            .unwrap();
+    }
+
+    runtime.as_mut().run();
+}
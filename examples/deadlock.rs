@@ -0,0 +1,44 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use greenthreads::sync::Mutex;
+use greenthreads::{yield_thread, Runtime};
+
+// 2つのスレッドが逆の順番でロックを取ってデッドロックする
+// NOTE: runは黙って終わらず、どのスレッドが何を待って止まっているかを報告してから戻る
+fn main() {
+    let found = Rc::new(RefCell::new(None));
+    let slot = found.clone();
+    let mut runtime = Runtime::builder()
+        .on_deadlock(move |deadlock| *slot.borrow_mut() = Some(deadlock.clone()))
+        .build();
+
+    let a = Rc::new(Mutex::new(0));
+    let b = Rc::new(Mutex::new(0));
+
+    let (a1, b1) = (a.clone(), b.clone());
+    runtime
+        .as_mut()
+        .spawn_named("first", move || {
+            let _a = a1.lock();
+            yield_thread();
+            let _b = b1.lock();
+        })
+        .unwrap();
+
+    runtime
+        .as_mut()
+        .spawn_named("second", move || {
+            let _b = b.lock();
+            yield_thread();
+            let _a = a.lock();
+        })
+        .unwrap();
+
+    runtime.as_mut().run();
+    let deadlock = found.borrow_mut().take();
+    if let Some(deadlock) = deadlock {
+        eprint!("{}", deadlock);
+    }
+    // 止まったままのスレッドは、Runtimeをドロップしたときに巻き戻して終わらせる
+}
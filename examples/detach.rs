@@ -0,0 +1,44 @@
+use std::cell::Cell;
+use std::rc::Rc;
+
+use greenthreads::{current, yield_thread, Runtime};
+
+// ドロップされたときに、どのスレッドでドロップされたかを表示する
+struct Noisy(usize);
+
+impl Drop for Noisy {
+    fn drop(&mut self) {
+        println!(
+            "value from thread {} dropped on thread {}",
+            self.0,
+            current().id()
+        );
+    }
+}
+
+fn main() {
+    let mut runtime = Runtime::new();
+    runtime.as_mut().init();
+
+    // 切り離したスレッドの戻り値は、スレッドが終わったときにそのスレッドでドロップされる
+    runtime
+        .as_mut()
+        .spawn(|| {
+            yield_thread();
+            Noisy(current().id())
+        })
+        .unwrap()
+        .detach();
+
+    // JoinHandleをドロップした場合も切り離される
+    let finished = Rc::new(Cell::new(0));
+    for _ in 0..1000 {
+        let finished = finished.clone();
+        let _ = runtime.as_mut().spawn(move || {
+            yield_thread();
+            finished.set(finished.get() + 1);
+        });
+    }
+    runtime.as_mut().run();
+    println!("finished: {}", finished.get());
+}
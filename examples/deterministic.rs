@@ -0,0 +1,42 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use greenthreads::{current, yield_thread, PinnedRuntime, Runtime};
+
+// 3つのスレッドがyieldしながら共有のログに書き込み、実行された順番を返す
+fn run(mut runtime: PinnedRuntime) -> (Vec<usize>, Vec<usize>) {
+    runtime.as_mut().init();
+    let log = Rc::new(RefCell::new(Vec::new()));
+    for _ in 0..3 {
+        let log = log.clone();
+        runtime
+            .as_mut()
+            .spawn(move || {
+                for _ in 0..3 {
+                    log.borrow_mut().push(current().id());
+                    yield_thread();
+                }
+            })
+            .unwrap();
+    }
+    runtime.as_mut().run();
+    let log = log.borrow().clone();
+    (log, runtime.schedule().unwrap())
+}
+
+fn main() {
+    // 同じシードなら毎回同じ順番になる
+    let (first, schedule) = run(Runtime::deterministic(42));
+    let (second, _) = run(Runtime::deterministic(42));
+    println!("seed 42: {:?}", first);
+    assert_eq!(first, second);
+
+    let (other, _) = run(Runtime::deterministic(7));
+    println!("seed 7:  {:?}", other);
+
+    // 記録した順番をなぞれば、シードが分からなくても同じ順番で実行し直せる
+    let (replayed, _) = run(Runtime::replay(schedule.clone()));
+    println!("replay:  {:?}", replayed);
+    assert_eq!(first, replayed);
+    println!("schedule: {:?}", schedule);
+}
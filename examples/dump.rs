@@ -0,0 +1,48 @@
+use std::os::raw::c_int;
+use std::rc::Rc;
+use std::time::Duration;
+
+use greenthreads::sync::Mutex;
+use greenthreads::{sleep, Runtime};
+
+extern "C" {
+    fn raise(sig: c_int) -> c_int;
+}
+
+const SIGUSR1: c_int = if cfg!(target_os = "linux") { 10 } else { 30 };
+
+fn main() {
+    // 実行中に別の端末から kill -USR1 <pid> や Ctrl+\ を送っても同じように表示される
+    let mut runtime = Runtime::builder()
+        .dump_on_signal(true)
+        .poison_stacks(true)
+        .build();
+    runtime.as_mut().init();
+
+    let mutex = Rc::new(Mutex::new(0));
+    let holder = mutex.clone();
+    runtime
+        .as_mut()
+        .spawn_named("holder", move || {
+            let _lock = holder.lock();
+            sleep(Duration::from_millis(100)).unwrap();
+        })
+        .unwrap();
+    runtime
+        .as_mut()
+        .spawn_named("waiter", move || {
+            *mutex.lock() += 1;
+        })
+        .unwrap();
+    runtime
+        .as_mut()
+        .spawn(|| {
+            sleep(Duration::from_millis(10)).unwrap();
+            // 自分にシグナルを送ると、次の切り替えでスレッドの状態が書き出される
+            unsafe { raise(SIGUSR1) };
+            sleep(Duration::from_millis(10)).unwrap();
+        })
+        .unwrap();
+
+    runtime.as_mut().run();
+}
@@ -0,0 +1,50 @@
+use std::io::{Read, Write};
+use std::net::Shutdown;
+
+use greenthreads::net::{TcpListener, TcpStream};
+use greenthreads::Runtime;
+
+fn main() {
+    let mut runtime = Runtime::new();
+    runtime.as_mut().init();
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    runtime
+        .as_mut()
+        .spawn(move || {
+            // 接続を待っている間やデータを待っている間は他のスレッドが実行される
+            for _ in 0..2 {
+                let (mut stream, peer) = listener.accept().unwrap();
+                println!("server: accepted {}", peer);
+                let mut buf = [0_u8; 1024];
+                loop {
+                    let n = stream.read(&mut buf).unwrap();
+                    if n == 0 {
+                        break;
+                    }
+                    stream.write_all(&buf[..n]).unwrap();
+                }
+            }
+        })
+        .unwrap();
+
+    for id in 1..=2 {
+        runtime
+            .as_mut()
+            .spawn(move || {
+                let mut stream = TcpStream::connect(addr).unwrap();
+                let msg = format!("hello from thread {}", id);
+                stream.write_all(msg.as_bytes()).unwrap();
+                stream.shutdown(Shutdown::Write).unwrap();
+
+                let mut reply = String::new();
+                stream.read_to_string(&mut reply).unwrap();
+                println!("client {}: {}", id, reply);
+            })
+            .unwrap();
+    }
+
+    runtime.as_mut().run();
+}
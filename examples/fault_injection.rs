@@ -0,0 +1,91 @@
+use std::cell::Cell;
+use std::rc::Rc;
+
+use greenthreads::sync::mpsc;
+use greenthreads::{park, yield_thread, FaultInjection, JoinHandle, PinnedRuntime, Runtime};
+
+// 生成に失敗したら、失敗した理由を表示してやり直す
+fn spawn_with_retry<F, T>(runtime: &mut PinnedRuntime, f: F) -> JoinHandle<T>
+where
+    F: FnOnce() -> T + Clone + 'static,
+    T: 'static,
+{
+    loop {
+        match runtime.as_mut().spawn(f.clone()) {
+            Ok(handle) => return handle,
+            Err(e) => println!("spawn failed: {}, retrying", e),
+        }
+    }
+}
+
+fn main() {
+    // テストが失敗したときのシードを引数に渡せば、同じ障害を起こして再現できる
+    let seed = std::env::args()
+        .nth(1)
+        .map(|s| s.parse().expect("seed must be a number."))
+        .unwrap_or(7);
+    let faults = FaultInjection::new(seed)
+        .fail_spawns(0.3)
+        .delay_wakeups(0.5, 4)
+        .spurious_wakeups(0.3)
+        .shuffle_ready(0.5);
+    let mut runtime = Runtime::builder()
+        .max_threads(8)
+        .fault_injection(faults)
+        .build();
+
+    // 3つの送信側が順に送った値は、起こすのが遅れたり順番が入れ替わったりしても、送信側ごとの順番は保たれる
+    let (tx, rx) = mpsc::channel();
+    for sender in 0..3 {
+        let tx = tx.clone();
+        spawn_with_retry(&mut runtime, move || {
+            for i in 0..5 {
+                tx.send((sender, i)).unwrap();
+                yield_thread();
+            }
+        });
+    }
+    drop(tx);
+    // NOTE: やり直すためにクロージャを複製できるように、Rcに入れる
+    let rx = Rc::new(rx);
+    let receiver = spawn_with_retry(&mut runtime, move || {
+        let mut next = [0; 3];
+        let mut order = Vec::new();
+        for (sender, i) in rx.iter() {
+            assert_eq!(next[sender], i, "messages from a sender were reordered.");
+            next[sender] += 1;
+            order.push(sender);
+        }
+        order
+    });
+
+    // parkはunparkされなくても戻ることがあるので、条件を確かめながらparkを繰り返す
+    let done = Rc::new(Cell::new(false));
+    let flag = done.clone();
+    let waiter = spawn_with_retry(&mut runtime, move || {
+        let mut spurious = 0;
+        while !flag.get() {
+            park();
+            if !flag.get() {
+                spurious += 1;
+            }
+        }
+        spurious
+    });
+    let thread = waiter.thread().clone();
+    spawn_with_retry(&mut runtime, move || {
+        for _ in 0..10 {
+            yield_thread();
+        }
+        done.set(true);
+        thread.unpark().unwrap();
+    });
+
+    runtime.as_mut().run();
+    println!("received in order: {:?}", receiver.join().unwrap());
+    println!(
+        "spurious wakeups seen by waiter: {}",
+        waiter.join().unwrap()
+    );
+    println!("seed {}: {}", seed, runtime.injected_faults().unwrap());
+}
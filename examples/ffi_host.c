@@ -0,0 +1,46 @@
+/*
+ * Cのプログラムからランタイムを動かす例
+ * NOTE: cargoのexampleではないので、共有ライブラリをビルドしてからccでビルドする
+ *   cargo rustc --lib --release --features ffi --crate-type cdylib
+ *   cc -Iinclude examples/ffi_host.c -Ltarget/release -lgreenthreads -o target/ffi_host
+ *   LD_LIBRARY_PATH=target/release target/ffi_host
+ */
+#include <stdio.h>
+
+#include "greenthreads.h"
+
+struct counter {
+    const char *name;
+    int count;
+};
+
+static void count_up(void *userdata) {
+    struct counter *counter = userdata;
+    for (int i = 0; i < 3; i++) {
+        counter->count++;
+        printf("%s (thread %d): %d\n", counter->name, gt_current(), counter->count);
+        if (gt_yield() == GT_CANCELLED) {
+            return;
+        }
+    }
+}
+
+int main(void) {
+    gt_runtime *rt = gt_runtime_new();
+    if (rt == NULL) {
+        return 1;
+    }
+
+    struct counter a = {"a", 0};
+    struct counter b = {"b", 100};
+    if (gt_spawn(count_up, &a) < 0 || gt_spawn(count_up, &b) < 0) {
+        return 1;
+    }
+    if (gt_run(rt) != GT_OK) {
+        return 1;
+    }
+    printf("a: %d, b: %d\n", a.count, b.count);
+
+    gt_runtime_free(rt);
+    return 0;
+}
@@ -0,0 +1,66 @@
+use std::os::raw::c_int;
+use std::time::Duration;
+
+use greenthreads::signal::{SIGINT, SIGTERM};
+use greenthreads::{sleep, yield_thread, Runtime};
+
+extern "C" {
+    fn getpid() -> c_int;
+    fn kill(pid: c_int, sig: c_int) -> c_int;
+}
+
+// ドロップされたことを表示する
+struct Noisy(&'static str);
+
+impl Drop for Noisy {
+    fn drop(&mut self) {
+        println!("{}: dropped", self.0);
+    }
+}
+
+fn main() {
+    let mut runtime = Runtime::builder()
+        .shutdown_grace(Duration::from_millis(300))
+        .build();
+
+    // キャンセルされたら、処理中のリクエストを終わらせてから自分で終わるワーカー
+    for id in 0..2 {
+        runtime
+            .as_mut()
+            .spawn(move || {
+                let mut handled = 0;
+                while sleep(Duration::from_millis(100)).is_ok() {
+                    handled += 1;
+                }
+                println!("worker {}: finished after {} requests", id, handled);
+            })
+            .unwrap();
+    }
+
+    // キャンセルを無視して動き続けるスレッド
+    // NOTE: 猶予の間に終わらないので、最後はスタックを巻き戻して終わらされる
+    runtime
+        .as_mut()
+        .spawn(|| {
+            let _noisy = Noisy("stubborn");
+            loop {
+                if sleep(Duration::from_millis(100)).is_err() {
+                    yield_thread();
+                }
+            }
+        })
+        .unwrap();
+
+    // Ctrl+Cが押されなくても終わるように、少し経ったら自分にSIGTERMを送る
+    std::thread::spawn(|| {
+        std::thread::sleep(Duration::from_millis(350));
+        unsafe { kill(getpid(), SIGTERM) };
+    });
+
+    println!("press Ctrl+C to stop");
+    let caught = runtime
+        .as_mut()
+        .run_until_signal(&[SIGINT, SIGTERM])
+        .unwrap();
+    println!("caught signal {:?}, all threads finished", caught);
+}
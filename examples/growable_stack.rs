@@ -0,0 +1,30 @@
+use greenthreads::{yield_thread, Runtime};
+
+// 1回の呼び出しで1KiBほどスタックを使う再帰
+#[inline(never)]
+fn depth(n: usize) -> usize {
+    let buf = [n as u8; 1024];
+    if n == 0 {
+        return std::hint::black_box(&buf)[0] as usize;
+    }
+    depth(n - 1) + std::hint::black_box(&buf)[1] as usize
+}
+
+fn main() {
+    // 最初は8KiBだけ読み書きできるスタックで始め、足りなくなったら伸ばす
+    let mut runtime = Runtime::builder().growable_stacks(8 * 1024).build();
+
+    for id in 1..=3 {
+        runtime
+            .as_mut()
+            .spawn(move || {
+                yield_thread();
+                // 8KiBを大きく超えるスタックを使っても、自動で伸びるので溢れない
+                let n = 256 * id;
+                println!("thread: {} depth: {} result: {}", id, n, depth(n));
+            })
+            .unwrap();
+    }
+
+    runtime.as_mut().run();
+}
@@ -0,0 +1,55 @@
+use std::os::raw::c_long;
+use std::time::{Duration, Instant};
+
+use greenthreads::{sleep, PinnedRuntime, Runtime};
+
+extern "C" {
+    // プロセスが使ったCPU時間(マイクロ秒)
+    fn clock() -> c_long;
+}
+
+fn cpu_time() -> Duration {
+    Duration::from_micros(unsafe { clock() } as u64)
+}
+
+// スレッドがすべてスリープしている間に、どれだけCPUを使うかを測る
+fn measure(mut runtime: PinnedRuntime, label: &str) {
+    runtime.as_mut().init();
+    for id in 1..=3 {
+        runtime
+            .as_mut()
+            .spawn(move || {
+                for _ in 0..5 {
+                    sleep(Duration::from_millis(20 * id)).unwrap();
+                }
+            })
+            .unwrap();
+    }
+    let (wall, cpu) = (Instant::now(), cpu_time());
+    runtime.as_mut().run();
+    println!(
+        "{}: wall {:?} cpu {:?}",
+        label,
+        wall.elapsed(),
+        cpu_time() - cpu
+    );
+}
+
+fn main() {
+    // 初期設定では次のタイマーの期限まで休止するので、CPUはほとんど使わない
+    measure(Runtime::new(), "park");
+    // 休止する前にしばらく回り続けると、その分CPUを使う
+    measure(
+        Runtime::builder()
+            .idle_spin(Duration::from_millis(5))
+            .build(),
+        "spin 5ms",
+    );
+    // 一度に休止する時間を短くすると、何もなくても定期的に起きる
+    measure(
+        Runtime::builder()
+            .max_park(Duration::from_millis(1))
+            .build(),
+        "max_park 1ms",
+    );
+}
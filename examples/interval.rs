@@ -0,0 +1,50 @@
+use std::time::{Duration, Instant};
+
+use greenthreads::sync::mpsc;
+use greenthreads::{interval, sleep, timeout, Runtime};
+
+fn main() {
+    let mut runtime = Runtime::new();
+    runtime.as_mut().init();
+
+    let start = Instant::now();
+    runtime
+        .as_mut()
+        .spawn(move || {
+            let mut ticker = interval(Duration::from_millis(100));
+            for i in 0..5 {
+                let scheduled = ticker.tick().unwrap();
+                println!(
+                    "tick: {} scheduled: {:?} elapsed: {:?}",
+                    i,
+                    scheduled - start,
+                    start.elapsed()
+                );
+                // 処理に時間がかかっても、次のtickは予定どおりの周期で戻る
+                sleep(Duration::from_millis(30)).unwrap();
+            }
+        })
+        .unwrap();
+
+    let (tx, rx) = mpsc::channel();
+    runtime
+        .as_mut()
+        .spawn(move || {
+            // 期限までに値が届かないので、recvが中断されてErr(Elapsed)になる
+            let result = timeout(Duration::from_millis(150), || rx.recv());
+            println!("recv: {:?} elapsed: {:?}", result, start.elapsed());
+            // 期限内に届いた値は受け取れる
+            let result = timeout(Duration::from_millis(500), || rx.recv());
+            println!("recv: {:?} elapsed: {:?}", result, start.elapsed());
+        })
+        .unwrap();
+    runtime
+        .as_mut()
+        .spawn(move || {
+            sleep(Duration::from_millis(300)).unwrap();
+            tx.send("hello").unwrap();
+        })
+        .unwrap();
+
+    runtime.as_mut().run();
+}
@@ -0,0 +1,39 @@
+use greenthreads::{yield_thread, Runtime};
+
+fn main() {
+    let mut runtime = Runtime::new();
+    runtime.as_mut().init();
+
+    let sum = runtime
+        .as_mut()
+        .spawn(|| {
+            let mut sum = 0;
+            for i in 1..=10 {
+                sum += i;
+                yield_thread();
+            }
+            sum
+        })
+        .unwrap();
+    let panicked = runtime
+        .as_mut()
+        .spawn(|| {
+            yield_thread();
+            panic!("something went wrong");
+        })
+        .unwrap();
+
+    runtime
+        .as_mut()
+        .spawn(move || {
+            // パニックしたスレッドはErrとして返ってくる
+            println!("sum: {:?}", sum.join().unwrap());
+            match panicked.join() {
+                Ok(()) => println!("panicked: finished"),
+                Err(e) => println!("panicked: {:?}", e.downcast_ref::<&str>()),
+            }
+        })
+        .unwrap();
+
+    runtime.as_mut().run();
+}
@@ -0,0 +1,57 @@
+use std::cell::Cell;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+use greenthreads::sync::mpsc;
+use greenthreads::{yield_thread, PinnedRuntime, Runtime};
+
+const ROUNDS: usize = 1000;
+
+// チャネルでピンポンし、1往復にかかった時間を測る
+// 他に計算し続けるスレッドがいると、起こされたスレッドはその後ろに並ぶので往復が遅くなる
+fn ping_pong(mut runtime: PinnedRuntime, label: &'static str) {
+    runtime.as_mut().init();
+    let (ping_tx, ping_rx) = mpsc::channel::<Instant>();
+    let (pong_tx, pong_rx) = mpsc::channel::<Instant>();
+    let done = Rc::new(Cell::new(false));
+
+    let finished = done.clone();
+    runtime
+        .as_mut()
+        .spawn(move || {
+            let mut total = Duration::ZERO;
+            for _ in 0..ROUNDS {
+                ping_tx.send(Instant::now()).unwrap();
+                total += pong_rx.recv().unwrap().elapsed();
+            }
+            finished.set(true);
+            println!("{}: average round trip {:?}", label, total / ROUNDS as u32);
+        })
+        .unwrap();
+    runtime
+        .as_mut()
+        .spawn(move || {
+            for sent in ping_rx.iter() {
+                pong_tx.send(sent).unwrap();
+            }
+        })
+        .unwrap();
+    runtime
+        .as_mut()
+        .spawn(move || {
+            while !done.get() {
+                // 少し計算してから譲る
+                let start = Instant::now();
+                while start.elapsed() < Duration::from_micros(20) {}
+                yield_thread();
+            }
+        })
+        .unwrap();
+
+    runtime.as_mut().run();
+}
+
+fn main() {
+    ping_pong(Runtime::new(), "round robin");
+    ping_pong(Runtime::builder().lifo_slot(3).build(), "lifo slot");
+}
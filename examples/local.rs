@@ -0,0 +1,29 @@
+use std::cell::Cell;
+
+use greenthreads::{green_local, yield_thread, Runtime};
+
+green_local! {
+    // スレッドごとに別々のカウンタを持つ
+    static COUNTER: Cell<usize> = Cell::new(0);
+}
+
+fn main() {
+    let mut runtime = Runtime::new();
+    runtime.as_mut().init();
+
+    for id in 1..=3 {
+        runtime
+            .as_mut()
+            .spawn(move || {
+                for _ in 0..id * 2 {
+                    COUNTER.with(|counter| counter.set(counter.get() + 1));
+                    yield_thread();
+                }
+                // 他のスレッドがインクリメントした分は含まれない
+                println!("thread: {} counter: {}", id, COUNTER.with(|c| c.get()));
+            })
+            .unwrap();
+    }
+
+    runtime.as_mut().run();
+}
@@ -0,0 +1,32 @@
+use std::time::Duration;
+
+use greenthreads::net::lookup_host;
+use greenthreads::{sleep, Runtime};
+
+fn main() {
+    let mut runtime = Runtime::new();
+    runtime.as_mut().init();
+
+    for host in ["localhost:80", "127.0.0.1:8080", "no-such-host.invalid:80"] {
+        runtime
+            .as_mut()
+            .spawn(move || match lookup_host(host) {
+                Ok(addrs) => println!("{} -> {:?}", host, addrs),
+                Err(e) => println!("{} -> error: {}", host, e),
+            })
+            .unwrap();
+    }
+
+    // 名前解決を待っている間も他のスレッドは動き続ける
+    runtime
+        .as_mut()
+        .spawn(|| {
+            for i in 0..3 {
+                println!("tick {}", i);
+                sleep(Duration::from_millis(1)).unwrap();
+            }
+        })
+        .unwrap();
+
+    runtime.as_mut().run();
+}
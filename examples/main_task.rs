@@ -0,0 +1,34 @@
+use std::time::{Duration, Instant};
+
+use greenthreads::sync::mpsc::channel;
+use greenthreads::{current, sleep, Runtime};
+
+fn main() {
+    let mut runtime = Runtime::new();
+    let start = Instant::now();
+
+    let (tx, rx) = channel();
+    runtime
+        .as_mut()
+        .spawn(move || {
+            for i in 0..3 {
+                sleep(Duration::from_millis(50)).unwrap();
+                tx.send(i * 10).unwrap();
+            }
+        })
+        .unwrap();
+
+    // mainの処理も普通のスレッドとして動くので、recvやsleepでブロックできる
+    // NOTE: 終わるまで戻らないので、スタック上のローカル変数も借用できる
+    let mut received = Vec::new();
+    let total = runtime.as_mut().block_on_main(|| {
+        println!("{:?} is running main", current().name());
+        while let Ok(value) = rx.recv() {
+            println!("main: received {} at {:?}", value, start.elapsed());
+            received.push(value);
+        }
+        sleep(Duration::from_millis(50)).unwrap();
+        received.iter().sum::<i32>()
+    });
+    println!("received {:?}, total {}", received, total);
+}
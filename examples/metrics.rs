@@ -0,0 +1,47 @@
+use std::io::{Read, Write};
+use std::time::Duration;
+
+use greenthreads::net::{TcpListener, TcpStream};
+use greenthreads::{serve_metrics, sleep, Runtime};
+
+fn main() {
+    let mut runtime = Runtime::new();
+    runtime.as_mut().init();
+
+    // 空いているポートでメトリクスを返すHTTPサーバーを動かす
+    // NOTE: Prometheusからは http://<addr>/metrics を取得するように設定する
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let server = runtime
+        .as_mut()
+        .spawn(move || serve_metrics(&listener))
+        .unwrap();
+
+    runtime
+        .as_mut()
+        .spawn(|| {
+            for _ in 0..10 {
+                sleep(Duration::from_millis(1)).unwrap();
+            }
+        })
+        .unwrap();
+
+    runtime
+        .as_mut()
+        .spawn(move || {
+            sleep(Duration::from_millis(5)).unwrap();
+            // 自分でサーバーに問い合わせて、返ってきたメトリクスを表示する
+            let mut stream = TcpStream::connect(addr).unwrap();
+            stream
+                .write_all(b"GET /metrics HTTP/1.1\r\nHost: localhost\r\n\r\n")
+                .unwrap();
+            let mut response = String::new();
+            stream.read_to_string(&mut response).unwrap();
+            println!("{}", response);
+            // サーバーはキャンセルされるまで戻らない
+            server.cancel();
+        })
+        .unwrap();
+
+    runtime.as_mut().run();
+}
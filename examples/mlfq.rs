@@ -0,0 +1,44 @@
+use std::time::{Duration, Instant};
+
+use greenthreads::{current, sleep, without_preemption, Runtime, SchedulerPolicy};
+
+fn main() {
+    // 持ち時間を測れるように、プリエンプションも有効にする
+    let mut runtime = Runtime::builder()
+        .scheduler(SchedulerPolicy::Mlfq)
+        .time_slice(Duration::from_millis(5))
+        .build();
+    runtime.as_mut().init();
+
+    // CPUを使い続けるスレッドは持ち時間を使い切るたびにレベルが下がる
+    runtime
+        .as_mut()
+        .spawn(|| {
+            let begin = Instant::now();
+            let mut last = None;
+            while begin.elapsed() < Duration::from_millis(150) {
+                let level = current().queue_level();
+                if level != last {
+                    without_preemption(|| println!("cpu-bound: level {:?}", level));
+                    last = level;
+                }
+            }
+        })
+        .unwrap();
+
+    // すぐにブロックするスレッドは一番上のレベルに留まる
+    runtime
+        .as_mut()
+        .spawn(|| {
+            for _ in 0..5 {
+                sleep(Duration::from_millis(20)).unwrap();
+                without_preemption(|| println!("interactive: level {:?}", current().queue_level()));
+            }
+        })
+        .unwrap();
+
+    runtime.as_mut().run();
+    for thread in runtime.stats().threads.iter().take(3) {
+        println!("thread: {} preemptions: {}", thread.id, thread.preemptions);
+    }
+}
@@ -0,0 +1,38 @@
+use std::thread;
+use std::time::Instant;
+
+use greenthreads::multi::MultiRuntime;
+use greenthreads::yield_thread;
+
+fn fib(n: u64) -> u64 {
+    if n < 2 {
+        n
+    } else {
+        fib(n - 1) + fib(n - 2)
+    }
+}
+
+fn main() {
+    let mut runtime = MultiRuntime::new(4);
+
+    let start = Instant::now();
+    for id in 1..=8 {
+        runtime.spawn(move || {
+            // CPUを使う処理は複数のワーカーで並列に実行される
+            let mut sum = 0;
+            for n in 25..30 {
+                sum += fib(n);
+                yield_thread();
+            }
+            println!(
+                "task: {} worker: {:?} sum: {} elapsed: {:?}",
+                id,
+                thread::current().id(),
+                sum,
+                start.elapsed()
+            );
+        });
+    }
+
+    runtime.run();
+}
@@ -0,0 +1,30 @@
+use std::thread;
+
+use greenthreads::{yield_thread, Runtime};
+
+fn run_runtime(name: &'static str) {
+    let mut runtime = Runtime::new();
+    for id in 1..=2 {
+        runtime
+            .as_mut()
+            .spawn(move || {
+                for i in 0..3 {
+                    println!("runtime: {} thread: {} counter: {}", name, id, i);
+                    yield_thread();
+                }
+            })
+            .unwrap();
+    }
+    // すべてのスレッドが終わったら戻る
+    runtime.as_mut().run();
+}
+
+fn main() {
+    // 別のOSスレッドでそれぞれ独立したRuntimeを動かす
+    let handle = thread::spawn(|| run_runtime("A"));
+    run_runtime("B");
+    handle.join().unwrap();
+
+    // 同じOSスレッドで続けて別のRuntimeを動かす
+    run_runtime("C");
+}
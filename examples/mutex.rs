@@ -0,0 +1,43 @@
+use std::collections::VecDeque;
+use std::rc::Rc;
+
+use greenthreads::sync::{Condvar, Mutex};
+use greenthreads::{yield_thread, Runtime};
+
+fn main() {
+    let mut runtime = Runtime::new();
+    runtime.as_mut().init();
+
+    // 生産者と消費者で共有するキュー
+    let queue = Rc::new((Mutex::new(VecDeque::new()), Condvar::new()));
+
+    let producer = queue.clone();
+    runtime
+        .as_mut()
+        .spawn(move || {
+            let (lock, cvar) = &*producer;
+            for i in 0..5 {
+                println!("produce: {}", i);
+                lock.lock().push_back(i);
+                cvar.notify_one();
+                // スレッド切り替え
+                yield_thread();
+            }
+        })
+        .unwrap();
+
+    let consumer = queue;
+    runtime
+        .as_mut()
+        .spawn(move || {
+            let (lock, cvar) = &*consumer;
+            for _ in 0..5 {
+                // キューが空の間はブロックしてスレッドを切り替える
+                let mut queue = cvar.wait_while(lock.lock(), |q| q.is_empty());
+                println!("consume: {}", queue.pop_front().unwrap());
+            }
+        })
+        .unwrap();
+
+    runtime.as_mut().run();
+}
@@ -0,0 +1,38 @@
+use std::time::Duration;
+
+use greenthreads::sync::{mpsc, oneshot};
+use greenthreads::{sleep, Runtime};
+
+fn main() {
+    let mut runtime = Runtime::new();
+    runtime.as_mut().init();
+
+    // リクエストと一緒に返信用のワンショットチャネルの送信側を送る
+    let (tx, rx) = mpsc::channel::<(u64, oneshot::Sender<u64>)>();
+    runtime
+        .as_mut()
+        .spawn(move || {
+            for (n, reply) in rx.iter() {
+                sleep(Duration::from_millis(10)).unwrap();
+                reply.send(n * n).unwrap();
+            }
+        })
+        .unwrap();
+
+    runtime
+        .as_mut()
+        .spawn(move || {
+            for n in 1..=3 {
+                let (reply_tx, reply_rx) = oneshot::channel();
+                tx.send((n, reply_tx)).unwrap();
+                println!("{} * {} = {}", n, n, reply_rx.recv().unwrap());
+            }
+            // 値を送らずに送信側をドロップすると、受信側はエラーになる
+            let (reply_tx, reply_rx) = oneshot::channel::<u64>();
+            drop(reply_tx);
+            println!("dropped: {:?}", reply_rx.recv());
+        })
+        .unwrap();
+
+    runtime.as_mut().run();
+}
@@ -0,0 +1,38 @@
+use std::cell::Cell;
+use std::rc::Rc;
+
+use greenthreads::{park, yield_thread, Runtime};
+
+fn main() {
+    let mut runtime = Runtime::new();
+    runtime.as_mut().init();
+
+    let ready = Rc::new(Cell::new(false));
+    let flag = ready.clone();
+    let waiter = runtime
+        .as_mut()
+        .spawn(move || {
+            // unparkされるまで止まる
+            while !flag.get() {
+                println!("waiter: parked");
+                park();
+            }
+            println!("waiter: unparked");
+        })
+        .unwrap();
+
+    let thread = waiter.thread().clone();
+    runtime
+        .as_mut()
+        .spawn(move || {
+            for i in 0..3 {
+                println!("notifier: counter: {}", i);
+                yield_thread();
+            }
+            ready.set(true);
+            thread.unpark().unwrap();
+        })
+        .unwrap();
+
+    runtime.as_mut().run();
+}
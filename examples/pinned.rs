@@ -0,0 +1,43 @@
+use greenthreads::{current, yield_thread, PinnedRuntime, Runtime};
+
+// Runtimeを作ってinitし、スレッドを生成してから返す
+// NOTE: Runtime::newやBuilder::buildはヒープに固定したPinnedRuntimeを返すので、initした後で返しても
+//       initで残したアドレスは動かない
+fn make_runtime(name: &'static str) -> PinnedRuntime {
+    let mut runtime = Runtime::builder().build();
+    runtime.as_mut().init();
+    for i in 0..2 {
+        runtime
+            .as_mut()
+            .spawn(move || {
+                for step in 0..2 {
+                    println!("{}: thread {} step {}", name, i, step);
+                    yield_thread();
+                }
+            })
+            .unwrap();
+    }
+    runtime
+}
+
+fn main() {
+    // PinnedRuntimeを関数から返したりVecに入れたりして動かしても、中のRuntimeは動かない
+    let mut runtimes = Vec::new();
+    for name in ["first", "second"] {
+        let runtime = make_runtime(name);
+        let addr: *const Runtime = &*runtime;
+        runtimes.push((runtime, addr));
+    }
+    for (runtime, addr) in &mut runtimes {
+        assert_eq!(*addr, &**runtime as *const Runtime);
+        runtime.as_mut().init();
+        // NOTE: initしたので、グリーンスレッドの外からもcurrentを呼べる
+        println!(
+            "main is thread {} of the runtime at {:p}",
+            current().id(),
+            *addr
+        );
+        runtime.as_mut().run();
+        println!("switched {} times", runtime.stats().switches);
+    }
+}
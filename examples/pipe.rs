@@ -0,0 +1,102 @@
+use std::io::{self, BufRead, Read, Write};
+
+use greenthreads::sync::{pipe, pipe_with_capacity};
+use greenthreads::Runtime;
+
+// 書かれたバイト数と、バイトの和を数えるWrite
+struct Checksum {
+    bytes: usize,
+    sum: u64,
+}
+
+impl Write for Checksum {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.bytes += buf.len();
+        self.sum += buf.iter().map(|&b| b as u64).sum::<u64>();
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+fn main() {
+    let mut runtime = Runtime::new();
+
+    // 行単位のテキストをwriteln!で書き、BufRead::linesで読む
+    let (reader, mut writer) = pipe();
+    runtime
+        .as_mut()
+        .spawn(move || {
+            for i in 1..=3 {
+                writeln!(writer, "line {}", i).unwrap();
+            }
+            // NOTE: 書く側をドロップすると、読む側は最後まで読んだところで終わる
+        })
+        .unwrap();
+    runtime
+        .as_mut()
+        .spawn(move || {
+            for line in reader.lines() {
+                println!("read: {}", line.unwrap());
+            }
+        })
+        .unwrap();
+    runtime.as_mut().run();
+
+    // 4KiBしかためられないパイプで1MiBを流す
+    // 書く側は満杯になるたびにブロックし、読む側が読んだ分だけ続きを書く
+    let (mut reader, mut writer) = pipe_with_capacity(4 * 1024);
+    let data: Vec<u8> = (0..1024 * 1024).map(|i| (i % 251) as u8).collect();
+    let expected: u64 = data.iter().map(|&b| b as u64).sum();
+    runtime
+        .as_mut()
+        .spawn(move || writer.write_all(&data).unwrap())
+        .unwrap();
+    let copied = runtime
+        .as_mut()
+        .spawn(move || {
+            let mut checksum = Checksum { bytes: 0, sum: 0 };
+            io::copy(&mut reader, &mut checksum).unwrap();
+            checksum
+        })
+        .unwrap();
+    runtime.as_mut().run();
+    let checksum = copied.join().unwrap();
+    println!(
+        "copied {} bytes, checksum {} (expected {})",
+        checksum.bytes, checksum.sum, expected
+    );
+
+    // 読む側がドロップされると、書く側はErr(BrokenPipe)を受け取る
+    let (reader, mut writer) = pipe();
+    drop(reader);
+    runtime
+        .as_mut()
+        .spawn(move || {
+            let err = writer.write(b"hello").unwrap_err();
+            println!("write after the reader was dropped: {:?}", err.kind());
+        })
+        .unwrap();
+    runtime.as_mut().run();
+
+    // 読み残しはread_to_stringで読める
+    let (mut reader, mut writer) = pipe();
+    runtime
+        .as_mut()
+        .spawn(move || {
+            writer.write_all(b"hello, ").unwrap();
+            writer.write_all(b"pipe").unwrap();
+        })
+        .unwrap();
+    runtime
+        .as_mut()
+        .spawn(move || {
+            let mut text = String::new();
+            reader.read_to_string(&mut text).unwrap();
+            println!("read_to_string: {:?}", text);
+        })
+        .unwrap();
+    runtime.as_mut().run();
+}
@@ -0,0 +1,28 @@
+use std::time::{Duration, Instant};
+
+use greenthreads::{without_preemption, Runtime};
+
+fn main() {
+    // 10msごとに時間切れを確認し、続けて実行しているスレッドを強制的に切り替える
+    let mut runtime = Runtime::builder()
+        .time_slice(Duration::from_millis(10))
+        .build();
+    runtime.as_mut().init();
+
+    let start = Instant::now();
+    for id in 1..=3 {
+        runtime
+            .as_mut()
+            .spawn(move || {
+                // 標準出力のロックを持ったまま切り替わらないようにする
+                without_preemption(|| println!("thread: {} started at {:?}", id, start.elapsed()));
+                // yield_threadを呼ばずにCPUを使い続けても、他のスレッドも並行して進む
+                let begin = Instant::now();
+                while begin.elapsed() < Duration::from_millis(100) {}
+                without_preemption(|| println!("thread: {} finished at {:?}", id, start.elapsed()));
+            })
+            .unwrap();
+    }
+
+    runtime.as_mut().run();
+}
@@ -0,0 +1,25 @@
+use greenthreads::{set_priority, yield_thread, Runtime, SchedulerPolicy};
+
+fn main() {
+    let mut runtime = Runtime::with_scheduler(SchedulerPolicy::Priority);
+    runtime.as_mut().init();
+
+    for (id, priority) in [(1, 0), (2, 5), (3, 10)] {
+        runtime
+            .as_mut()
+            .spawn_with_priority(priority, move || {
+                for i in 0..5 {
+                    // 優先度が高いスレッドほど多く実行されるが、低いスレッドも待った分だけ優先される
+                    println!("thread: {} priority: {} counter: {}", id, priority, i);
+                    if i == 2 && id == 3 {
+                        // 途中で優先度を下げる
+                        set_priority(0);
+                    }
+                    yield_thread();
+                }
+            })
+            .unwrap();
+    }
+
+    runtime.as_mut().run();
+}
@@ -0,0 +1,73 @@
+use std::cell::Cell;
+use std::rc::Rc;
+use std::time::Duration;
+
+use greenthreads::sync::Mutex;
+use greenthreads::{sleep, yield_thread, Runtime, SchedulerPolicy};
+
+fn main() {
+    let mut runtime = Runtime::with_scheduler(SchedulerPolicy::Priority);
+    runtime.as_mut().init();
+
+    let lock = Rc::new(Mutex::new(0));
+    let high_done = Rc::new(Cell::new(false));
+    // lowがロックを持って処理している間にmediumが実行された回数
+    let medium_runs = Rc::new(Cell::new(0));
+    let low_working = Rc::new(Cell::new(false));
+
+    // 優先度の低いスレッドがロックを持ったまま、しばらく処理を続ける
+    let (l, working) = (lock.clone(), low_working.clone());
+    runtime
+        .as_mut()
+        .spawn_with_priority(0, move || {
+            let mut guard = l.lock();
+            println!("low: locked");
+            let _ = sleep(Duration::from_millis(10));
+            working.set(true);
+            for i in 0..3 {
+                println!("low: working {}", i);
+                *guard += 1;
+                yield_thread();
+            }
+            working.set(false);
+            println!("low: unlock");
+        })
+        .unwrap();
+
+    // 優先度の高いスレッドは、lowがロックを持っている間にロックを待つ
+    // 待っている間はlowが同じ優先度で実行されるので、mediumに割り込まれない
+    let (l, done) = (lock.clone(), high_done.clone());
+    runtime
+        .as_mut()
+        .spawn_with_priority(100, move || {
+            let _ = sleep(Duration::from_millis(5));
+            println!("high: waiting");
+            let guard = l.lock();
+            println!("high: locked value = {}", *guard);
+            done.set(true);
+        })
+        .unwrap();
+
+    // 優先度が中くらいのスレッドは、highが終わるまで実行し続ける
+    let (done, runs, working) = (high_done.clone(), medium_runs.clone(), low_working.clone());
+    runtime
+        .as_mut()
+        .spawn_with_priority(50, move || {
+            let _ = sleep(Duration::from_millis(1));
+            while !done.get() {
+                if working.get() {
+                    runs.set(runs.get() + 1);
+                }
+                yield_thread();
+            }
+        })
+        .unwrap();
+
+    runtime.as_mut().run();
+    // NOTE: yield_threadは再開可能な他のスレッドに必ず1回譲るので、lowが1回yieldするたびにmediumも1回実行される
+    //       優先度を継承しなければ、lowは待たされた分だけ優先度が上がるまでmediumに何十回も割り込まれる
+    println!(
+        "medium ran {} times while low was holding the lock",
+        medium_runs.get()
+    );
+}
@@ -0,0 +1,74 @@
+use std::io::{Read, Write};
+use std::time::{Duration, Instant};
+
+use greenthreads::process::{Command, Stdio};
+use greenthreads::{sleep, Runtime};
+
+fn main() {
+    let mut runtime = Runtime::new();
+    runtime.as_mut().init();
+
+    let start = Instant::now();
+
+    // 子プロセスが終わるのを待っている間も、他のスレッドは動き続ける
+    runtime
+        .as_mut()
+        .spawn(move || {
+            let status = Command::new("sleep").arg("0.2").status().unwrap();
+            println!("sleep: {} at {:?}", status, start.elapsed());
+        })
+        .unwrap();
+
+    runtime
+        .as_mut()
+        .spawn(move || {
+            for i in 0..4 {
+                println!("ticker: {} at {:?}", i, start.elapsed());
+                sleep(Duration::from_millis(50)).unwrap();
+            }
+        })
+        .unwrap();
+
+    // 標準出力と標準エラー出力を読み込む
+    runtime
+        .as_mut()
+        .spawn(|| {
+            let output = Command::new("sh")
+                .arg("-c")
+                .arg("echo hello; echo oops >&2; exit 3")
+                .output()
+                .unwrap();
+            println!(
+                "sh: {} stdout={:?} stderr={:?}",
+                output.status,
+                String::from_utf8_lossy(&output.stdout),
+                String::from_utf8_lossy(&output.stderr)
+            );
+
+            // パイプで子プロセスとやり取りする
+            let mut child = Command::new("tr")
+                .arg("a-z")
+                .arg("A-Z")
+                .stdin(Stdio::piped())
+                .stdout(Stdio::piped())
+                .spawn()
+                .unwrap();
+            child
+                .stdin
+                .take()
+                .unwrap()
+                .write_all(b"green threads\n")
+                .unwrap();
+            let mut upper = String::new();
+            child
+                .stdout
+                .take()
+                .unwrap()
+                .read_to_string(&mut upper)
+                .unwrap();
+            println!("tr: {:?} {}", upper, child.wait().unwrap());
+        })
+        .unwrap();
+
+    runtime.as_mut().run();
+}
@@ -0,0 +1,47 @@
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+
+use greenthreads::{current, park, Runtime};
+
+fn main() {
+    let mut runtime = Runtime::new();
+    let remote = runtime.as_mut().handle().unwrap();
+
+    // 他のOSスレッドから起こされるまで止まっているスレッド
+    let (id_tx, id_rx) = mpsc::channel();
+    runtime
+        .as_mut()
+        .spawn(move || {
+            id_tx.send(current().thread_id()).unwrap();
+            println!("waiter: parked");
+            park();
+            println!("waiter: woken by the os thread");
+        })
+        .unwrap();
+
+    // GUIやコールバックのスレッドの代わり
+    let os_thread = thread::spawn(move || {
+        let waiter = id_rx.recv().unwrap();
+        let (tx, rx) = mpsc::channel();
+        for i in 0..3 {
+            let tx = tx.clone();
+            remote
+                .spawn(move || {
+                    println!("task {}: running on thread {}", i, current().id());
+                    tx.send(i * 10).unwrap();
+                })
+                .unwrap();
+            thread::sleep(Duration::from_millis(10));
+        }
+        drop(tx);
+        let results: Vec<i32> = rx.iter().collect();
+        println!("os thread: results {:?}", results);
+        remote.wake(waiter).unwrap();
+        // ハンドルをドロップすると、残りのスレッドが終わったらrunが戻る
+    });
+
+    runtime.as_mut().run();
+    os_thread.join().unwrap();
+    println!("run returned");
+}
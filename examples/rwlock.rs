@@ -0,0 +1,38 @@
+use std::rc::Rc;
+use std::time::Duration;
+
+use greenthreads::sync::{RwLock, RwLockPreference};
+use greenthreads::{current, sleep, Runtime};
+
+fn main() {
+    let mut runtime = Runtime::new();
+    runtime.as_mut().init();
+
+    // 書き込み優先にすると、書き込みを待っている間は新しい読み込みを待たせる
+    let lock = Rc::new(RwLock::with_preference(0, RwLockPreference::Write));
+    for _ in 0..2 {
+        let lock = lock.clone();
+        runtime
+            .as_mut()
+            .spawn(move || {
+                for _ in 0..3 {
+                    let value = lock.read();
+                    println!("reader {}: read {}", current().id(), *value);
+                    sleep(Duration::from_millis(30)).unwrap();
+                }
+            })
+            .unwrap();
+    }
+    let writer = lock.clone();
+    runtime
+        .as_mut()
+        .spawn(move || {
+            sleep(Duration::from_millis(10)).unwrap();
+            let mut value = writer.write();
+            *value += 1;
+            println!("writer {}: wrote {}", current().id(), *value);
+        })
+        .unwrap();
+
+    runtime.as_mut().run();
+}
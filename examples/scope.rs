@@ -0,0 +1,31 @@
+use std::cell::Cell;
+
+use greenthreads::{yield_thread, Runtime};
+
+fn main() {
+    let mut runtime = Runtime::new();
+    runtime.as_mut().init();
+
+    // スコープ内のスレッドはローカル変数をそのまま借用できる
+    let numbers = [1, 2, 3, 4, 5, 6];
+    let total = Cell::new(0);
+    runtime.as_mut().scope(|s| {
+        for chunk in numbers.chunks(2) {
+            let total = &total;
+            s.spawn(move || {
+                for n in chunk {
+                    total.set(total.get() + n);
+                    yield_thread();
+                }
+            });
+        }
+    });
+    // スコープを抜けた時点ですべてのスレッドが終わっている
+    println!("total: {}", total.get());
+
+    let sum = runtime.as_mut().scope(|s| {
+        let handle = s.spawn(|| numbers.iter().sum::<i32>());
+        handle.join().unwrap()
+    });
+    println!("sum: {}", sum);
+}
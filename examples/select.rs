@@ -0,0 +1,60 @@
+use std::time::Duration;
+
+use greenthreads::sync::{mpsc, Select};
+use greenthreads::{sleep, Runtime};
+
+fn main() {
+    let mut runtime = Runtime::new();
+    runtime.as_mut().init();
+
+    let (fast_tx, fast_rx) = mpsc::channel();
+    let (slow_tx, slow_rx) = mpsc::channel();
+    runtime
+        .as_mut()
+        .spawn(move || {
+            for i in 0..3 {
+                sleep(Duration::from_millis(30)).unwrap();
+                fast_tx.send(i).unwrap();
+            }
+        })
+        .unwrap();
+    runtime
+        .as_mut()
+        .spawn(move || {
+            sleep(Duration::from_millis(50)).unwrap();
+            slow_tx.send("slow").unwrap();
+        })
+        .unwrap();
+
+    runtime
+        .as_mut()
+        .spawn(move || {
+            let (mut fast_open, mut slow_open) = (true, true);
+            loop {
+                // 2つのチャネルとタイムアウトのうち、最初に準備ができたものを待つ
+                // NOTE: 閉じたチャネルはいつでも準備ができている扱いになるので、待つものから外す
+                let mut sel = Select::new();
+                let fast = fast_open.then(|| sel.recv(&fast_rx));
+                let slow = slow_open.then(|| sel.recv(&slow_rx));
+                sel.timeout(Duration::from_millis(100));
+                let i = Some(sel.wait().unwrap());
+                if i == fast {
+                    match fast_rx.try_recv() {
+                        Ok(v) => println!("fast: {}", v),
+                        Err(_) => fast_open = false,
+                    }
+                } else if i == slow {
+                    match slow_rx.try_recv() {
+                        Ok(v) => println!("slow: {}", v),
+                        Err(_) => slow_open = false,
+                    }
+                } else {
+                    println!("timeout");
+                    break;
+                }
+            }
+        })
+        .unwrap();
+
+    runtime.as_mut().run();
+}
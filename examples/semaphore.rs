@@ -0,0 +1,30 @@
+use std::rc::Rc;
+use std::time::Duration;
+
+use greenthreads::sync::Semaphore;
+use greenthreads::{current, sleep, Runtime};
+
+fn main() {
+    let mut runtime = Runtime::new();
+    runtime.as_mut().init();
+
+    // 同時に処理できるのは2つまで
+    let semaphore = Rc::new(Semaphore::new(2));
+    for _ in 0..3 {
+        let semaphore = semaphore.clone();
+        runtime
+            .as_mut()
+            .spawn(move || {
+                for _ in 0..2 {
+                    let _permit = semaphore.acquire();
+                    println!("thread: {} acquired", current().id());
+                    sleep(Duration::from_millis(50)).unwrap();
+                    println!("thread: {} released", current().id());
+                }
+            })
+            .unwrap();
+    }
+
+    runtime.as_mut().run();
+    assert_eq!(semaphore.available_permits(), 2);
+}
@@ -0,0 +1,41 @@
+use std::time::Duration;
+
+use greenthreads::{sleep, Cancelled, Runtime};
+
+// ドロップされたことを表示する
+struct Noisy(&'static str);
+
+impl Drop for Noisy {
+    fn drop(&mut self) {
+        println!("{}: dropped", self.0);
+    }
+}
+
+fn main() {
+    let mut runtime = Runtime::new();
+
+    // 終わらないバックグラウンドのスレッド
+    let background = runtime
+        .as_mut()
+        .spawn(|| {
+            let _noisy = Noisy("background");
+            loop {
+                sleep(Duration::from_millis(50)).unwrap();
+                println!("background: tick");
+            }
+        })
+        .unwrap();
+
+    runtime.as_mut().scope(|s| {
+        s.spawn(|| {
+            sleep(Duration::from_millis(120)).unwrap();
+            println!("main work: done");
+        });
+    });
+
+    // 残っているスレッドをキャンセルする
+    // キャンセルされたスレッドのスタックも巻き戻されるので、ローカル変数はドロップされる
+    runtime.as_mut().shutdown();
+    let err = background.join().unwrap_err();
+    println!("background: cancelled: {}", err.is::<Cancelled>());
+}
@@ -0,0 +1,51 @@
+use std::os::raw::c_int;
+use std::time::Duration;
+
+use greenthreads::signal::{signal, SIGINT};
+use greenthreads::{sleep, Runtime};
+
+extern "C" {
+    fn raise(sig: c_int) -> c_int;
+}
+
+fn main() {
+    let mut runtime = Runtime::new();
+    runtime.as_mut().init();
+
+    let worker = runtime
+        .as_mut()
+        .spawn(|| {
+            let mut n = 0;
+            // キャンセルされるとsleepがErrを返すので、そこで後片付けをして終わる
+            while sleep(Duration::from_millis(100)).is_ok() {
+                n += 1;
+                println!("worker: tick {}", n);
+            }
+            println!("worker: cleaned up after {} ticks", n);
+        })
+        .unwrap();
+
+    // Ctrl+Cが押されるまで待ち、ワーカーを止める
+    // NOTE: 待っている間は他のスレッドが動き続ける
+    let mut sigint = signal(SIGINT).unwrap();
+    runtime
+        .as_mut()
+        .spawn(move || {
+            println!("press Ctrl+C to stop (or wait for one second)");
+            sigint.recv().unwrap();
+            println!("received SIGINT, shutting down");
+            worker.cancel();
+        })
+        .unwrap();
+
+    // 押されなくても終わるように、1秒後に自分にSIGINTを送る
+    runtime
+        .as_mut()
+        .spawn(|| {
+            sleep(Duration::from_secs(1)).unwrap();
+            unsafe { raise(SIGINT) };
+        })
+        .unwrap();
+
+    runtime.as_mut().run();
+}
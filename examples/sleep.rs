@@ -0,0 +1,29 @@
+use std::time::{Duration, Instant};
+
+use greenthreads::{sleep, Runtime};
+
+fn main() {
+    let mut runtime = Runtime::new();
+    runtime.as_mut().init();
+
+    let start = Instant::now();
+    for id in 1..=3 {
+        runtime
+            .as_mut()
+            .spawn(move || {
+                for i in 0..3 {
+                    // スリープ中は他のスレッドが実行され、すべてスリープ中の場合はプロセスごと休止する
+                    sleep(Duration::from_millis(100 * id)).unwrap();
+                    println!(
+                        "thread: {} counter: {} elapsed: {:?}",
+                        id,
+                        i,
+                        start.elapsed()
+                    );
+                }
+            })
+            .unwrap();
+    }
+
+    runtime.as_mut().run();
+}
@@ -0,0 +1,68 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use greenthreads::snapshot::{self, Checkpoint, Fork, Snapshot};
+use greenthreads::{current, Runtime};
+
+fn main() {
+    let mut runtime = Runtime::new();
+
+    // プロセスのforkのように、親と子の両方がfork_taskから続きを実行する
+    runtime
+        .as_mut()
+        .spawn_named("forker", || {
+            let answer = snapshot::forkable(|| {
+                let base = 40;
+                let role = match unsafe { snapshot::fork_task() }.unwrap() {
+                    Fork::Parent(child) => {
+                        // waitpidのように子が終わるのを待つ
+                        child.join().unwrap();
+                        "parent"
+                    }
+                    Fork::Child => "child",
+                };
+                // NOTE: ローカル変数のbaseは子のスタックにも写されている
+                println!(
+                    "{:>6} on thread {}: base + 2 = {}",
+                    role,
+                    current().id(),
+                    base + 2
+                );
+                base + 2
+            });
+            // 子はforkableの終わりで終わるので、ここに来るのは親だけ
+            println!("only the parent leaves forkable with {}", answer);
+        })
+        .unwrap();
+
+    // setjmp/longjmpのように、保存したところまでスタックを巻き戻してやり直す
+    // NOTE: スタックの上の値は巻き戻されるので、スナップショットとやり直した回数はスレッドの外に置く
+    let state: Rc<RefCell<(Option<Snapshot>, u32)>> = Rc::new(RefCell::new((None, 0)));
+    runtime
+        .as_mut()
+        .spawn_named("replayer", move || {
+            let mut sum = 0;
+            match snapshot::checkpoint() {
+                Checkpoint::Saved(saved) => {
+                    println!("saved {} bytes of the stack", saved.size());
+                    state.borrow_mut().0 = Some(saved);
+                }
+                Checkpoint::Restored => println!("restored, sum is back to {}", sum),
+            }
+            sum += 10;
+            let attempt = {
+                let mut state = state.borrow_mut();
+                state.1 += 1;
+                state.1
+            };
+            println!("attempt {}: sum = {}", attempt, sum);
+            if attempt < 3 {
+                // NOTE: 巻き戻すときに借用が残っていないように、ポインタにしてから借用を終える
+                let saved: *const Snapshot = state.borrow().0.as_ref().unwrap();
+                unsafe { snapshot::restore(&*saved) };
+            }
+        })
+        .unwrap();
+
+    runtime.as_mut().run();
+}
@@ -0,0 +1,33 @@
+use std::time::Duration;
+
+use greenthreads::{sleep, Runtime, SpawnError};
+
+fn main() {
+    let mut runtime = Runtime::new();
+
+    // 空いているスレッドの数だけ生成できる
+    let mut handles = Vec::new();
+    for id in 1.. {
+        match runtime.as_mut().try_spawn(move || {
+            sleep(Duration::from_millis(100)).unwrap();
+            id
+        }) {
+            Ok(handle) => handles.push(handle),
+            Err(SpawnError::PoolExhausted) => {
+                println!("try_spawn: no thread is available after {} threads", id - 1);
+                break;
+            }
+            Err(e) => panic!("{}", e),
+        }
+    }
+
+    // 空いているスレッドがない場合は、どれかのスレッドが終わるまで待ってから生成する
+    let last = runtime
+        .as_mut()
+        .spawn(|| "spawned after a thread finished")
+        .unwrap();
+    for handle in handles {
+        println!("thread: {} finished", handle.join().unwrap());
+    }
+    println!("spawn: {}", last.join().unwrap());
+}
@@ -0,0 +1,35 @@
+use std::time::{Duration, Instant};
+
+use greenthreads::{sleep, spawn_blocking, Runtime};
+
+fn main() {
+    let mut runtime = Runtime::new();
+    let start = Instant::now();
+
+    // ブロックする処理はOSスレッドで実行されるので、その間も他のスレッドは動き続ける
+    let blocking = runtime
+        .as_mut()
+        .spawn(move || {
+            let n = spawn_blocking(|| {
+                std::thread::sleep(Duration::from_millis(300));
+                42
+            })
+            .unwrap();
+            println!("[{:?}] blocking job returned {}", start.elapsed(), n);
+        })
+        .unwrap();
+
+    let ticker = runtime
+        .as_mut()
+        .spawn(move || {
+            for i in 0..3 {
+                sleep(Duration::from_millis(50)).unwrap();
+                println!("[{:?}] tick {}", start.elapsed(), i);
+            }
+        })
+        .unwrap();
+
+    runtime.as_mut().run();
+    blocking.join().unwrap();
+    ticker.join().unwrap();
+}
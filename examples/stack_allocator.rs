@@ -0,0 +1,59 @@
+use std::cell::Cell;
+use std::io;
+use std::rc::Rc;
+
+use greenthreads::{yield_thread, Runtime, Stack, StackAllocator};
+
+// Vec<u8>をスタックにし、確保と解放の回数を数えるStackAllocator
+struct CountingAllocator {
+    allocated: Rc<Cell<usize>>,
+    deallocated: Rc<Cell<usize>>,
+}
+
+impl StackAllocator for CountingAllocator {
+    fn allocate(&mut self, size: usize) -> io::Result<Stack> {
+        self.allocated.set(self.allocated.get() + 1);
+        Ok(Stack::from_memory(vec![0u8; size]))
+    }
+
+    fn deallocate(&mut self, stack: Stack) {
+        self.deallocated.set(self.deallocated.get() + 1);
+        drop(stack);
+    }
+}
+
+fn main() {
+    let allocated = Rc::new(Cell::new(0));
+    let deallocated = Rc::new(Cell::new(0));
+    let mut runtime = Runtime::builder()
+        .max_idle_stacks(1)
+        .stack_allocator(Box::new(CountingAllocator {
+            allocated: allocated.clone(),
+            deallocated: deallocated.clone(),
+        }))
+        .build();
+
+    let mut handles = Vec::new();
+    for id in 0..100 {
+        let handle = runtime
+            .as_mut()
+            .spawn(move || {
+                yield_thread();
+                id
+            })
+            .unwrap();
+        handles.push(handle);
+    }
+    runtime.as_mut().run();
+    let sum: u64 = handles.into_iter().map(|h| h.join().unwrap()).sum();
+    println!("sum: {}", sum);
+
+    // プールに残しておけない分はその場で、残りはRuntimeをドロップしたときに返される
+    drop(runtime);
+    println!(
+        "allocated: {} deallocated: {}",
+        allocated.get(),
+        deallocated.get()
+    );
+    assert_eq!(allocated.get(), deallocated.get());
+}
@@ -0,0 +1,29 @@
+use std::time::Instant;
+
+use greenthreads::{yield_thread, Runtime};
+
+fn main() {
+    // 終わったスレッドのスタックは使い回されるので、たくさん生成してもmmapは最初の数回だけになる
+    let mut runtime = Runtime::builder()
+        .max_idle_stacks(2)
+        .release_idle_stacks(true)
+        .build();
+
+    let start = Instant::now();
+    let mut handles = Vec::new();
+    for id in 0..10000 {
+        // 空いているスレッドがない場合は、どれかのスレッドが終わるまで待つ
+        let handle = runtime
+            .as_mut()
+            .spawn(move || {
+                yield_thread();
+                id
+            })
+            .unwrap();
+        handles.push(handle);
+    }
+    runtime.as_mut().run();
+
+    let sum: u64 = handles.into_iter().map(|h| h.join().unwrap()).sum();
+    println!("sum: {} elapsed: {:?}", sum, start.elapsed());
+}
@@ -0,0 +1,52 @@
+use std::hint::black_box;
+use std::ptr;
+
+use greenthreads::{Runtime, StackRecyclePolicy};
+
+const SECRET: u8 = 0x5A;
+const SECRET_LEN: usize = 32 * 1024;
+
+// スタックの深いところに秘密の値を置いてから戻る
+#[inline(never)]
+fn handle_secret() {
+    let buf = [SECRET; SECRET_LEN];
+    black_box(&buf);
+}
+
+// 今のスタックポインタより下(前のタスクが使った領域)に、秘密の値が残っているかを調べる
+// NOTE: 初期化していない領域を読むのは未定義動作なので、例のためだけに行う
+#[inline(never)]
+fn find_leftover() -> usize {
+    let marker = 0u8;
+    let sp = black_box(&marker) as *const u8 as usize;
+    let mut found = 0;
+    for addr in (sp - 2 * SECRET_LEN)..(sp - 4096) {
+        if unsafe { ptr::read_volatile(addr as *const u8) } == SECRET {
+            found += 1;
+        }
+    }
+    found
+}
+
+fn main() {
+    for policy in [
+        StackRecyclePolicy::KeepDirty,
+        StackRecyclePolicy::Zero,
+        StackRecyclePolicy::Release,
+    ] {
+        let mut runtime = Runtime::builder()
+            .max_threads(1)
+            .stack_recycle(policy)
+            .build();
+        runtime.as_mut().spawn(handle_secret).unwrap();
+        runtime.as_mut().run();
+        // 終わったスレッドのスタックが、次のスレッドで使い回される
+        let leaked = runtime.as_mut().spawn(find_leftover).unwrap();
+        runtime.as_mut().run();
+        println!(
+            "{:?}: {} bytes of the previous task are visible",
+            policy,
+            leaked.join().unwrap()
+        );
+    }
+}
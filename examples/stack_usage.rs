@@ -0,0 +1,41 @@
+use greenthreads::{current, yield_thread, Runtime};
+
+#[inline(never)]
+fn recurse(n: usize) -> usize {
+    let buf = [n as u8; 256];
+    if n == 0 {
+        return std::hint::black_box(&buf)[0] as usize;
+    }
+    recurse(n - 1) + std::hint::black_box(&buf)[1] as usize
+}
+
+fn main() {
+    // スレッドが終わったときにスタックの使用量が1%を超えていたら警告する
+    let mut runtime = Runtime::builder().stack_usage_warning(1).build();
+
+    let mut ids = Vec::new();
+    for depth in [10, 100, 1000] {
+        let handle = runtime
+            .as_mut()
+            .spawn(move || {
+                recurse(depth);
+                yield_thread();
+                println!(
+                    "depth: {} stack usage: {:?}",
+                    depth,
+                    current().stack_usage()
+                );
+            })
+            .unwrap();
+        ids.push(handle.thread().id());
+    }
+    runtime.as_mut().run();
+
+    for id in ids {
+        println!(
+            "thread: {} max stack usage: {:?}",
+            id,
+            runtime.max_stack_usage(id)
+        );
+    }
+}
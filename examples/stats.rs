@@ -0,0 +1,35 @@
+use greenthreads::{yield_thread, Runtime, SchedulerPolicy};
+
+fn work(n: u64) -> u64 {
+    let mut sum = 0;
+    for i in 0..n {
+        sum += i;
+        if i % 1000 == 0 {
+            yield_thread();
+        }
+    }
+    sum
+}
+
+fn main() {
+    // スケジューラごとに同じ処理を実行して、切り替え回数や実行時間を比べる
+    for policy in [SchedulerPolicy::RoundRobin, SchedulerPolicy::Priority] {
+        let mut runtime = Runtime::with_scheduler(policy);
+        for priority in 1..=3 {
+            runtime
+                .as_mut()
+                .spawn_with_priority(priority, move || work(10000 * priority as u64))
+                .unwrap();
+        }
+        runtime.as_mut().run();
+
+        let stats = runtime.stats();
+        println!("{:?}: total switches: {}", policy, stats.switches);
+        for thread in &stats.threads[1..] {
+            println!(
+                "  thread: {} switches: {} run time: {:?} peak stack: {} bytes",
+                thread.id, thread.switches, thread.run_time, thread.peak_stack
+            );
+        }
+    }
+}
@@ -0,0 +1,38 @@
+use std::io::Write;
+
+use greenthreads::{io, yield_thread, Runtime};
+
+fn main() {
+    let mut runtime = Runtime::new();
+    runtime.as_mut().init();
+
+    for id in 1..=3 {
+        runtime
+            .as_mut()
+            .spawn(move || {
+                let mut out = io::stdout();
+                for i in 0..3 {
+                    // 1行を何回かに分けて書いても、途中で他のスレッドの出力が混ざらない
+                    write!(out, "counter: ").unwrap();
+                    yield_thread();
+                    writeln!(out, "{} (from {})", i, id).unwrap();
+                }
+            })
+            .unwrap();
+    }
+
+    // 名前を付けたスレッドは名前が行頭に付く
+    runtime
+        .as_mut()
+        .spawn_named("logger", || {
+            let mut out = io::stdout();
+            writeln!(out, "hello").unwrap();
+            // 改行で終わらない行はflushで書き出す
+            write!(out, "no newline").unwrap();
+            out.flush().unwrap();
+        })
+        .unwrap();
+
+    runtime.as_mut().run();
+    io::stdout().flush().unwrap();
+}
@@ -0,0 +1,73 @@
+use std::cell::Cell;
+use std::rc::Rc;
+use std::time::Duration;
+
+use greenthreads::supervisor::{RestartStrategy, Supervisor};
+use greenthreads::{sleep, yield_thread, Runtime};
+
+fn main() {
+    // パニックの表示を短くする
+    std::panic::set_hook(Box::new(|info| {
+        let msg = info.payload().downcast_ref::<&str>().unwrap_or(&"?");
+        println!("  panic: {}", msg);
+    }));
+
+    let mut runtime = Runtime::new();
+    runtime.as_mut().init();
+
+    // 2回パニックしてから成功する子と、ずっと動いている子
+    println!("one for one:");
+    let attempts = Rc::new(Cell::new(0));
+    let a = attempts.clone();
+    let supervisor = Supervisor::new(RestartStrategy::OneForOne)
+        .child("flaky", move || {
+            a.set(a.get() + 1);
+            println!("  flaky: attempt {}", a.get());
+            yield_thread();
+            if a.get() < 3 {
+                panic!("flaky failed");
+            }
+            println!("  flaky: done");
+        })
+        .child("steady", || {
+            println!("  steady: started");
+            for _ in 0..5 {
+                yield_thread();
+            }
+            println!("  steady: done");
+        });
+    let result = runtime
+        .as_mut()
+        .spawn_supervisor(supervisor)
+        .unwrap()
+        .join()
+        .unwrap();
+    println!("result: {:?}", result);
+
+    // 1つがパニックしたら、もう1つもキャンセルして両方作り直す
+    // 再起動の上限を超えたら諦める
+    println!("one for all:");
+    let supervisor = Supervisor::new(RestartStrategy::OneForAll)
+        .max_restarts(2, Duration::from_secs(1))
+        .child("crasher", || {
+            println!("  crasher: started");
+            yield_thread();
+            panic!("crasher always fails");
+        })
+        .child("sleeper", || {
+            println!("  sleeper: started");
+            if sleep(Duration::from_secs(10)).is_err() {
+                println!("  sleeper: cancelled");
+            }
+        });
+    let result = runtime
+        .as_mut()
+        .spawn_supervisor(supervisor)
+        .unwrap()
+        .join()
+        .unwrap();
+    match result {
+        Ok(()) => println!("result: ok"),
+        Err(e) => println!("result: {}", e),
+    }
+}
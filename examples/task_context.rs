@@ -0,0 +1,60 @@
+use std::time::Duration;
+
+use greenthreads::sync::mpsc::channel;
+use greenthreads::{sleep, with_value, Context, ContextKey, Runtime, TaskGroup};
+
+static REQUEST_ID: ContextKey<u32> = ContextKey::new("request_id");
+static USER: ContextKey<String> = ContextKey::new("user");
+
+// どのスレッドから呼ばれても、今のコンテキストのリクエストIDを付けて出力する
+fn log(message: &str) {
+    println!(
+        "[request {:?} user {:?}] {}",
+        REQUEST_ID.get(),
+        USER.get(),
+        message
+    );
+}
+
+fn main() {
+    let mut runtime = Runtime::new();
+
+    // パイプラインの後段
+    // NOTE: 先に生成したスレッドはコンテキストを引き継がないので、処理と一緒に送られたコンテキストに入る
+    let (tx, rx) = channel::<(Context, String)>();
+    runtime
+        .as_mut()
+        .spawn(move || {
+            while let Ok((context, body)) = rx.recv() {
+                context.enter(|| log(&format!("stored {:?}", body)));
+            }
+        })
+        .unwrap();
+
+    runtime.as_mut().block_on_main(move || {
+        for id in 1..=2 {
+            with_value(&REQUEST_ID, id, || {
+                log("accepted");
+                // 生成したスレッドはコンテキストを引き継ぐ
+                let mut group: TaskGroup<String> = TaskGroup::new();
+                group
+                    .spawn(|| {
+                        with_value(&USER, "alice".to_string(), || {
+                            sleep(Duration::from_millis(10)).unwrap();
+                            log("authenticated");
+                        });
+                        log("after the inner scope");
+                        Ok(())
+                    })
+                    .unwrap();
+                group.join().unwrap();
+                tx.send((Context::current(), format!("body of {}", id)))
+                    .unwrap();
+            });
+        }
+        log("all requests dispatched");
+        println!("{:?}", Context::current().with_value(&REQUEST_ID, 3));
+    });
+
+    runtime.as_mut().run();
+}
@@ -0,0 +1,61 @@
+use std::time::Duration;
+
+use greenthreads::{sleep, yield_thread, Runtime, TaskGroup};
+
+fn main() {
+    let mut runtime = Runtime::new();
+    runtime.as_mut().init();
+
+    // joinはすべてのスレッドを待ち、失敗したものをまとめて返す
+    let mut group = TaskGroup::new();
+    for id in 0..3 {
+        group
+            .spawn(move || {
+                yield_thread();
+                if id == 1 {
+                    return Err(format!("task {} failed", id));
+                }
+                println!("task {} done", id);
+                Ok(())
+            })
+            .unwrap();
+    }
+    match group.join() {
+        Ok(()) => println!("all tasks succeeded"),
+        Err(e) => println!("join: {}", e),
+    }
+
+    // グループをキャンセルすると、グループの中で作ったグループのスレッドもキャンセルされる
+    let mut outer: TaskGroup<String> = TaskGroup::new();
+    outer
+        .spawn(|| {
+            let mut inner: TaskGroup<String> = TaskGroup::new();
+            inner
+                .spawn(|| loop {
+                    // yieldしたところで巻き戻される
+                    yield_thread();
+                })
+                .unwrap();
+            match inner.join() {
+                Ok(()) => Ok(()),
+                Err(e) => Err(format!("inner: {:?}", e.errors)),
+            }
+        })
+        .unwrap();
+    outer
+        .spawn(|| {
+            // ブロックしている処理はErr(Cancelled)を返す
+            sleep(Duration::from_secs(10)).map_err(|e| format!("sleeper: {}", e))
+        })
+        .unwrap();
+    sleep(Duration::from_millis(10)).unwrap();
+    outer.cancel();
+    match outer.join() {
+        Ok(()) => println!("outer succeeded"),
+        Err(e) => {
+            for error in e.errors {
+                println!("outer: {}", error);
+            }
+        }
+    }
+}
@@ -0,0 +1,33 @@
+use greenthreads::{current, Runtime, ThreadFinished};
+
+fn main() {
+    let mut runtime = Runtime::new();
+    runtime.as_mut().init();
+
+    let first = runtime
+        .as_mut()
+        .spawn_named("first", || current().thread_id())
+        .unwrap();
+    let old = first.thread().clone();
+    println!("first: {}", first.join().unwrap());
+
+    // 終わったスレッドの枠は次に生成したスレッドに使い回される
+    let second = runtime
+        .as_mut()
+        .spawn_named("second", || {
+            greenthreads::park();
+            current().thread_id()
+        })
+        .unwrap();
+    let new = second.thread().clone();
+    assert_eq!(old.id(), new.id());
+    assert_ne!(old.thread_id(), new.thread_id());
+
+    // 古いハンドルは新しいスレッドではなく、終わったスレッドを指したまま
+    println!("old: state: {:?} name: {:?}", old.state(), old.name());
+    assert_eq!(old.unpark(), Err(ThreadFinished));
+    println!("new: state: {:?} name: {:?}", new.state(), new.name());
+
+    new.unpark().unwrap();
+    println!("second: {}", second.join().unwrap());
+}
@@ -0,0 +1,46 @@
+use std::time::Duration;
+
+use greenthreads::multi::MultiRuntime;
+use greenthreads::{sleep, Runtime};
+
+// 現在のOSスレッドの名前
+// NOTE: top -Hやgdbのinfo threadsに出るのと同じ名前
+fn os_thread_name() -> String {
+    std::fs::read_to_string("/proc/thread-self/comm")
+        .map(|name| name.trim_end().to_string())
+        .unwrap_or_else(|_| "?".to_string())
+}
+
+fn main() {
+    let mut runtime = Runtime::builder().os_thread_names(true).build();
+    runtime
+        .as_mut()
+        .spawn_named("fetcher", || {
+            println!("fetcher runs as OS thread {:?}", os_thread_name());
+            sleep(Duration::from_millis(10)).unwrap();
+            println!("fetcher resumed as OS thread {:?}", os_thread_name());
+        })
+        .unwrap();
+    runtime
+        .as_mut()
+        .spawn(|| println!("unnamed runs as OS thread {:?}", os_thread_name()))
+        .unwrap();
+    // パニックのメッセージの前に"panic in green thread 3 (parser)"と書かれる
+    let parser = runtime
+        .as_mut()
+        .spawn_named("parser", || panic!("unexpected token"))
+        .unwrap();
+    runtime.as_mut().run();
+    assert!(parser.join().is_err());
+    println!("back on OS thread {:?}", os_thread_name());
+
+    // ワーカーのOSスレッドはworker-<番号>で、名前を付けたタスクを実行している間はその名前になる
+    let mut multi = MultiRuntime::new(2);
+    for i in 0..4 {
+        multi.spawn_named(format!("job-{}", i), move || {
+            println!("job {} runs as OS thread {:?}", i, os_thread_name());
+        });
+    }
+    multi.spawn(|| println!("unnamed job runs as OS thread {:?}", os_thread_name()));
+    multi.run();
+}
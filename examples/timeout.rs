@@ -0,0 +1,80 @@
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+use greenthreads::sync::{mpsc, Mutex};
+use greenthreads::{sleep, Runtime};
+
+fn main() {
+    let mut runtime = Runtime::new();
+    runtime.as_mut().init();
+
+    let start = Instant::now();
+    let mutex = Rc::new(Mutex::new(0));
+    let (tx, rx) = mpsc::channel::<&str>();
+
+    // ロックを持ったまま長くスリープするスレッド
+    let holder = {
+        let mutex = mutex.clone();
+        runtime
+            .as_mut()
+            .spawn(move || {
+                let _lock = mutex.lock();
+                sleep(Duration::from_millis(300)).unwrap();
+                drop(tx);
+                "done"
+            })
+            .unwrap()
+    };
+
+    runtime
+        .as_mut()
+        .spawn(move || {
+            // ロックが解放されないので期限で諦める
+            let lock = mutex.lock_timeout(Duration::from_millis(100));
+            println!(
+                "lock_timeout: {:?} elapsed: {:?}",
+                lock.is_some(),
+                start.elapsed()
+            );
+
+            // 値が届かないので期限で諦める
+            let received = rx.recv_timeout(Duration::from_millis(50));
+            println!(
+                "recv_timeout: {:?} elapsed: {:?}",
+                received,
+                start.elapsed()
+            );
+
+            // 期限までに終わらなければハンドルが返ってくるので、もう一度待てる
+            let holder = match holder.join_timeout(Duration::from_millis(50)) {
+                Ok(_) => unreachable!(),
+                Err(holder) => {
+                    println!("join_timeout: timed out elapsed: {:?}", start.elapsed());
+                    holder
+                }
+            };
+            let result = holder.join_timeout(Duration::from_secs(1));
+            println!(
+                "join_timeout: {:?} elapsed: {:?}",
+                result.ok().map(|r| r.unwrap()),
+                start.elapsed()
+            );
+
+            // 送信側がドロップされたので期限を待たずに戻る
+            let received = rx.recv_timeout(Duration::from_secs(1));
+            println!(
+                "recv_timeout: {:?} elapsed: {:?}",
+                received,
+                start.elapsed()
+            );
+            let lock = mutex.lock_timeout(Duration::from_millis(100));
+            println!(
+                "lock_timeout: {:?} elapsed: {:?}",
+                lock.is_some(),
+                start.elapsed()
+            );
+        })
+        .unwrap();
+
+    runtime.as_mut().run();
+}
@@ -0,0 +1,46 @@
+use std::cell::Cell;
+use std::thread;
+
+use greenthreads::multi::MultiRuntime;
+use greenthreads::{green_local, yield_thread};
+
+thread_local! {
+    // OSスレッド(ワーカー)ごとの値で、同じワーカーで動くタスクで共有する
+    static WORKER_COUNTER: Cell<usize> = const { Cell::new(0) };
+}
+
+green_local! {
+    // グリーンスレッドごとの値
+    static TASK_COUNTER: Cell<usize> = Cell::new(0);
+}
+
+fn main() {
+    let mut runtime = MultiRuntime::new(2);
+
+    for id in 0..4 {
+        let task = move || {
+            // 始まってから終わるまで同じワーカーで動くので、thread_local!は同じOSスレッドのものを指し続ける
+            let worker = thread::current().id();
+            for _ in 0..3 {
+                WORKER_COUNTER.with(|c| c.set(c.get() + 1));
+                TASK_COUNTER.with(|c| c.set(c.get() + 1));
+                yield_thread();
+                assert_eq!(thread::current().id(), worker);
+            }
+            println!(
+                "task: {} worker counter: {} task counter: {}",
+                id,
+                WORKER_COUNTER.with(|c| c.get()),
+                TASK_COUNTER.with(|c| c.get())
+            );
+        };
+        // 偶数のタスクは0番のワーカーに固定する
+        if id % 2 == 0 {
+            runtime.spawn_on(0, task);
+        } else {
+            runtime.spawn(task);
+        }
+    }
+
+    runtime.run();
+}
@@ -0,0 +1,46 @@
+use std::time::{Duration, Instant};
+
+use greenthreads::sync::mpsc;
+use greenthreads::{sleep, yield_thread, Runtime, TraceEvent};
+
+// cargo run --example trace --features trace
+fn main() {
+    let mut runtime = Runtime::new();
+    let start = Instant::now();
+    runtime.as_mut().on_trace(move |at, event| {
+        let at = at.duration_since(start);
+        match event {
+            TraceEvent::Spawn { id, name: None } => println!("{:>10?} spawn  {}", at, id),
+            TraceEvent::Spawn {
+                id,
+                name: Some(name),
+            } => println!("{:>10?} spawn  {} ({})", at, id, name),
+            TraceEvent::Switch { from, to } => println!("{:>10?} switch {} -> {}", at, from, to),
+            TraceEvent::Block { id } => println!("{:>10?} block  {}", at, id),
+            TraceEvent::Wake { id } => println!("{:>10?} wake   {}", at, id),
+            TraceEvent::Exit { id } => println!("{:>10?} exit   {}", at, id),
+        }
+    });
+
+    let (tx, rx) = mpsc::channel();
+    runtime
+        .as_mut()
+        .spawn_named("receiver", move || {
+            for value in rx.iter() {
+                println!("           recv {}", value);
+            }
+        })
+        .unwrap();
+    runtime
+        .as_mut()
+        .spawn_named("sender", move || {
+            for value in 0..2 {
+                tx.send(value).unwrap();
+                yield_thread();
+            }
+            sleep(Duration::from_millis(10)).unwrap();
+        })
+        .unwrap();
+
+    runtime.as_mut().run();
+}
@@ -0,0 +1,41 @@
+use greenthreads::net::UdpSocket;
+use greenthreads::Runtime;
+
+fn main() {
+    let mut runtime = Runtime::new();
+    runtime.as_mut().init();
+
+    let server = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let addr = server.local_addr().unwrap();
+
+    runtime
+        .as_mut()
+        .spawn(move || {
+            // データグラムが届くまでの間は他のスレッドが実行される
+            let mut buf = [0_u8; 1024];
+            for _ in 0..2 {
+                let (n, peer) = server.recv_from(&mut buf).unwrap();
+                println!("server: {} bytes from {}", n, peer);
+                server.send_to(&buf[..n], peer).unwrap();
+            }
+        })
+        .unwrap();
+
+    for id in 1..=2 {
+        runtime
+            .as_mut()
+            .spawn(move || {
+                let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+                socket.connect(addr).unwrap();
+                socket
+                    .send(format!("ping from thread {}", id).as_bytes())
+                    .unwrap();
+                let mut buf = [0_u8; 1024];
+                let n = socket.recv(&mut buf).unwrap();
+                println!("client {}: {}", id, String::from_utf8_lossy(&buf[..n]));
+            })
+            .unwrap();
+    }
+
+    runtime.as_mut().run();
+}
@@ -0,0 +1,52 @@
+use std::io::{Read, Write};
+use std::net::Shutdown;
+
+use greenthreads::net::{UnixListener, UnixStream};
+use greenthreads::Runtime;
+
+fn main() {
+    let mut runtime = Runtime::new();
+    runtime.as_mut().init();
+
+    let path = std::env::temp_dir().join(format!("greenthreads-{}.sock", std::process::id()));
+    let listener = UnixListener::bind(&path).unwrap();
+
+    runtime
+        .as_mut()
+        .spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut request = String::new();
+            stream.read_to_string(&mut request).unwrap();
+            println!("server: {}", request);
+            stream.write_all(request.to_uppercase().as_bytes()).unwrap();
+        })
+        .unwrap();
+
+    let client_path = path.clone();
+    runtime
+        .as_mut()
+        .spawn(move || {
+            let mut stream = UnixStream::connect(&client_path).unwrap();
+            stream.write_all(b"hello over a unix socket").unwrap();
+            stream.shutdown(Shutdown::Write).unwrap();
+            let mut reply = String::new();
+            stream.read_to_string(&mut reply).unwrap();
+            println!("client: {}", reply);
+        })
+        .unwrap();
+
+    // つながったペアはプロセス内の通信に使える
+    let (a, b) = UnixStream::pair().unwrap();
+    runtime
+        .as_mut()
+        .spawn(move || {
+            let mut buf = [0_u8; 16];
+            let n = (&b).read(&mut buf).unwrap();
+            println!("pair: {}", String::from_utf8_lossy(&buf[..n]));
+        })
+        .unwrap();
+    (&a).write_all(b"ping").unwrap();
+
+    runtime.as_mut().run();
+    std::fs::remove_file(&path).unwrap();
+}
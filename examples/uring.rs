@@ -0,0 +1,55 @@
+use std::io::{Read, Write};
+use std::time::Duration;
+
+use greenthreads::uring::fs::File;
+use greenthreads::uring::net::{TcpListener, TcpStream};
+use greenthreads::{sleep, Runtime};
+
+fn main() {
+    let mut runtime = Runtime::new();
+    let path = std::env::temp_dir().join("greenthreads-uring.txt");
+
+    // ファイルの読み書きはio_uringに任せるので、その間も他のスレッドは動き続ける
+    let file = runtime
+        .as_mut()
+        .spawn(move || {
+            let mut file = File::create(&path).unwrap();
+            file.write_all(b"hello from io_uring\n").unwrap();
+            let mut contents = String::new();
+            File::open(&path)
+                .unwrap()
+                .read_to_string(&mut contents)
+                .unwrap();
+            std::fs::remove_file(&path).unwrap();
+            print!("file: {}", contents);
+        })
+        .unwrap();
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let server = runtime
+        .as_mut()
+        .spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut buf = [0; 64];
+            let n = stream.read(&mut buf).unwrap();
+            stream.write_all(&buf[..n]).unwrap();
+        })
+        .unwrap();
+    let client = runtime
+        .as_mut()
+        .spawn(move || {
+            sleep(Duration::from_millis(10)).unwrap();
+            let mut stream = TcpStream::connect(addr).unwrap();
+            stream.write_all(b"ping").unwrap();
+            let mut buf = [0; 64];
+            let n = stream.read(&mut buf).unwrap();
+            println!("echo: {}", String::from_utf8_lossy(&buf[..n]));
+        })
+        .unwrap();
+
+    runtime.as_mut().run();
+    file.join().unwrap();
+    server.join().unwrap();
+    client.join().unwrap();
+}
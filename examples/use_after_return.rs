@@ -0,0 +1,39 @@
+use std::cell::Cell;
+use std::rc::Rc;
+
+use greenthreads::{yield_thread, Runtime};
+
+// 終わったスレッドのスタックを指すポインタで書き込む
+// NOTE: デバッグビルドでは、プールに戻したスタックは読み書きできないので、診断を出してabortする
+//       リリースビルドでは黙ってスタックを壊す
+fn main() {
+    let mut runtime = Runtime::new();
+    runtime.as_mut().init();
+
+    let leaked = Rc::new(Cell::new(0usize));
+
+    let l = leaked.clone();
+    runtime
+        .as_mut()
+        .spawn(move || {
+            let mut local = 1u64;
+            // スタック上の変数のアドレスを外に漏らしたまま終わる
+            l.set(&mut local as *mut u64 as usize);
+            println!("thread 1: leaked {:#x} and finished", l.get());
+        })
+        .unwrap();
+
+    runtime
+        .as_mut()
+        .spawn(move || {
+            // スレッド1が終わるのを待つ
+            yield_thread();
+            yield_thread();
+            println!("thread 2: writing to {:#x}", leaked.get());
+            unsafe { *(leaked.get() as *mut u64) = 2 };
+            println!("thread 2: the write went unnoticed");
+        })
+        .unwrap();
+
+    runtime.as_mut().run();
+}
@@ -0,0 +1,34 @@
+use std::time::Duration;
+
+use greenthreads::sync::WaitGroup;
+use greenthreads::{sleep, Runtime};
+
+fn main() {
+    let mut runtime = Runtime::new();
+    runtime.as_mut().init();
+
+    let wg = WaitGroup::new();
+    for id in 1..=2 {
+        wg.add(1);
+        let wg = wg.clone();
+        runtime
+            .as_mut()
+            .spawn(move || {
+                sleep(Duration::from_millis(100 * id)).unwrap();
+                println!("worker: {} done", id);
+                wg.done();
+            })
+            .unwrap();
+    }
+
+    runtime
+        .as_mut()
+        .spawn(move || {
+            // すべてのワーカーが終わるまでブロックする
+            wg.wait();
+            println!("all workers finished");
+        })
+        .unwrap();
+
+    runtime.as_mut().run();
+}
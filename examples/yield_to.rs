@@ -0,0 +1,70 @@
+use std::cell::Cell;
+use std::rc::Rc;
+use std::time::Instant;
+
+use greenthreads::{yield_thread, yield_to, PinnedRuntime, Runtime};
+
+const ROUNDS: usize = 100_000;
+
+// 2つのスレッドで交互にカウンタを進める
+// 他に再開可能なスレッドがあっても、yield_toなら相手に直接切り替わる
+fn ping_pong(direct: bool) {
+    let mut runtime = Runtime::new();
+    runtime.as_mut().init();
+
+    let ids = Rc::new(Cell::new((0, 0)));
+    let counter = Rc::new(Cell::new(0));
+    let done = Rc::new(Cell::new(false));
+    let spawn_player = |runtime: &mut PinnedRuntime, parity: usize| {
+        let (ids, counter, done) = (ids.clone(), counter.clone(), done.clone());
+        runtime
+            .as_mut()
+            .spawn(move || {
+                while counter.get() < ROUNDS {
+                    if counter.get() % 2 == parity {
+                        counter.set(counter.get() + 1);
+                    }
+                    let (a, b) = ids.get();
+                    let other = if parity == 0 { b } else { a };
+                    if direct {
+                        yield_to(other);
+                    } else {
+                        yield_thread();
+                    }
+                }
+                done.set(true);
+            })
+            .unwrap()
+            .thread()
+            .id()
+    };
+    let a = spawn_player(&mut runtime, 0);
+    let b = spawn_player(&mut runtime, 1);
+    ids.set((a, b));
+    // 順番を待たせるだけのスレッド
+    // NOTE: ベーススレッドを含めて4つまでしか同時に動かせないので1つだけ
+    let waiter = done.clone();
+    runtime
+        .as_mut()
+        .spawn(move || {
+            while !waiter.get() {
+                yield_thread();
+            }
+        })
+        .unwrap();
+
+    let start = Instant::now();
+    runtime.as_mut().run();
+    println!(
+        "{}: {} rounds in {:?} ({} switches)",
+        if direct { "yield_to" } else { "yield_thread" },
+        counter.get(),
+        start.elapsed(),
+        runtime.stats().switches
+    );
+}
+
+fn main() {
+    ping_pong(false);
+    ping_pong(true);
+}
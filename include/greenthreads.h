@@ -0,0 +1,47 @@
+/*
+ * greenthreadsのランタイムをCから動かすためのヘッダ
+ * NOTE: src/ffi.rsと対応させること
+ *       共有ライブラリは cargo rustc --lib --release --features ffi --crate-type cdylib でビルドする
+ */
+#ifndef GREENTHREADS_H
+#define GREENTHREADS_H
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/* 成功 */
+#define GT_OK 0
+/* 失敗(ランタイムがない、スレッドを生成できない、デッドロックしたなど) */
+#define GT_ERROR (-1)
+/* スレッドがキャンセルされた(タスク関数はできるだけ早く戻ること) */
+#define GT_CANCELLED (-2)
+
+typedef struct gt_runtime gt_runtime;
+
+/* タスク関数(userdataはgt_spawnに渡したものがそのまま渡される) */
+typedef void (*gt_task)(void *userdata);
+
+/* ランタイムを作り、このOSスレッドのランタイムにする(失敗した場合はNULL) */
+gt_runtime *gt_runtime_new(void);
+
+/* ランタイムを解放する(終わっていないスレッドはキャンセルし、戻るまで待つ) */
+void gt_runtime_free(gt_runtime *rt);
+
+/* task(userdata)を実行するスレッドを生成し、スレッドのIDを返す(失敗した場合はGT_ERROR) */
+int gt_spawn(gt_task task, void *userdata);
+
+/* 他のスレッドに切り替える(キャンセルされていたらGT_CANCELLED) */
+int gt_yield(void);
+
+/* 現在のスレッドのID(ベーススレッドは0) */
+int gt_current(void);
+
+/* すべてのスレッドが終わるまで実行する */
+int gt_run(gt_runtime *rt);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
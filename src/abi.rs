@@ -0,0 +1,67 @@
+// スレッドのスタックがABIの求める形になっているかの検査
+// NOTE: Cのライブラリ(printfの可変長引数やSSEを使う関数など)は、呼び出す時点でスタックポインタが16byte境界に
+//       揃っていることを前提にmovapsなどを使うので、ずれていると呼び出した先で落ちる
+//       ずれはスレッドを作るときのスタックの形の誤りから生じるので、形を直接確かめられるようにする
+use std::error::Error;
+
+use crate::context::StackLayoutError;
+use crate::{preempt, runtime_ptr, thread_main, Runtime, State, ThreadId};
+
+impl Error for StackLayoutError {}
+
+impl Runtime {
+    // indexのスレッドのスタックの形を確かめる
+    // 実行中のスレッドは保存したスタックポインタがないので、今のスタックポインタがスタックの中にあるかだけを確かめる
+    pub(crate) fn verify_stack(&self, index: usize) -> Result<(), StackLayoutError> {
+        let thread = &self.threads[index];
+        if thread.state == State::Available {
+            return Err(StackLayoutError::Finished);
+        }
+        let bounds = thread
+            .stack
+            .as_ref()
+            .map(|stack| (stack.bottom(), stack.top() as usize));
+        if index == self.current {
+            let sp = crate::context::current_stack_pointer();
+            return match bounds {
+                Some((bottom, top)) if sp < bottom || sp >= top => {
+                    Err(StackLayoutError::OutOfBounds { sp })
+                }
+                _ => Ok(()),
+            };
+        }
+        // NOTE: タスクがまだ取り出されていなければ、一度も切り替えていないスレッド
+        let fresh = thread
+            .task
+            .is_some()
+            .then_some((thread_main as extern "C" fn(usize), 0));
+        unsafe { thread.ctx.verify(fresh, bounds) }
+    }
+
+    // Builder::strict_abiを指定した場合に、作ったばかりのスレッドのスタックの形を確かめる
+    // NOTE: 誤りがあるまま切り替えると、どこで落ちたか分からなくなるので、その場でパニックする
+    pub(crate) fn check_new_stack(&self, index: usize) {
+        if !self.strict_abi {
+            return;
+        }
+        if let Err(e) = self.verify_stack(index) {
+            panic!(
+                "strict ABI: the new stack of thread {} is broken: {}",
+                index, e
+            );
+        }
+    }
+}
+
+// idのスレッドのスタックが、切り替えたときにABIの求める形になるかを確かめる
+// まだ始まっていないスレッドは、最初に飛ぶ先(偽の戻りアドレス)とentryの呼び出し時点の16byte境界への揃い方を、
+// 止まっているスレッドは、保存したスタックポインタの位置と揃い方、MXCSRを確かめる
+// NOTE: 名前の通りデバッグ用で、スタックを読むだけなので遅くはないが、すべての誤りを見つけられるわけではない
+pub fn debug_verify_stack(id: ThreadId) -> Result<(), StackLayoutError> {
+    let _guard = preempt::disable();
+    let rt = unsafe { &*runtime_ptr() };
+    if rt.thread_of(id).is_none() {
+        return Err(StackLayoutError::Finished);
+    }
+    rt.verify_stack(id.index())
+}
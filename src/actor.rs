@@ -0,0 +1,130 @@
+// メッセージをやり取りするアクター
+// NOTE: アクターは1つのスレッドで動き、メールボックス(容量のあるmpscチャネル)に届いたメッセージを順番にhandleで処理する
+//       メールボックスが満杯の場合、Address::sendは空きができるまでブロックするので、送る側が処理の速さに合わせられる
+//       アドレスがすべてドロップされるか、スレッドがキャンセルされると止まる
+use std::error::Error;
+use std::fmt;
+use std::pin::Pin;
+
+use crate::sync::mpsc::{self, SendError, TrySendError};
+use crate::sync::oneshot;
+use crate::{JoinHandle, Runtime, SpawnError};
+
+// メールボックスに溜められるメッセージの数の既定値
+pub const DEFAULT_MAILBOX_CAPACITY: usize = 32;
+
+pub trait Actor: 'static {
+    type Message: 'static;
+
+    // 最初のメッセージを受け取る前に呼ばれる
+    fn started(&mut self) {}
+
+    // メッセージを1つ処理する
+    fn handle(&mut self, msg: Self::Message);
+
+    // メッセージを受け取れなくなって止まるときに呼ばれる
+    fn stopped(&mut self) {}
+}
+
+// アクターにメッセージを送るためのアドレス
+// NOTE: クローンして複数のスレッドから送ってよい
+pub struct Address<M> {
+    sender: mpsc::Sender<M>,
+}
+
+impl<M> Address<M> {
+    // メッセージを送る
+    // メールボックスが満杯の場合は空きができるまでブロックする
+    // アクターが止まっている場合はメッセージをそのまま返す
+    pub fn send(&self, msg: M) -> Result<(), SendError<M>> {
+        self.sender.send(msg)
+    }
+
+    // ブロックせずにメッセージを送る
+    pub fn try_send(&self, msg: M) -> Result<(), TrySendError<M>> {
+        self.sender.try_send(msg)
+    }
+
+    // 返事を受け取るためのoneshot::Senderを渡してメッセージを作って送り、返事が届くまでブロックする
+    pub fn call<R, F>(&self, f: F) -> Result<R, CallError>
+    where
+        F: FnOnce(oneshot::Sender<R>) -> M,
+    {
+        let (reply, rx) = oneshot::channel();
+        self.sender.send(f(reply)).map_err(|_| CallError::Stopped)?;
+        rx.recv().map_err(|e| match e {
+            oneshot::RecvError::Disconnected => CallError::NoReply,
+            oneshot::RecvError::Cancelled => CallError::Cancelled,
+        })
+    }
+}
+
+impl<M> Clone for Address<M> {
+    fn clone(&self) -> Self {
+        Address {
+            sender: self.sender.clone(),
+        }
+    }
+}
+
+impl<M> fmt::Debug for Address<M> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Address").finish_non_exhaustive()
+    }
+}
+
+// callで返事を受け取れなかった理由
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum CallError {
+    // アクターが止まっていてメッセージを送れなかった
+    Stopped,
+    // アクターが返事をせずにoneshot::Senderをドロップした
+    NoReply,
+    // 返事を待っている間にキャンセルされた
+    Cancelled,
+}
+
+impl fmt::Display for CallError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CallError::Stopped => "the actor has stopped".fmt(f),
+            CallError::NoReply => "the actor dropped the reply sender".fmt(f),
+            CallError::Cancelled => "waiting for the reply was cancelled".fmt(f),
+        }
+    }
+}
+
+impl Error for CallError {}
+
+impl Runtime {
+    // アクターを動かすスレッドを生成し、アドレスを返す
+    // JoinHandleは止まったアクターを返す
+    pub fn spawn_actor<A: Actor>(
+        self: Pin<&mut Self>,
+        actor: A,
+    ) -> Result<(Address<A::Message>, JoinHandle<A>), SpawnError> {
+        self.spawn_actor_with_capacity(DEFAULT_MAILBOX_CAPACITY, actor)
+    }
+
+    // spawn_actorと同じだが、メールボックスの容量を指定する
+    pub fn spawn_actor_with_capacity<A: Actor>(
+        self: Pin<&mut Self>,
+        capacity: usize,
+        actor: A,
+    ) -> Result<(Address<A::Message>, JoinHandle<A>), SpawnError> {
+        let (sender, mailbox) = mpsc::sync_channel(capacity);
+        let handle = self.spawn(move || run(actor, mailbox))?;
+        Ok((Address { sender }, handle))
+    }
+}
+
+// アクターのスレッドで実行する関数
+fn run<A: Actor>(mut actor: A, mailbox: mpsc::Receiver<A::Message>) -> A {
+    actor.started();
+    // NOTE: アドレスがすべてドロップされるか、キャンセルされるとErrが返る
+    while let Ok(msg) = mailbox.recv() {
+        actor.handle(msg);
+    }
+    actor.stopped();
+    actor
+}
@@ -0,0 +1,262 @@
+// OSのない環境(カーネルなど)で使う、stdに依存しない最小限のランタイム
+// NOTE: Runtimeと同じThreadContextとswitch_to、スケジューラを使い、スレッドの管理だけを自前で行う
+//       スタックは呼び出し側がアロケータなどで確保し、StackMemoryとして渡す
+//       タイマー、I/O、プリエンプション、パニックの捕捉はない
+//       実行中のExecutorはstaticに1つだけ持つので、1つのCPUで同時に動かせるのは1つだけ
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use core::ptr;
+use core::sync::atomic::{AtomicPtr, Ordering};
+use core::time::Duration;
+
+use crate::context::{self, ThreadContext};
+use crate::scheduler::{Scheduler, SchedulerPolicy, SwitchReason};
+
+// スレッドのスタックに使うメモリ
+// NOTE: Executorはスレッドが終わるまで持ち、終わったらドロップする
+pub trait StackMemory {
+    // スタックの一番上(アドレスが大きいほうの端)
+    // NOTE: 16byteに揃っていなくてもよい(切り捨てて使う)
+    fn top(&mut self) -> *mut u8;
+}
+
+impl StackMemory for Box<[u8]> {
+    fn top(&mut self) -> *mut u8 {
+        self.as_mut_ptr_range().end
+    }
+}
+
+impl StackMemory for &'static mut [u8] {
+    fn top(&mut self) -> *mut u8 {
+        self.as_mut_ptr_range().end
+    }
+}
+
+#[derive(PartialEq, Eq, Debug)]
+enum State {
+    Available,
+    Running,
+    Ready,
+    Parked,
+}
+
+struct Thread {
+    ctx: ThreadContext,
+    state: State,
+    task: Option<Box<dyn FnOnce()>>,
+    // NOTE: ベーススレッド(runを呼んだ側)は自分のスタックで動くのでNone
+    stack: Option<Box<dyn StackMemory>>,
+}
+
+pub struct Executor {
+    threads: Vec<Thread>,
+    current: usize,
+    scheduler: Box<dyn Scheduler>,
+    // 終わったスレッドのID
+    // NOTE: 終わったスレッドはまだ自分のスタックの上にいるので、切り替えた後にスタックを解放する
+    finished: Option<usize>,
+}
+
+// 実行中のExecutor
+static CURRENT: AtomicPtr<Executor> = AtomicPtr::new(ptr::null_mut());
+
+fn executor_ptr() -> *mut Executor {
+    let ex = CURRENT.load(Ordering::Relaxed);
+    assert!(!ex.is_null(), "no executor is running.");
+    ex
+}
+
+impl Executor {
+    pub fn new() -> Self {
+        Executor::with_scheduler(SchedulerPolicy::RoundRobin)
+    }
+
+    pub fn with_scheduler(policy: SchedulerPolicy) -> Self {
+        Executor::with_custom_scheduler(policy.build())
+    }
+
+    pub fn with_custom_scheduler(scheduler: Box<dyn Scheduler>) -> Self {
+        let base_thread = Thread {
+            ctx: ThreadContext::default(),
+            state: State::Running,
+            task: None,
+            stack: None,
+        };
+        Executor {
+            threads: alloc::vec![base_thread],
+            current: 0,
+            scheduler,
+            finished: None,
+        }
+    }
+
+    // stackの上でfを実行するスレッドを作り、IDを返す
+    // NOTE: 終わったスレッドのIDは使い回す
+    pub fn spawn<S, F>(&mut self, stack: S, f: F) -> usize
+    where
+        S: StackMemory + 'static,
+        F: FnOnce() + 'static,
+    {
+        let mut stack: Box<dyn StackMemory> = Box::new(stack);
+        let mut ctx = ThreadContext::default();
+        unsafe {
+            // 最初に切り替えたときにthread_mainから始まるようにする
+            ctx.prepare(stack.top(), thread_main, 0);
+        }
+        let thread = Thread {
+            ctx,
+            state: State::Ready,
+            task: Some(Box::new(f)),
+            stack: Some(stack),
+        };
+        let id = match self
+            .threads
+            .iter()
+            .position(|t| t.state == State::Available)
+        {
+            Some(id) => {
+                self.threads[id] = thread;
+                id
+            }
+            None => {
+                self.threads.push(thread);
+                self.threads.len() - 1
+            }
+        };
+        self.scheduler.ready(id);
+        id
+    }
+
+    // 再開可能なスレッドがなくなるまで実行する
+    // NOTE: parkしたままのスレッドは残り、unparkしてからもう一度runを呼ぶと続きから実行する
+    pub fn run(&mut self) {
+        let prev = CURRENT.swap(self, Ordering::Relaxed);
+        assert!(prev.is_null(), "an executor is already running.");
+        unsafe { while Executor::t_yield(self, SwitchReason::Yielded) {} }
+        CURRENT.store(ptr::null_mut(), Ordering::Relaxed);
+    }
+
+    // parkしたスレッドを再開可能にする
+    // NOTE: 割り込みハンドラから呼んではいけない
+    pub fn unpark(&mut self, id: usize) {
+        if self.threads[id].state == State::Parked {
+            self.threads[id].state = State::Ready;
+            self.scheduler.ready(id);
+        }
+    }
+
+    // 実行中でないスレッドの数
+    pub fn pending(&self) -> usize {
+        self.threads
+            .iter()
+            .filter(|t| matches!(t.state, State::Ready | State::Parked))
+            .count()
+    }
+
+    // 次のスレッドに切り替える
+    // 切り替え先がない場合は何もせずにfalseを返す
+    unsafe fn t_yield(ex: *mut Executor, reason: SwitchReason) -> bool {
+        let (old, new) = {
+            let ex = &mut *ex;
+            let next = match ex.scheduler.pick_next() {
+                Some(next) => next,
+                None => return false,
+            };
+            let current = ex.current;
+            ex.scheduler.ran(current, Duration::ZERO, reason);
+            if ex.threads[current].state == State::Running {
+                ex.threads[current].state = State::Ready;
+                ex.scheduler.ready(current);
+            }
+            ex.threads[next].state = State::Running;
+            ex.current = next;
+            let old: *mut ThreadContext = &mut ex.threads[current].ctx;
+            let new: *const ThreadContext = &ex.threads[next].ctx;
+            (old, new)
+        };
+        context::switch_to(old, new);
+
+        (*ex).release_finished();
+        true
+    }
+
+    // 現在のスレッドを止めて、unparkされるまで他のスレッドを実行する
+    // NOTE: ベーススレッドはrunの中で再開可能になっているので、切り替え先は必ずある
+    unsafe fn t_park(ex: *mut Executor) {
+        {
+            let ex = &mut *ex;
+            let current = ex.current;
+            ex.threads[current].state = State::Parked;
+            ex.scheduler.block(current);
+        }
+        Executor::t_yield(ex, SwitchReason::Blocked);
+    }
+
+    // thread_mainから呼ばれ、スレッドが終わったら切り替えて二度と戻らない
+    unsafe fn t_finish(ex: *mut Executor) -> ! {
+        {
+            let ex = &mut *ex;
+            let current = ex.current;
+            ex.threads[current].state = State::Available;
+            ex.finished = Some(current);
+        }
+        Executor::t_yield(ex, SwitchReason::Finished);
+        unreachable!("finished thread was resumed.");
+    }
+
+    fn release_finished(&mut self) {
+        if let Some(id) = self.finished.take() {
+            self.threads[id].stack = None;
+        }
+    }
+}
+
+impl Default for Executor {
+    fn default() -> Self {
+        Executor::new()
+    }
+}
+
+// 実行中のExecutorにスレッドを追加する
+pub fn spawn<S, F>(stack: S, f: F) -> usize
+where
+    S: StackMemory + 'static,
+    F: FnOnce() + 'static,
+{
+    unsafe { (*executor_ptr()).spawn(stack, f) }
+}
+
+pub fn yield_thread() {
+    unsafe {
+        Executor::t_yield(executor_ptr(), SwitchReason::Yielded);
+    }
+}
+
+pub fn park() {
+    unsafe { Executor::t_park(executor_ptr()) }
+}
+
+pub fn unpark(id: usize) {
+    unsafe { (*executor_ptr()).unpark(id) }
+}
+
+// 現在のスレッドのID
+pub fn current() -> usize {
+    unsafe { (*executor_ptr()).current }
+}
+
+// 新しいスレッドが最初に実行する関数
+extern "C" fn thread_main(_: usize) {
+    unsafe {
+        let ex = executor_ptr();
+        let task = {
+            let ex = &mut *ex;
+            ex.release_finished();
+            ex.threads[ex.current].task.take()
+        };
+        if let Some(task) = task {
+            task();
+        }
+        Executor::t_finish(ex);
+    }
+}
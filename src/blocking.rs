@@ -0,0 +1,217 @@
+// ブロックする処理をOSスレッドで実行する
+// NOTE: グリーンスレッドでブロックするシステムコール(名前解決やファイルI/Oなど)を呼ぶと、
+//       同じOSスレッドで動いているすべてのグリーンスレッドが止まってしまう
+//       そういう処理は小さなOSスレッドのプールに渡し、終わるまでグリーンスレッドだけをブロックする
+//       終わったらOSスレッドが完了キューにスレッドのIDを積み、ソケットに1byte書いてリアクターを起こす
+use std::any::Any;
+use std::collections::VecDeque;
+use std::io::{self, Read, Write};
+use std::os::unix::io::{AsRawFd, RawFd};
+use std::os::unix::net::UnixStream;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+
+use crate::deadlock::BlockedOn;
+use crate::reactor::Interest;
+use crate::{preempt, runtime_ptr, Runtime};
+
+// プールのOSスレッドの最大数
+const MAX_BLOCKING_THREADS: usize = 8;
+
+// 完了を知らせるソケットを待っているのがスレッドではなくプールであることを表すID
+pub(crate) const BLOCKING_WAKER: usize = usize::MAX;
+
+type Job = Box<dyn FnOnce() + Send>;
+
+struct Jobs {
+    queue: VecDeque<(usize, Job)>,
+    // 起動したOSスレッドの数
+    threads: usize,
+    // ジョブを待っているOSスレッドの数
+    idle: usize,
+    shutdown: bool,
+}
+
+// RuntimeとプールのOSスレッドで共有する部分
+struct Shared {
+    jobs: Mutex<Jobs>,
+    available: Condvar,
+    // 終わったジョブを待っているスレッドのID
+    completed: Mutex<Vec<usize>>,
+    // NOTE: 書き込み側はOSスレッドが、読み込み側はリアクターが使う
+    notify: UnixStream,
+    wakeup: UnixStream,
+}
+
+pub(crate) struct BlockingPool {
+    shared: Arc<Shared>,
+    // 投入してまだ完了を受け取っていないジョブの数
+    pending: usize,
+    // 読み込み側をリアクターに登録しているかどうか
+    armed: bool,
+}
+
+impl BlockingPool {
+    fn new() -> io::Result<Self> {
+        let (notify, wakeup) = UnixStream::pair()?;
+        wakeup.set_nonblocking(true)?;
+        Ok(BlockingPool {
+            shared: Arc::new(Shared {
+                jobs: Mutex::new(Jobs {
+                    queue: VecDeque::new(),
+                    threads: 0,
+                    idle: 0,
+                    shutdown: false,
+                }),
+                available: Condvar::new(),
+                completed: Mutex::new(Vec::new()),
+                notify,
+                wakeup,
+            }),
+            pending: 0,
+            armed: false,
+        })
+    }
+
+    fn wakeup_fd(&self) -> RawFd {
+        self.shared.wakeup.as_raw_fd()
+    }
+
+    // idのスレッドのジョブを投入する
+    // NOTE: 空いているOSスレッドがなければ、最大数まで新しく起動する
+    fn submit(&mut self, id: usize, job: Job) -> io::Result<()> {
+        let mut jobs = self.shared.jobs.lock().unwrap();
+        if jobs.idle == 0 && jobs.threads < MAX_BLOCKING_THREADS {
+            let shared = self.shared.clone();
+            thread::Builder::new()
+                .name("greenthreads-blocking".to_string())
+                .spawn(move || worker(shared))?;
+            jobs.threads += 1;
+        }
+        jobs.queue.push_back((id, job));
+        self.shared.available.notify_one();
+        self.pending += 1;
+        Ok(())
+    }
+
+    // 終わったジョブを待っているスレッドのIDを取り出す
+    fn take_completed(&mut self) -> Vec<usize> {
+        // NOTE: 書き込まれた分を読み捨てて、次の完了でまたリアクターが起きるようにする
+        let mut buf = [0u8; 64];
+        while let Ok(n) = (&self.shared.wakeup).read(&mut buf) {
+            if n == 0 {
+                break;
+            }
+        }
+        let completed = std::mem::take(&mut *self.shared.completed.lock().unwrap());
+        self.pending -= completed.len();
+        completed
+    }
+}
+
+impl Drop for BlockingPool {
+    // NOTE: 実行中のジョブは待たずに、終わったらOSスレッドが自分で終わる
+    fn drop(&mut self) {
+        self.shared.jobs.lock().unwrap().shutdown = true;
+        self.shared.available.notify_all();
+    }
+}
+
+fn worker(shared: Arc<Shared>) {
+    loop {
+        let (id, job) = {
+            let mut jobs = shared.jobs.lock().unwrap();
+            loop {
+                if let Some(job) = jobs.queue.pop_front() {
+                    break job;
+                }
+                if jobs.shutdown {
+                    jobs.threads -= 1;
+                    return;
+                }
+                jobs.idle += 1;
+                jobs = shared.available.wait(jobs).unwrap();
+                jobs.idle -= 1;
+            }
+        };
+        job();
+        shared.completed.lock().unwrap().push(id);
+        let _ = (&shared.notify).write(&[1]);
+    }
+}
+
+impl Runtime {
+    // jobをプールに渡し、終わるまで現在のスレッドをブロックする
+    unsafe fn t_run_blocking(rt: *mut Runtime, job: Job) -> io::Result<()> {
+        let _guard = preempt::disable();
+        {
+            let rt = &mut *rt;
+            if rt.blocking.is_none() {
+                rt.blocking = Some(BlockingPool::new()?);
+            }
+            let pool = rt.blocking.as_mut().unwrap();
+            pool.submit(rt.current, job)?;
+            if !pool.armed {
+                rt.reactor
+                    .register(pool.wakeup_fd(), Interest::Readable, BLOCKING_WAKER)?;
+                pool.armed = true;
+            }
+        }
+        crate::block_thread(BlockedOn {
+            what: "spawn_blocking",
+            addr: 0,
+            holder: None,
+        });
+        Ok(())
+    }
+
+    // 完了したジョブを待っていたスレッドを再開可能にする
+    // NOTE: リアクターが完了を知らせるソケットの準備ができたと返したときに呼ぶ
+    pub(crate) fn wake_blocking(&mut self) {
+        let pool = match &mut self.blocking {
+            Some(pool) => pool,
+            None => return,
+        };
+        pool.armed = false;
+        let completed = pool.take_completed();
+        // まだ終わっていないジョブがあれば、また起こされるように登録し直す
+        if pool.pending > 0 {
+            let fd = pool.wakeup_fd();
+            self.reactor
+                .register(fd, Interest::Readable, BLOCKING_WAKER)
+                .expect("failed to register the blocking pool.");
+            self.blocking.as_mut().unwrap().armed = true;
+        }
+        for id in completed {
+            self.t_wake(id);
+        }
+    }
+}
+
+// fをOSスレッドのプールで実行し、終わるまで現在のスレッドだけをブロックして結果を返す
+// その間、他のスレッドは動き続ける
+// NOTE: fの中でパニックした場合は、呼び出したスレッドでパニックを再開する
+pub fn spawn_blocking<F, T>(f: F) -> io::Result<T>
+where
+    F: FnOnce() -> T + Send + 'static,
+    T: Send + 'static,
+{
+    type Slot<T> = Mutex<Option<Result<T, Box<dyn Any + Send>>>>;
+    let slot: Arc<Slot<T>> = Arc::new(Mutex::new(None));
+    let their_slot = slot.clone();
+    let job = Box::new(move || {
+        let result = panic::catch_unwind(AssertUnwindSafe(f));
+        *their_slot.lock().unwrap() = Some(result);
+    });
+    unsafe { Runtime::t_run_blocking(runtime_ptr(), job)? };
+    let result = slot
+        .lock()
+        .unwrap()
+        .take()
+        .expect("blocking job has not completed.");
+    match result {
+        Ok(value) => Ok(value),
+        Err(payload) => panic::resume_unwind(payload),
+    }
+}
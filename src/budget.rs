@@ -0,0 +1,109 @@
+// シグナルを使わずに、スレッドが続けて実行できる量を制限する協調的な予算
+// NOTE: チャネルやロック、ソケットの読み書きなどは、待たずに済むと切り替えずにそのまま戻るので、
+//       相手がいつも準備できているスレッドは他のスレッドを止め続けてしまう
+//       そういう操作の入口で予算を1つ使い、使い切っていたら(操作の回数か時間)他のスレッドに切り替える
+//       プリエンプションと違い、予算を使う操作を呼ばないループは止められないので、
+//       続けて実行した時間が長すぎたスレッドは切り替えるときに標準エラー出力に報告する
+//
+// 計算のループなど、予算を使う操作を呼ばない処理にはmaybe_yield(checkpoint!)を挟む
+// NOTE: 毎回切り替えるyield_threadと違い、時刻を比べるだけなので、ループのたびに呼んでも遅くならない
+use std::time::{Duration, Instant};
+
+use crate::{preempt, Runtime, CURRENT};
+
+// Builder::time_sliceを指定していない場合に、maybe_yieldが切り替えずに続けて実行させる時間
+const DEFAULT_CHECKPOINT_SLICE: Duration = Duration::from_millis(10);
+
+impl Runtime {
+    // 予算を1つ使い、使い切っていたらtrueを返す
+    fn consume_budget(&mut self) -> bool {
+        if let Some(ops) = self.op_budget {
+            self.budget_used += 1;
+            if self.budget_used >= ops {
+                return true;
+            }
+        }
+        match self.time_budget {
+            Some(time) => self.budget_since.elapsed() >= time,
+            None => false,
+        }
+    }
+
+    // 切り替え元のスレッドの予算を締め、時間の予算を超えて実行していたら報告する
+    // NOTE: 切り替え先を選んだ後、切り替える前に呼ぶ
+    pub(crate) fn end_budget(&mut self, from: usize) {
+        self.budget_used = 0;
+        let now = Instant::now();
+        let ran = now - self.budget_since;
+        self.budget_since = now;
+        if let Some(time) = self.time_budget {
+            if ran > time {
+                warn_hog(self.threads[from].id, ran);
+            }
+        }
+    }
+
+    // 実行中のスレッドが、切り替えずに続けて実行してよい時間を超えたかどうか
+    fn checkpoint_expired(&self) -> bool {
+        let slice = self.time_slice.unwrap_or(DEFAULT_CHECKPOINT_SLICE);
+        self.budget_since.elapsed() >= slice
+    }
+
+    // 再開可能なスレッドがなくて休止していた時間を、実行していた時間に数えないようにする
+    pub(crate) fn restart_budget(&mut self) {
+        self.budget_since = Instant::now();
+    }
+}
+
+fn warn_hog(id: usize, ran: Duration) {
+    eprintln!(
+        "warning: thread {} hogged the CPU for {:.1} ms",
+        id,
+        ran.as_secs_f64() * 1000.0
+    );
+}
+
+// 予算を1つ使い、使い切っていたら他のスレッドに切り替える
+// NOTE: Builder::budgetもtime_budgetも指定しない場合や、ランタイムの外では何もしない
+//       操作の途中で呼ばれるので、キャンセルされていても巻き戻さない
+//       待たずに済む操作を繰り返すループの中で呼ぶと、他のスレッドにも実行する機会を与えられる
+pub fn consume_budget() {
+    let rt = CURRENT.with(|current| current.get());
+    if rt.is_null() {
+        return;
+    }
+    let _guard = preempt::disable();
+    unsafe {
+        if (*rt).consume_budget() {
+            Runtime::t_yield(rt);
+        }
+    }
+}
+
+// 現在のスレッドが続けて実行した時間がtime_slice(指定していなければ10ms)を超えていれば、他のスレッドに切り替える
+// NOTE: 切り替えた場合はyield_threadと同じく、キャンセルされていれば巻き戻す
+//       他に再開可能なスレッドがなくて切り替えなかった場合は、そこからまた数え直す
+pub fn maybe_yield() {
+    let rt = CURRENT.with(|current| current.get());
+    if rt.is_null() {
+        return;
+    }
+    unsafe {
+        if !(*rt).checkpoint_expired() {
+            return;
+        }
+        if !Runtime::t_yield(rt) {
+            (*rt).restart_budget();
+        }
+        Runtime::check_cancelled_at_yield(rt);
+    }
+}
+
+// 計算のループなどに挟んで、時間を使いすぎていれば他のスレッドに切り替える
+// maybe_yieldと同じ
+#[macro_export]
+macro_rules! checkpoint {
+    () => {
+        $crate::maybe_yield()
+    };
+}
@@ -0,0 +1,307 @@
+use std::time::Duration;
+
+use crate::config::{self, ConfigError};
+use crate::deadlock::{Deadlock, DeadlockHandler};
+use crate::dump;
+use crate::fault::{FaultInjection, Faults};
+use crate::naming::OsThreadName;
+use crate::scheduler::{Scheduler, SchedulerPolicy};
+use crate::shutdown::DEFAULT_SHUTDOWN_GRACE;
+use crate::stack::{
+    MmapStackAllocator, StackAllocator, StackRecyclePolicy, DEFAULT_MAX_IDLE_STACKS,
+};
+use crate::{PinnedRuntime, Runtime};
+
+// Runtimeの設定を組み立てるビルダー
+pub struct Builder {
+    scheduler: Option<Box<dyn Scheduler>>,
+    time_slice: Option<Duration>,
+    max_threads: Option<usize>,
+    stack_size: Option<usize>,
+    max_idle_stacks: usize,
+    stack_recycle: StackRecyclePolicy,
+    initial_stack_size: Option<usize>,
+    stack_allocator: Option<Box<dyn StackAllocator>>,
+    poison_stacks: bool,
+    stack_usage_warning: Option<u8>,
+    strict_abi: bool,
+    lifo_limit: usize,
+    idle_spin: Duration,
+    max_park: Option<Duration>,
+    dump_on_signal: bool,
+    os_thread_names: bool,
+    op_budget: Option<u32>,
+    time_budget: Option<Duration>,
+    shutdown_grace: Duration,
+    fault_injection: Option<FaultInjection>,
+    on_deadlock: Option<DeadlockHandler>,
+    panic_on_deadlock: bool,
+}
+
+impl Builder {
+    pub fn new() -> Self {
+        Builder {
+            scheduler: None,
+            time_slice: None,
+            max_threads: None,
+            stack_size: None,
+            max_idle_stacks: DEFAULT_MAX_IDLE_STACKS,
+            stack_recycle: StackRecyclePolicy::KeepDirty,
+            initial_stack_size: None,
+            stack_allocator: None,
+            poison_stacks: false,
+            stack_usage_warning: None,
+            strict_abi: false,
+            lifo_limit: 0,
+            idle_spin: Duration::ZERO,
+            max_park: None,
+            dump_on_signal: false,
+            os_thread_names: false,
+            op_budget: None,
+            time_budget: None,
+            shutdown_grace: DEFAULT_SHUTDOWN_GRACE,
+            fault_injection: None,
+            on_deadlock: None,
+            panic_on_deadlock: false,
+        }
+    }
+
+    pub fn scheduler(mut self, policy: SchedulerPolicy) -> Self {
+        self.scheduler = Some(policy.build());
+        self
+    }
+
+    pub fn custom_scheduler(mut self, scheduler: Box<dyn Scheduler>) -> Self {
+        self.scheduler = Some(scheduler);
+        self
+    }
+
+    // プリエンプションを有効にし、1つのスレッドが続けて実行できる時間を指定する
+    // 指定した時間を過ぎても切り替えないスレッドは、タイマーシグナルで強制的に切り替えられる
+    // NOTE: タイマーはプロセス全体で1つなので、プリエンプションを有効にしたRuntimeは同時に1つしか動かせない
+    pub fn time_slice(mut self, time_slice: Duration) -> Self {
+        assert!(!time_slice.is_zero(), "time_slice must not be zero.");
+        self.time_slice = Some(time_slice);
+        self
+    }
+
+    // ベーススレッドを除いて、同時に動かせるスレッドの数
+    // NOTE: 指定しない場合は環境変数GREENTHREADS_MAX_THREADSの値を使い、それもなければ3
+    pub fn max_threads(mut self, max_threads: usize) -> Self {
+        self.max_threads = Some(max_threads);
+        self
+    }
+
+    // スレッドのスタックの大きさ(バイト)
+    // NOTE: 指定しない場合は環境変数GREENTHREADS_STACK_SIZEの値("256K"や"8M"とも書ける)を使い、それもなければ2MiB
+    //       growable_stacksを指定した場合は、伸ばせる最大の大きさになる
+    pub fn stack_size(mut self, stack_size: usize) -> Self {
+        self.stack_size = Some(stack_size);
+        self
+    }
+
+    // 終わったスレッドのスタックを使い回すために残しておく最大数
+    // 超えた分はOSに返す
+    pub fn max_idle_stacks(mut self, max_idle_stacks: usize) -> Self {
+        self.max_idle_stacks = max_idle_stacks;
+        self
+    }
+
+    // trueにすると、使い回すために残しておくスタックのページもOSに返す
+    // NOTE: メモリ使用量は減るが、次にそのスタックを使うときにページフォールトが起きる
+    //       stack_recycle(StackRecyclePolicy::Release)と同じで、falseの場合はKeepDirtyに戻す
+    pub fn release_idle_stacks(mut self, release_idle_stacks: bool) -> Self {
+        self.stack_recycle = if release_idle_stacks {
+            StackRecyclePolicy::Release
+        } else {
+            StackRecyclePolicy::KeepDirty
+        };
+        self
+    }
+
+    // スレッドが終わってスタックをプールに戻すときに、前のタスクが書いた値をどう扱うか
+    // NOTE: 指定しない場合はKeepDirtyで、次のタスクから前のタスクの値が見えることがある
+    //       互いに見せたくない値を扱うタスクがある場合はZeroかReleaseを指定する
+    pub fn stack_recycle(mut self, policy: StackRecyclePolicy) -> Self {
+        self.stack_recycle = policy;
+        self
+    }
+
+    // 伸ばせるスタックを使う
+    // スレッドは最初initial_sizeのスタックで始まり、足りなくなったら最大の大きさまで自動で伸びる
+    // NOTE: 使っていないスレッドがたくさんある場合にメモリを節約できるが、伸ばすたびにシグナルハンドラが動く
+    pub fn growable_stacks(mut self, initial_size: usize) -> Self {
+        assert!(initial_size > 0, "initial stack size must not be zero.");
+        self.initial_stack_size = Some(initial_size);
+        self
+    }
+
+    // スレッドのスタックをallocatorで確保する
+    // NOTE: 指定しない場合はMmapStackAllocatorを使う
+    //       指定した場合はgrowable_stacksは使われない
+    pub fn stack_allocator(mut self, allocator: Box<dyn StackAllocator>) -> Self {
+        self.stack_allocator = Some(allocator);
+        self
+    }
+
+    // スタックを決まった値で埋めておき、Runtime::max_stack_usageで使用量を測れるようにする
+    // NOTE: スタックを確保するたびにすべてのページに書き込むので、生成が遅くなり物理メモリも多く使う
+    pub fn poison_stacks(mut self, poison_stacks: bool) -> Self {
+        self.poison_stacks = poison_stacks;
+        self
+    }
+
+    // スレッドが終わったときにスタックの使用量がpercent(%)を超えていたら標準エラー出力に警告を出す
+    // NOTE: 使用量を測るためにpoison_stacksも有効になる
+    pub fn stack_usage_warning(mut self, percent: u8) -> Self {
+        assert!(percent <= 100, "percent must be 100 or less.");
+        self.poison_stacks = true;
+        self.stack_usage_warning = Some(percent);
+        self
+    }
+
+    // スレッドを作るたびに、最初に切り替えたときのスタックの形(偽の戻りアドレスと16byte境界への揃い方)を確かめ、
+    // 誤りがあればその場でパニックする
+    // NOTE: StackAllocatorで自分で用意したスタックを使う場合に、Cの関数を呼んで落ちる原因を早く見つけられる
+    pub fn strict_abi(mut self, strict_abi: bool) -> Self {
+        self.strict_abi = strict_abi;
+        self
+    }
+
+    // 実行中のスレッドがチャネルの送信などで起こしたスレッドを、スケジューラの順番を待たずに次に実行する
+    // 続けてlimit回そうしたら、飢餓状態を防ぐために一度スケジューラに選ばせる(0の場合は使わない)
+    // NOTE: ピンポンのようにスレッド間で値をやり取りする処理の待ち時間が短くなる
+    pub fn lifo_slot(mut self, limit: usize) -> Self {
+        self.lifo_limit = limit;
+        self
+    }
+
+    // 再開可能なスレッドがなくなったときに、OSスレッドを休止する前にイベントを確認し続ける時間
+    // NOTE: タイマーやI/Oで起きるまでの遅れが短くなる代わりに、その間はCPUを使い続ける
+    //       初期値は0で、すぐに次のタイマーの期限かI/Oイベントまで休止する
+    pub fn idle_spin(mut self, idle_spin: Duration) -> Self {
+        self.idle_spin = idle_spin;
+        self
+    }
+
+    // 再開可能なスレッドがなくなったときに、一度にOSスレッドを休止する時間の上限
+    // NOTE: 指定しない場合は、次のタイマーの期限かI/Oイベントが届くまで休止し続ける
+    pub fn max_park(mut self, max_park: Duration) -> Self {
+        assert!(!max_park.is_zero(), "max_park must not be zero.");
+        self.max_park = Some(max_park);
+        self
+    }
+
+    // SIGQUIT(Ctrl+\)かSIGUSR1を受け取ったら、すべてのスレッドの状態を標準エラー出力に書く
+    // NOTE: 書き出すのは次にスレッドを切り替えるときなので、切り替えずに動き続けるスレッドがいると書き出されない
+    pub fn dump_on_signal(mut self, dump_on_signal: bool) -> Self {
+        self.dump_on_signal = dump_on_signal;
+        self
+    }
+
+    // 実行中のグリーンスレッドの名前(spawn_namedで付けた名前)をOSスレッドの名前にする
+    // 名前のないスレッドを実行している間は、buildを呼んだOSスレッドの名前に戻す
+    // NOTE: top -Hやデバッガでどのグリーンスレッドが動いているかがわかるが、名前が変わるたびにシステムコールを呼ぶ
+    //       Linuxでは名前は先頭の15バイトに切り詰められる
+    pub fn os_thread_names(mut self, os_thread_names: bool) -> Self {
+        self.os_thread_names = os_thread_names;
+        self
+    }
+
+    // スレッドが切り替えずにチャネルやロック、ソケットの読み書きなどをops回続けたら、他のスレッドに切り替える
+    // NOTE: プリエンプションと違ってシグナルを使わないので、予算を使う操作(consume_budget)を呼ばないループは止められない
+    pub fn budget(mut self, ops: u32) -> Self {
+        assert!(ops > 0, "budget must not be zero.");
+        self.op_budget = Some(ops);
+        self
+    }
+
+    // スレッドが切り替えずにtimeより長く実行したら、次に予算を使う操作で他のスレッドに切り替える
+    // 切り替えるときにtimeより長く実行していたスレッドは、標準エラー出力に報告する
+    // NOTE: 予算を使う操作のたびに時刻を取得するので、その分遅くなる
+    pub fn time_budget(mut self, time: Duration) -> Self {
+        assert!(!time.is_zero(), "time_budget must not be zero.");
+        self.time_budget = Some(time);
+        self
+    }
+
+    // Runtime::run_until_signalでシグナルを受け取ってから、キャンセルしたスレッドが自分で終わるのを待つ時間
+    // NOTE: 過ぎても終わらないスレッドは、次にyieldやブロックしたところでスタックを巻き戻して終わらせる
+    pub fn shutdown_grace(mut self, grace: Duration) -> Self {
+        self.shutdown_grace = grace;
+        self
+    }
+
+    // テストのために、設定した確率でスレッドの生成の失敗や起こすのの遅れなどの障害をわざと起こす
+    // 起こした回数はRuntime::injected_faultsで取得できる
+    // NOTE: どの障害を起こすかはシードで決まるので、失敗したテストは同じシードで再現できる
+    pub fn fault_injection(mut self, config: FaultInjection) -> Self {
+        self.fault_injection = Some(config);
+        self
+    }
+
+    // runが終わったときに止まったまま二度と起こされないスレッドが残っていた場合に、その報告を渡して呼ぶ関数
+    // NOTE: 指定しない場合は標準エラー出力に書き出し、どちらの場合もrunはパニックせずに戻る
+    pub fn on_deadlock<F>(mut self, f: F) -> Self
+    where
+        F: FnMut(&Deadlock) + 'static,
+    {
+        self.on_deadlock = Some(Box::new(f));
+        self
+    }
+
+    // デッドロックした場合に、runから戻らずに報告を載せてパニックする
+    // NOTE: テストでデッドロックを失敗として扱いたい場合に使う
+    pub fn panic_on_deadlock(mut self, panic_on_deadlock: bool) -> Self {
+        self.panic_on_deadlock = panic_on_deadlock;
+        self
+    }
+
+    // NOTE: max_threadsやstack_size、環境変数の値が正しくない場合はパニックする
+    pub fn build(self) -> PinnedRuntime {
+        self.try_build().unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    // 設定からRuntimeを作り、動かせないようにヒープに固定して返す
+    // max_threadsやstack_size、環境変数の値が正しくない場合はErrを返す
+    pub fn try_build(self) -> Result<PinnedRuntime, ConfigError> {
+        let (max_threads, stack_size) = config::resolve(self.max_threads, self.stack_size)?;
+        let scheduler = self
+            .scheduler
+            .unwrap_or_else(|| SchedulerPolicy::default().build());
+        let mut runtime = Runtime::with_capacity(scheduler, max_threads, stack_size);
+        runtime.time_slice = self.time_slice;
+        runtime.stacks.max_idle = self.max_idle_stacks;
+        runtime.stacks.recycle = self.stack_recycle;
+        runtime.stacks.allocator = self.stack_allocator.unwrap_or_else(|| {
+            Box::new(match self.initial_stack_size {
+                Some(initial) => MmapStackAllocator::growable(initial),
+                None => MmapStackAllocator::new(),
+            })
+        });
+        runtime.stacks.poison = self.poison_stacks;
+        runtime.stack_usage_warning = self.stack_usage_warning;
+        runtime.strict_abi = self.strict_abi;
+        runtime.lifo_limit = self.lifo_limit;
+        runtime.idle_spin = self.idle_spin;
+        runtime.max_park = self.max_park;
+        runtime.op_budget = self.op_budget;
+        runtime.time_budget = self.time_budget;
+        runtime.shutdown_grace = self.shutdown_grace;
+        runtime.os_thread_name = self.os_thread_names.then(OsThreadName::new);
+        runtime.faults = self.fault_injection.map(Faults::new);
+        runtime.on_deadlock = self.on_deadlock;
+        runtime.panic_on_deadlock = self.panic_on_deadlock;
+        if self.dump_on_signal {
+            dump::install().expect("failed to install the dump handler.");
+            runtime.dump_requests = Some(dump::requests());
+        }
+        Ok(runtime.pinned())
+    }
+}
+
+impl Default for Builder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
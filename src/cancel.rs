@@ -0,0 +1,100 @@
+// スレッドごとのキャンセル
+// NOTE: キャンセルされたスレッドは、sleepやチャネルのrecv、I/O待ちでブロックしている場合は起こされ、
+//       それらがErr(Cancelled)を返す
+//       キャンセルされてもスレッドが勝手に終わることはないので、タスク側でエラーを見て終わること
+use std::cell::{Cell, RefCell};
+use std::error::Error;
+use std::fmt;
+use std::rc::{Rc, Weak};
+
+// キャンセルやshutdownで処理が中断されたことを表すエラー
+// NOTE: shutdownでキャンセルされたスレッドのJoinHandle::joinもこの値をErrで返す
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cancelled;
+
+impl fmt::Display for Cancelled {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        "the thread was cancelled".fmt(f)
+    }
+}
+
+impl Error for Cancelled {}
+
+// スレッドがキャンセルされたかどうかを共有するトークン
+// スレッドを生成するたびに作られ、JoinHandle::cancelかcancelでキャンセルされる
+#[derive(Clone, Debug)]
+pub struct CancellationToken {
+    inner: Rc<Inner>,
+}
+
+#[derive(Debug)]
+struct Inner {
+    cancelled: Cell<bool>,
+    // このトークンを持つスレッド
+    thread: usize,
+    // このトークンと一緒にキャンセルするトークン(TaskGroupで生成した子スレッドのもの)
+    // NOTE: 終わった子スレッドのトークンを残し続けないようにWeakで持つ
+    children: RefCell<Vec<Weak<Inner>>>,
+    // キャンセルされたら、ブロックする処理だけでなくyield_threadでもスタックを巻き戻して終わらせるか
+    // NOTE: TaskGroupで生成したスレッドはグループと一緒に終わるべきなので、shutdownと同じく巻き戻す
+    unwind: Cell<bool>,
+}
+
+impl CancellationToken {
+    pub(crate) fn new(thread: usize) -> Self {
+        CancellationToken {
+            inner: Rc::new(Inner {
+                cancelled: Cell::new(false),
+                thread,
+                children: RefCell::new(Vec::new()),
+                unwind: Cell::new(false),
+            }),
+        }
+    }
+
+    // キャンセルし、スレッドがキャンセルできる処理でブロックしていれば起こす
+    pub fn cancel(&self) {
+        if self.inner.cancelled.replace(true) {
+            return;
+        }
+        crate::cancel_thread(self.inner.thread, self);
+        let children: Vec<Weak<Inner>> = self.inner.children.borrow_mut().drain(..).collect();
+        for inner in children.iter().filter_map(Weak::upgrade) {
+            CancellationToken { inner }.cancel();
+        }
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.inner.cancelled.get()
+    }
+
+    // childをこのトークンの子にし、このトークンがキャンセルされたら一緒にキャンセルされるようにする
+    pub(crate) fn add_child(&self, child: &CancellationToken) {
+        if self.is_cancelled() {
+            child.cancel();
+            return;
+        }
+        let mut children = self.inner.children.borrow_mut();
+        children.retain(|c| c.strong_count() > 0);
+        children.push(Rc::downgrade(&child.inner));
+    }
+
+    pub(crate) fn set_unwind(&self) {
+        self.inner.unwind.set(true);
+    }
+
+    // キャンセルされていて、yield_threadで巻き戻すべきかどうか
+    pub(crate) fn should_unwind(&self) -> bool {
+        self.inner.unwind.get() && self.is_cancelled()
+    }
+
+    // 同じスレッドのトークンかどうか
+    pub(crate) fn same(&self, other: &CancellationToken) -> bool {
+        Rc::ptr_eq(&self.inner, &other.inner)
+    }
+}
+
+// 現在のスレッドのキャンセルトークンを返す
+pub fn cancellation_token() -> CancellationToken {
+    crate::current_token()
+}
@@ -0,0 +1,103 @@
+// 同時に動かせるスレッドの数とスタックの大きさの設定
+// NOTE: Builder::max_threadsとBuilder::stack_sizeで指定しなかった場合は、
+//       環境変数GREENTHREADS_MAX_THREADSとGREENTHREADS_STACK_SIZEを読み、それもなければ既定値を使う
+//       再コンパイルせずに、デモやベンチマークでスレッドの数やスタックの大きさを変えて試せる
+use std::error::Error;
+use std::fmt;
+
+use crate::DEFAULT_STACK_SIZE;
+
+// ベーススレッドを除いて、同時に動かせるスレッドの数の既定値
+pub(crate) const DEFAULT_MAX_THREADS: usize = 3;
+// 同時に動かせるスレッドの数の上限
+// NOTE: 桁を間違えたときに、大量のスレッドの枠を作ってメモリを使い果たさないようにする
+const MAX_THREADS_LIMIT: usize = 1 << 16;
+// スタックの大きさの下限と上限
+// NOTE: 下限より小さいと、パニックの処理やprintln!だけでスタックを使い切ってしまう
+const MIN_STACK_SIZE: usize = 16 * 1024;
+const MAX_STACK_SIZE: usize = 1 << 30;
+
+const MAX_THREADS_ENV: &str = "GREENTHREADS_MAX_THREADS";
+const STACK_SIZE_ENV: &str = "GREENTHREADS_STACK_SIZE";
+
+// Runtimeの設定が正しくない
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConfigError {
+    // 環境変数の値を大きさとして読めない
+    InvalidEnv { name: &'static str, value: String },
+    // 同時に動かせるスレッドの数が範囲外
+    MaxThreads(usize),
+    // スタックの大きさが範囲外
+    StackSize(usize),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::InvalidEnv { name, value } => write!(
+                f,
+                "{} must be a positive integer (with an optional K, M or G suffix), but got {:?}",
+                name, value
+            ),
+            ConfigError::MaxThreads(n) => write!(
+                f,
+                "max_threads must be between 1 and {}, but got {}",
+                MAX_THREADS_LIMIT, n
+            ),
+            ConfigError::StackSize(size) => write!(
+                f,
+                "stack_size must be between {} and {} bytes, but got {}",
+                MIN_STACK_SIZE, MAX_STACK_SIZE, size
+            ),
+        }
+    }
+}
+
+impl Error for ConfigError {}
+
+// 指定された値、環境変数、既定値の順に使う値を決め、(同時に動かせるスレッドの数, スタックの大きさ)を返す
+pub(crate) fn resolve(
+    max_threads: Option<usize>,
+    stack_size: Option<usize>,
+) -> Result<(usize, usize), ConfigError> {
+    let max_threads = match max_threads {
+        Some(n) => n,
+        None => from_env(MAX_THREADS_ENV)?.unwrap_or(DEFAULT_MAX_THREADS),
+    };
+    let stack_size = match stack_size {
+        Some(size) => size,
+        None => from_env(STACK_SIZE_ENV)?.unwrap_or(DEFAULT_STACK_SIZE),
+    };
+    if max_threads == 0 || max_threads > MAX_THREADS_LIMIT {
+        return Err(ConfigError::MaxThreads(max_threads));
+    }
+    if !(MIN_STACK_SIZE..=MAX_STACK_SIZE).contains(&stack_size) {
+        return Err(ConfigError::StackSize(stack_size));
+    }
+    Ok((max_threads, stack_size))
+}
+
+// 環境変数nameの値を読む
+// 設定されていない場合や空の場合はNoneを返す
+fn from_env(name: &'static str) -> Result<Option<usize>, ConfigError> {
+    let value = match std::env::var(name) {
+        Ok(value) if !value.trim().is_empty() => value,
+        _ => return Ok(None),
+    };
+    parse_size(value.trim())
+        .map(Some)
+        .ok_or(ConfigError::InvalidEnv { name, value })
+}
+
+// "64"や"256K"、"8M"のような大きさを読む
+// NOTE: 接尾辞は1024倍ずつで、大文字と小文字を区別しない
+fn parse_size(s: &str) -> Option<usize> {
+    let (digits, shift) = match s.as_bytes().last()?.to_ascii_uppercase() {
+        b'K' => (&s[..s.len() - 1], 10),
+        b'M' => (&s[..s.len() - 1], 20),
+        b'G' => (&s[..s.len() - 1], 30),
+        _ => (s, 0),
+    };
+    let n: usize = digits.parse().ok()?;
+    n.checked_mul(1 << shift)
+}
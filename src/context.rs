@@ -0,0 +1,368 @@
+// スレッドの切り替えに使うレジスタの保存先と、切り替えを行うswitch_to
+// NOTE: stdに依存しないので、Runtimeとコルーチン、bareのExecutorで共有する
+//       x86_64とx86(i686)に対応し、アーキテクチャによる違いはこのファイルの中だけに閉じ込める
+//       fs/gs(TLSのベースレジスタ)は保存も復元もしない
+//       TLSはOSスレッドのもので、グリーンスレッドは始まったOSスレッドから別のOSスレッドに移ることがないので、
+//       切り替えの前後でfs/gsは同じ値のままでよい(保存して別のOSスレッドで復元すると、他のOSスレッドのTLSを使ってしまう)
+use core::arch::{asm, naked_asm};
+#[cfg(feature = "std")]
+use core::fmt;
+use core::ptr;
+
+#[cfg(not(any(target_arch = "x86_64", target_arch = "x86")))]
+compile_error!("greenthreads supports only x86_64 and x86.");
+
+// MXCSRの初期値(すべての例外をマスクし、最近接偶数丸め)
+const DEFAULT_MXCSR: u32 = 0x1F80;
+// x87 FPUコントロールワードの初期値(すべての例外をマスクし、拡張倍精度、最近接偶数丸め)
+const DEFAULT_FPU_CW: u16 = 0x037F;
+
+// 1ワードの大きさ
+#[cfg(feature = "std")]
+const WORD: usize = core::mem::size_of::<usize>();
+// 切り替えた先が再開するときに、スタックポインタから見て再開するアドレスとMXCSRが並ぶ大きさ
+#[cfg(feature = "std")]
+const SAVED_BYTES: usize = 2 * WORD;
+// switch_toがasmブロックに入ってから、保存するスタックポインタまでに積む大きさ
+#[cfg(all(feature = "std", target_arch = "x86_64"))]
+const SWITCH_FRAME: usize = 32;
+#[cfg(all(feature = "std", target_arch = "x86"))]
+const SWITCH_FRAME: usize = 28;
+// prepareで作ったスタックに最初に切り替えたときに、entryが呼ばれた時点のスタックポインタの保存した位置からのずれ
+#[cfg(all(feature = "std", target_arch = "x86_64"))]
+const ENTRY_OFFSET: usize = 16;
+#[cfg(all(feature = "std", target_arch = "x86"))]
+const ENTRY_OFFSET: usize = 12;
+
+// スタックの形を確かめたときに見つかった誤り
+#[cfg(feature = "std")]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum StackLayoutError {
+    // スレッドがすでに終わっている
+    Finished,
+    // 保存したスタックポインタがスタックの外を指している
+    OutOfBounds { sp: usize },
+    // 切り替えた後のスタックポインタが、ABIが求める16byte境界からずれる
+    Misaligned { sp: usize },
+    // 最初に切り替えたときに飛ぶ先(偽の戻りアドレス)がstartではない
+    MissingReturnAddress { found: usize },
+    // prepareで書いたentryやargが書き換えられている
+    Corrupted { addr: usize },
+    // 保存したMXCSRの予約ビットが立っていて、復元すると例外になる
+    InvalidMxcsr { value: u32 },
+}
+
+#[cfg(feature = "std")]
+impl fmt::Display for StackLayoutError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StackLayoutError::Finished => "the thread has already finished".fmt(f),
+            StackLayoutError::OutOfBounds { sp } => {
+                write!(f, "the saved stack pointer {:#x} is outside the stack", sp)
+            }
+            StackLayoutError::Misaligned { sp } => write!(
+                f,
+                "the saved stack pointer {:#x} breaks the 16-byte alignment of the ABI",
+                sp
+            ),
+            StackLayoutError::MissingReturnAddress { found } => write!(
+                f,
+                "the entry trampoline is missing (found {:#x} as the return address)",
+                found
+            ),
+            StackLayoutError::Corrupted { addr } => {
+                write!(f, "the initial stack frame was overwritten at {:#x}", addr)
+            }
+            StackLayoutError::InvalidMxcsr { value } => {
+                write!(f, "the saved MXCSR {:#x} has reserved bits set", value)
+            }
+        }
+    }
+}
+
+// NOTE: 保存するレジスタは切り替え元のスタックに積み、ここには積んだ後のスタックポインタだけを保存する
+//       スタックには上から順に、rbp, rbx, MXCSRとx87 FPUコントロールワード, 再開するアドレスが並ぶ
+//       (x86ではebp, ebx, esi, edi, MXCSRとx87 FPUコントロールワード, 再開するアドレス)
+#[derive(Debug, Default)]
+#[repr(C)]
+pub(crate) struct ThreadContext {
+    sp: usize,
+}
+
+impl ThreadContext {
+    // stack_topを一番上とするスタックで、最初に切り替えたときにentry(arg)が呼ばれるようにする
+    // NOTE: switch_toが積むのと同じ形にスタックを作り、再開するアドレスとしてstartを書き込む
+    //       entryとargはcallee-savedなレジスタを復元する位置に書いておき、startでレジスタに戻して使う
+    //       entryは戻ってはいけない
+    #[cfg(target_arch = "x86_64")]
+    pub(crate) unsafe fn prepare(
+        &mut self,
+        stack_top: *mut u8,
+        entry: extern "C" fn(usize),
+        arg: usize,
+    ) {
+        // 16byteアライメント
+        let s_ptr = (stack_top as usize & !15) as *mut u64;
+        // NOTE: startがentryを呼ぶときにrspが16byte境界に揃うように、一番上の8byteは空ける
+        let sp = s_ptr.offset(-5);
+        ptr::write(sp, start as *const () as usize as u64);
+        ptr::write(
+            sp.add(1),
+            DEFAULT_MXCSR as u64 | (DEFAULT_FPU_CW as u64) << 32,
+        );
+        ptr::write(sp.add(2), entry as *const () as usize as u64);
+        ptr::write(sp.add(3), arg as u64);
+        self.sp = sp as usize;
+    }
+
+    #[cfg(target_arch = "x86")]
+    pub(crate) unsafe fn prepare(
+        &mut self,
+        stack_top: *mut u8,
+        entry: extern "C" fn(usize),
+        arg: usize,
+    ) {
+        // 16byteアライメント
+        let s_ptr = (stack_top as usize & !15) as *mut u32;
+        let sp = s_ptr.offset(-8);
+        ptr::write(sp, start as *const () as usize as u32);
+        ptr::write(sp.add(1), DEFAULT_MXCSR);
+        ptr::write(sp.add(2), DEFAULT_FPU_CW as u32);
+        // edi, esi, ebx, ebpの順に復元される
+        ptr::write(sp.add(3), 0);
+        ptr::write(sp.add(4), entry as *const () as usize as u32);
+        ptr::write(sp.add(5), arg as u32);
+        ptr::write(sp.add(6), 0);
+        self.sp = sp as usize;
+    }
+
+    // 保存したスタックポインタ
+    #[cfg(feature = "std")]
+    pub(crate) fn stack_pointer(&self) -> usize {
+        self.sp
+    }
+
+    // 保存したスタックポインタを書き換える
+    // NOTE: 保存したレジスタはスタックに積んであるので、スタックを写した先で再開させるのに使う
+    #[cfg(feature = "std")]
+    pub(crate) unsafe fn set_stack_pointer(&mut self, sp: usize) {
+        self.sp = sp;
+    }
+
+    // 止まっているスレッドのスタックの形を確かめる
+    // freshには、まだ始まっていないスレッドの場合にprepareに渡したentryとargを指定する
+    // boundsには、分かっていればスタックの(一番下, 一番上)のアドレスを指定する
+    // NOTE: 始まったスレッドはswitch_toの中で止まっていて、再開するアドレスは関数ごとに違うので確かめられない
+    //       代わりに、asmブロックに入った時点のスタックポインタが16byte境界に揃っていたかを確かめる
+    #[cfg(feature = "std")]
+    pub(crate) unsafe fn verify(
+        &self,
+        fresh: Option<(extern "C" fn(usize), usize)>,
+        bounds: Option<(usize, usize)>,
+    ) -> Result<(), StackLayoutError> {
+        let sp = self.sp;
+        if let Some((bottom, top)) = bounds {
+            if sp < bottom || sp + SAVED_BYTES > top {
+                return Err(StackLayoutError::OutOfBounds { sp });
+            }
+        }
+        if !sp.is_multiple_of(WORD) {
+            return Err(StackLayoutError::Misaligned { sp });
+        }
+        let words = sp as *const usize;
+        let mxcsr = ptr::read(words.add(1)) as u32;
+        if mxcsr & 0xFFFF_0000 != 0 {
+            return Err(StackLayoutError::InvalidMxcsr { value: mxcsr });
+        }
+        let (entry, arg) = match fresh {
+            Some(fresh) => fresh,
+            None if (sp + SWITCH_FRAME).is_multiple_of(16) => return Ok(()),
+            None => return Err(StackLayoutError::Misaligned { sp }),
+        };
+
+        // startに切り替えてentryを呼んだときに、戻りアドレスを積んだ後のスタックポインタが16byte境界から1ワードずれるか
+        if !(sp + ENTRY_OFFSET + WORD).is_multiple_of(16) {
+            return Err(StackLayoutError::Misaligned { sp });
+        }
+        let found = ptr::read(words);
+        if found != start as *const () as usize {
+            return Err(StackLayoutError::MissingReturnAddress { found });
+        }
+        #[cfg(target_arch = "x86_64")]
+        let expected = [(2, entry as usize), (3, arg)];
+        #[cfg(target_arch = "x86")]
+        let expected = [(4, entry as usize), (5, arg)];
+        for (index, value) in expected {
+            if ptr::read(words.add(index)) != value {
+                return Err(StackLayoutError::Corrupted {
+                    addr: sp + index * WORD,
+                });
+            }
+        }
+        Ok(())
+    }
+}
+
+// 今のスタックポインタ
+#[cfg(feature = "std")]
+#[inline(always)]
+pub(crate) fn current_stack_pointer() -> usize {
+    let sp: usize;
+    unsafe {
+        #[cfg(target_arch = "x86_64")]
+        asm!("mov {}, rsp", out(reg) sp);
+        #[cfg(target_arch = "x86")]
+        asm!("mov {}, esp", out(reg) sp);
+    }
+    sp
+}
+
+// oldに今のレジスタを保存してnewに切り替える
+// NOTE: 関数を呼ばずにasmブロックの中で切り替え、再開したらasmブロックの続きから戻る
+//       caller-savedなレジスタは切り替え先のスレッドが自由に使うので、clobber_abi("C")で壊れるとコンパイラに教え、
+//       生きている値があるものだけをコンパイラに退避させる
+//       callee-savedなレジスタのうち、asmのオペランドに使えるもの(x86_64のr12からr15)は壊れると教えて、
+//       使っているものだけをコンパイラに退避させ、LLVMが予約していてオペランドに使えないもの(rbx, rbpやx86のebx, esi, ebp)と、
+//       MXCSRとx87 FPUコントロールワードはasmの中でスタックに積む
+//       (System V ABIではMXCSRとx87 FPUコントロールワードの制御ビットもcallee-savedで、
+//       保存しないと、あるスレッドで変えた丸めモードなどが他のスレッドに漏れる)
+//       再開するアドレスもスタックに積み、retではなくjmpで飛ぶ
+//       (retで飛ぶと、callと対にならないのでリターンスタックバッファがずれ、切り替え先の関数が戻るたびに予測を外す)
+//       asmの中でスタックに積むので、nostackは付けない(付けなければ、レッドゾーンは使われずrspは16byte境界に揃っている)
+#[cfg(target_arch = "x86_64")]
+#[inline(always)]
+pub(crate) unsafe fn switch_to(old: *mut ThreadContext, new: *const ThreadContext) {
+    asm!(
+        "push rbp",
+        "push rbx",
+        "sub rsp, 8",
+        "stmxcsr [rsp]",
+        "fnstcw [rsp + 4]",
+        "lea rax, [rip + 2f]",
+        "push rax",
+        "mov [rdi], rsp",
+        "mov rsp, [rsi]",
+        "pop rax",
+        "jmp rax",
+        "2:",
+        "ldmxcsr [rsp]",
+        "fldcw [rsp + 4]",
+        "add rsp, 8",
+        "pop rbx",
+        "pop rbp",
+        in("rdi") old,
+        in("rsi") new,
+        out("r12") _,
+        out("r13") _,
+        out("r14") _,
+        out("r15") _,
+        clobber_abi("C"),
+    );
+}
+
+// x86版のswitch_to
+// NOTE: ediもオペランドに使えるが、4つとも積む方が単純なので積む
+//       x86にはrip相対のleaがないので、次の命令へのcallで今のアドレスを取り、再開するアドレスを計算する
+//       (次の命令へのcallは、CPUがリターンスタックバッファに積まないように特別扱いする)
+//       LLVMのIntel記法のパーサーはラベル同士の引き算を受け付けないので、このブロックだけAT&T記法で書く
+//       MXCSRはSSEのレジスタなので、SSEのない古いCPUでは動かない(i686ターゲットはSSE2を前提にしている)
+#[cfg(target_arch = "x86")]
+#[inline(always)]
+pub(crate) unsafe fn switch_to(old: *mut ThreadContext, new: *const ThreadContext) {
+    asm!(
+        "pushl %ebp",
+        "pushl %ebx",
+        "pushl %esi",
+        "pushl %edi",
+        "subl $8, %esp",
+        "stmxcsr (%esp)",
+        "fnstcw 4(%esp)",
+        "call 3f",
+        "3:",
+        "popl %eax",
+        "addl $(2f - 3b), %eax",
+        "pushl %eax",
+        "movl %esp, (%ecx)",
+        "movl (%edx), %esp",
+        "popl %eax",
+        "jmpl *%eax",
+        "2:",
+        "ldmxcsr (%esp)",
+        "fldcw 4(%esp)",
+        "addl $8, %esp",
+        "popl %edi",
+        "popl %esi",
+        "popl %ebx",
+        "popl %ebp",
+        in("ecx") old,
+        in("edx") new,
+        clobber_abi("C"),
+        options(att_syntax),
+    );
+}
+
+// 新しいスレッドがswitch_toのjmpで最初に実行する関数
+// prepareで書いたMXCSRとx87 FPUコントロールワードを読み込み、entryをrbxに、argをrbpに戻してentry(arg)を呼ぶ
+// NOTE: スレッドのスタックはこの関数から始まるので、バックトレースがここで終わるようにする
+//  naked関数にはコンパイラがCFI(巻き戻しの情報)を出力しないので、.cfi_startprocと.cfi_endprocで自分で書く
+//  .cfi_undefined rip: DWARFの情報で巻き戻すとき(RUST_BACKTRACEやgdb)に、これより前の呼び出し元はないと教える
+//  rbp = 0: フレームポインタをたどるときに、ここで終わりだと分かるようにする
+//  push rbp: 呼び出す関数の入口でrspが16byte境界から8byteずれた位置になるように合わせる
+#[cfg(target_arch = "x86_64")]
+#[unsafe(naked)]
+unsafe extern "C" fn start() {
+    naked_asm!(
+        ".cfi_startproc",
+        ".cfi_undefined rip",
+        "ldmxcsr [rsp]",
+        "fldcw [rsp + 4]",
+        "add rsp, 8",
+        ".cfi_adjust_cfa_offset -8",
+        "pop rbx",
+        ".cfi_adjust_cfa_offset -8",
+        "pop rdi",
+        ".cfi_adjust_cfa_offset -8",
+        "xor ebp, ebp",
+        "push rbp",
+        ".cfi_adjust_cfa_offset 8",
+        "call rbx",
+        "ud2",
+        ".cfi_endproc",
+    );
+}
+
+// x86版のstart
+// prepareで書いたMXCSRとx87 FPUコントロールワードを読み込み、entryをesiに、argをebxに戻して呼ぶ
+// NOTE: cdeclなので引数はスタックに積んで渡す
+//  i386 System V ABIでも呼び出す時点でespが16byte境界に揃っている必要があるので、
+//  ebpと引数の間を空けて、argを積んだ後のespが16byte境界になるようにする
+#[cfg(target_arch = "x86")]
+#[unsafe(naked)]
+unsafe extern "C" fn start() {
+    naked_asm!(
+        ".cfi_startproc",
+        ".cfi_undefined eip",
+        "ldmxcsr [esp]",
+        "fldcw [esp + 4]",
+        "add esp, 8",
+        ".cfi_adjust_cfa_offset -8",
+        "pop edi",
+        ".cfi_adjust_cfa_offset -4",
+        "pop esi",
+        ".cfi_adjust_cfa_offset -4",
+        "pop ebx",
+        ".cfi_adjust_cfa_offset -4",
+        "pop ebp",
+        ".cfi_adjust_cfa_offset -4",
+        "xor ebp, ebp",
+        "push ebp",
+        ".cfi_adjust_cfa_offset 4",
+        "sub esp, 4",
+        ".cfi_adjust_cfa_offset 4",
+        "push ebx",
+        ".cfi_adjust_cfa_offset 4",
+        "call esi",
+        "ud2",
+        ".cfi_endproc",
+    );
+}
@@ -0,0 +1,159 @@
+// コルーチン(ジェネレータ)
+// NOTE: スレッドと同じThreadContextとswitch_toを使うが、スケジューラは通さない
+//       resumeを呼んだ側とコルーチンの間だけで、値を渡しながら直接切り替える
+//       Runtimeがなくても使える
+//       終わる前にドロップした場合、コルーチンのスタックに残っている値はドロップされずにスタックごと捨てられる
+use std::any::Any;
+use std::cell::Cell;
+use std::io;
+use std::panic::{self, AssertUnwindSafe};
+use std::rc::Rc;
+
+use crate::context::{self, ThreadContext};
+use crate::stack::Stack;
+use crate::DEFAULT_STACK_SIZE;
+
+// resumeの結果
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CoroutineState<Y, R> {
+    // yield_withで値を渡して止まった
+    Yielded(Y),
+    // 関数が終わった
+    Complete(R),
+}
+
+// 切り替えに使う、型パラメータによらない部分
+// NOTE: ポインタをYielderやcoroutine_startに渡すので、Boxに入れてアドレスを変えない
+struct Context {
+    // resumeを呼んだ側
+    caller: ThreadContext,
+    // コルーチン
+    callee: ThreadContext,
+    body: Option<Box<dyn FnOnce()>>,
+    finished: bool,
+    panic: Option<Box<dyn Any + Send>>,
+}
+
+pub struct Coroutine<Y, R> {
+    ctx: Box<Context>,
+    yielded: Rc<Cell<Option<Y>>>,
+    returned: Rc<Cell<Option<R>>>,
+    // NOTE: ctxより後に並べ、コルーチンのスタックを最後に解放する
+    _stack: Stack,
+}
+
+// コルーチンの中から、resumeを呼んだ側に値を渡して止まるためのハンドル
+pub struct Yielder<Y> {
+    ctx: *mut Context,
+    slot: Rc<Cell<Option<Y>>>,
+}
+
+impl<Y, R> Coroutine<Y, R>
+where
+    Y: 'static,
+    R: 'static,
+{
+    // fを実行するコルーチンを作る
+    // NOTE: 最初にresumeを呼ぶまでfは実行されない
+    pub fn new<F>(f: F) -> io::Result<Self>
+    where
+        F: FnOnce(&Yielder<Y>) -> R + 'static,
+    {
+        Coroutine::with_stack_size(DEFAULT_STACK_SIZE, f)
+    }
+
+    pub fn with_stack_size<F>(stack_size: usize, f: F) -> io::Result<Self>
+    where
+        F: FnOnce(&Yielder<Y>) -> R + 'static,
+    {
+        let stack = Stack::new(stack_size, None)?;
+        let yielded = Rc::new(Cell::new(None));
+        let returned = Rc::new(Cell::new(None));
+        let mut ctx = Box::new(Context {
+            caller: ThreadContext::default(),
+            callee: ThreadContext::default(),
+            body: None,
+            finished: false,
+            panic: None,
+        });
+
+        let yielder = Yielder {
+            ctx: &mut *ctx,
+            slot: yielded.clone(),
+        };
+        let their_returned = returned.clone();
+        ctx.body = Some(Box::new(move || their_returned.set(Some(f(&yielder)))));
+
+        unsafe {
+            // スレッドと同じく、最初に切り替えたときにcoroutine_mainから始まるようにする
+            let arg = &mut *ctx as *mut Context as usize;
+            ctx.callee.prepare(stack.top(), coroutine_main, arg);
+        }
+
+        Ok(Coroutine {
+            ctx,
+            yielded,
+            returned,
+            _stack: stack,
+        })
+    }
+
+    // コルーチンに切り替え、yield_withで止まるか関数が終わるまで実行する
+    // NOTE: コルーチンの中でパニックした場合は、resumeを呼んだ側でパニックを再開する
+    pub fn resume(&mut self) -> CoroutineState<Y, R> {
+        assert!(
+            !self.ctx.finished,
+            "cannot resume a coroutine that has already completed."
+        );
+        unsafe {
+            let ctx: *mut Context = &mut *self.ctx;
+            transfer(&mut (*ctx).caller, &(*ctx).callee);
+        }
+        if !self.ctx.finished {
+            let value = self.yielded.take().expect("coroutine yielded no value.");
+            return CoroutineState::Yielded(value);
+        }
+        if let Some(payload) = self.ctx.panic.take() {
+            panic::resume_unwind(payload);
+        }
+        let value = self.returned.take().expect("coroutine returned no value.");
+        CoroutineState::Complete(value)
+    }
+
+    // 関数が終わったかどうか
+    pub fn is_finished(&self) -> bool {
+        self.ctx.finished
+    }
+}
+
+impl<Y> Yielder<Y> {
+    // valueをresumeを呼んだ側に渡して止まる
+    // 次にresumeが呼ばれたら戻る
+    pub fn yield_with(&self, value: Y) {
+        self.slot.set(Some(value));
+        unsafe {
+            transfer(&mut (*self.ctx).callee, &(*self.ctx).caller);
+        }
+    }
+}
+
+// oldに今のレジスタを保存してnewに切り替える
+unsafe fn transfer(old: *mut ThreadContext, new: *const ThreadContext) {
+    context::switch_to(old, new);
+}
+
+// コルーチンに最初に切り替えたときに呼ばれ、関数を実行する
+// NOTE: 終わったらresumeを呼んだ側に戻り、二度と再開されない
+//       パニックはここで止めてresumeに渡す(自前で積んだスタックの先に巻き戻すとプロセスが落ちるため)
+extern "C" fn coroutine_main(ctx: usize) {
+    let ctx = ctx as *mut Context;
+    unsafe {
+        if let Some(body) = (*ctx).body.take() {
+            if let Err(payload) = panic::catch_unwind(AssertUnwindSafe(body)) {
+                (*ctx).panic = Some(payload);
+            }
+        }
+        (*ctx).finished = true;
+        transfer(&mut (*ctx).callee, &(*ctx).caller);
+    }
+}
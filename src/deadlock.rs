@@ -0,0 +1,140 @@
+// デッドロックの診断
+// NOTE: ブロックするときに何を待っているか(と、分かる場合はそれを持っているスレッド)を記録しておき、
+//       再開可能なスレッドがなくなったときに、止まっているスレッドとその依存関係を並べて報告する
+use std::error::Error;
+use std::fmt::{self, Write};
+
+use crate::{Runtime, State};
+
+// スレッドが何を待ってブロックしているか
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct BlockedOn {
+    // 待っているものの種類
+    pub(crate) what: &'static str,
+    // 待っているもののアドレス
+    // NOTE: 同じものを待っているスレッドを見分けるために使う
+    pub(crate) addr: usize,
+    // 待っているものを持っているスレッド
+    pub(crate) holder: Option<usize>,
+}
+
+// runが終わったときに、止まったまま二度と起こされないスレッドが残っていたことを表す
+// NOTE: Builder::on_deadlockで指定した関数に渡され、指定しない場合は標準エラー出力に書き出す
+#[derive(Clone, Debug)]
+pub struct Deadlock {
+    report: String,
+}
+
+impl Deadlock {
+    // 止まっているスレッドとそれぞれが待っているもの、待ち合っているスレッドの循環を1行ずつ並べたもの
+    pub fn report(&self) -> &str {
+        &self.report
+    }
+}
+
+impl fmt::Display for Deadlock {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "deadlock: no thread is ready to run.\n{}", self.report)
+    }
+}
+
+impl Error for Deadlock {}
+
+pub(crate) type DeadlockHandler = Box<dyn FnMut(&Deadlock)>;
+
+impl Runtime {
+    // 止まったまま二度と起こされないスレッドが残っていれば、黙って終わらずに報告する
+    // NOTE: Builder::panic_on_deadlockを指定した場合だけパニックし、それ以外はrunから戻れるようにする
+    //       残ったスレッドはshutdownかRuntimeをドロップしたときに巻き戻して終わらせる
+    pub(crate) fn report_deadlock(&mut self) {
+        if !self.has_stuck_threads() {
+            return;
+        }
+        let deadlock = Deadlock {
+            report: self.blocked_threads_report(),
+        };
+        if self.panic_on_deadlock {
+            panic!("{}", deadlock);
+        }
+        match &mut self.on_deadlock {
+            Some(handler) => handler(&deadlock),
+            None => eprintln!("{}", deadlock),
+        }
+    }
+
+    // 現在のスレッド以外に、起こされるのを待って止まっているスレッドがあるかどうか
+    pub(crate) fn has_stuck_threads(&self) -> bool {
+        self.threads
+            .iter()
+            .any(|t| t.id != self.current && matches!(t.state, State::Blocked | State::Parked))
+    }
+
+    // 止まっているスレッドとそれぞれが待っているものを1行ずつ並べる
+    // 待っているものを持っているスレッドを辿って循環していれば、その循環も並べる
+    pub(crate) fn blocked_threads_report(&self) -> String {
+        let mut report = String::new();
+        for thread in &self.threads {
+            let _ = match (&thread.state, &thread.blocked_on) {
+                (State::Blocked, Some(b)) => write!(
+                    report,
+                    "  {} is blocked on {} at {:#x}",
+                    self.thread_label(thread.id),
+                    b.what,
+                    b.addr
+                ),
+                (State::Blocked, None) => {
+                    write!(report, "  {} is blocked", self.thread_label(thread.id))
+                }
+                (State::Parked, _) => {
+                    write!(report, "  {} is parked", self.thread_label(thread.id))
+                }
+                _ => continue,
+            };
+            if let Some(holder) = thread.blocked_on.and_then(|b| b.holder) {
+                let _ = write!(report, " held by {}", self.thread_label(holder));
+            }
+            report.push('\n');
+        }
+        for cycle in self.wait_cycles() {
+            let labels: Vec<String> = cycle.iter().map(|&id| self.thread_label(id)).collect();
+            let _ = writeln!(report, "  cycle: {} -> {}", labels.join(" -> "), labels[0]);
+        }
+        report
+    }
+
+    pub(crate) fn thread_label(&self, id: usize) -> String {
+        match &self.threads[id].name {
+            Some(name) => format!("thread {} ({})", id, name),
+            None => format!("thread {}", id),
+        }
+    }
+
+    // 待っているものを持っているスレッドを辿り、お互いを待ち合っているスレッドの組を返す
+    fn wait_cycles(&self) -> Vec<Vec<usize>> {
+        let holder = |id: usize| {
+            let thread = &self.threads[id];
+            if thread.state != State::Blocked {
+                return None;
+            }
+            thread.blocked_on.and_then(|b| b.holder)
+        };
+        let mut cycles: Vec<Vec<usize>> = Vec::new();
+        for start in 0..self.threads.len() {
+            let mut path = vec![start];
+            let mut id = start;
+            while let Some(next) = holder(id) {
+                if let Some(pos) = path.iter().position(|&p| p == next) {
+                    let cycle = path.split_off(pos);
+                    // NOTE: 同じ循環を別のスレッドから辿った場合は一番小さいIDから始まるものだけを残す
+                    if cycle.iter().min() == Some(&cycle[0]) && !cycles.contains(&cycle) {
+                        cycles.push(cycle);
+                    }
+                    break;
+                }
+                path.push(next);
+                id = next;
+            }
+        }
+        cycles
+    }
+}
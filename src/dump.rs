@@ -0,0 +1,119 @@
+// シグナル(SIGQUITかSIGUSR1)を受け取ったら、すべてのスレッドの状態を標準エラー出力に書く
+// NOTE: シグナルハンドラの中ではメモリの確保や標準エラー出力のロックができないので、ハンドラは要求を数えるだけにする
+//       Runtimeはスレッドを切り替えるたびに数を確認し、増えていればその場で書き出す
+//       すべてのスレッドが止まっていても、I/O待ちならepoll_waitがシグナルで中断されるので書き出される
+//       SIGQUITのハンドラを登録すると、Ctrl+\でコアダンプして終わらなくなる
+use std::backtrace::Backtrace;
+use std::fmt::Write;
+use std::io;
+use std::os::raw::c_int;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::preempt::{sigaction, SigAction, SA_RESTART};
+use crate::{Runtime, State};
+
+const SIGQUIT: c_int = 3;
+#[cfg(target_os = "linux")]
+const SIGUSR1: c_int = 10;
+#[cfg(not(target_os = "linux"))]
+const SIGUSR1: c_int = 30;
+
+// シグナルを受け取った回数
+static REQUESTS: AtomicU64 = AtomicU64::new(0);
+
+// SIGQUITとSIGUSR1のハンドラを登録する
+pub(crate) fn install() -> io::Result<()> {
+    for signum in [SIGQUIT, SIGUSR1] {
+        let action = SigAction {
+            sa_handler: handle_dump as extern "C" fn(c_int) as usize,
+            sa_mask: Default::default(),
+            sa_flags: SA_RESTART,
+            #[cfg(target_os = "linux")]
+            sa_restorer: 0,
+        };
+        if unsafe { sigaction(signum, &action, std::ptr::null_mut()) } < 0 {
+            return Err(io::Error::last_os_error());
+        }
+    }
+    Ok(())
+}
+
+pub(crate) fn requests() -> u64 {
+    REQUESTS.load(Ordering::Relaxed)
+}
+
+extern "C" fn handle_dump(_signum: c_int) {
+    REQUESTS.fetch_add(1, Ordering::Relaxed);
+}
+
+impl Runtime {
+    // シグナルを受け取っていれば、すべてのスレッドの状態を標準エラー出力に書く
+    pub(crate) fn check_dump_request(&mut self) {
+        let seen = match self.dump_requests {
+            Some(seen) => seen,
+            None => return,
+        };
+        let requests = requests();
+        if requests == seen {
+            return;
+        }
+        self.dump_requests = Some(requests);
+        eprint!("{}", self.dump_threads());
+    }
+
+    // 終わっていないすべてのスレッドについて、状態と何を待っているか、スタックの使用量を並べる
+    // 実行中のスレッドはバックトレースも並べる
+    // NOTE: 止まっているスレッドのバックトレースは取れないので、再開するときのスタックポインタを並べる
+    pub fn dump_threads(&self) -> String {
+        let mut dump = String::new();
+        let live = self
+            .threads
+            .iter()
+            .filter(|t| t.state != State::Available)
+            .count();
+        let _ = writeln!(
+            dump,
+            "greenthreads: {} of {} threads alive, {} switches",
+            live,
+            self.threads.len(),
+            self.switches
+        );
+        for thread in &self.threads {
+            let _ = match thread.state {
+                State::Available => continue,
+                State::Running => write!(dump, "  {} running", self.thread_label(thread.id)),
+                State::Ready => write!(dump, "  {} ready", self.thread_label(thread.id)),
+                State::Parked => write!(dump, "  {} parked", self.thread_label(thread.id)),
+                State::Blocked => match thread.blocked_on {
+                    Some(b) => write!(
+                        dump,
+                        "  {} blocked on {} at {:#x}",
+                        self.thread_label(thread.id),
+                        b.what,
+                        b.addr
+                    ),
+                    None => write!(dump, "  {} blocked", self.thread_label(thread.id)),
+                },
+            };
+            if let Some(holder) = thread.blocked_on.and_then(|b| b.holder) {
+                let _ = write!(dump, " held by {}", self.thread_label(holder));
+            }
+            dump.push('\n');
+
+            let _ = match (&thread.stack, self.max_stack_usage(thread.id)) {
+                (Some(stack), Some(used)) => {
+                    writeln!(dump, "    stack: {} of {} bytes used", used, stack.size())
+                }
+                (Some(stack), None) => writeln!(dump, "    stack: {} bytes", stack.size()),
+                (None, _) => writeln!(dump, "    stack: OS thread"),
+            };
+            let _ = writeln!(dump, "    switches: {}", thread.counters.switches);
+            if thread.id == self.current {
+                let _ = writeln!(dump, "    backtrace:\n{}", Backtrace::force_capture());
+            } else {
+                let _ = writeln!(dump, "    saved sp: {:#x}", thread.ctx.stack_pointer());
+            }
+        }
+        dump
+    }
+}
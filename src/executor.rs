@@ -0,0 +1,227 @@
+// Futureをグリーンスレッドで動かすための仕組み
+// NOTE: Futureごとにグリーンスレッドを1つ使い、Pendingの間はスレッドをブロックして他のスレッドに切り替える
+//       wakeが呼ばれたらスレッドを再開可能にして、もう一度pollする
+//       Wakerはstd::task::Wakeを実装したThreadWakerなので、既存のFutureをそのままpollでき、
+//       Spawnerを渡せば、タスクを生成するライブラリもグリーンスレッドの上で動かせる
+use std::future::Future;
+use std::marker::PhantomData;
+use std::pin::{pin, Pin};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::task::{Context, Poll, Wake, Waker};
+
+use crate::deadlock::BlockedOn;
+use crate::remote::RemoteWaker;
+use crate::{runtime_ptr, JoinHandle, Runtime, SpawnError, ThreadId, CURRENT, DEFAULT_PRIORITY};
+
+// グリーンスレッドに結び付いたWaker
+// NOTE: Waker::from(ThreadWaker::current())でWakerにして、既存のFutureのpollに渡せる
+//       wakeされたら、waitで待っているスレッドを再開可能にする(待っていなければ次のwaitがすぐに戻る)
+//       別のOSスレッドからwakeした場合はRuntimeに触れないので、RemoteHandleと同じくインジェクターに積んでリアクターを起こし、
+//       ランタイムのOSスレッドで起こしてもらう
+//       そのため、waitで待っている間は他のスレッドがすべて止まってもデッドロックとせずに、wakeされるのを待ち続ける
+pub struct ThreadWaker {
+    id: ThreadId,
+    // スレッドが動いているRuntime
+    runtime: *mut Runtime,
+    // waitしてからwakeが呼ばれたかどうか
+    notified: AtomicBool,
+    // スレッドがwaitで止まっているかどうか
+    // NOTE: 他の理由(ロックやスリープなど)で止まっているスレッドを起こさないように、waitの中にいるときだけ起こす
+    waiting: AtomicBool,
+    // 別のOSスレッドからwakeされたときにランタイムに知らせる
+    remote: RemoteWaker,
+}
+
+// NOTE: wakeは同じOSスレッドのRuntimeからしかスレッドを起こさず、別のOSスレッドからはインジェクターを通す
+unsafe impl Send for ThreadWaker {}
+unsafe impl Sync for ThreadWaker {}
+
+impl ThreadWaker {
+    // 現在のスレッドに結び付いたWakerを作る
+    pub fn current() -> Arc<ThreadWaker> {
+        let runtime = runtime_ptr();
+        let (id, remote) = unsafe {
+            let rt = &mut *runtime;
+            (rt.thread_id(rt.current), rt.remote_waker())
+        };
+        Arc::new(ThreadWaker {
+            id,
+            runtime,
+            notified: AtomicBool::new(false),
+            waiting: AtomicBool::new(false),
+            remote,
+        })
+    }
+
+    // 結び付いたスレッドのID
+    pub fn id(&self) -> ThreadId {
+        self.id
+    }
+
+    // wakeされるまで現在のスレッドをブロックする
+    // 前のwaitから後にすでにwakeされていた場合はすぐに戻る
+    // NOTE: 結び付いたスレッドから呼ぶこと
+    pub fn wait(&self) {
+        // NOTE: 確認してからブロックするまでの間にプリエンプションで切り替わると起こされそこねるので止める
+        let _guard = crate::preempt::disable();
+        assert_eq!(
+            crate::current().thread_id(),
+            self.id,
+            "ThreadWaker::wait must be called from the thread it belongs to."
+        );
+        if self.notified.swap(false, Ordering::SeqCst) {
+            return;
+        }
+        self.waiting.store(true, Ordering::SeqCst);
+        let _hold = unsafe { (*self.runtime).hold_remote_waker(&self.remote) };
+        // NOTE: waitの外で起こされることはないので、notifiedが立つまでブロックし直す
+        while !self.notified.swap(false, Ordering::SeqCst) {
+            crate::block_thread(BlockedOn {
+                what: "future",
+                addr: self as *const ThreadWaker as usize,
+                holder: None,
+            });
+        }
+        self.waiting.store(false, Ordering::SeqCst);
+    }
+
+    // 別のOSスレッドからのwakeをインジェクターから取り出したときに、ランタイムのOSスレッドで呼ぶ
+    // NOTE: 積んでから取り出すまでの間にwaitから戻っていることがあるので、もう一度確かめる
+    //       切り替えの途中なので、LIFOスロットには入れずにスケジューラに渡す
+    pub(crate) fn wake_remote(&self, rt: &mut Runtime) {
+        if self.waiting.load(Ordering::SeqCst) && rt.thread_of(self.id).is_some() {
+            rt.t_wake(self.id.index());
+        }
+    }
+}
+
+impl Wake for ThreadWaker {
+    fn wake(self: Arc<Self>) {
+        self.wake_by_ref();
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        self.notified.store(true, Ordering::SeqCst);
+        if !self.waiting.load(Ordering::SeqCst) {
+            return;
+        }
+        if CURRENT.with(|current| current.get()) != self.runtime {
+            self.remote.wake(self.clone());
+            return;
+        }
+        let _guard = crate::preempt::disable();
+        // NOTE: スレッドが終わって枠が使い回されていたら世代が変わっているので起こさない
+        let rt = unsafe { &mut *self.runtime };
+        if rt.thread_of(self.id).is_some() {
+            rt.t_wake_from_current(self.id.index());
+        }
+    }
+}
+
+// 現在のグリーンスレッドでFutureを完了するまでpollする
+pub(crate) fn run_future<F: Future>(future: F) -> F::Output {
+    let state = ThreadWaker::current();
+    let waker = Waker::from(state.clone());
+    let mut cx = Context::from_waker(&waker);
+    let mut future = pin!(future);
+
+    loop {
+        if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+            return output;
+        }
+        // poll中にwakeされていれば他のスレッドに譲ってからpollし直し、そうでなければwakeされるまでブロックする
+        // NOTE: 確認してからブロックするまでの間にプリエンプションで切り替わると起こされそこねるので止める
+        let _guard = crate::preempt::disable();
+        if state.notified.swap(false, Ordering::SeqCst) {
+            crate::yield_thread();
+        } else {
+            state.wait();
+        }
+    }
+}
+
+// Futureをグリーンスレッドとして生成するためのハンドル
+// NOTE: Executorを受け取って中でタスクを生成するライブラリに渡すためのもので、
+//       futures::task::LocalSpawnのようなトレイトは、このクレートでは依存を増やさないように実装しない
+//       必要な場合は、使う側でSpawnerを包んだ型にspawn_local_objを実装し、spawn_objを呼ぶ
+//       生成するときに現在のOSスレッドのランタイムを使うので、他のOSスレッドには渡せない
+#[derive(Clone, Debug)]
+pub struct Spawner {
+    _not_send: PhantomData<*const ()>,
+}
+
+// 現在のOSスレッドのランタイムにFutureを生成するSpawnerを返す
+pub fn spawner() -> Spawner {
+    Spawner {
+        _not_send: PhantomData,
+    }
+}
+
+impl Spawner {
+    // Futureを切り離したグリーンスレッドとして生成する
+    // NOTE: 利用可能なスレッドがない場合、スレッドの中から呼んだときは他のスレッドを実行して空くまで待つ
+    pub fn spawn<F>(&self, future: F) -> Result<(), SpawnError>
+    where
+        F: Future<Output = ()> + 'static,
+    {
+        self.spawn_with_handle(future).map(JoinHandle::detach)
+    }
+
+    // Futureをグリーンスレッドとして生成し、結果を受け取るためのJoinHandleを返す
+    pub fn spawn_with_handle<F>(&self, future: F) -> Result<JoinHandle<F::Output>, SpawnError>
+    where
+        F: Future + 'static,
+    {
+        // NOTE: 空くまで待つ間は他のスレッドに切り替えるので、&mut Runtimeを作らずにポインタのまま渡す
+        unsafe {
+            Runtime::t_spawn(runtime_ptr(), DEFAULT_PRIORITY, None, true, move || {
+                run_future(future)
+            })
+        }
+    }
+
+    // 型を消したFutureを生成する
+    // NOTE: futures::task::LocalFutureObjなどから変換したものを渡す
+    pub fn spawn_obj(&self, future: Pin<Box<dyn Future<Output = ()>>>) -> Result<(), SpawnError> {
+        self.spawn(future)
+    }
+}
+
+impl Runtime {
+    // Futureをグリーンスレッドとして実行する
+    pub fn spawn_async<F>(
+        self: Pin<&mut Self>,
+        future: F,
+    ) -> Result<crate::JoinHandle<F::Output>, crate::SpawnError>
+    where
+        F: Future + 'static,
+    {
+        self.spawn(move || run_future(future))
+    }
+
+    // Futureが完了するまでRuntimeを動かし、結果を返す
+    // NOTE: 他に生成されたスレッドもFutureが完了するまで一緒に実行される
+    pub fn block_on<F>(mut self: Pin<&mut Self>, future: F) -> F::Output
+    where
+        F: Future + 'static,
+    {
+        let handle = self
+            .as_mut()
+            .spawn_async(future)
+            .expect("failed to spawn the future passed to block_on.");
+        let this = unsafe { self.get_unchecked_mut() };
+        while !handle.is_finished() {
+            if !this.run_once() {
+                panic!(
+                    "deadlock: the future passed to block_on can never complete.\n{}",
+                    this.blocked_threads_report()
+                );
+            }
+        }
+        match handle.join() {
+            Ok(output) => output,
+            Err(payload) => std::panic::resume_unwind(payload),
+        }
+    }
+}
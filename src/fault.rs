@@ -0,0 +1,285 @@
+// テストのために、スケジューラとスレッドの生成にわざと障害を起こす
+// NOTE: スレッドの生成の失敗、起こすのの遅れ、parkからの見せかけの起床、再開可能なスレッドの順番の入れ替えを
+//       シードで決まる乱数で指定した確率で起こし、普段は通らないエラー処理や、実行の順番への思い込みを試す
+//       同じシードで同じプログラムを動かせば、同じところで同じ障害が起きる
+//       (DeterministicSchedulerと同じく、スリープやI/O、プリエンプションを使う場合は同じになるとは限らない)
+use std::fmt;
+
+use crate::spawn::SpawnError;
+use crate::{Runtime, State};
+
+// 障害を起こす確率の設定
+// Builder::fault_injectionに渡す
+#[derive(Clone, Debug)]
+pub struct FaultInjection {
+    seed: u64,
+    spawn_failure: f64,
+    wake_delay: f64,
+    // 起こすのを最大何回の切り替えの間遅らせるか
+    max_wake_delay: u32,
+    spurious_wakeup: f64,
+    shuffle: f64,
+}
+
+impl FaultInjection {
+    // seedで乱数を初期化し、どの障害も起こさない設定を作る
+    pub fn new(seed: u64) -> Self {
+        FaultInjection {
+            seed,
+            spawn_failure: 0.0,
+            wake_delay: 0.0,
+            max_wake_delay: 0,
+            spurious_wakeup: 0.0,
+            shuffle: 0.0,
+        }
+    }
+
+    // spawn、spawn_named、try_spawnなどを、probabilityの確率でErr(SpawnError)にする
+    // NOTE: PoolExhaustedとStackAllocationのどちらを返すかも乱数で決める
+    //       スコープ付きスレッドやmain_taskのように、失敗を返せない生成は失敗させない
+    pub fn fail_spawns(mut self, probability: f64) -> Self {
+        self.spawn_failure = check_probability(probability);
+        self
+    }
+
+    // ブロック中やparkで止まっているスレッドを起こすときに、probabilityの確率で
+    // 1からmax_switches回までのスレッドの切り替えの間、再開可能にするのを遅らせる
+    // NOTE: 他に再開可能なスレッドがなくなった場合は、遅らせているスレッドをすぐに再開可能にする
+    pub fn delay_wakeups(mut self, probability: f64, max_switches: u32) -> Self {
+        assert!(max_switches > 0, "max_switches must not be zero.");
+        self.wake_delay = check_probability(probability);
+        self.max_wake_delay = max_switches;
+        self
+    }
+
+    // スレッドを切り替えるたびに、probabilityの確率でparkで止まっているスレッドを1つ、unparkせずに再開可能にする
+    // NOTE: parkはunparkされなくても戻ることがあるので、条件を確かめながらparkを繰り返しているかを試せる
+    //       待ちキューやロックでブロックしているスレッドは起こさない
+    pub fn spurious_wakeups(mut self, probability: f64) -> Self {
+        self.spurious_wakeup = check_probability(probability);
+        self
+    }
+
+    // スケジューラに次のスレッドを選ばせるときに、probabilityの確率で再開可能なスレッドの中から乱数で選び直す
+    // NOTE: スケジューラがScheduler::takeに対応していない場合は、スケジューラが選んだスレッドのままにする
+    pub fn shuffle_ready(mut self, probability: f64) -> Self {
+        self.shuffle = check_probability(probability);
+        self
+    }
+}
+
+fn check_probability(probability: f64) -> f64 {
+    assert!(
+        (0.0..=1.0).contains(&probability),
+        "probability must be between 0 and 1."
+    );
+    probability
+}
+
+// 起こした障害の回数
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct InjectedFaults {
+    pub spawn_failures: u64,
+    pub delayed_wakeups: u64,
+    pub spurious_wakeups: u64,
+    pub shuffles: u64,
+}
+
+impl fmt::Display for InjectedFaults {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} spawn failures, {} delayed wakeups, {} spurious wakeups, {} shuffles",
+            self.spawn_failures, self.delayed_wakeups, self.spurious_wakeups, self.shuffles
+        )
+    }
+}
+
+pub(crate) struct Faults {
+    config: FaultInjection,
+    // xorshift64*の状態
+    state: u64,
+    // 起こすのを遅らせているスレッドと、再開可能にするまでの残りの切り替えの回数
+    delayed: Vec<(usize, u32)>,
+    injected: InjectedFaults,
+}
+
+impl Faults {
+    pub(crate) fn new(config: FaultInjection) -> Self {
+        Faults {
+            // NOTE: xorshiftは状態が0だと0しか出さないので避ける
+            state: if config.seed == 0 {
+                0x9E37_79B9_7F4A_7C15
+            } else {
+                config.seed
+            },
+            config,
+            delayed: Vec::new(),
+            injected: InjectedFaults::default(),
+        }
+    }
+
+    // NOTE: DeterministicSchedulerと同じxorshift64*
+    fn next_random(&mut self) -> u64 {
+        self.state ^= self.state >> 12;
+        self.state ^= self.state << 25;
+        self.state ^= self.state >> 27;
+        self.state.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+
+    // probabilityの確率でtrueを返す
+    fn hit(&mut self, probability: f64) -> bool {
+        if probability <= 0.0 {
+            return false;
+        }
+        // 上位53ビットから[0, 1)の値を作る
+        let x = (self.next_random() >> 11) as f64 / (1u64 << 53) as f64;
+        x < probability
+    }
+
+    fn below(&mut self, n: usize) -> usize {
+        (self.next_random() % n as u64) as usize
+    }
+}
+
+impl Runtime {
+    // Builder::fault_injectionを指定した場合に、これまでに起こした障害の回数を返す
+    pub fn injected_faults(&self) -> Option<InjectedFaults> {
+        self.faults.as_ref().map(|faults| faults.injected)
+    }
+
+    // 設定した確率でスレッドの生成を失敗させる
+    pub(crate) fn inject_spawn_failure(&mut self) -> Result<(), SpawnError> {
+        let faults = match &mut self.faults {
+            Some(faults) => faults,
+            None => return Ok(()),
+        };
+        if !faults.hit(faults.config.spawn_failure) {
+            return Ok(());
+        }
+        faults.injected.spawn_failures += 1;
+        Err(if faults.next_random() & 1 == 0 {
+            SpawnError::PoolExhausted
+        } else {
+            SpawnError::StackAllocation
+        })
+    }
+
+    // 設定した確率で、idのスレッドを起こすのを遅らせる
+    // 遅らせた場合はtrueを返し、呼び出し元はスレッドを再開可能にしない
+    // NOTE: すでに遅らせているスレッドをもう一度起こそうとした場合も、まとめて遅らせる
+    pub(crate) fn delay_wakeup(&mut self, id: usize) -> bool {
+        let faults = match &mut self.faults {
+            Some(faults) => faults,
+            None => return false,
+        };
+        if faults.delayed.iter().any(|(delayed, _)| *delayed == id) {
+            return true;
+        }
+        if !faults.hit(faults.config.wake_delay) {
+            return false;
+        }
+        let switches = faults.below(faults.config.max_wake_delay as usize) as u32 + 1;
+        faults.delayed.push((id, switches));
+        faults.injected.delayed_wakeups += 1;
+        true
+    }
+
+    // 切り替えるたびに呼び、遅らせていたスレッドを起こし、見せかけの起床を起こす
+    pub(crate) fn inject_switch_faults(&mut self) {
+        let faults = match &mut self.faults {
+            Some(faults) => faults,
+            None => return,
+        };
+        let mut due = Vec::new();
+        faults.delayed.retain_mut(|(id, switches)| {
+            *switches -= 1;
+            if *switches == 0 {
+                due.push(*id);
+            }
+            *switches > 0
+        });
+        let spurious = faults.hit(faults.config.spurious_wakeup);
+        for id in due {
+            self.release_wakeup(id);
+        }
+        if spurious {
+            self.spurious_wakeup();
+        }
+    }
+
+    // parkで止まっているスレッドを1つ選び、unparkせずに再開可能にする
+    fn spurious_wakeup(&mut self) {
+        let parked: Vec<usize> = self
+            .threads
+            .iter()
+            .filter(|t| t.state == State::Parked)
+            .map(|t| t.id)
+            .collect();
+        let faults = self.faults.as_mut().unwrap();
+        if parked.is_empty() {
+            return;
+        }
+        let id = parked[faults.below(parked.len())];
+        faults.injected.spurious_wakeups += 1;
+        self.make_ready(id);
+    }
+
+    // 遅らせていたidのスレッドを、まだ止まっていれば再開可能にする
+    fn release_wakeup(&mut self, id: usize) {
+        if matches!(self.threads[id].state, State::Blocked | State::Parked) {
+            self.make_ready(id);
+        }
+    }
+
+    // posのスレッドに切り替えるので、遅らせていた起床を取り消す
+    // NOTE: キャンセルなど別の理由で先に起きた場合、後から遅れて起こすと次のブロックから誤って起こしてしまう
+    pub(crate) fn forget_delayed_wakeup(&mut self, pos: usize) {
+        if let Some(faults) = &mut self.faults {
+            faults.delayed.retain(|(id, _)| *id != pos);
+        }
+    }
+
+    // スケジューラに次に実行するスレッドを選ばせる
+    // NOTE: 設定した確率で再開可能なスレッドの中から乱数で選び直し、
+    //       選べるスレッドがなければ遅らせている起床をすべて済ませてから選び直す
+    pub(crate) fn pick_next(&mut self) -> Option<usize> {
+        if self.faults.is_none() {
+            return self.scheduler.pick_next();
+        }
+        if let Some(id) = self.pick_shuffled() {
+            return Some(id);
+        }
+        if let Some(id) = self.scheduler.pick_next() {
+            return Some(id);
+        }
+        let delayed = std::mem::take(&mut self.faults.as_mut().unwrap().delayed);
+        for (id, _) in delayed {
+            self.release_wakeup(id);
+        }
+        self.scheduler.pick_next()
+    }
+
+    fn pick_shuffled(&mut self) -> Option<usize> {
+        let faults = self.faults.as_mut().unwrap();
+        if !faults.hit(faults.config.shuffle) {
+            return None;
+        }
+        let ready: Vec<usize> = self
+            .threads
+            .iter()
+            .filter(|t| t.state == State::Ready && self.lifo_slot != Some(t.id))
+            .map(|t| t.id)
+            .collect();
+        if ready.is_empty() {
+            return None;
+        }
+        let faults = self.faults.as_mut().unwrap();
+        let id = ready[faults.below(ready.len())];
+        if !self.scheduler.take(id) {
+            return None;
+        }
+        self.faults.as_mut().unwrap().injected.shuffles += 1;
+        Some(id)
+    }
+}
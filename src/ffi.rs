@@ -0,0 +1,139 @@
+// Cのプログラムからランタイムを動かすためのFFI
+// NOTE: ffiフィーチャーを有効にして、共有ライブラリとしてビルドする
+//         cargo rustc --lib --release --features ffi --crate-type cdylib
+//       Cからはinclude/greenthreads.hをインクルードし、target/release/libgreenthreads.soをリンクして使う
+//       (Cargo.tomlでcrate-typeにcdylibを足すと、no_stdでビルドできなくなるので足さない)
+//
+//       RustのパニックがCの関数のフレームを巻き戻すのは未定義動作なので、
+//       どの関数も中でcatch_unwindで止めて、エラーの値を返す
+//       逆に、Cの関数からlongjmpやC++の例外でRustのフレームを抜けるのも未定義動作なので、タスク関数の中で止めること
+//       ポインタを受け取る関数の守るべき条件は、それぞれの関数のコメントに書く
+#![allow(clippy::missing_safety_doc)]
+use std::os::raw::{c_int, c_void};
+use std::panic::{self, AssertUnwindSafe};
+use std::pin::Pin;
+use std::ptr;
+
+use crate::{runtime_ptr, Cancelled, Runtime, CURRENT, DEFAULT_PRIORITY};
+
+// 成功
+pub const GT_OK: c_int = 0;
+// 失敗(ランタイムがない、スレッドを生成できない、デッドロックしたなど)
+pub const GT_ERROR: c_int = -1;
+// スレッドがキャンセルされた
+// NOTE: gt_yieldがこれを返したら、タスク関数はできるだけ早く戻ること
+//       gt_runtime_freeは終わっていないスレッドをキャンセルして、戻るまで待つ
+pub const GT_CANCELLED: c_int = -2;
+
+// タスク関数
+// NOTE: userdataはそのまま渡すだけで、Rust側では読み書きも解放もしない
+pub type GtTask = extern "C" fn(userdata: *mut c_void);
+
+// ランタイムを作り、このOSスレッドのランタイムにする
+// 失敗した場合はNULLを返す
+// NOTE: Runtimeは中身の見えないポインタとしてCに渡す
+#[no_mangle]
+pub extern "C" fn gt_runtime_new() -> *mut Runtime {
+    catch(ptr::null_mut(), || {
+        let mut runtime = Runtime::new();
+        runtime.as_mut().init();
+        runtime.into_raw()
+    })
+}
+
+// ランタイムを解放する
+// NOTE: 終わっていないスレッドはキャンセルし、戻るまで待つ
+//       rtはgt_runtime_newで作ったものを一度だけ渡し、スレッドの中から呼んではいけない
+#[no_mangle]
+pub unsafe extern "C" fn gt_runtime_free(rt: *mut Runtime) {
+    if rt.is_null() {
+        return;
+    }
+    catch((), || {
+        // 解放したランタイムを指したままにしない
+        CURRENT.with(|current| {
+            if current.get() == rt {
+                current.set(ptr::null_mut());
+            }
+        });
+        drop(Box::from_raw(rt));
+    })
+}
+
+// このOSスレッドのランタイムに、task(userdata)を実行するスレッドを生成する
+// 生成したスレッドのIDを返し、失敗した場合はGT_ERRORを返す
+// NOTE: スレッドの中からも呼べ、利用可能なスレッドがない場合は他のスレッドを実行して空くまで待つ
+//       生成したスレッドの終わりは待てない(JoinHandleは手放す)
+#[no_mangle]
+pub extern "C" fn gt_spawn(task: Option<GtTask>, userdata: *mut c_void) -> c_int {
+    let task = match task {
+        Some(task) => task,
+        None => return GT_ERROR,
+    };
+    if !in_runtime() {
+        return GT_ERROR;
+    }
+    // NOTE: ポインタは'staticなクロージャに入れられないので、整数にして渡す
+    let userdata = userdata as usize;
+    catch(GT_ERROR, || {
+        // NOTE: 空くまで待つ間は他のスレッドに切り替えるので、&mut Runtimeを作らずにポインタのまま渡す
+        let spawned = unsafe {
+            Runtime::t_spawn(runtime_ptr(), DEFAULT_PRIORITY, None, true, move || {
+                task(userdata as *mut c_void)
+            })
+        };
+        match spawned {
+            Ok(handle) => handle.thread().id() as c_int,
+            Err(_) => GT_ERROR,
+        }
+    })
+}
+
+// 他のスレッドに切り替える
+// 再び実行されたらGT_OKを、キャンセルされていたらGT_CANCELLEDを返す
+// NOTE: Rustのyield_threadはキャンセルされると巻き戻すが、巻き戻すとCのフレームを抜けることになるので、
+//       ここで止めてGT_CANCELLEDを返す
+#[no_mangle]
+pub extern "C" fn gt_yield() -> c_int {
+    if !in_runtime() {
+        return GT_ERROR;
+    }
+    match panic::catch_unwind(crate::yield_thread) {
+        Ok(()) => GT_OK,
+        Err(payload) if payload.is::<Cancelled>() => GT_CANCELLED,
+        Err(_) => GT_ERROR,
+    }
+}
+
+// 現在のスレッドのIDを返す(ベーススレッドは0)
+#[no_mangle]
+pub extern "C" fn gt_current() -> c_int {
+    if !in_runtime() {
+        return GT_ERROR;
+    }
+    crate::current_thread() as c_int
+}
+
+// すべてのスレッドが終わるまで実行する
+// デッドロックしたなどで途中で止まった場合はGT_ERRORを返す
+// NOTE: rtはgt_runtime_newで作ってまだ解放していないものを渡し、スレッドの中から呼んではいけない
+#[no_mangle]
+pub unsafe extern "C" fn gt_run(rt: *mut Runtime) -> c_int {
+    if rt.is_null() {
+        return GT_ERROR;
+    }
+    catch(GT_ERROR, || {
+        // NOTE: gt_runtime_newで固定したまま渡したものなので、固定した参照に戻してよい
+        Pin::new_unchecked(&mut *rt).run();
+        GT_OK
+    })
+}
+
+fn in_runtime() -> bool {
+    !CURRENT.with(|current| current.get()).is_null()
+}
+
+// fを実行し、パニックした場合はerrorを返す
+fn catch<T>(error: T, f: impl FnOnce() -> T) -> T {
+    panic::catch_unwind(AssertUnwindSafe(f)).unwrap_or(error)
+}
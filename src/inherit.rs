@@ -0,0 +1,89 @@
+// Mutexの優先度継承
+// NOTE: 優先度の低いスレッドがロックを持ったまま、優先度の高いスレッドを待たせ続けないように、
+//       ロックを持っているスレッドの優先度を、待っているスレッドの中で一番高い優先度まで一時的に上げる
+//       ロックごとに継承した優先度を覚えておき、ロックを手放したらその分だけ取り消す
+use crate::{Runtime, State};
+
+// 継承した優先度を辿るロックの種類
+// NOTE: 優先度を戻すのはMutexを手放したときだけなので、それ以外を待っているスレッドの先には伝えない
+const MUTEX: &str = "Mutex::lock";
+
+impl Runtime {
+    // 実際にスケジューラに渡す優先度
+    // 自分の優先度と、持っているロックを待っているスレッドから継承した優先度の高い方
+    pub(crate) fn effective_priority(&self, id: usize) -> u8 {
+        let thread = &self.threads[id];
+        thread
+            .inherited
+            .iter()
+            .map(|&(_, priority)| priority)
+            .fold(thread.priority, u8::max)
+    }
+
+    // holderが持っているlockをpriorityのスレッドが待つので、holderの優先度をpriority以上に上げる
+    // holderも別のMutexを待っている場合は、そのMutexを持っているスレッドにも伝える
+    pub(crate) fn inherit_priority(&mut self, holder: usize, lock: usize, priority: u8) {
+        let (mut holder, mut lock) = (holder, lock);
+        // NOTE: デッドロックで循環している場合に止まるように、辿る数はスレッドの数までにする
+        for _ in 0..self.threads.len() {
+            let before = self.effective_priority(holder);
+            let thread = &mut self.threads[holder];
+            // NOTE: 今の優先度が十分高くても、他のロックを手放したときのために継承した分は覚えておく
+            match thread.inherited.iter_mut().find(|(l, _)| *l == lock) {
+                Some((_, p)) => *p = (*p).max(priority),
+                None => thread.inherited.push((lock, priority)),
+            }
+            // NOTE: 既に高い優先度になっていれば、その先のスレッドにも伝わっている
+            if before >= priority {
+                return;
+            }
+            self.update_priority(holder);
+
+            let thread = &self.threads[holder];
+            match (&thread.state, thread.blocked_on) {
+                (State::Blocked, Some(b)) if b.what == MUTEX => match b.holder {
+                    Some(next) => {
+                        holder = next;
+                        lock = b.addr;
+                    }
+                    None => return,
+                },
+                _ => return,
+            }
+        }
+    }
+
+    // 現在のスレッドがlockを取ったので、lockを待っている残りのスレッドの優先度を継承する
+    // NOTE: 待っているスレッドが待っている相手も、現在のスレッドに書き換える
+    pub(crate) fn acquire_priority(&mut self, lock: usize, waiters: &[usize]) {
+        let current = self.current;
+        let mut priority = None;
+        for &id in waiters {
+            if let Some(b) = self.threads[id].blocked_on.as_mut() {
+                b.holder = Some(current);
+            }
+            let p = self.effective_priority(id);
+            priority = Some(priority.map_or(p, |max: u8| max.max(p)));
+        }
+        if let Some(priority) = priority {
+            self.inherit_priority(current, lock, priority);
+        }
+    }
+
+    // 現在のスレッドがlockを手放したので、lockで継承した優先度を取り消す
+    pub(crate) fn release_priority(&mut self, lock: usize) {
+        let current = self.current;
+        let inherited = &mut self.threads[current].inherited;
+        let len = inherited.len();
+        inherited.retain(|(l, _)| *l != lock);
+        if inherited.len() != len {
+            self.update_priority(current);
+        }
+    }
+
+    // 優先度が変わったことをスケジューラに伝える
+    pub(crate) fn update_priority(&mut self, id: usize) {
+        let priority = self.effective_priority(id);
+        self.scheduler.set_priority(id, priority);
+    }
+}
@@ -0,0 +1,206 @@
+// グリーンスレッドから使う標準出力
+// NOTE: std::io::stdoutに直接書くと、スレッドが途中で切り替わったときに行が混ざり、
+//       パイプの先が読むのが遅くて書き込めないとOSスレッドごと止まって、すべてのグリーンスレッドが止まる
+//       ここではスレッドごとに改行までためて、1行ずつ行頭にスレッドの名前を付けて書き出す
+//       書き込めない間はリアクターで書き込めるようになるまで待ち、他のスレッドに切り替える
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, Write};
+use std::mem::ManuallyDrop;
+use std::os::raw::c_int;
+use std::os::unix::io::{FromRawFd, RawFd};
+
+use crate::reactor::Interest;
+use crate::sync::WaitQueue;
+use crate::{preempt, CURRENT};
+
+const STDOUT_FD: RawFd = 1;
+
+// 書き出し待ちの行がこれを超えたら、書き込む側のスレッドも書き出し終わるまで待つ
+const MAX_PENDING: usize = 64 * 1024;
+
+const F_GETFL: c_int = 3;
+const F_SETFL: c_int = 4;
+#[cfg(target_os = "linux")]
+const O_NONBLOCK: c_int = 0o4000;
+#[cfg(not(target_os = "linux"))]
+const O_NONBLOCK: c_int = 0x0004;
+
+extern "C" {
+    fn fcntl(fd: c_int, cmd: c_int, ...) -> c_int;
+}
+
+struct Shared {
+    // スレッドIDごとの、まだ改行が来ていない書きかけの行
+    partial: HashMap<usize, Vec<u8>>,
+    // 行頭に名前を付けた、書き出し待ちの行
+    pending: Vec<u8>,
+    // どれかのスレッドが書き出している途中かどうか
+    flushing: bool,
+}
+
+thread_local! {
+    // NOTE: 標準出力はプロセスで1つだが、グリーンスレッドの切り替えはOSスレッドの中で閉じているので、OSスレッドごとに持つ
+    static SHARED: RefCell<Shared> = RefCell::new(Shared {
+        partial: HashMap::new(),
+        pending: Vec::new(),
+        flushing: false,
+    });
+    // 書き出しが終わるのを待っているスレッド
+    static WAITERS: WaitQueue = WaitQueue::new("io::Stdout::flush");
+}
+
+// 標準出力へのハンドル
+// NOTE: 書き込んだ内容は改行までたまり、flushするか改行を書いたときに書き出される
+pub struct Stdout {
+    _private: (),
+}
+
+pub fn stdout() -> Stdout {
+    Stdout { _private: () }
+}
+
+impl Write for Stdout {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if !in_runtime() {
+            return io::stdout().write(buf);
+        }
+        let full = {
+            let _guard = preempt::disable();
+            let id = crate::current_thread();
+            SHARED.with(|shared| {
+                let mut shared = shared.borrow_mut();
+                let shared = &mut *shared;
+                let line = shared.partial.entry(id).or_default();
+                line.extend_from_slice(buf);
+                // 改行までの部分を書き出し待ちに移す
+                if let Some(end) = line.iter().rposition(|&b| b == b'\n') {
+                    let rest = line.split_off(end + 1);
+                    let lines = std::mem::replace(line, rest);
+                    push_lines(&mut shared.pending, &lines);
+                }
+                shared.pending.len() > MAX_PENDING
+            })
+        };
+        // 書き出し待ちが多すぎる場合だけ書き出し終わるまで待つ
+        // NOTE: そうでなければ、書き出すのは1つのスレッドに任せて待たずに戻る
+        drain(full)?;
+        Ok(buf.len())
+    }
+
+    // 現在のスレッドの書きかけの行も含めて、すべて書き出す
+    // NOTE: 改行で終わらない行を書いたスレッドは、終わる前にflushすること
+    //       書きかけのまま終わると、同じIDで次に生成されたスレッドの行の前に付いてしまう
+    fn flush(&mut self) -> io::Result<()> {
+        if !in_runtime() {
+            return io::stdout().flush();
+        }
+        {
+            let _guard = preempt::disable();
+            let id = crate::current_thread();
+            SHARED.with(|shared| {
+                let mut shared = shared.borrow_mut();
+                let shared = &mut *shared;
+                if let Some(mut line) = shared.partial.remove(&id) {
+                    if !line.is_empty() {
+                        line.push(b'\n');
+                        push_lines(&mut shared.pending, &line);
+                    }
+                }
+            });
+        }
+        drain(true)
+    }
+}
+
+fn in_runtime() -> bool {
+    !CURRENT.with(|current| current.get()).is_null()
+}
+
+// 1行ずつ行頭に現在のスレッドの名前を付けてpendingに積む
+fn push_lines(pending: &mut Vec<u8>, lines: &[u8]) {
+    let handle = crate::current();
+    let tag = match handle.name() {
+        Some(name) => format!("[{}] ", name),
+        None => format!("[thread {}] ", handle.id()),
+    };
+    for line in lines.split_inclusive(|&b| b == b'\n') {
+        pending.extend_from_slice(tag.as_bytes());
+        pending.extend_from_slice(line);
+    }
+}
+
+// 書き出し待ちの行を書き出す
+// 他のスレッドが書き出している場合は、waitがtrueなら書き出し終わるまで待ち、falseなら任せて戻る
+fn drain(wait: bool) -> io::Result<()> {
+    loop {
+        let _guard = preempt::disable();
+        let flushing = SHARED.with(|shared| {
+            let mut shared = shared.borrow_mut();
+            if shared.flushing {
+                return true;
+            }
+            shared.flushing = true;
+            false
+        });
+        if !flushing {
+            break;
+        }
+        if !wait {
+            return Ok(());
+        }
+        WAITERS.with(|waiters| waiters.wait());
+    }
+
+    let result = write_pending();
+    let _guard = preempt::disable();
+    SHARED.with(|shared| shared.borrow_mut().flushing = false);
+    WAITERS.with(|waiters| waiters.notify_all());
+    result
+}
+
+// 書き出している間に他のスレッドが積んだ行も含めて、pendingが空になるまで書き出す
+fn write_pending() -> io::Result<()> {
+    loop {
+        let buf = {
+            let _guard = preempt::disable();
+            SHARED.with(|shared| std::mem::take(&mut shared.borrow_mut().pending))
+        };
+        if buf.is_empty() {
+            return Ok(());
+        }
+        let mut written = 0;
+        while written < buf.len() {
+            match write_nonblocking(&buf[written..]) {
+                Ok(0) => return Err(io::ErrorKind::WriteZero.into()),
+                Ok(n) => written += n,
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => {
+                    crate::wait_io(STDOUT_FD, Interest::Writable)?
+                }
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+    }
+}
+
+// 標準出力にノンブロッキングで書き込む
+// NOTE: O_NONBLOCKはfdを共有している他のプロセスやstd::io::stdoutにも効いてしまうので、書き込む間だけ立てる
+fn write_nonblocking(buf: &[u8]) -> io::Result<usize> {
+    let flags = unsafe { fcntl(STDOUT_FD, F_GETFL) };
+    if flags < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    let set = flags & O_NONBLOCK == 0;
+    if set && unsafe { fcntl(STDOUT_FD, F_SETFL, flags | O_NONBLOCK) } < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    // NOTE: fdを閉じないようにManuallyDropで包む
+    let mut file = ManuallyDrop::new(unsafe { File::from_raw_fd(STDOUT_FD) });
+    let result = file.write(buf);
+    if set {
+        unsafe { fcntl(STDOUT_FD, F_SETFL, flags) };
+    }
+    result
+}
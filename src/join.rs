@@ -0,0 +1,132 @@
+// スレッドの終了を待ち、戻り値を受け取るためのハンドル
+use std::any::Any;
+use std::cell::{Cell, RefCell};
+use std::panic::{self, AssertUnwindSafe};
+use std::rc::Rc;
+use std::thread::Result;
+use std::time::{Duration, Instant};
+
+use crate::sync::WaitQueue;
+use crate::{CancellationToken, ThreadHandle};
+
+struct Packet<T> {
+    // タスクの戻り値、パニックした場合はパニックの内容
+    result: RefCell<Option<Result<T>>>,
+    // 終了を待っているスレッド
+    waiters: WaitQueue,
+    // JoinHandleを手放したかどうか
+    detached: Cell<bool>,
+}
+
+pub struct JoinHandle<T> {
+    packet: Rc<Packet<T>>,
+    thread: ThreadHandle,
+    pub(crate) token: CancellationToken,
+}
+
+impl<T> JoinHandle<T> {
+    // スレッドが終わるまでブロックし、戻り値を返す
+    // スレッドがパニックした場合はパニックの内容をErrで返す
+    pub fn join(self) -> Result<T> {
+        let _guard = crate::preempt::disable();
+        loop {
+            if let Some(result) = self.packet.result.borrow_mut().take() {
+                return result;
+            }
+            self.packet.waiters.wait_for(Some(self.thread.id()));
+        }
+    }
+
+    // joinと同じだが、durが経過してもスレッドが終わらない場合はハンドルをErrで返す
+    // NOTE: 返したハンドルでもう一度待ったり、キャンセルしたりできる
+    pub fn join_timeout(self, dur: Duration) -> std::result::Result<Result<T>, Self> {
+        self.join_until(Instant::now() + dur)
+    }
+
+    // joinと同じだが、deadlineになってもスレッドが終わらない場合はハンドルをErrで返す
+    pub fn join_until(self, deadline: Instant) -> std::result::Result<Result<T>, Self> {
+        let _guard = crate::preempt::disable();
+        loop {
+            if let Some(result) = self.packet.result.borrow_mut().take() {
+                return Ok(result);
+            }
+            if !self
+                .packet
+                .waiters
+                .wait_until(deadline, Some(self.thread.id()))
+            {
+                return Err(self);
+            }
+        }
+    }
+
+    pub fn thread(&self) -> &ThreadHandle {
+        &self.thread
+    }
+
+    // スレッドをキャンセルする
+    // NOTE: スレッドはすぐには終わらず、sleepなどがErr(Cancelled)を返すのでそれを見て終わる
+    pub fn cancel(&self) {
+        self.token.cancel();
+    }
+
+    pub fn cancellation_token(&self) -> &CancellationToken {
+        &self.token
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.packet.result.borrow().is_some()
+    }
+
+    // スレッドを切り離し、終了を待たないことにする
+    // 切り離したスレッドが終わったら、戻り値はすぐにドロップされ、スタックとスレッドの枠はすぐに使い回される
+    // NOTE: JoinHandleをドロップした場合も同じ
+    pub fn detach(self) {}
+}
+
+impl<T> Drop for JoinHandle<T> {
+    fn drop(&mut self) {
+        self.packet.detached.set(true);
+    }
+}
+
+// タスクを、パニックを捕まえて結果をJoinHandleに渡すタスクに包む
+// NOTE: パニックをそのまま巻き戻すと自前で積んだスタックの先に抜けてプロセスが落ちるので、ここで止める
+pub(crate) fn wrap<'a, F, T>(f: F, thread: ThreadHandle) -> (Box<dyn FnOnce() + 'a>, JoinHandle<T>)
+where
+    F: FnOnce() -> T + 'a,
+    T: 'a,
+{
+    let packet = Rc::new(Packet {
+        result: RefCell::new(None),
+        waiters: WaitQueue::new("JoinHandle::join"),
+        detached: Cell::new(false),
+    });
+    let their_packet = packet.clone();
+    let task = move || {
+        // shutdownでキャンセルされた場合は、始まる前にやめる
+        let result: std::result::Result<T, Box<dyn Any + Send>> = if crate::shutdown::is_cancelled()
+        {
+            Err(Box::new(crate::Cancelled))
+        } else {
+            panic::catch_unwind(AssertUnwindSafe(f))
+        };
+        let _guard = crate::preempt::disable();
+        // 切り離されている場合は受け取るスレッドがいないので、このスレッドでドロップする
+        if their_packet.detached.get() {
+            drop(result);
+            return;
+        }
+        *their_packet.result.borrow_mut() = Some(result);
+        their_packet.waiters.notify_all();
+    };
+    let token = CancellationToken::new(thread.id());
+    (
+        Box::new(task),
+        JoinHandle {
+            packet,
+            thread,
+            token,
+        },
+    )
+}
@@ -0,0 +1,1355 @@
+// NOTE: stdフィーチャーを無効にすると、bareのExecutorとスケジューラだけをno_stdで使える
+#![cfg_attr(not(feature = "std"), no_std)]
+extern crate alloc;
+
+#[cfg(feature = "std")]
+use std::cell::{Cell, RefCell};
+#[cfg(feature = "std")]
+use std::collections::BTreeSet;
+#[cfg(feature = "std")]
+use std::marker::{PhantomData, PhantomPinned};
+#[cfg(feature = "std")]
+use std::os::unix::io::RawFd;
+#[cfg(feature = "std")]
+use std::pin::Pin;
+#[cfg(feature = "std")]
+use std::ptr::{self, addr_of, addr_of_mut};
+#[cfg(feature = "std")]
+use std::rc::Rc;
+#[cfg(feature = "std")]
+use std::time::{Duration, Instant};
+
+#[cfg(feature = "std")]
+mod abi;
+#[cfg(feature = "std")]
+pub mod actor;
+pub mod bare;
+#[cfg(feature = "std")]
+mod blocking;
+#[cfg(feature = "std")]
+mod budget;
+#[cfg(feature = "std")]
+mod builder;
+#[cfg(feature = "std")]
+mod cancel;
+#[cfg(feature = "std")]
+mod config;
+mod context;
+#[cfg(feature = "std")]
+mod coroutine;
+#[cfg(feature = "std")]
+mod deadlock;
+#[cfg(feature = "std")]
+mod dump;
+#[cfg(feature = "std")]
+mod executor;
+#[cfg(feature = "std")]
+mod fault;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+#[cfg(feature = "std")]
+mod inherit;
+#[cfg(feature = "std")]
+pub mod io;
+#[cfg(feature = "std")]
+mod join;
+#[cfg(feature = "std")]
+mod local;
+#[cfg(feature = "std")]
+mod main_task;
+#[cfg(feature = "metrics")]
+mod metrics;
+#[cfg(all(feature = "std", greenthreads_model))]
+pub mod model;
+#[cfg(feature = "std")]
+pub mod multi;
+#[cfg(feature = "std")]
+mod naming;
+#[cfg(feature = "std")]
+pub mod net;
+#[cfg(feature = "std")]
+mod park;
+#[cfg(feature = "std")]
+mod pinned;
+#[cfg(feature = "std")]
+mod preempt;
+#[cfg(feature = "std")]
+pub mod process;
+#[cfg(feature = "std")]
+mod reactor;
+#[cfg(feature = "std")]
+mod remote;
+#[cfg(feature = "sanitize")]
+mod sanitize;
+pub mod scheduler;
+#[cfg(feature = "std")]
+mod scope;
+#[cfg(feature = "std")]
+mod shutdown;
+#[cfg(feature = "std")]
+pub mod signal;
+#[cfg(feature = "std")]
+pub mod snapshot;
+#[cfg(feature = "std")]
+mod spawn;
+#[cfg(feature = "std")]
+mod stack;
+#[cfg(feature = "std")]
+mod stats;
+#[cfg(feature = "std")]
+pub mod supervisor;
+#[cfg(feature = "std")]
+pub mod sync;
+#[cfg(feature = "std")]
+mod task_context;
+#[cfg(feature = "std")]
+mod task_group;
+#[cfg(feature = "std")]
+mod timer;
+#[cfg(feature = "trace")]
+mod trace;
+#[cfg(all(feature = "io-uring", target_os = "linux"))]
+pub mod uring;
+
+#[cfg(feature = "std")]
+pub use abi::debug_verify_stack;
+#[cfg(feature = "std")]
+pub use blocking::spawn_blocking;
+#[cfg(feature = "std")]
+use blocking::{BlockingPool, BLOCKING_WAKER};
+#[cfg(feature = "std")]
+pub use budget::{consume_budget, maybe_yield};
+#[cfg(feature = "std")]
+pub use builder::Builder;
+#[cfg(feature = "std")]
+pub use cancel::{cancellation_token, CancellationToken, Cancelled};
+#[cfg(feature = "std")]
+pub use config::ConfigError;
+#[cfg(feature = "std")]
+pub use context::StackLayoutError;
+#[cfg(feature = "std")]
+use context::ThreadContext;
+#[cfg(feature = "std")]
+pub use coroutine::{Coroutine, CoroutineState, Yielder};
+#[cfg(feature = "std")]
+pub use deadlock::Deadlock;
+#[cfg(feature = "std")]
+use deadlock::{BlockedOn, DeadlockHandler};
+#[cfg(feature = "std")]
+pub use executor::{spawner, Spawner, ThreadWaker};
+#[cfg(feature = "std")]
+use fault::Faults;
+#[cfg(feature = "std")]
+pub use fault::{FaultInjection, InjectedFaults};
+#[cfg(feature = "std")]
+pub use join::JoinHandle;
+#[cfg(feature = "std")]
+pub use local::LocalKey;
+#[cfg(feature = "std")]
+use local::Locals;
+#[cfg(feature = "metrics")]
+pub use metrics::serve_metrics;
+#[cfg(feature = "std")]
+pub use park::{current, park, ThreadFinished, ThreadHandle, ThreadId, ThreadState};
+#[cfg(feature = "std")]
+pub use pinned::PinnedRuntime;
+#[cfg(feature = "std")]
+pub use preempt::without_preemption;
+#[cfg(feature = "std")]
+use reactor::{Interest, Reactor};
+#[cfg(feature = "std")]
+pub use remote::{RemoteHandle, RuntimeClosed};
+#[cfg(feature = "std")]
+use remote::{Remotes, REMOTE_WAKER};
+#[cfg(feature = "std")]
+use scheduler::{Scheduler, SwitchReason};
+pub use scheduler::{SchedulerPolicy, DEFAULT_PRIORITY};
+#[cfg(feature = "std")]
+pub use scope::{Scope, ScopedJoinHandle};
+#[cfg(feature = "std")]
+use shutdown::DEFAULT_SHUTDOWN_GRACE;
+#[cfg(feature = "std")]
+pub use spawn::SpawnError;
+#[cfg(feature = "std")]
+use stack::StackPool;
+#[cfg(feature = "std")]
+pub use stack::{MmapStackAllocator, Stack, StackAllocator, StackRecyclePolicy};
+#[cfg(feature = "std")]
+use stats::Counters;
+#[cfg(feature = "std")]
+pub use stats::{Stats, ThreadStats};
+#[cfg(feature = "std")]
+pub use task_context::{with_value, Context, ContextKey};
+#[cfg(feature = "std")]
+pub use task_group::{GroupError, TaskError, TaskGroup};
+#[cfg(feature = "std")]
+use timer::Timers;
+#[cfg(feature = "std")]
+pub use timer::{
+    interval, interval_at, sleep, sleep_until, timeout, timeout_at, Elapsed, Interval,
+};
+#[cfg(feature = "trace")]
+pub use trace::TraceEvent;
+
+#[cfg(feature = "std")]
+const DEFAULT_STACK_SIZE: usize = 1024 * 1024 * 2;
+
+#[cfg(feature = "std")]
+thread_local! {
+    // 現在のOSスレッドで動いているRuntime
+    // NOTE: 複数のRuntimeを作れるように、runで実行中のRuntimeに差し替え、終わったら元に戻す
+    static CURRENT: Cell<*mut Runtime> = const { Cell::new(ptr::null_mut()) };
+}
+
+// 現在のOSスレッドで動いているRuntimeへのポインタを返す
+// NOTE: スレッドを切り替えると切り替え先のスレッドが同じRuntimeを書き換えるため、
+//       &mut Runtimeはスレッドの切り替えをまたいで保持してはいけない
+//       切り替えを伴う処理(t_yield, t_blockなど)はポインタのまま受け取り、その都度短い参照を作る
+#[cfg(feature = "std")]
+fn runtime_ptr() -> *mut Runtime {
+    let rt = CURRENT.with(|current| current.get());
+    assert!(!rt.is_null(), "no runtime is running on this thread.");
+    rt
+}
+
+// ドロップしたときに、現在のOSスレッドのRuntimeを持っている値に戻す
+// NOTE: scopeやblock_on_mainが、戻るときもパニックで巻き戻るときも呼び出し元のRuntimeに戻すのに使う
+#[cfg(feature = "std")]
+pub(crate) struct RestoreCurrent(pub(crate) *mut Runtime);
+
+#[cfg(feature = "std")]
+impl Drop for RestoreCurrent {
+    fn drop(&mut self) {
+        CURRENT.with(|current| current.set(self.0));
+    }
+}
+
+#[cfg(feature = "std")]
+pub struct Runtime {
+    threads: Vec<Thread>,
+    // 利用可能(Available)なスレッドのID
+    // NOTE: スレッドを生成するたびにthreadsを先頭から探さないように、状態が変わるところで出し入れする
+    //       IDの小さいスレッドから使うようにBTreeSetで持つ
+    available: BTreeSet<usize>,
+    current: usize,
+    timers: Timers,
+    reactor: Reactor,
+    scheduler: Box<dyn Scheduler>,
+    // Noneの場合はプリエンプションしない
+    time_slice: Option<Duration>,
+    // スレッドを切り替えた回数
+    switches: u64,
+    // make_readyでスレッドを再開可能にした回数
+    // NOTE: spin_eventsで、スレッドを調べずに再開可能になったスレッドがあるかを知るのに使う
+    wakeups: u64,
+    // 実行中のスレッドに切り替えた時刻
+    running_since: Instant,
+    // 切り替えずに予算を使う操作を続けられる回数と時間(Noneの場合は制限しない)
+    op_budget: Option<u32>,
+    time_budget: Option<Duration>,
+    // 実行中のスレッドが使った予算と、予算を数え始めた時刻
+    // NOTE: 時刻は休止していた時間を除くため、running_sinceとは別に持つ
+    budget_used: u32,
+    budget_since: Instant,
+    // スレッドが終わったときに、スタックの使用量がこの割合(%)を超えていたら警告する
+    stack_usage_warning: Option<u8>,
+    // 作ったスレッドのスタックの形を確かめるかどうか
+    strict_abi: bool,
+    // 再開可能なスレッドがないときに、OSスレッドを休止する前にイベントを確認し続ける時間
+    idle_spin: Duration,
+    // 再開可能なスレッドがないときに、一度にOSスレッドを休止する時間の上限
+    max_park: Option<Duration>,
+    // shutdown中かどうか
+    cancelled: bool,
+    // run_until_signalでシグナルを受け取ってから、キャンセルしたスレッドが自分で終わるのを待つ時間
+    shutdown_grace: Duration,
+    // プリエンプションで切り替えようとしているところかどうか
+    // NOTE: スケジューラに切り替えの理由を伝えるのに使う
+    preempted: bool,
+    // シグナルでスレッドの状態を書き出す要求をいくつまで処理したか
+    // NOTE: Builder::dump_on_signalを指定しない場合はNoneで、要求を確認しない
+    dump_requests: Option<u64>,
+    // 実行中のスレッドの名前を付けているOSスレッドの名前
+    // NOTE: Builder::os_thread_namesを指定しない場合はNoneで、OSスレッドの名前を変えない
+    os_thread_name: Option<naming::OsThreadName>,
+    // yield_toで次に実行してほしいスレッド
+    next_hint: Option<usize>,
+    // 実行中のスレッドが起こしたスレッド(LIFOスロット)
+    // NOTE: スケジューラのキューの後ろに並ばせずに次に実行し、チャネルのやり取りなどの待ち時間を減らす
+    lifo_slot: Option<usize>,
+    // LIFOスロットのスレッドを続けて実行できる回数(0の場合はLIFOスロットを使わない)
+    lifo_limit: usize,
+    // LIFOスロットのスレッドを続けて実行した回数
+    lifo_streak: usize,
+    // DeterministicSchedulerが選んだスレッドの記録
+    schedule: Option<Rc<RefCell<Vec<usize>>>>,
+    // runが終わったときに止まったままのスレッドが残っていた場合に呼ぶ関数
+    // NOTE: Builder::on_deadlockを指定しない場合はNoneで、標準エラー出力に書き出す
+    on_deadlock: Option<DeadlockHandler>,
+    // デッドロックした場合に報告してからパニックするかどうか
+    panic_on_deadlock: bool,
+    // わざと起こす障害の設定と状態
+    // NOTE: Builder::fault_injectionを指定しない場合はNoneで、障害を起こさない
+    faults: Option<Faults>,
+    // 終わって切り替えようとしているスレッド
+    // NOTE: 終わったスレッドはまだ自分のスタックの上にいるので、切り替えた先でスタックをプールに戻す
+    finished: Option<usize>,
+    stacks: StackPool,
+    // NOTE: spawn_blockingを使わないプログラムでOSスレッドを作らないように、最初に使うときに作る
+    blocking: Option<BlockingPool>,
+    // 他のOSスレッドから渡されたタスクなどを受け取るインジェクター
+    // NOTE: Runtime::handleを最初に呼んだときに作る
+    remotes: Option<Remotes>,
+    // NOTE: 最初にsnapshot::fork_taskかsnapshot::checkpointを使うときに作る
+    scratch: Option<snapshot::Scratch>,
+    // NOTE: 最初にio_uringで読み書きするときに作る
+    #[cfg(all(feature = "io-uring", target_os = "linux"))]
+    uring: Option<uring::Ring>,
+    #[cfg(feature = "trace")]
+    tracer: Option<trace::Tracer>,
+    // AddressSanitizerに教えるベーススレッドのスタックの(一番下のアドレス, 大きさ)
+    #[cfg(feature = "sanitize")]
+    #[cfg_attr(not(greenthreads_asan), allow(dead_code))]
+    main_stack: (usize, usize),
+    // Runtimeを作ったOSスレッドの外に移せないようにする(!Send, !Sync)
+    // NOTE: switchはfs/gs(TLSのベースレジスタ)を切り替えないので、グリーンスレッドの中のthread_local!は
+    //       Runtimeを動かしているOSスレッドのものになる
+    //       Runtimeごと別のOSスレッドに移ると、止まっているグリーンスレッドのスタックに残った
+    //       thread_local!への参照が別のOSスレッドのTLSを指すことになるので、型で禁止する
+    _not_send: PhantomData<*mut ()>,
+    // Pinで固定したRuntimeを取り出して動かせないようにする(!Unpin)
+    // NOTE: Runtime::newやBuilder::buildが返すPinnedRuntimeが使う
+    _pinned: PhantomPinned,
+}
+
+#[cfg(feature = "std")]
+#[derive(PartialEq, Eq, Debug)]
+enum State {
+    Available, // 利用可能
+    Running,   // 実行中
+    Ready,     // 再開可能
+    Blocked,   // ブロック中
+    Parked,    // parkで停止中
+}
+
+#[cfg(feature = "std")]
+struct Thread {
+    id: usize,
+    // 枠を使い回した回数
+    // NOTE: 新しいタスクに枠を使うたびに増やし、ThreadHandleが終わったタスクを指しているかどうかを見分けるのに使う
+    generation: u64,
+    name: Option<String>,
+    // NOTE: 初めてスレッドを生成するときに確保し、スレッドが終わったらプールに戻す
+    stack: Option<Stack>,
+    ctx: ThreadContext,
+    state: State,
+    task: Option<Box<dyn FnOnce()>>,
+    // set_priorityやspawn_with_priorityで指定した優先度
+    priority: u8,
+    // 持っているMutexのアドレスと、それを待っているスレッドから継承した優先度
+    inherited: Vec<(usize, u8)>,
+    locals: Locals,
+    // with_valueで付けた値と、生成したスレッドから引き継いだ値
+    context: Context,
+    // unparkされたがまだparkで消費されていない
+    unpark_token: bool,
+    token: CancellationToken,
+    // キャンセルされたら起こしてよい処理でブロックしているかどうか
+    cancellable: bool,
+    // ブロックしている間、何を待っているか
+    blocked_on: Option<BlockedOn>,
+    // timeoutの期限
+    // NOTE: 過ぎたらキャンセルされた場合と同じく、キャンセルできる待ちから起こす
+    deadline: Option<Instant>,
+    counters: Counters,
+    // 実行中のsnapshot::forkableの深さ
+    forkable: u32,
+}
+
+#[cfg(feature = "std")]
+impl Thread {
+    fn new(id: usize) -> Self {
+        Thread {
+            id,
+            generation: 0,
+            name: None,
+            stack: None,
+            ctx: ThreadContext::default(),
+            state: State::Available,
+            task: None,
+            priority: DEFAULT_PRIORITY,
+            inherited: Vec::new(),
+            locals: Locals::new(),
+            context: Context::new(),
+            unpark_token: false,
+            token: CancellationToken::new(id),
+            cancellable: false,
+            blocked_on: None,
+            deadline: None,
+            counters: Counters::default(),
+            forkable: 0,
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl Runtime {
+    // NOTE: Runtimeは動かせないように、ヒープに固定したPinnedRuntimeとして返す
+    #[allow(clippy::new_ret_no_self)]
+    pub fn new() -> PinnedRuntime {
+        Runtime::with_scheduler(SchedulerPolicy::RoundRobin)
+    }
+
+    pub fn with_scheduler(policy: SchedulerPolicy) -> PinnedRuntime {
+        Runtime::with_custom_scheduler(policy.build())
+    }
+
+    // 独自のスケジューラを使うRuntimeを作る
+    // NOTE: 環境変数GREENTHREADS_MAX_THREADSかGREENTHREADS_STACK_SIZEの値が正しくない場合はパニックする
+    pub fn with_custom_scheduler(scheduler: Box<dyn Scheduler>) -> PinnedRuntime {
+        Runtime::from_scheduler(scheduler).pinned()
+    }
+
+    // with_custom_schedulerと同じだが、固定する前に設定を変えられるように固定せずに返す
+    pub(crate) fn from_scheduler(scheduler: Box<dyn Scheduler>) -> Runtime {
+        let (max_threads, stack_size) =
+            config::resolve(None, None).unwrap_or_else(|e| panic!("{}", e));
+        Runtime::with_capacity(scheduler, max_threads, stack_size)
+    }
+
+    // ベーススレッドを除いてmax_threads個のスレッドを同時に動かせ、スタックの大きさがstack_sizeのRuntimeを作る
+    pub(crate) fn with_capacity(
+        scheduler: Box<dyn Scheduler>,
+        max_threads: usize,
+        stack_size: usize,
+    ) -> Self {
+        let base_thread = Thread {
+            id: 0,
+            generation: 0,
+            name: Some("main".to_string()),
+            // NOTE: ベーススレッドはOSスレッドのスタックで動くので確保しない
+            stack: None,
+            ctx: ThreadContext::default(),
+            state: State::Running,
+            task: None,
+            priority: DEFAULT_PRIORITY,
+            inherited: Vec::new(),
+            locals: Locals::new(),
+            context: Context::new(),
+            unpark_token: false,
+            token: CancellationToken::new(0),
+            cancellable: false,
+            blocked_on: None,
+            deadline: None,
+            counters: Counters::default(),
+            forkable: 0,
+        };
+
+        naming::install_panic_hook();
+
+        let mut threads = vec![base_thread];
+        let mut available_threads: Vec<Thread> = (1..=max_threads).map(Thread::new).collect();
+        threads.append(&mut available_threads);
+
+        Runtime {
+            threads,
+            available: (1..=max_threads).collect(),
+            current: 0,
+            timers: Timers::new(),
+            reactor: Reactor::new(),
+            scheduler,
+            time_slice: None,
+            switches: 0,
+            wakeups: 0,
+            running_since: Instant::now(),
+            op_budget: None,
+            time_budget: None,
+            budget_used: 0,
+            budget_since: Instant::now(),
+            stack_usage_warning: None,
+            strict_abi: false,
+            idle_spin: Duration::ZERO,
+            max_park: None,
+            cancelled: false,
+            shutdown_grace: DEFAULT_SHUTDOWN_GRACE,
+            preempted: false,
+            dump_requests: None,
+            os_thread_name: None,
+            next_hint: None,
+            lifo_slot: None,
+            lifo_limit: 0,
+            lifo_streak: 0,
+            schedule: None,
+            on_deadlock: None,
+            panic_on_deadlock: false,
+            faults: None,
+            finished: None,
+            stacks: StackPool::new(stack_size),
+            blocking: None,
+            remotes: None,
+            scratch: None,
+            #[cfg(all(feature = "io-uring", target_os = "linux"))]
+            uring: None,
+            #[cfg(feature = "trace")]
+            tracer: None,
+            #[cfg(feature = "sanitize")]
+            main_stack: (0, 0),
+            _not_send: PhantomData,
+            _pinned: PhantomPinned,
+        }
+    }
+
+    pub fn builder() -> Builder {
+        Builder::new()
+    }
+
+    // このRuntimeを現在のOSスレッドで動いているRuntimeにする
+    // NOTE: runを呼ぶと自動で設定されるので、呼ばなくてもよい
+    //       自分のアドレスを残すので、呼んだ後でRuntimeが動かないように固定したものにだけ呼べる
+    //       ふつうはPinnedRuntime::as_mutで取ったPinから呼ぶ
+    pub fn init(self: Pin<&mut Self>) {
+        let r_ptr: *mut Runtime = unsafe { self.get_unchecked_mut() };
+        CURRENT.with(|current| current.set(r_ptr));
+    }
+
+    // すべてのスレッドが終わるまで実行する
+    // NOTE: 終わったら戻るので、同じOSスレッドで別のRuntimeを続けて動かせる
+    //       止まったまま二度と起こされないスレッドが残った場合も、Builder::on_deadlockで報告してから戻る
+    pub fn run(self: Pin<&mut Self>) {
+        let this = unsafe { self.get_unchecked_mut() };
+        let rt: *mut Runtime = this;
+        let prev = CURRENT.with(|current| current.replace(rt));
+        let _guard = preempt::disable();
+        if let Some(time_slice) = this.time_slice {
+            preempt::start(time_slice).expect("failed to start preemption timer.");
+        }
+        // 再開可能なスレッドがなくなっても、スリープ中やI/O待ちのスレッドがあれば起きるまで待つ
+        unsafe { while Runtime::t_yield(rt) || (*rt).wait_events() {} }
+        if this.time_slice.is_some() {
+            preempt::stop().expect("failed to stop preemption timer.");
+        }
+        CURRENT.with(|current| current.set(prev));
+        // 最後に終わったスレッドのスタックもプールに戻す
+        this.recycle_stacks();
+        // 止まったまま二度と起こされないスレッドが残っている場合は、黙って終わらずに報告する
+        this.report_deadlock();
+    }
+
+    // 他のスレッドを一度だけ実行し、再開可能なスレッドがなければスリープ中やI/O待ちのスレッドが起きるまで待つ
+    // 実行するスレッドがなかった場合はfalseを返す
+    pub(crate) fn run_once(&mut self) -> bool {
+        unsafe { Runtime::t_run_once(self) }
+    }
+
+    // run_onceと同じだが、グリーンスレッドの中から&mut Runtimeを持たずに呼べるようにポインタで受け取る
+    pub(crate) unsafe fn t_run_once(rt: *mut Runtime) -> bool {
+        CURRENT.with(|current| current.set(rt));
+        let _guard = preempt::disable();
+        Runtime::t_yield(rt) || (*rt).wait_events()
+    }
+
+    unsafe fn t_return(rt: *mut Runtime) {
+        if (*rt).current != 0 {
+            // タスクの処理が終わったときにこの関数が呼ばれるため、現在のスレッドを
+            // Ready(再開可能)ではなくAvailable(利用可能)の状態にする
+            {
+                let rt = &mut *rt;
+                rt.threads[rt.current].state = State::Available;
+                rt.available.insert(rt.current);
+                rt.finished = Some(rt.current);
+                #[cfg(feature = "trace")]
+                rt.trace(trace::TraceEvent::Exit { id: rt.current });
+                // 終わったスレッドのスタックに書き込んだスレッドがいないかを確かめる
+                #[cfg(debug_assertions)]
+                rt.stacks.check_freed();
+            }
+            Runtime::t_yield(rt);
+        }
+    }
+
+    unsafe fn t_yield(rt: *mut Runtime) -> bool {
+        // NOTE: 切り替えの途中でシグナルハンドラから切り替えられないようにする
+        //       ガードは切り替え先のスレッドのスタックにあるものが戻るときに元に戻す
+        let _guard = preempt::disable();
+        let (old_pos, pos) = match (*rt).switch_target() {
+            Some(target) => target,
+            None => return false,
+        };
+
+        // 現在スレッドの再開処理に必要なコンテキスト情報を取得
+        // 再開するスレッドの再開処理に必要なコンテキスト情報を取得
+        // NOTE: &mut Runtimeを作らずにポインタから直接取得する
+        (*rt).record_switch(old_pos, pos, context::current_stack_pointer());
+        let threads = (*rt).threads.as_mut_ptr();
+        let old: *mut ThreadContext = addr_of_mut!((*threads.add(old_pos)).ctx);
+        let new: *const ThreadContext = addr_of!((*threads.add(pos)).ctx);
+        // それぞれのコンテキスト情報のアドレスをレジスタに保持
+        // NOTE: 終わったスレッドには戻らないので、AddressSanitizerに切り替え元のスタックを捨ててよいと伝える
+        #[cfg(feature = "sanitize")]
+        let mut fake_stack = ptr::null_mut();
+        #[cfg(feature = "sanitize")]
+        sanitize::start_switch(
+            rt,
+            pos,
+            if (*threads.add(old_pos)).state == State::Available {
+                ptr::null_mut()
+            } else {
+                &mut fake_stack
+            },
+        );
+        context::switch_to(old, new);
+        #[cfg(feature = "sanitize")]
+        sanitize::finish_switch(rt, fake_stack);
+        (*rt).reclaim_finished();
+
+        // コンパイラの最適化をさせないようにするためらしい(よくわからん)
+        !(*rt).threads.is_empty()
+    }
+
+    // 切り替え先のスレッドを選び、状態を更新して(切り替え元, 切り替え先)を返す
+    // 再開可能なスレッドがない場合はNoneを返す
+    // NOTE: 再開可能なスレッドはスケジューラのキューから取り出すので、スレッドの数によらず切り替えにかかる時間は変わらない
+    //       終わったスレッドのスタックもここでは探さず、切り替えた先でreclaim_finishedで戻す
+    fn switch_target(&mut self) -> Option<(usize, usize)> {
+        self.check_dump_request();
+        // 期限が来たスリープ中のスレッドや、I/Oの準備ができたスレッドを再開可能にする
+        self.wake_expired_timers();
+        if self.reactor.has_waiters() {
+            self.poll_io(Some(Duration::ZERO));
+        }
+        // 他のOSスレッドから渡されたタスクを生成し、起こすように頼まれたスレッドを再開可能にする
+        self.drain_remotes();
+        // 遅らせていたスレッドを起こし、見せかけの起床を起こす
+        self.inject_switch_faults();
+
+        let preempted = std::mem::take(&mut self.preempted);
+        // yield_toで指定されたスレッドが再開可能で、スケジューラから取り除けた場合はそのスレッドに切り替える
+        let hint = self
+            .next_hint
+            .take()
+            .filter(|id| *id != self.current && self.take_ready(*id));
+        // 次にLIFOスロットのスレッド、どちらもない場合はスケジューラが選んだスレッドに切り替える
+        // 再開可能なスレッドがない場合は処理しない
+        let pos = match hint.or_else(|| self.take_lifo_slot()) {
+            Some(id) => id,
+            None => {
+                self.lifo_streak = 0;
+                self.pick_next()?
+            }
+        };
+
+        // 切り替え元のスレッドがどれだけ続けて実行したかと、止めた理由をスケジューラに伝える
+        // NOTE: yieldした場合は再開可能にする前に伝え、次に積むキューを決められるようにする
+        let reason = match self.threads[self.current].state {
+            State::Running if preempted => SwitchReason::Preempted,
+            State::Running => SwitchReason::Yielded,
+            State::Available => SwitchReason::Finished,
+            _ => SwitchReason::Blocked,
+        };
+        if reason == SwitchReason::Preempted {
+            self.threads[self.current].counters.preemptions += 1;
+        }
+        self.scheduler
+            .ran(self.current, self.running_since.elapsed(), reason);
+        self.end_budget(self.current);
+
+        // 現在のスレッドの状態をReady(再開可能)に変更
+        // NOTE: 現在のスレッドが利用可能やブロック中の場合は状態を変えない
+        if self.threads[self.current].state == State::Running {
+            self.make_ready(self.current);
+        }
+
+        // 再開可能なスレッドの状態をRunning(実行中)に変更
+        self.threads[pos].state = State::Running;
+        self.forget_delayed_wakeup(pos);
+        let old_pos = self.current;
+        // 実行中のスレッドを切り替え先のスレッドに変更
+        self.current = pos;
+        self.switches += 1;
+        self.name_os_thread(pos);
+        #[cfg(feature = "trace")]
+        self.trace(trace::TraceEvent::Switch {
+            from: old_pos,
+            to: pos,
+        });
+        Some((old_pos, pos))
+    }
+
+    // 終わったスレッドのスタックをプールに戻す
+    // NOTE: 終わったばかりのスレッドは切り替えが終わるまで自分のスタックで動いているので、
+    //       実行中のスレッドのスタックは戻さない
+    fn recycle_stacks(&mut self) {
+        for id in 0..self.threads.len() {
+            self.recycle_stack(id);
+        }
+    }
+
+    // 切り替える前に終わったスレッドのスタックを、次の切り替えを待たずにプールに戻す
+    // NOTE: 切り替えた先(t_yieldから戻ったところか、新しいスレッドの始まり)で呼ぶ
+    fn reclaim_finished(&mut self) {
+        if let Some(id) = self.finished.take() {
+            self.recycle_stack(id);
+        }
+    }
+
+    fn recycle_stack(&mut self, id: usize) {
+        let thread = &mut self.threads[id];
+        if id == self.current || thread.state != State::Available {
+            return;
+        }
+        if let Some(stack) = thread.stack.take() {
+            // スタックを手放す前に使用量を記録しておく
+            if let Some(used) = stack.high_water_mark() {
+                thread.counters.peak_stack = thread.counters.peak_stack.max(used);
+            }
+            if let Some(percent) = self.stack_usage_warning {
+                warn_stack_usage(thread, &stack, percent);
+            }
+            let used = thread.counters.peak_stack;
+            self.stacks.put(stack, used);
+        }
+    }
+
+    // スタックを使った量の最大値を返す
+    // スタックをPOISONで埋めていない場合(Builder::poison_stacks)やスタックがない場合はNoneを返す
+    pub fn max_stack_usage(&self, id: usize) -> Option<usize> {
+        let thread = self.threads.get(id)?;
+        match &thread.stack {
+            Some(stack) => stack.high_water_mark(),
+            // 終わってスタックをプールに戻したスレッドは、戻したときに測った値を返す
+            None if self.stacks.poison && id != 0 => Some(thread.counters.peak_stack),
+            None => None,
+        }
+    }
+
+    fn make_ready(&mut self, id: usize) {
+        #[cfg(feature = "trace")]
+        self.trace_wake(id);
+        self.threads[id].state = State::Ready;
+        self.scheduler.ready(id);
+        self.wakeups += 1;
+    }
+
+    // 再開可能なidのスレッドを、次に実行するスレッドとして取り出す
+    // 再開可能でない場合や、スケジューラが取り除けなかった場合はfalseを返す
+    fn take_ready(&mut self, id: usize) -> bool {
+        if self.threads.get(id).is_none_or(|t| t.state != State::Ready) {
+            return false;
+        }
+        if self.lifo_slot == Some(id) {
+            self.lifo_slot = None;
+            return true;
+        }
+        self.scheduler.take(id)
+    }
+
+    // LIFOスロットのスレッドを取り出す
+    // NOTE: 2つのスレッドが起こし合うと他のスレッドが実行されなくなるので、続けてlifo_limit回取り出したら、
+    //       スロットのスレッドをスケジューラに渡して、スケジューラに選ばせる
+    fn take_lifo_slot(&mut self) -> Option<usize> {
+        let id = self.lifo_slot.take()?;
+        if self.lifo_streak < self.lifo_limit {
+            self.lifo_streak += 1;
+            return Some(id);
+        }
+        self.scheduler.ready(id);
+        None
+    }
+
+    unsafe fn t_block(rt: *mut Runtime) {
+        // 現在のスレッドをBlocked(ブロック中)にして他のスレッドに切り替える
+        Runtime::t_suspend(rt, State::Blocked);
+    }
+
+    // 現在のスレッドをstateにして他のスレッドに切り替え、再開可能に戻されるまで戻らない
+    // 切り替え先がない場合は二度と起こされないのでデッドロックになる
+    // NOTE: スリープ中やI/O待ちのスレッドがあれば、起きるまで待ってから切り替え先を探し直す
+    unsafe fn t_suspend(rt: *mut Runtime, state: State) {
+        let _guard = preempt::disable();
+        {
+            let rt = &mut *rt;
+            rt.threads[rt.current].state = state;
+            rt.scheduler.block(rt.current);
+            #[cfg(feature = "trace")]
+            rt.trace(trace::TraceEvent::Block { id: rt.current });
+        }
+        while !Runtime::t_yield(rt) {
+            if !(*rt).wait_events() {
+                panic!(
+                    "deadlock: no thread is ready to run.\n{}",
+                    (*rt).blocked_threads_report()
+                );
+            }
+        }
+        Runtime::check_cancelled(rt);
+    }
+
+    // 現在のスレッドをブロックするが、キャンセルされた場合は起こされるのを待たずにErr(Cancelled)を返す
+    // timeoutの期限を過ぎた場合も同じくErr(Cancelled)を返す
+    // NOTE: Errを返した場合、呼び出し元はタイマーや待ちキューに残った登録を取り除くこと
+    unsafe fn t_block_cancellable(rt: *mut Runtime) -> Result<(), Cancelled> {
+        let _guard = preempt::disable();
+        let expired = |deadline: Option<Instant>| deadline.is_some_and(|d| d <= Instant::now());
+        let (token, deadline) = {
+            let rt = &mut *rt;
+            let current = rt.current;
+            let thread = &mut rt.threads[current];
+            if thread.token.is_cancelled() || expired(thread.deadline) {
+                return Err(Cancelled);
+            }
+            thread.cancellable = true;
+            let deadline = thread.deadline;
+            // 期限が来たら起こされるようにする
+            if let Some(deadline) = deadline {
+                rt.timers.add(deadline, current);
+            }
+            (thread.token.clone(), deadline)
+        };
+        Runtime::t_block(rt);
+        {
+            let rt = &mut *rt;
+            let current = rt.current;
+            rt.threads[current].cancellable = false;
+            // NOTE: 待っていたのが期限でなくても、起きた後に残っているこのスレッドのタイマーは不要になっている
+            if deadline.is_some() {
+                rt.timers.remove(current);
+            }
+        }
+        if token.is_cancelled() || expired(deadline) {
+            return Err(Cancelled);
+        }
+        Ok(())
+    }
+
+    // deadlineにタイマーを登録してから現在のスレッドをブロックし、起きたらタイマーを取り除く
+    // cancellableがtrueの場合はt_block_cancellableと同じくキャンセルで起きる
+    unsafe fn t_block_until(
+        rt: *mut Runtime,
+        deadline: Instant,
+        cancellable: bool,
+    ) -> Result<(), Cancelled> {
+        let _guard = preempt::disable();
+        let current = (*rt).current;
+        (*rt).timers.add(deadline, current);
+        let result = if cancellable {
+            Runtime::t_block_cancellable(rt)
+        } else {
+            Runtime::t_block(rt);
+            Ok(())
+        };
+        (*rt).timers.remove(current);
+        result
+    }
+
+    unsafe fn t_sleep_until(rt: *mut Runtime, deadline: Instant) -> Result<(), Cancelled> {
+        let _guard = preempt::disable();
+        let current = (*rt).current;
+        (*rt).timers.add(deadline, current);
+        let result = Runtime::t_block_cancellable(rt);
+        if result.is_err() {
+            (*rt).timers.remove(current);
+        }
+        result
+    }
+
+    fn wake_expired_timers(&mut self) {
+        let now = Instant::now();
+        while let Some(id) = self.timers.pop_expired(now) {
+            self.t_wake(id);
+        }
+    }
+
+    unsafe fn t_wait_io(rt: *mut Runtime, fd: RawFd, interest: Interest) -> std::io::Result<()> {
+        let _guard = preempt::disable();
+        let current = (*rt).current;
+        (*rt).reactor.register(fd, interest, current)?;
+        if let Err(e) = Runtime::t_block_cancellable(rt) {
+            (*rt).reactor.cancel(fd, interest, current);
+            return Err(std::io::Error::other(e));
+        }
+        Ok(())
+    }
+
+    // t_wait_ioと同じだが、fdsのどれかの準備ができるまで待つ
+    unsafe fn t_wait_io_any(rt: *mut Runtime, fds: &[(RawFd, Interest)]) -> std::io::Result<()> {
+        let _guard = preempt::disable();
+        let current = (*rt).current;
+        let cancel_all = |rt: *mut Runtime| {
+            for &(fd, interest) in fds {
+                (*rt).reactor.cancel(fd, interest, current);
+            }
+        };
+        for &(fd, interest) in fds {
+            if let Err(e) = (*rt).reactor.register(fd, interest, current) {
+                cancel_all(rt);
+                return Err(e);
+            }
+        }
+        let result = Runtime::t_block_cancellable(rt);
+        // NOTE: 起こしたfd以外の登録は残っているので取り除く
+        cancel_all(rt);
+        result.map_err(std::io::Error::other)
+    }
+
+    fn poll_io(&mut self, timeout: Option<Duration>) {
+        let mut ready = Vec::new();
+        self.reactor
+            .poll(timeout, &mut ready)
+            .expect("failed to poll I/O events.");
+        for id in ready {
+            match id {
+                BLOCKING_WAKER => self.wake_blocking(),
+                REMOTE_WAKER => self.wake_remotes(),
+                signal::SIGNAL_WAKER => signal::drain_local_pipe(),
+                #[cfg(all(feature = "io-uring", target_os = "linux"))]
+                uring::URING_WAKER => self.reap_uring(),
+                id => self.t_wake(id),
+            }
+        }
+    }
+
+    // 再開可能なスレッドがないときに、一番近いタイマーの期限かI/Oイベントが届くまでOSスレッドごと休止する
+    // スリープ中やI/O待ちのスレッドがない場合はfalseを返す
+    // NOTE: max_parkを指定した場合は、一度に休止する時間をその長さまでにする
+    fn wait_events(&mut self) -> bool {
+        let deadline = self.timers.next_deadline();
+        if deadline.is_none() && !self.reactor.has_waiters() {
+            return false;
+        }
+        if self.spin_events() {
+            return true;
+        }
+        let mut timeout =
+            deadline.map(|deadline| deadline.saturating_duration_since(Instant::now()));
+        if let Some(max_park) = self.max_park {
+            timeout = Some(timeout.map_or(max_park, |timeout| timeout.min(max_park)));
+        }
+        if self.reactor.has_waiters() {
+            self.poll_io(timeout);
+        } else if let Some(timeout) = timeout {
+            std::thread::sleep(timeout);
+        }
+        self.wake_expired_timers();
+        self.restart_budget();
+        true
+    }
+
+    // idle_spinの間、OSスレッドを休止せずにタイマーとI/Oイベントを確認し続ける
+    // 再開可能になったスレッドがあればtrueを返す
+    // NOTE: 休止してから起きるまでの遅れがなくなる代わりに、その間はCPUを使い続ける
+    //       スレッドの状態を一つずつ調べず、make_readyした回数が増えたかで判断するので、
+    //       一回りにかかる時間はスレッドの数によらない
+    fn spin_events(&mut self) -> bool {
+        if self.idle_spin.is_zero() {
+            return false;
+        }
+        let start = Instant::now();
+        let wakeups = self.wakeups;
+        loop {
+            self.wake_expired_timers();
+            if self.reactor.has_waiters() {
+                self.poll_io(Some(Duration::ZERO));
+            }
+            if self.wakeups != wakeups {
+                return true;
+            }
+            if start.elapsed() >= self.idle_spin {
+                return false;
+            }
+            std::hint::spin_loop();
+        }
+    }
+
+    // tokenのスレッドがキャンセルできる処理でブロックしていれば再開可能にする
+    // NOTE: スレッドが終わって別のタスクに使われている場合は何もしない
+    fn t_cancel(&mut self, id: usize, token: &CancellationToken) {
+        let thread = &self.threads[id];
+        if thread.token.same(token) && thread.cancellable && thread.state == State::Blocked {
+            self.make_ready(id);
+        }
+    }
+
+    fn t_wake(&mut self, id: usize) {
+        // ブロック中のスレッドのみReady(再開可能)に戻す
+        if self.threads[id].state == State::Blocked && !self.delay_wakeup(id) {
+            self.make_ready(id);
+        }
+    }
+
+    // 実行中のスレッドが起こしたスレッドを再開可能にする
+    // NOTE: LIFOスロットを使う場合はスケジューラに渡さずにLIFOスロットに入れ、
+    //       すでに入っていたスレッドはスケジューラに渡す
+    fn t_wake_from_current(&mut self, id: usize) {
+        if self.lifo_limit == 0 || self.threads[id].state != State::Blocked {
+            self.t_wake(id);
+            return;
+        }
+        if self.delay_wakeup(id) {
+            return;
+        }
+        #[cfg(feature = "trace")]
+        self.trace_wake(id);
+        self.threads[id].state = State::Ready;
+        self.wakeups += 1;
+        if let Some(prev) = self.lifo_slot.replace(id) {
+            self.scheduler.ready(prev);
+        }
+    }
+
+    fn has_available_thread(&self) -> bool {
+        !self.available.is_empty()
+    }
+
+    // 利用可能なスレッドを取得
+    fn available_thread(&self) -> Option<usize> {
+        self.available.first().copied()
+    }
+
+    // 利用可能なスレッドでtaskを実行できるようにする
+    fn start_thread(
+        &mut self,
+        id: usize,
+        priority: u8,
+        name: Option<String>,
+        task: Box<dyn FnOnce()>,
+        token: CancellationToken,
+    ) {
+        self.available.remove(&id);
+        // 生成したスレッドのコンテキストを引き継ぐ
+        let context = self.threads[self.current].context.clone();
+        let available = &mut self.threads[id];
+        // 前のタスクのFPUの設定などを引き継がないように初期化する
+        available.ctx = ThreadContext::default();
+
+        unsafe {
+            // スタックポインタ
+            let s_ptr = available
+                .stack
+                .as_ref()
+                .expect("stack is not allocated.")
+                .top();
+            // 最初に切り替えたときにthread_mainから始まるようにする
+            available.ctx.prepare(s_ptr, thread_main, 0);
+        }
+
+        available.name = name;
+        available.task = Some(task);
+        available.unpark_token = false;
+        available.token = token;
+        available.context = context;
+        available.cancellable = false;
+        available.counters = Counters::default();
+        available.forkable = 0;
+        available.priority = priority;
+        available.inherited.clear();
+        self.update_priority(id);
+        #[cfg(feature = "trace")]
+        self.trace(trace::TraceEvent::Spawn {
+            id,
+            name: self.threads[id].name.clone(),
+        });
+        // 現在のスレッドを再開可能の状態に変更
+        self.make_ready(id);
+        self.check_new_stack(id);
+    }
+}
+
+#[cfg(feature = "std")]
+fn warn_stack_usage(thread: &Thread, stack: &Stack, percent: u8) {
+    let used = match stack.high_water_mark() {
+        Some(used) => used,
+        None => return,
+    };
+    let usage = used * 100 / stack.size();
+    if usage > percent as usize {
+        eprintln!(
+            "warning: thread {} used {} of {} bytes of its stack ({}%)",
+            thread.id,
+            used,
+            stack.size(),
+            usage
+        );
+    }
+}
+
+// thread_mainから呼ばれ、スレッドに登録されたタスク関数を実行する
+#[cfg(feature = "std")]
+fn call() {
+    // 新しく始まったスレッドには切り替え前に保存したものがないのでNULLを渡す
+    #[cfg(feature = "sanitize")]
+    unsafe {
+        sanitize::finish_switch(runtime_ptr(), ptr::null_mut());
+    }
+    unsafe {
+        (*runtime_ptr()).reclaim_finished();
+    }
+    // 新しく始まったスレッドはプリエンプションできる状態から始める
+    preempt::enable();
+    let f = unsafe {
+        let rt = &mut *runtime_ptr();
+        rt.threads[rt.current].task.take()
+    };
+    if let Some(f) = f {
+        f();
+    }
+    // タスクが終わったらスレッドローカル変数とコンテキストをドロップする
+    // NOTE: ドロップ中に他のスレッドローカル変数にアクセスしてもよいように、取り出してからドロップする
+    let (locals, context) = unsafe {
+        let rt = &mut *runtime_ptr();
+        let thread = &mut rt.threads[rt.current];
+        (
+            std::mem::take(&mut thread.locals),
+            std::mem::take(&mut thread.context),
+        )
+    };
+    drop(locals);
+    drop(context);
+}
+
+// タスクの処理が完了したときにthread_mainから呼ばれる
+// NOTE: 他のスレッドに切り替えて二度と戻らない
+#[cfg(feature = "std")]
+fn guard() {
+    unsafe {
+        let rt_ptr = runtime_ptr();
+        Runtime::t_return(rt_ptr);
+    }
+}
+
+// 新しいスレッドが最初に実行する関数
+// NOTE: タスクを実行し、終わったら他のスレッドに切り替えて二度と戻らない
+#[cfg(feature = "std")]
+extern "C" fn thread_main(_: usize) {
+    call();
+    guard();
+}
+
+#[cfg(feature = "std")]
+pub fn yield_thread() {
+    unsafe {
+        let rt_ptr = runtime_ptr();
+        Runtime::t_yield(rt_ptr);
+        Runtime::check_cancelled_at_yield(rt_ptr);
+    }
+}
+
+// idのスレッドに直接切り替える
+// idのスレッドが再開可能でない場合や、スケジューラが対応していない場合はyield_threadと同じく次のスレッドに切り替える
+// NOTE: 次に実行すべきスレッドが分かっている場合(ピンポンやパイプラインなど)に、順番を待たずに渡せる
+#[cfg(feature = "std")]
+pub fn yield_to(id: usize) {
+    unsafe {
+        let rt_ptr = runtime_ptr();
+        {
+            // NOTE: 指定してから切り替えるまでにプリエンプションで切り替わると、指定が別の切り替えで使われる
+            let _guard = preempt::disable();
+            (*rt_ptr).next_hint = Some(id);
+            Runtime::t_yield(rt_ptr);
+        }
+        Runtime::check_cancelled_at_yield(rt_ptr);
+    }
+}
+
+// 現在のスレッドの優先度を変更する
+// NOTE: 次にスケジュールされるときから反映される
+//       Mutexを持っていて優先度を継承している間は、継承した優先度の方が高ければそちらが使われる
+#[cfg(feature = "std")]
+pub fn set_priority(priority: u8) {
+    let _guard = preempt::disable();
+    unsafe {
+        let rt_ptr = runtime_ptr();
+        let rt = &mut *rt_ptr;
+        let current = rt.current;
+        rt.threads[current].priority = priority;
+        rt.update_priority(current);
+    }
+}
+
+// 現在のスレッドがholderの持っているlockを待つので、holderに現在のスレッドの優先度を継承させる
+#[cfg(feature = "std")]
+pub(crate) fn inherit_priority(holder: usize, lock: usize) {
+    let _guard = preempt::disable();
+    unsafe {
+        let rt = &mut *runtime_ptr();
+        let priority = rt.effective_priority(rt.current);
+        rt.inherit_priority(holder, lock, priority);
+    }
+}
+
+// 現在のスレッドがlockを取ったので、lockを待っているwaitersの優先度を継承する
+#[cfg(feature = "std")]
+pub(crate) fn acquire_priority(lock: usize, waiters: &[usize]) {
+    let _guard = preempt::disable();
+    unsafe { (*runtime_ptr()).acquire_priority(lock, waiters) }
+}
+
+// 現在のスレッドがlockを手放したので、lockで継承した優先度を取り消す
+#[cfg(feature = "std")]
+pub(crate) fn release_priority(lock: usize) {
+    let _guard = preempt::disable();
+    unsafe { (*runtime_ptr()).release_priority(lock) }
+}
+
+// 現在実行中のスレッドのIDを返す
+#[cfg(feature = "std")]
+pub(crate) fn current_thread() -> usize {
+    unsafe {
+        let rt_ptr = runtime_ptr();
+        (*rt_ptr).current
+    }
+}
+
+// 現在のスレッドのスレッドローカル変数を返す
+// NOTE: 参照はスレッドの切り替えをまたいで保持しないこと
+#[cfg(feature = "std")]
+pub(crate) fn current_locals() -> *mut Locals {
+    unsafe {
+        let rt = &mut *runtime_ptr();
+        &mut rt.threads[rt.current].locals
+    }
+}
+
+// 現在のスレッドをブロックし、wake_threadで起こされるまで戻らない
+// blocked_onはデッドロックしたときの診断に使う
+#[cfg(feature = "std")]
+pub(crate) fn block_thread(blocked_on: BlockedOn) {
+    unsafe {
+        let rt_ptr = runtime_ptr();
+        set_blocked_on(rt_ptr, Some(blocked_on));
+        Runtime::t_block(rt_ptr);
+        set_blocked_on(rt_ptr, None);
+    }
+}
+
+// block_threadと同じだが、キャンセルされた場合はErr(Cancelled)を返す
+#[cfg(feature = "std")]
+pub(crate) fn block_thread_cancellable(blocked_on: BlockedOn) -> Result<(), Cancelled> {
+    unsafe {
+        let rt_ptr = runtime_ptr();
+        set_blocked_on(rt_ptr, Some(blocked_on));
+        let result = Runtime::t_block_cancellable(rt_ptr);
+        set_blocked_on(rt_ptr, None);
+        result
+    }
+}
+
+// block_threadと同じだが、deadlineになったら起こされていなくても戻る
+// NOTE: 待ちキューなどとタイマーの両方に登録し、先に来た方で起きる
+//       どちらで起きたかは、呼び出し元が待ちキューに残っているかどうかで判断する
+#[cfg(feature = "std")]
+pub(crate) fn block_thread_until(blocked_on: BlockedOn, deadline: Instant) {
+    unsafe {
+        let rt_ptr = runtime_ptr();
+        set_blocked_on(rt_ptr, Some(blocked_on));
+        let _ = Runtime::t_block_until(rt_ptr, deadline, false);
+        set_blocked_on(rt_ptr, None);
+    }
+}
+
+// block_thread_untilと同じだが、キャンセルされた場合はErr(Cancelled)を返す
+#[cfg(feature = "std")]
+pub(crate) fn block_thread_cancellable_until(
+    blocked_on: BlockedOn,
+    deadline: Instant,
+) -> Result<(), Cancelled> {
+    unsafe {
+        let rt_ptr = runtime_ptr();
+        set_blocked_on(rt_ptr, Some(blocked_on));
+        let result = Runtime::t_block_until(rt_ptr, deadline, true);
+        set_blocked_on(rt_ptr, None);
+        result
+    }
+}
+
+#[cfg(feature = "std")]
+unsafe fn set_blocked_on(rt: *mut Runtime, blocked_on: Option<BlockedOn>) {
+    let _guard = preempt::disable();
+    let rt = &mut *rt;
+    rt.threads[rt.current].blocked_on = blocked_on;
+}
+
+// 現在のスレッドのキャンセルトークンを返す
+#[cfg(feature = "std")]
+pub(crate) fn current_token() -> CancellationToken {
+    unsafe {
+        let rt = &*runtime_ptr();
+        rt.threads[rt.current].token.clone()
+    }
+}
+
+// キャンセルされたスレッドを起こす
+// NOTE: Runtimeの外でキャンセルされた場合は、ブロックしているスレッドはいないので何もしない
+#[cfg(feature = "std")]
+pub(crate) fn cancel_thread(id: usize, token: &CancellationToken) {
+    let rt_ptr = CURRENT.with(|current| current.get());
+    if rt_ptr.is_null() {
+        return;
+    }
+    let _guard = preempt::disable();
+    unsafe {
+        (*rt_ptr).t_cancel(id, token);
+    }
+}
+
+// 現在のスレッドのtimeoutの期限を変更し、前の期限を返す
+#[cfg(feature = "std")]
+pub(crate) fn replace_deadline(deadline: Option<Instant>) -> Option<Instant> {
+    let _guard = preempt::disable();
+    unsafe {
+        let rt = &mut *runtime_ptr();
+        let current = rt.current;
+        std::mem::replace(&mut rt.threads[current].deadline, deadline)
+    }
+}
+
+// 現在のスレッドを指定した時刻までブロックする
+// キャンセルされた場合はErr(Cancelled)を返す
+#[cfg(feature = "std")]
+pub(crate) fn sleep_thread_until(deadline: Instant) -> Result<(), Cancelled> {
+    unsafe {
+        let rt_ptr = runtime_ptr();
+        Runtime::t_sleep_until(rt_ptr, deadline)
+    }
+}
+
+// fdの読み書きの準備ができるまで現在のスレッドをブロックする
+#[cfg(feature = "std")]
+pub(crate) fn wait_io(fd: RawFd, interest: Interest) -> std::io::Result<()> {
+    unsafe {
+        let rt_ptr = runtime_ptr();
+        Runtime::t_wait_io(rt_ptr, fd, interest)
+    }
+}
+
+// fdsのどれかの読み書きの準備ができるまで現在のスレッドをブロックする
+#[cfg(feature = "std")]
+pub(crate) fn wait_io_any(fds: &[(RawFd, Interest)]) -> std::io::Result<()> {
+    unsafe {
+        let rt_ptr = runtime_ptr();
+        Runtime::t_wait_io_any(rt_ptr, fds)
+    }
+}
+
+// fdをI/Oの監視対象から外す
+// NOTE: Runtimeの外でドロップされた場合は登録されていないので何もしない
+#[cfg(feature = "std")]
+pub(crate) fn deregister_io(fd: RawFd) {
+    let rt_ptr = CURRENT.with(|current| current.get());
+    if rt_ptr.is_null() {
+        return;
+    }
+    let _guard = preempt::disable();
+    unsafe {
+        (*rt_ptr).reactor.deregister(fd);
+    }
+}
+
+// ブロック中のスレッドを再開可能にする
+#[cfg(feature = "std")]
+pub(crate) fn wake_thread(id: usize) {
+    let _guard = preempt::disable();
+    unsafe {
+        let rt_ptr = runtime_ptr();
+        (*rt_ptr).t_wake_from_current(id);
+    }
+}
@@ -0,0 +1,63 @@
+// グリーンスレッドごとのスレッドローカル変数
+// NOTE: thread_local!はOSスレッドごとなので、同じOSスレッドで動くグリーンスレッドはすべて同じ値を共有してしまう
+//       LocalKeyの値はグリーンスレッドごとに持ち、タスクが終わったときにドロップする
+use std::any::Any;
+use std::collections::HashMap;
+
+// スレッドごとのLocalKeyの値
+// NOTE: キーはLocalKeyのstaticのアドレス
+pub(crate) type Locals = HashMap<usize, Box<dyn Any>>;
+
+// green_local!で宣言するスレッドローカル変数のキー
+pub struct LocalKey<T: 'static> {
+    init: fn() -> T,
+}
+
+impl<T: 'static> LocalKey<T> {
+    #[doc(hidden)]
+    pub const fn new(init: fn() -> T) -> Self {
+        LocalKey { init }
+    }
+
+    // 現在のスレッドの値への参照をfに渡す
+    // 初めてアクセスしたときに初期値を作る
+    pub fn with<F, R>(&'static self, f: F) -> R
+    where
+        F: FnOnce(&T) -> R,
+    {
+        let key = self as *const LocalKey<T> as usize;
+        let value: *const T = {
+            let _guard = crate::preempt::disable();
+            let locals = crate::current_locals();
+            // NOTE: 初期化の中で他のLocalKeyにアクセスしてもよいように、localsの参照を持ったまま初期化しない
+            if !unsafe { (&*locals).contains_key(&key) } {
+                let value: Box<dyn Any> = Box::new((self.init)());
+                unsafe { (&mut *locals).insert(key, value) };
+            }
+            let value = unsafe { &(&*locals)[&key] };
+            value.downcast_ref::<T>().unwrap()
+        };
+        // NOTE: 値はBoxに入っていてスレッドが終わるまでドロップされないので、fの中で切り替わっても有効
+        f(unsafe { &*value })
+    }
+}
+
+// グリーンスレッドごとのスレッドローカル変数を宣言する
+// thread_local!と同じように使える
+#[macro_export]
+macro_rules! green_local {
+    () => {};
+    ($(#[$attr:meta])* $vis:vis static $name:ident: $t:ty = $init:expr; $($rest:tt)*) => {
+        $crate::green_local!($(#[$attr])* $vis static $name: $t = $init);
+        $crate::green_local!($($rest)*);
+    };
+    ($(#[$attr:meta])* $vis:vis static $name:ident: $t:ty = $init:expr) => {
+        $(#[$attr])*
+        $vis static $name: $crate::LocalKey<$t> = $crate::LocalKey::new({
+            fn __init() -> $t {
+                $init
+            }
+            __init
+        });
+    };
+}
@@ -0,0 +1,57 @@
+// 呼び出し元の処理を普通のスレッドとして動かす
+// NOTE: ベーススレッド(スレッド0)はrunなどでRuntimeを動かすためのもので、タスクを持たないので、
+//       チャネルのrecvやsleep、Mutexのlockなどのブロックする処理を呼べない
+//       block_on_mainに渡した処理は生成したスレッドで動くので、他のスレッドと同じくすべての処理を使える
+use std::panic;
+use std::pin::Pin;
+
+use crate::join;
+use crate::{preempt, RestoreCurrent, Runtime, ThreadHandle, CURRENT, DEFAULT_PRIORITY};
+
+impl Runtime {
+    // fを"main"という名前の新しいスレッドで実行し、終わるまでRuntimeを動かして戻り値を返す
+    // fがパニックした場合は、呼び出し元で同じパニックを起こす
+    // NOTE: scopeと同じくfが終わるまで戻らないので、fは'staticでないデータを借用できる
+    //       fが終わったときに終わっていないスレッドはそのまま残るので、runかshutdownで片付ける
+    pub fn block_on_main<F, T>(self: Pin<&mut Self>, f: F) -> T
+    where
+        F: FnOnce() -> T,
+    {
+        let this = unsafe { self.get_unchecked_mut() };
+        let rt: *mut Runtime = this;
+        // NOTE: 他のRuntimeのスレッドの中から呼ばれた場合に、戻った後もそのRuntimeを使えるように、
+        //       戻るときもパニックで巻き戻るときも元のRuntimeに戻す
+        let _restore = RestoreCurrent(CURRENT.with(|current| current.replace(rt)));
+        let handle = {
+            let _guard = preempt::disable();
+            // 利用可能なスレッドがない場合は空くまで待つ
+            let id = this
+                .prepare_thread(true)
+                .expect("failed to spawn the main task.");
+            let (task, handle) = join::wrap(f, ThreadHandle::new(this.thread_id(id)));
+            // NOTE: fが終わるまで戻らないので、taskが借用しているデータより長く実行されることはない
+            let task: Box<dyn FnOnce()> = unsafe { std::mem::transmute(task) };
+            this.start_thread(
+                id,
+                DEFAULT_PRIORITY,
+                Some("main".to_string()),
+                task,
+                handle.token.clone(),
+            );
+            handle
+        };
+
+        while !handle.is_finished() {
+            if !this.run_once() {
+                panic!(
+                    "deadlock: the main task can never finish.\n{}",
+                    this.blocked_threads_report()
+                );
+            }
+        }
+        match handle.join() {
+            Ok(output) => output,
+            Err(payload) => panic::resume_unwind(payload),
+        }
+    }
+}
@@ -0,0 +1,192 @@
+// ランタイムのカウンタをPrometheusのテキスト形式で公開する
+// NOTE: 1秒あたりの切り替え回数のような割合はここでは計算せず、Prometheus側でrate(greenthreads_switches_total[1m])のように求める
+//       serve_metricsはHTTPサーバーをグリーンスレッドで動かすので、他のスレッドと同じくスレッドの枠を1つ使う
+//       キャンセルされるまで戻らないので、生成したスレッドのJoinHandleをキャンセルして止める
+use std::fmt::Write as _;
+use std::io::{self, Read, Write};
+
+use crate::net::TcpListener;
+use crate::{runtime_ptr, Runtime, State};
+
+// リクエストの大きさの上限
+// NOTE: ヘッダーを読み捨てるだけなので、これを超えるリクエストは受け付けない
+const MAX_REQUEST: usize = 8 * 1024;
+
+impl Runtime {
+    // ランタイムのカウンタをPrometheusのテキスト形式(text/plain; version=0.0.4)で返す
+    pub fn metrics(&self) -> String {
+        let mut out = String::new();
+        let count = |state: State| self.threads.iter().filter(|t| t.state == state).count();
+
+        metric(
+            &mut out,
+            "switches_total",
+            "counter",
+            "Total number of context switches.",
+        );
+        let _ = writeln!(out, "greenthreads_switches_total {}", self.switches);
+
+        metric(&mut out, "threads", "gauge", "Number of threads by state.");
+        for (label, state) in [
+            ("running", State::Running),
+            ("ready", State::Ready),
+            ("blocked", State::Blocked),
+            ("parked", State::Parked),
+            ("available", State::Available),
+        ] {
+            let _ = writeln!(
+                out,
+                "greenthreads_threads{{state=\"{}\"}} {}",
+                label,
+                count(state)
+            );
+        }
+
+        // NOTE: リアクターにはスレッド以外(spawn_blockingやインジェクターなど)も登録しているので、スレッドだけを数える
+        metric(
+            &mut out,
+            "io_waiters",
+            "gauge",
+            "Number of threads waiting for I/O readiness.",
+        );
+        let io = self
+            .reactor
+            .waiters()
+            .filter(|id| *id < self.threads.len())
+            .count();
+        let _ = writeln!(out, "greenthreads_io_waiters {}", io);
+
+        metric(
+            &mut out,
+            "timers",
+            "gauge",
+            "Number of pending timer entries.",
+        );
+        let _ = writeln!(out, "greenthreads_timers {}", self.timers.count());
+
+        // NOTE: 使った量はStats::peak_stackと同じく、スタックをPOISONで埋めていない場合は切り替えるときのスタックポインタから測った最大値
+        metric(
+            &mut out,
+            "stack_bytes",
+            "gauge",
+            "Stack bytes held by live threads.",
+        );
+        let live = || {
+            self.threads
+                .iter()
+                .filter(|t| t.state != State::Available)
+                .filter_map(|t| t.stack.as_ref().map(|s| (t, s)))
+        };
+        let mapped: usize = live().map(|(_, s)| s.size()).sum();
+        let used: usize = live()
+            .map(|(t, s)| s.high_water_mark().unwrap_or(0).max(t.counters.peak_stack))
+            .sum();
+        let _ = writeln!(
+            out,
+            "greenthreads_stack_bytes{{kind=\"mapped\"}} {}",
+            mapped
+        );
+        let _ = writeln!(out, "greenthreads_stack_bytes{{kind=\"used\"}} {}", used);
+
+        metric(
+            &mut out,
+            "thread_switches_total",
+            "counter",
+            "Number of switches into each thread slot.",
+        );
+        for thread in &self.threads {
+            let _ = writeln!(
+                out,
+                "greenthreads_thread_switches_total{{{}}} {}",
+                thread_labels(thread.id, thread.name.as_deref()),
+                thread.counters.switches
+            );
+        }
+
+        metric(
+            &mut out,
+            "thread_run_seconds_total",
+            "counter",
+            "Time each thread slot has spent running.",
+        );
+        for thread in &self.threads {
+            let _ = writeln!(
+                out,
+                "greenthreads_thread_run_seconds_total{{{}}} {}",
+                thread_labels(thread.id, thread.name.as_deref()),
+                thread.counters.run_time.as_secs_f64()
+            );
+        }
+        out
+    }
+}
+
+fn metric(out: &mut String, name: &str, kind: &str, help: &str) {
+    let _ = writeln!(out, "# HELP greenthreads_{} {}", name, help);
+    let _ = writeln!(out, "# TYPE greenthreads_{} {}", name, kind);
+}
+
+fn thread_labels(id: usize, name: Option<&str>) -> String {
+    let mut labels = format!("id=\"{}\"", id);
+    if let Some(name) = name {
+        labels.push_str(",name=\"");
+        // NOTE: ラベルの値ではバックスラッシュ、ダブルクォート、改行をエスケープする
+        for c in name.chars() {
+            match c {
+                '\\' => labels.push_str("\\\\"),
+                '"' => labels.push_str("\\\""),
+                '\n' => labels.push_str("\\n"),
+                c => labels.push(c),
+            }
+        }
+        labels.push('"');
+    }
+    labels
+}
+
+// listenerで接続を受け付け、GET /metricsにこのランタイムのmetricsを返すHTTPサーバーを動かす
+// キャンセルされるか、接続を受け付けられなくなるまで戻らない
+// NOTE: 1つずつ順番に応答するので、遅いクライアントがいると他のクライアントは待たされる
+pub fn serve_metrics(listener: &TcpListener) -> io::Result<()> {
+    loop {
+        let (mut stream, _) = listener.accept()?;
+        // NOTE: 1つの接続の失敗でサーバーを止めない
+        let _ = respond(&mut stream);
+    }
+}
+
+fn respond<S: Read + Write>(stream: &mut S) -> io::Result<()> {
+    let mut request = Vec::new();
+    let mut buf = [0u8; 1024];
+    while !request.windows(4).any(|w| w == b"\r\n\r\n") {
+        if request.len() > MAX_REQUEST {
+            return write_response(stream, "413 Payload Too Large", "");
+        }
+        let n = stream.read(&mut buf)?;
+        if n == 0 {
+            return Ok(());
+        }
+        request.extend_from_slice(&buf[..n]);
+    }
+    let line = request.split(|&b| b == b'\r').next().unwrap_or_default();
+    let mut parts = line.split(|&b| b == b' ');
+    match (parts.next(), parts.next()) {
+        (Some(b"GET"), Some(b"/metrics")) => {
+            let body = unsafe { &*runtime_ptr() }.metrics();
+            write_response(stream, "200 OK", &body)
+        }
+        (Some(b"GET"), _) => write_response(stream, "404 Not Found", ""),
+        _ => write_response(stream, "405 Method Not Allowed", ""),
+    }
+}
+
+fn write_response<S: Write>(stream: &mut S, status: &str, body: &str) -> io::Result<()> {
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+    )?;
+    stream.flush()
+}
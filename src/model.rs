@@ -0,0 +1,212 @@
+// モデル検査
+// NOTE: RUSTFLAGS="--cfg greenthreads_model" でビルドしたときだけ有効になる
+//       同じ処理を何度も新しいRuntimeで実行し、再開可能なスレッドが複数ある切り替えのたびに
+//       まだ試していないスレッドを選ぶことで、切り替えの順番を深さ優先ですべて試す
+//       同期プリミティブは操作の前に切り替えを挟み(yield_point)、自身の不変条件をassertで確認する
+//       プリエンプションはシグナルで起きて順番を決められないので使わないこと
+use std::cell::RefCell;
+use std::panic;
+
+use crate::scheduler::Scheduler;
+use crate::{PinnedRuntime, Runtime};
+
+// 切り替えの順番を選べる箇所の数の上限の初期値
+const DEFAULT_MAX_DEPTH: usize = 64;
+// 実行する回数の上限の初期値
+const DEFAULT_MAX_ITERATIONS: usize = 100_000;
+
+thread_local! {
+    // 探索中の状態
+    // NOTE: パニックフックから失敗した順番を表示できるように、スケジューラではなくここに持つ
+    static EXPLORATION: RefCell<Option<Exploration>> = const { RefCell::new(None) };
+}
+
+// 選べる箇所で何番目を選んだか
+#[derive(Debug, Clone, Copy)]
+struct Branch {
+    chosen: usize,
+    options: usize,
+}
+
+struct Exploration {
+    branches: Vec<Branch>,
+    // 今回の実行で次に使うbranchesの位置
+    pos: usize,
+    max_depth: usize,
+    iteration: usize,
+}
+
+impl Exploration {
+    // 次の実行で試す順番に進める
+    // すべて試し終わった場合はfalseを返す
+    fn advance(&mut self) -> bool {
+        while let Some(last) = self.branches.last_mut() {
+            if last.chosen + 1 < last.options {
+                last.chosen += 1;
+                self.pos = 0;
+                self.iteration += 1;
+                return true;
+            }
+            self.branches.pop();
+        }
+        false
+    }
+
+    fn choose(&mut self, options: usize) -> usize {
+        if options < 2 || self.pos >= self.max_depth {
+            return 0;
+        }
+        let pos = self.pos;
+        self.pos += 1;
+        match self.branches.get(pos) {
+            Some(branch) => {
+                assert_eq!(
+                    branch.options, options,
+                    "model: execution is not deterministic (iteration {}).",
+                    self.iteration
+                );
+                branch.chosen
+            }
+            None => {
+                self.branches.push(Branch { chosen: 0, options });
+                0
+            }
+        }
+    }
+
+    fn choices(&self) -> Vec<usize> {
+        self.branches[..self.pos].iter().map(|b| b.chosen).collect()
+    }
+}
+
+// 探索中の順番どおりにスレッドを選ぶスケジューラ
+struct ModelScheduler {
+    ready: Vec<usize>,
+}
+
+impl Scheduler for ModelScheduler {
+    fn ready(&mut self, thread_id: usize) {
+        self.ready.push(thread_id);
+    }
+
+    fn pick_next(&mut self) -> Option<usize> {
+        if self.ready.is_empty() {
+            return None;
+        }
+        let options = self.ready.len();
+        let index = EXPLORATION.with(|e| match e.borrow_mut().as_mut() {
+            Some(exploration) => exploration.choose(options),
+            None => 0,
+        });
+        Some(self.ready.remove(index))
+    }
+}
+
+// 探索の設定
+pub struct Model {
+    max_depth: usize,
+    max_iterations: usize,
+}
+
+impl Default for Model {
+    fn default() -> Self {
+        Model::new()
+    }
+}
+
+impl Model {
+    pub fn new() -> Self {
+        Model {
+            max_depth: DEFAULT_MAX_DEPTH,
+            max_iterations: DEFAULT_MAX_ITERATIONS,
+        }
+    }
+
+    // 1回の実行で順番を選ぶ箇所の数の上限
+    // NOTE: 超えた後の切り替えは再開可能になった順に選ぶ
+    pub fn max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = max_depth;
+        self
+    }
+
+    // 実行する回数の上限
+    // NOTE: 超えた場合は、試していない順番が残っていても終わる
+    pub fn max_iterations(mut self, max_iterations: usize) -> Self {
+        self.max_iterations = max_iterations;
+        self
+    }
+
+    // fを新しいRuntimeのベーススレッドで実行してからrunするのを、切り替えの順番を変えながら繰り返す
+    // 実行した回数を返す
+    // NOTE: fの中でパニックした場合や、デッドロックした場合は、その実行で選んだ順番を表示してからパニックする
+    //       生成したスレッドでのパニックはjoinでErrとして返るので、fの中でjoinして確認する
+    pub fn check<F>(&self, f: F) -> usize
+    where
+        F: Fn(&mut PinnedRuntime),
+    {
+        EXPLORATION.with(|e| {
+            assert!(e.borrow().is_none(), "model: check cannot be nested.");
+            *e.borrow_mut() = Some(Exploration {
+                branches: Vec::new(),
+                pos: 0,
+                max_depth: self.max_depth,
+                iteration: 1,
+            });
+        });
+        let prev_hook = panic::take_hook();
+        panic::set_hook(Box::new(|info| {
+            EXPLORATION.with(|e| {
+                if let Ok(e) = e.try_borrow() {
+                    if let Some(exploration) = e.as_ref() {
+                        eprintln!(
+                            "model: failed at iteration {} with choices {:?}",
+                            exploration.iteration,
+                            exploration.choices()
+                        );
+                    }
+                }
+            });
+            eprintln!("{}", info);
+        }));
+
+        let result = panic::catch_unwind(panic::AssertUnwindSafe(|| {
+            let mut iterations = 0;
+            loop {
+                let mut runtime =
+                    Runtime::with_custom_scheduler(Box::new(ModelScheduler { ready: Vec::new() }));
+                runtime.as_mut().init();
+                f(&mut runtime);
+                runtime.as_mut().run();
+                drop(runtime);
+                iterations += 1;
+                let more = EXPLORATION.with(|e| e.borrow_mut().as_mut().unwrap().advance());
+                if !more || iterations >= self.max_iterations {
+                    return iterations;
+                }
+            }
+        }));
+
+        panic::set_hook(prev_hook);
+        EXPLORATION.with(|e| e.borrow_mut().take());
+        match result {
+            Ok(iterations) => iterations,
+            Err(payload) => panic::resume_unwind(payload),
+        }
+    }
+}
+
+// 初期設定でfの切り替えの順番をすべて試す
+pub fn model<F>(f: F) -> usize
+where
+    F: Fn(&mut PinnedRuntime),
+{
+    Model::new().check(f)
+}
+
+// 探索中であれば、ここで他のスレッドに切り替える順番も試す
+pub(crate) fn yield_point() {
+    let exploring = EXPLORATION.with(|e| e.try_borrow().is_ok_and(|e| e.is_some()));
+    if exploring {
+        crate::yield_thread();
+    }
+}
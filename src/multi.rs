@@ -0,0 +1,210 @@
+// 複数のOSスレッドでグリーンスレッドを動かすM:Nランタイム
+// NOTE: ワーカー(OSスレッド)ごとにRuntimeを持ち、まだ始まっていないタスクを他のワーカーから盗んで実行する
+//       一度始まったグリーンスレッドはスタックごと別のワーカーに移動することはない
+//       盗むのはタスクが始まる前だけなので、これが移動の境目になり、タスクの中のthread_local!やライブラリのロケールなどは
+//       始まってから終わるまで同じワーカーのものになる(ただし同じワーカーで動く他のタスクとは共有する)
+//       特定のワーカーのTLSを使いたいタスクはspawn_onで追加する
+use std::collections::VecDeque;
+use std::io;
+#[cfg(target_os = "linux")]
+use std::os::raw::c_int;
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use crate::{preempt, Runtime, DEFAULT_PRIORITY};
+
+// ワーカーのLIFOスロットのスレッドを続けて実行できる回数
+const LIFO_SLOT_LIMIT: usize = 3;
+
+// cpu_set_tの大きさ(1024個のCPU)
+#[cfg(target_os = "linux")]
+const CPU_SET_WORDS: usize = 16;
+
+#[cfg(target_os = "linux")]
+extern "C" {
+    fn sched_setaffinity(pid: c_int, cpusetsize: usize, mask: *const u64) -> c_int;
+}
+
+struct Task {
+    // spawn_namedで付けた名前
+    name: Option<String>,
+    f: Box<dyn FnOnce() + Send>,
+}
+
+struct Shared {
+    // ワーカーごとのタスクのキュー
+    // 持ち主は後ろから取り出し、他のワーカーは前から盗む
+    queues: Vec<Mutex<VecDeque<Task>>>,
+    // ワーカーごとの、そのワーカーでしか実行しないタスクのキュー
+    // NOTE: 他のワーカーからは盗まない
+    pinned: Vec<Mutex<VecDeque<Task>>>,
+}
+
+impl Shared {
+    fn pop(&self, worker: usize) -> Option<Task> {
+        if let Some(task) = self.pinned[worker].lock().unwrap().pop_front() {
+            return Some(task);
+        }
+        if let Some(task) = self.queues[worker].lock().unwrap().pop_back() {
+            return Some(task);
+        }
+        // 自分のキューが空の場合は他のワーカーのキューから盗む
+        let n = self.queues.len();
+        (1..n).find_map(|i| self.queues[(worker + i) % n].lock().unwrap().pop_front())
+    }
+}
+
+pub struct MultiRuntime {
+    shared: Arc<Shared>,
+    // 次にタスクを積むワーカー
+    next: usize,
+    // ワーカーを固定するCPUの番号
+    cores: Option<Vec<usize>>,
+}
+
+impl MultiRuntime {
+    pub fn new(workers: usize) -> Self {
+        assert!(workers > 0, "workers must be greater than 0.");
+        MultiRuntime {
+            shared: Arc::new(Shared {
+                queues: (0..workers).map(|_| Mutex::new(VecDeque::new())).collect(),
+                pinned: (0..workers).map(|_| Mutex::new(VecDeque::new())).collect(),
+            }),
+            next: 0,
+            cores: None,
+        }
+    }
+
+    // 各ワーカーのOSスレッドをCPUに固定する
+    // i番目のワーカーはcores[i % cores.len()]のCPUだけで動く
+    // NOTE: Linuxのsched_setaffinityを使うので、他のOSではrunがパニックする
+    pub fn pin_workers(mut self, cores: Vec<usize>) -> Self {
+        assert!(!cores.is_empty(), "cores must not be empty.");
+        self.cores = Some(cores);
+        self
+    }
+
+    pub fn workers(&self) -> usize {
+        self.shared.queues.len()
+    }
+
+    // タスクを追加する
+    // NOTE: どのワーカーで実行されるかは空き具合によって決まる
+    pub fn spawn<F>(&mut self, f: F)
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        self.push(None, f);
+    }
+
+    // 名前を付けてタスクを追加する
+    // NOTE: 実行している間は、ワーカーのOSスレッドの名前がこの名前になる
+    pub fn spawn_named<F>(&mut self, name: impl Into<String>, f: F)
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        self.push(Some(name.into()), f);
+    }
+
+    fn push<F>(&mut self, name: Option<String>, f: F)
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        let worker = self.next % self.shared.queues.len();
+        self.next += 1;
+        self.shared.queues[worker].lock().unwrap().push_back(Task {
+            name,
+            f: Box::new(f),
+        });
+    }
+
+    // workerのワーカーでだけ実行するタスクを追加する
+    // NOTE: 他のワーカーに盗まれないので、始まる前も始まった後も別のワーカーに移ることはない
+    pub fn spawn_on<F>(&mut self, worker: usize, f: F)
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        assert!(worker < self.workers(), "no such worker: {}", worker);
+        self.shared.pinned[worker].lock().unwrap().push_back(Task {
+            name: None,
+            f: Box::new(f),
+        });
+    }
+
+    // ワーカーを起動し、すべてのタスクが終わるまで待つ
+    // NOTE: ワーカーのOSスレッドの名前はworker-<番号>で、名前を付けたタスクを実行している間はその名前になる
+    pub fn run(self) {
+        let handles: Vec<_> = (0..self.shared.queues.len())
+            .map(|worker| {
+                let shared = self.shared.clone();
+                let core = self.cores.as_ref().map(|cores| cores[worker % cores.len()]);
+                thread::Builder::new()
+                    .name(format!("worker-{}", worker))
+                    .spawn(move || {
+                        if let Some(core) = core {
+                            pin_to_core(core).expect("failed to pin a worker to the core.");
+                        }
+                        run_worker(shared, worker)
+                    })
+                    .expect("failed to spawn a worker.")
+            })
+            .collect();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+    }
+}
+
+fn run_worker(shared: Arc<Shared>, worker: usize) {
+    let mut pinned = Runtime::builder()
+        .lifo_slot(LIFO_SLOT_LIMIT)
+        .os_thread_names(true)
+        .build();
+    // NOTE: 固定したRuntimeを動かさずに、クレートの中のメソッドを呼ぶ
+    let runtime = unsafe { pinned.as_mut().get_unchecked_mut() };
+
+    loop {
+        // 空いているスレッドの分だけタスクを取ってきて実行できるようにする
+        while runtime.has_available_thread() {
+            let task = match shared.pop(worker) {
+                Some(task) => task,
+                None => break,
+            };
+            let _guard = preempt::disable();
+            let id = runtime
+                .prepare_thread(false)
+                .expect("failed to spawn a task.");
+            // NOTE: JoinHandleは使わないので手放す
+            drop(runtime.spawn_on(id, DEFAULT_PRIORITY, task.name, task.f));
+        }
+
+        // 他のグリーンスレッドを実行し、すべてスリープ中やI/O待ちなら起きるまで待つ
+        if runtime.run_once() {
+            continue;
+        }
+        // NOTE: タスクはすべてrunの前に積まれるので、盗めるタスクもグリーンスレッドもなければ、
+        //       このワーカーにはもう仕事が来ない
+        //       他のワーカーが終わるのを待って回り続けるとCPUを使い切ってしまうので、ここで終わる
+        break;
+    }
+}
+
+// 現在のOSスレッドをcoreのCPUだけで動くようにする
+#[cfg(target_os = "linux")]
+fn pin_to_core(core: usize) -> io::Result<()> {
+    let mut mask = [0u64; CPU_SET_WORDS];
+    if core >= CPU_SET_WORDS * 64 {
+        return Err(io::Error::from(io::ErrorKind::InvalidInput));
+    }
+    mask[core / 64] |= 1 << (core % 64);
+    // NOTE: pidに0を指定すると呼び出したスレッドが対象になる
+    if unsafe { sched_setaffinity(0, std::mem::size_of_val(&mask), mask.as_ptr()) } < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+#[cfg(not(target_os = "linux"))]
+fn pin_to_core(_core: usize) -> io::Result<()> {
+    Err(io::Error::from(io::ErrorKind::Unsupported))
+}
@@ -0,0 +1,127 @@
+// スレッドの名前をパニックのメッセージやOSのツールに伝える
+// NOTE: 標準のパニックのメッセージにはOSスレッドの名前しか出ないので、どのグリーンスレッドでパニックしたかを先に書く
+//       Builder::os_thread_namesを指定すると、実行中のグリーンスレッドの名前をOSスレッドの名前にするので、
+//       top -Hやgdbのinfo threadsでどのグリーンスレッドが動いているかがわかる
+use std::ffi::CString;
+use std::os::raw::{c_char, c_int};
+use std::panic;
+use std::sync::Once;
+
+use crate::{Runtime, CURRENT};
+
+// OSスレッドの名前の最大の長さ(終端のNULを除く)
+#[cfg(target_os = "linux")]
+const MAX_OS_NAME_LEN: usize = 15;
+#[cfg(not(target_os = "linux"))]
+const MAX_OS_NAME_LEN: usize = 63;
+
+#[cfg(target_os = "linux")]
+extern "C" {
+    fn pthread_self() -> usize;
+    fn pthread_setname_np(thread: usize, name: *const c_char) -> c_int;
+}
+
+#[cfg(target_os = "macos")]
+extern "C" {
+    fn pthread_setname_np(name: *const c_char) -> c_int;
+}
+
+// OSスレッドに付けている名前
+pub(crate) struct OsThreadName {
+    // ベーススレッドを実行しているときの名前(Runtimeを作ったときのOSスレッドの名前)
+    base: String,
+    // 今付けている名前
+    shown: CString,
+}
+
+impl OsThreadName {
+    pub(crate) fn new() -> Self {
+        let base = std::thread::current()
+            .name()
+            .unwrap_or("greenthreads")
+            .to_string();
+        OsThreadName {
+            shown: to_os_name(&base),
+            base,
+        }
+    }
+}
+
+impl Runtime {
+    // 切り替え先のposのスレッドの名前をOSスレッドの名前にする
+    // 名前のないスレッドとベーススレッドを実行している間は、元のOSスレッドの名前に戻す
+    // NOTE: 切り替えのたびに呼ぶので、名前が変わらない場合はシステムコールを呼ばない
+    pub(crate) fn name_os_thread(&mut self, pos: usize) {
+        let os_name = match &mut self.os_thread_name {
+            Some(os_name) => os_name,
+            None => return,
+        };
+        let name = match &self.threads[pos].name {
+            Some(name) if pos != 0 => name,
+            _ => &os_name.base,
+        };
+        if truncate(name).as_bytes() == os_name.shown.as_bytes() {
+            return;
+        }
+        os_name.shown = to_os_name(name);
+        set_os_thread_name(&os_name.shown);
+    }
+}
+
+// NULを含む名前は使えないので、NULの手前までにする
+fn to_os_name(name: &str) -> CString {
+    let name = truncate(name);
+    let len = name.find('\0').unwrap_or(name.len());
+    CString::new(&name[..len]).unwrap()
+}
+
+// OSスレッドの名前に使える長さに文字の境目で切り詰める
+fn truncate(name: &str) -> &str {
+    let mut len = name.len().min(MAX_OS_NAME_LEN);
+    while !name.is_char_boundary(len) {
+        len -= 1;
+    }
+    &name[..len]
+}
+
+// 現在のOSスレッドの名前をnameにする
+// NOTE: 名前を付けられなくても実行には関係ないので、失敗は無視する
+#[cfg(target_os = "linux")]
+fn set_os_thread_name(name: &CString) {
+    unsafe { pthread_setname_np(pthread_self(), name.as_ptr()) };
+}
+
+#[cfg(target_os = "macos")]
+fn set_os_thread_name(name: &CString) {
+    unsafe { pthread_setname_np(name.as_ptr()) };
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+fn set_os_thread_name(_name: &CString) {}
+
+// グリーンスレッドでパニックしたときに、標準のメッセージの前にスレッドのIDと名前を書くフックを入れる
+// NOTE: 前に設定されていたフックはそのまま呼ぶ
+//       プロセス全体で1回だけ入れる
+pub(crate) fn install_panic_hook() {
+    static INSTALL: Once = Once::new();
+    INSTALL.call_once(|| {
+        let prev = panic::take_hook();
+        panic::set_hook(Box::new(move |info| {
+            if let Some(label) = current_label() {
+                eprintln!("panic in green {}", label);
+            }
+            prev(info);
+        }));
+    });
+}
+
+// 現在のOSスレッドでグリーンスレッドを実行中なら、そのスレッドの"thread ID (名前)"を返す
+// NOTE: ベーススレッドは標準のメッセージのOSスレッドの名前でわかるので返さない
+fn current_label() -> Option<String> {
+    let rt = CURRENT.try_with(|current| current.get()).ok()?;
+    if rt.is_null() {
+        return None;
+    }
+    let rt = unsafe { &*rt };
+    (rt.current != 0).then(|| rt.thread_label(rt.current))
+}
@@ -0,0 +1,43 @@
+// リアクターと連携するノンブロッキングなネットワークI/O
+// NOTE: WouldBlockになったらfdの準備ができるまでスレッドをブロックし、他のスレッドに切り替える
+use std::io;
+use std::net::{SocketAddr, ToSocketAddrs};
+use std::os::unix::io::RawFd;
+
+use crate::reactor::Interest;
+
+mod tcp_listener;
+mod tcp_stream;
+mod udp_socket;
+mod unix_listener;
+mod unix_stream;
+
+pub use tcp_listener::TcpListener;
+pub use tcp_stream::TcpStream;
+pub use udp_socket::UdpSocket;
+pub use unix_listener::UnixListener;
+pub use unix_stream::UnixStream;
+
+// fの結果がWouldBlockの間、fdの準備ができるまで待ってから再実行する
+pub(crate) fn retry<T>(
+    fd: RawFd,
+    interest: Interest,
+    mut f: impl FnMut() -> io::Result<T>,
+) -> io::Result<T> {
+    crate::consume_budget();
+    loop {
+        match f() {
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock => crate::wait_io(fd, interest)?,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            result => return result,
+        }
+    }
+}
+
+// ホスト名とポート("example.com:80"など)をアドレスに解決する
+// NOTE: ToSocketAddrsはgetaddrinfoでOSスレッドごとブロックするので、ブロッキングプールのOSスレッドで解決する
+//       解決している間は現在のスレッドだけがブロックし、他のスレッドは動き続ける
+pub fn lookup_host(host: &str) -> io::Result<Vec<SocketAddr>> {
+    let host = host.to_owned();
+    crate::spawn_blocking(move || host.to_socket_addrs().map(Iterator::collect))?
+}
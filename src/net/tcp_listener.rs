@@ -0,0 +1,42 @@
+use std::io;
+use std::net::{self, SocketAddr, ToSocketAddrs};
+use std::os::unix::io::{AsRawFd, RawFd};
+
+use super::{retry, TcpStream};
+use crate::reactor::Interest;
+
+pub struct TcpListener {
+    inner: net::TcpListener,
+}
+
+impl TcpListener {
+    pub fn bind<A: ToSocketAddrs>(addr: A) -> io::Result<TcpListener> {
+        let inner = net::TcpListener::bind(addr)?;
+        inner.set_nonblocking(true)?;
+        Ok(TcpListener { inner })
+    }
+
+    // 接続を受け付ける
+    // 接続要求が来ていない場合は来るまでブロックする
+    pub fn accept(&self) -> io::Result<(TcpStream, SocketAddr)> {
+        let (stream, addr) = retry(self.as_raw_fd(), Interest::Readable, || self.inner.accept())?;
+        Ok((TcpStream::from_std(stream)?, addr))
+    }
+
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.inner.local_addr()
+    }
+}
+
+impl AsRawFd for TcpListener {
+    fn as_raw_fd(&self) -> RawFd {
+        self.inner.as_raw_fd()
+    }
+}
+
+impl Drop for TcpListener {
+    fn drop(&mut self) {
+        // fdが閉じられる前に監視対象から外す
+        crate::deregister_io(self.as_raw_fd());
+    }
+}
@@ -0,0 +1,91 @@
+use std::io::{self, Read, Write};
+use std::net::{self, Shutdown, SocketAddr, ToSocketAddrs};
+use std::os::unix::io::{AsRawFd, RawFd};
+
+use super::retry;
+use crate::reactor::Interest;
+
+pub struct TcpStream {
+    inner: net::TcpStream,
+}
+
+impl TcpStream {
+    // 接続する
+    // NOTE: 接続の確立自体はOSスレッドをブロックして待つ
+    //       ホスト名を渡すと名前解決でもブロックするので、先にlookup_hostで解決したアドレスを渡すとよい
+    pub fn connect<A: ToSocketAddrs>(addr: A) -> io::Result<TcpStream> {
+        TcpStream::from_std(net::TcpStream::connect(addr)?)
+    }
+
+    pub(crate) fn from_std(inner: net::TcpStream) -> io::Result<TcpStream> {
+        inner.set_nonblocking(true)?;
+        Ok(TcpStream { inner })
+    }
+
+    pub fn peer_addr(&self) -> io::Result<SocketAddr> {
+        self.inner.peer_addr()
+    }
+
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.inner.local_addr()
+    }
+
+    pub fn shutdown(&self, how: Shutdown) -> io::Result<()> {
+        self.inner.shutdown(how)
+    }
+
+    pub fn set_nodelay(&self, nodelay: bool) -> io::Result<()> {
+        self.inner.set_nodelay(nodelay)
+    }
+}
+
+// 読み込めるデータがない場合は届くまでブロックする
+impl Read for &TcpStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        retry(self.as_raw_fd(), Interest::Readable, || {
+            (&self.inner).read(buf)
+        })
+    }
+}
+
+// 送信バッファが満杯の場合は空きができるまでブロックする
+impl Write for &TcpStream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        retry(self.as_raw_fd(), Interest::Writable, || {
+            (&self.inner).write(buf)
+        })
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Read for TcpStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        (&*self).read(buf)
+    }
+}
+
+impl Write for TcpStream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        (&*self).write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl AsRawFd for TcpStream {
+    fn as_raw_fd(&self) -> RawFd {
+        self.inner.as_raw_fd()
+    }
+}
+
+impl Drop for TcpStream {
+    fn drop(&mut self) {
+        // fdが閉じられる前に監視対象から外す
+        crate::deregister_io(self.as_raw_fd());
+    }
+}
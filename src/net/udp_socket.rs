@@ -0,0 +1,82 @@
+use std::io;
+use std::net::{self, SocketAddr, ToSocketAddrs};
+use std::os::unix::io::{AsRawFd, RawFd};
+
+use super::retry;
+use crate::reactor::Interest;
+
+pub struct UdpSocket {
+    inner: net::UdpSocket,
+}
+
+impl UdpSocket {
+    pub fn bind<A: ToSocketAddrs>(addr: A) -> io::Result<UdpSocket> {
+        let inner = net::UdpSocket::bind(addr)?;
+        inner.set_nonblocking(true)?;
+        Ok(UdpSocket { inner })
+    }
+
+    // send/recvで使う相手を決める
+    // NOTE: UDPなので相手と通信はせず、すぐに戻る
+    pub fn connect<A: ToSocketAddrs>(&self, addr: A) -> io::Result<()> {
+        self.inner.connect(addr)
+    }
+
+    // データグラムを受信し、送信元のアドレスを返す
+    // 届いていない場合は届くまでブロックする
+    pub fn recv_from(&self, buf: &mut [u8]) -> io::Result<(usize, SocketAddr)> {
+        retry(self.as_raw_fd(), Interest::Readable, || {
+            self.inner.recv_from(buf)
+        })
+    }
+
+    // 送信バッファが満杯の場合は空きができるまでブロックする
+    pub fn send_to<A: ToSocketAddrs>(&self, buf: &[u8], addr: A) -> io::Result<usize> {
+        // NOTE: 書き込めるようになるのを待ってやり直すたびに解決し直さないように、先にアドレスにしておく
+        let addr = addr.to_socket_addrs()?.next().ok_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidInput, "no addresses to send data to")
+        })?;
+        retry(self.as_raw_fd(), Interest::Writable, || {
+            self.inner.send_to(buf, addr)
+        })
+    }
+
+    // connectした相手からデータグラムを受信する
+    pub fn recv(&self, buf: &mut [u8]) -> io::Result<usize> {
+        retry(self.as_raw_fd(), Interest::Readable, || {
+            self.inner.recv(buf)
+        })
+    }
+
+    // connectした相手にデータグラムを送信する
+    pub fn send(&self, buf: &[u8]) -> io::Result<usize> {
+        retry(self.as_raw_fd(), Interest::Writable, || {
+            self.inner.send(buf)
+        })
+    }
+
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.inner.local_addr()
+    }
+
+    pub fn peer_addr(&self) -> io::Result<SocketAddr> {
+        self.inner.peer_addr()
+    }
+
+    pub fn set_broadcast(&self, broadcast: bool) -> io::Result<()> {
+        self.inner.set_broadcast(broadcast)
+    }
+}
+
+impl AsRawFd for UdpSocket {
+    fn as_raw_fd(&self) -> RawFd {
+        self.inner.as_raw_fd()
+    }
+}
+
+impl Drop for UdpSocket {
+    fn drop(&mut self) {
+        // fdが閉じられる前に監視対象から外す
+        crate::deregister_io(self.as_raw_fd());
+    }
+}
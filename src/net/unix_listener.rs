@@ -0,0 +1,43 @@
+use std::io;
+use std::os::unix::io::{AsRawFd, RawFd};
+use std::os::unix::net::{self, SocketAddr};
+use std::path::Path;
+
+use super::{retry, UnixStream};
+use crate::reactor::Interest;
+
+pub struct UnixListener {
+    inner: net::UnixListener,
+}
+
+impl UnixListener {
+    pub fn bind<P: AsRef<Path>>(path: P) -> io::Result<UnixListener> {
+        let inner = net::UnixListener::bind(path)?;
+        inner.set_nonblocking(true)?;
+        Ok(UnixListener { inner })
+    }
+
+    // 接続を受け付ける
+    // 接続要求が来ていない場合は来るまでブロックする
+    pub fn accept(&self) -> io::Result<(UnixStream, SocketAddr)> {
+        let (stream, addr) = retry(self.as_raw_fd(), Interest::Readable, || self.inner.accept())?;
+        Ok((UnixStream::from_std(stream)?, addr))
+    }
+
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.inner.local_addr()
+    }
+}
+
+impl AsRawFd for UnixListener {
+    fn as_raw_fd(&self) -> RawFd {
+        self.inner.as_raw_fd()
+    }
+}
+
+impl Drop for UnixListener {
+    fn drop(&mut self) {
+        // fdが閉じられる前に監視対象から外す
+        crate::deregister_io(self.as_raw_fd());
+    }
+}
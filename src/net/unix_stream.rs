@@ -0,0 +1,94 @@
+use std::io::{self, Read, Write};
+use std::net::Shutdown;
+use std::os::unix::io::{AsRawFd, RawFd};
+use std::os::unix::net::{self, SocketAddr};
+use std::path::Path;
+
+use super::retry;
+use crate::reactor::Interest;
+
+pub struct UnixStream {
+    inner: net::UnixStream,
+}
+
+impl UnixStream {
+    // 接続する
+    // NOTE: Unixドメインソケットの接続は相手がacceptするのを待たないので、すぐに戻る
+    pub fn connect<P: AsRef<Path>>(path: P) -> io::Result<UnixStream> {
+        UnixStream::from_std(net::UnixStream::connect(path)?)
+    }
+
+    // つながった2つのストリームを作る
+    pub fn pair() -> io::Result<(UnixStream, UnixStream)> {
+        let (a, b) = net::UnixStream::pair()?;
+        Ok((UnixStream::from_std(a)?, UnixStream::from_std(b)?))
+    }
+
+    pub(crate) fn from_std(inner: net::UnixStream) -> io::Result<UnixStream> {
+        inner.set_nonblocking(true)?;
+        Ok(UnixStream { inner })
+    }
+
+    pub fn peer_addr(&self) -> io::Result<SocketAddr> {
+        self.inner.peer_addr()
+    }
+
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.inner.local_addr()
+    }
+
+    pub fn shutdown(&self, how: Shutdown) -> io::Result<()> {
+        self.inner.shutdown(how)
+    }
+}
+
+// 読み込めるデータがない場合は届くまでブロックする
+impl Read for &UnixStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        retry(self.as_raw_fd(), Interest::Readable, || {
+            (&self.inner).read(buf)
+        })
+    }
+}
+
+// 送信バッファが満杯の場合は空きができるまでブロックする
+impl Write for &UnixStream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        retry(self.as_raw_fd(), Interest::Writable, || {
+            (&self.inner).write(buf)
+        })
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Read for UnixStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        (&*self).read(buf)
+    }
+}
+
+impl Write for UnixStream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        (&*self).write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl AsRawFd for UnixStream {
+    fn as_raw_fd(&self) -> RawFd {
+        self.inner.as_raw_fd()
+    }
+}
+
+impl Drop for UnixStream {
+    fn drop(&mut self) {
+        // fdが閉じられる前に監視対象から外す
+        crate::deregister_io(self.as_raw_fd());
+    }
+}
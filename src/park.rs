@@ -0,0 +1,190 @@
+// 特定のスレッドを止めたり再開したりするための低レベルなAPI
+// NOTE: unparkはトークンを1つだけ残すので、parkより先にunparkされてもparkはすぐに戻る
+use std::error::Error;
+use std::fmt;
+
+use crate::{preempt, runtime_ptr, Runtime, State, Thread};
+
+// スレッドの枠の番号と、枠を何回使い回したか(世代)の組
+// NOTE: スレッドの枠は終わると別のタスクに使い回されるので、番号だけでは終わったタスクと新しいタスクを見分けられない
+//       世代も比べて、終わったタスクのハンドルで新しいタスクを操作しないようにする
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct ThreadId {
+    index: usize,
+    generation: u64,
+}
+
+impl ThreadId {
+    // 枠の番号(ThreadHandle::idと同じ)
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+}
+
+impl fmt::Display for ThreadId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}", self.index, self.generation)
+    }
+}
+
+// ハンドルのスレッドがすでに終わっているので操作できなかった
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct ThreadFinished;
+
+impl fmt::Display for ThreadFinished {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        "the thread has already finished".fmt(f)
+    }
+}
+
+impl Error for ThreadFinished {}
+
+// スレッドを指すハンドル
+// NOTE: スレッドが終わった後は、枠が別のタスクに使い回されていても、そのタスクではなく終わったスレッドを指し続ける
+#[derive(Clone, Debug)]
+pub struct ThreadHandle {
+    id: ThreadId,
+}
+
+impl ThreadHandle {
+    pub(crate) fn new(id: ThreadId) -> Self {
+        ThreadHandle { id }
+    }
+
+    // スレッドの枠の番号
+    // NOTE: 終わったスレッドと、同じ枠を使い回した新しいスレッドは同じ番号になる
+    pub fn id(&self) -> usize {
+        self.id.index
+    }
+
+    // 世代を含めたスレッドのID
+    pub fn thread_id(&self) -> ThreadId {
+        self.id
+    }
+
+    // スレッドの名前を返す
+    // 名前を付けずに生成したスレッドや、枠が使い回されたスレッドはNoneを返す
+    pub fn name(&self) -> Option<String> {
+        let _guard = preempt::disable();
+        let rt = unsafe { &*runtime_ptr() };
+        rt.thread_of(self.id)?.name.clone()
+    }
+
+    pub fn state(&self) -> ThreadState {
+        let _guard = preempt::disable();
+        let rt = unsafe { &*runtime_ptr() };
+        let thread = match rt.thread_of(self.id) {
+            Some(thread) => thread,
+            None => return ThreadState::Finished,
+        };
+        match thread.state {
+            State::Available => ThreadState::Finished,
+            State::Running => ThreadState::Running,
+            State::Ready => ThreadState::Ready,
+            State::Blocked => ThreadState::Blocked,
+            State::Parked => ThreadState::Parked,
+        }
+    }
+
+    // スタックを使った量の最大値を返す
+    // NOTE: Runtime::max_stack_usageと同じだが、枠が使い回された場合はNoneを返す
+    pub fn stack_usage(&self) -> Option<usize> {
+        let _guard = preempt::disable();
+        let rt = unsafe { &*runtime_ptr() };
+        rt.thread_of(self.id)?;
+        rt.max_stack_usage(self.id.index)
+    }
+
+    // スケジューラのキューのレベルを返す
+    // NOTE: キューのレベルを持たないスケジューラ(MLFQ以外)や、終わったスレッドではNone
+    pub fn queue_level(&self) -> Option<usize> {
+        let _guard = preempt::disable();
+        let rt = unsafe { &*runtime_ptr() };
+        match rt.thread_of(self.id)?.state {
+            State::Available => None,
+            _ => rt.scheduler.level(self.id.index),
+        }
+    }
+
+    // parkで止まっているスレッドを再開可能にする
+    // 止まっていない場合は、次のparkがすぐに戻るようにトークンを残す
+    // スレッドがすでに終わっている場合はErr(ThreadFinished)を返す
+    pub fn unpark(&self) -> Result<(), ThreadFinished> {
+        let _guard = preempt::disable();
+        let rt = unsafe { &mut *runtime_ptr() };
+        match rt.thread_of(self.id) {
+            Some(thread) if thread.state != State::Available => {}
+            _ => return Err(ThreadFinished),
+        }
+        rt.t_unpark(self.id.index);
+        Ok(())
+    }
+}
+
+// ThreadHandle::stateで返すスレッドの状態
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ThreadState {
+    Running,  // 実行中
+    Ready,    // 再開可能
+    Blocked,  // スリープやI/O待ちなどでブロック中
+    Parked,   // parkで停止中
+    Finished, // タスクが終わった
+}
+
+// 現在のスレッドのハンドルを返す
+pub fn current() -> ThreadHandle {
+    let _guard = preempt::disable();
+    let rt = unsafe { &*runtime_ptr() };
+    ThreadHandle::new(rt.thread_id(rt.current))
+}
+
+// unparkされるまで現在のスレッドを止める
+// すでにunparkされていた場合はトークンを消費してすぐに戻る
+pub fn park() {
+    let _guard = preempt::disable();
+    unsafe { Runtime::t_park(runtime_ptr()) }
+}
+
+impl Runtime {
+    // 枠の番号と今の世代からスレッドのIDを作る
+    pub(crate) fn thread_id(&self, index: usize) -> ThreadId {
+        ThreadId {
+            index,
+            generation: self.threads[index].generation,
+        }
+    }
+
+    // idの世代が今の世代と同じならスレッドを返す
+    // NOTE: 終わったが枠がまだ使い回されていないスレッドも返すので、終わったかどうかは状態で確かめる
+    pub(crate) fn thread_of(&self, id: ThreadId) -> Option<&Thread> {
+        self.threads
+            .get(id.index)
+            .filter(|t| t.generation == id.generation)
+    }
+
+    unsafe fn t_park(rt: *mut Runtime) {
+        {
+            let rt = &mut *rt;
+            let thread = &mut rt.threads[rt.current];
+            if thread.unpark_token {
+                thread.unpark_token = false;
+                return;
+            }
+        }
+        Runtime::t_suspend(rt, State::Parked);
+    }
+
+    pub(crate) fn t_unpark(&mut self, id: usize) {
+        if self.threads[id].state == State::Parked {
+            if !self.delay_wakeup(id) {
+                self.make_ready(id);
+            }
+        } else {
+            self.threads[id].unpark_token = true;
+        }
+    }
+}
@@ -0,0 +1,51 @@
+// 動かせないようにヒープに固定したRuntime
+// NOTE: Runtime::init(とrun、scope、block_on_main)は自分のアドレスをOSスレッドごとの変数に残すので、
+//       その後でRuntimeを動かす(Vecに入れる、関数から返すなど)と、残したアドレスが古い場所を指したままになり、
+//       グリーンスレッドの外でcurrentやparkなどを呼んだときに壊れたメモリを読む
+//       Runtime::newやBuilder::buildはRuntimeをPin<Box<Runtime>>に入れたPinnedRuntimeを返し、
+//       Runtimeの状態を変えるメソッドはすべてPin<&mut Runtime>で受け取るので、動かせないことを型で保証できる
+//       PinnedRuntime自体を動かしてもRuntimeは動かない
+//       Runtimeは!Unpinなので、Pinから取り出して動かすにはunsafeが必要になる
+use std::ops::Deref;
+use std::pin::Pin;
+
+use crate::Runtime;
+
+pub struct PinnedRuntime {
+    inner: Pin<Box<Runtime>>,
+}
+
+impl Runtime {
+    // Runtimeをヒープに移して固定する
+    // NOTE: Runtimeは作ったらすぐにここで固定するので、クレートの中で&mut Runtimeを持っている場合も
+    //       Runtimeは固定されている
+    pub(crate) fn pinned(self) -> PinnedRuntime {
+        PinnedRuntime {
+            inner: Box::pin(self),
+        }
+    }
+}
+
+impl PinnedRuntime {
+    // 固定したRuntimeへの参照
+    // spawnやrunなど、Runtimeの状態を変えるメソッドはこれを通して呼ぶ
+    pub fn as_mut(&mut self) -> Pin<&mut Runtime> {
+        self.inner.as_mut()
+    }
+
+    // Cに渡すために、固定したままヒープの領域を手放す
+    // NOTE: 受け取った側はRuntimeを動かさず、Box::from_rawで戻して解放すること
+    #[cfg(feature = "ffi")]
+    pub(crate) fn into_raw(self) -> *mut Runtime {
+        Box::into_raw(unsafe { Pin::into_inner_unchecked(self.inner) })
+    }
+}
+
+// statsやscheduleなど、&selfを取るメソッドはそのまま呼べる
+impl Deref for PinnedRuntime {
+    type Target = Runtime;
+
+    fn deref(&self) -> &Runtime {
+        &self.inner
+    }
+}
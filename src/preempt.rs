@@ -0,0 +1,164 @@
+// タイマーシグナルを使ったプリエンプション
+// NOTE: 一定時間ごとにSIGALRMを送り、シグナルハンドラの中から実行中のスレッドを切り替える
+//       シグナルハンドラのフレームは切り替え元のスレッドのスタックに残り、再開されたときにハンドラから戻る
+//       ランタイムや同期プリミティブの処理中に切り替わると状態が壊れるので、その間はdisableで切り替えを止める
+//       また、mallocなどのロックを持ったまま切り替わる可能性があるため、プリエンプションはあくまで実験的な機能である
+use std::cell::Cell;
+use std::io;
+use std::os::raw::{c_int, c_long};
+use std::time::Duration;
+
+use crate::{Runtime, CURRENT};
+
+const SIGALRM: c_int = 14;
+const ITIMER_REAL: c_int = 0;
+
+#[cfg(target_os = "linux")]
+pub(crate) const SA_RESTART: c_int = 0x10000000;
+#[cfg(target_os = "linux")]
+const SA_NODEFER: c_int = 0x40000000;
+#[cfg(not(target_os = "linux"))]
+pub(crate) const SA_RESTART: c_int = 0x0002;
+#[cfg(not(target_os = "linux"))]
+const SA_NODEFER: c_int = 0x0010;
+
+#[cfg(target_os = "linux")]
+#[repr(C)]
+pub(crate) struct SigAction {
+    pub(crate) sa_handler: usize,
+    pub(crate) sa_mask: [u64; 16],
+    pub(crate) sa_flags: c_int,
+    pub(crate) sa_restorer: usize,
+}
+
+#[cfg(not(target_os = "linux"))]
+#[repr(C)]
+pub(crate) struct SigAction {
+    pub(crate) sa_handler: usize,
+    pub(crate) sa_mask: u32,
+    pub(crate) sa_flags: c_int,
+}
+
+#[repr(C)]
+struct Timeval {
+    // NOTE: time_tはlongなので、32bitのLinuxでは4byte
+    tv_sec: c_long,
+    #[cfg(target_os = "linux")]
+    tv_usec: c_long,
+    #[cfg(not(target_os = "linux"))]
+    tv_usec: i32,
+}
+
+#[repr(C)]
+struct Itimerval {
+    it_interval: Timeval,
+    it_value: Timeval,
+}
+
+extern "C" {
+    pub(crate) fn sigaction(signum: c_int, act: *const SigAction, oldact: *mut SigAction) -> c_int;
+    fn setitimer(which: c_int, new_value: *const Itimerval, old_value: *mut Itimerval) -> c_int;
+    #[cfg_attr(target_os = "linux", link_name = "__errno_location")]
+    #[cfg_attr(not(target_os = "linux"), link_name = "__error")]
+    pub(crate) fn errno_location() -> *mut c_int;
+}
+
+thread_local! {
+    // trueの間はシグナルが届いてもスレッドを切り替えない
+    // NOTE: スレッドを切り替えるときにそれぞれのスレッドのスタックに退避され、再開時に元に戻る
+    static DISABLED: Cell<bool> = const { Cell::new(false) };
+    // 前回シグナルが届いたときの切り替え回数
+    static LAST_SWITCHES: Cell<u64> = const { Cell::new(0) };
+}
+
+// ドロップされるまでプリエンプションを止めるガード
+pub(crate) struct PreemptGuard {
+    prev: bool,
+}
+
+impl Drop for PreemptGuard {
+    fn drop(&mut self) {
+        DISABLED.with(|disabled| disabled.set(self.prev));
+    }
+}
+
+// 返されたガードがドロップされるまで、現在のスレッドを切り替えないようにする
+// NOTE: ガードを持ったままブロックしても、切り替え先のスレッドでは切り替え先の状態に戻る
+pub(crate) fn disable() -> PreemptGuard {
+    PreemptGuard {
+        prev: DISABLED.with(|disabled| disabled.replace(true)),
+    }
+}
+
+// fを実行している間はプリエンプションで切り替えない
+// NOTE: 標準出力などロックを使う標準ライブラリの処理は、途中で切り替わると他のスレッドから使えなくなるので囲む
+pub fn without_preemption<F, R>(f: F) -> R
+where
+    F: FnOnce() -> R,
+{
+    let _guard = disable();
+    f()
+}
+
+// 新しいスレッドの開始時に呼ぶ
+pub(crate) fn enable() {
+    DISABLED.with(|disabled| disabled.set(false));
+}
+
+// SIGALRMのハンドラを登録し、time_sliceごとにシグナルを送るタイマーを開始する
+pub(crate) fn start(time_slice: Duration) -> io::Result<()> {
+    let action = SigAction {
+        sa_handler: handle_alarm as extern "C" fn(c_int) as usize,
+        sa_mask: Default::default(),
+        // NOTE: ハンドラの中で切り替えるので、ハンドラ実行中もSIGALRMを受け取れるようにする
+        sa_flags: SA_RESTART | SA_NODEFER,
+        #[cfg(target_os = "linux")]
+        sa_restorer: 0,
+    };
+    if unsafe { sigaction(SIGALRM, &action, std::ptr::null_mut()) } < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    set_timer(time_slice)
+}
+
+// タイマーを止める
+pub(crate) fn stop() -> io::Result<()> {
+    set_timer(Duration::ZERO)
+}
+
+fn set_timer(interval: Duration) -> io::Result<()> {
+    let timeval = || Timeval {
+        tv_sec: interval.as_secs() as c_long,
+        tv_usec: interval.subsec_micros() as _,
+    };
+    let timer = Itimerval {
+        it_interval: timeval(),
+        it_value: timeval(),
+    };
+    if unsafe { setitimer(ITIMER_REAL, &timer, std::ptr::null_mut()) } < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+extern "C" fn handle_alarm(_signum: c_int) {
+    if DISABLED.with(|disabled| disabled.get()) {
+        return;
+    }
+    let rt = CURRENT.with(|current| current.get());
+    if rt.is_null() {
+        return;
+    }
+    unsafe {
+        // 前回のシグナルから一度も切り替わっていない場合のみ、時間切れとして切り替える
+        let switches = (*rt).switches;
+        if LAST_SWITCHES.with(|last| last.replace(switches)) != switches {
+            return;
+        }
+        // NOTE: 割り込まれた処理から見てerrnoが変わらないように退避しておく
+        let errno = *errno_location();
+        (*rt).preempted = true;
+        Runtime::t_yield(rt);
+        *errno_location() = errno;
+    }
+}
@@ -0,0 +1,397 @@
+// 子プロセスの終了や出力を、現在のスレッドだけをブロックして待つ
+// NOTE: std::process::Child::waitやパイプの読み書きはOSスレッドごとブロックして、すべてのグリーンスレッドが止まる
+//       Linuxではpidfd(子プロセスが終わると読み込めるようになるfd)をリアクターで待ち、
+//       パイプはノンブロッキングにしてnetと同じくリアクターで待つ
+//       pidfdを使えない場合(Linux以外や5.3より前のカーネル)は、スリープしながら間隔を広げて終了を確かめる
+use std::ffi::OsStr;
+use std::io::{self, Read, Write};
+use std::os::raw::c_int;
+#[cfg(target_os = "linux")]
+use std::os::raw::c_long;
+use std::os::unix::io::{AsRawFd, FromRawFd, OwnedFd, RawFd};
+use std::path::Path;
+use std::process;
+use std::time::Duration;
+
+pub use std::process::{ExitStatus, Output, Stdio};
+
+use crate::net::retry;
+use crate::reactor::Interest;
+
+const F_GETFL: c_int = 3;
+const F_SETFL: c_int = 4;
+#[cfg(target_os = "linux")]
+const O_NONBLOCK: c_int = 0o4000;
+#[cfg(not(target_os = "linux"))]
+const O_NONBLOCK: c_int = 0x0004;
+
+#[cfg(target_os = "linux")]
+const SYS_PIDFD_OPEN: c_long = 434;
+
+// pidfdを使えない場合に終了を確かめる間隔の最初と最大
+const MIN_POLL_INTERVAL: Duration = Duration::from_millis(1);
+const MAX_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+extern "C" {
+    fn fcntl(fd: c_int, cmd: c_int, ...) -> c_int;
+    #[cfg(target_os = "linux")]
+    fn syscall(num: c_long, ...) -> c_long;
+}
+
+fn set_nonblocking(fd: RawFd) -> io::Result<()> {
+    let flags = unsafe { fcntl(fd, F_GETFL) };
+    if flags < 0 || unsafe { fcntl(fd, F_SETFL, flags | O_NONBLOCK) } < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+// pidの子プロセスのpidfdを開く
+// 開けない場合はNoneを返す
+#[cfg(target_os = "linux")]
+fn pidfd_open(pid: u32) -> Option<OwnedFd> {
+    let fd = unsafe { syscall(SYS_PIDFD_OPEN, pid as c_int, 0 as c_int) };
+    if fd < 0 {
+        return None;
+    }
+    Some(unsafe { OwnedFd::from_raw_fd(fd as RawFd) })
+}
+
+#[cfg(not(target_os = "linux"))]
+fn pidfd_open(_pid: u32) -> Option<OwnedFd> {
+    None
+}
+
+// std::process::Commandと同じように使えるコマンド
+pub struct Command {
+    inner: process::Command,
+    // 標準入出力を指定したかどうか
+    // NOTE: outputで、指定していないものだけをパイプなどにする
+    stdin_set: bool,
+    stdout_set: bool,
+    stderr_set: bool,
+}
+
+impl Command {
+    pub fn new<S: AsRef<OsStr>>(program: S) -> Command {
+        Command {
+            inner: process::Command::new(program),
+            stdin_set: false,
+            stdout_set: false,
+            stderr_set: false,
+        }
+    }
+
+    pub fn arg<S: AsRef<OsStr>>(&mut self, arg: S) -> &mut Command {
+        self.inner.arg(arg);
+        self
+    }
+
+    pub fn args<I, S>(&mut self, args: I) -> &mut Command
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<OsStr>,
+    {
+        self.inner.args(args);
+        self
+    }
+
+    pub fn env<K, V>(&mut self, key: K, val: V) -> &mut Command
+    where
+        K: AsRef<OsStr>,
+        V: AsRef<OsStr>,
+    {
+        self.inner.env(key, val);
+        self
+    }
+
+    pub fn env_remove<K: AsRef<OsStr>>(&mut self, key: K) -> &mut Command {
+        self.inner.env_remove(key);
+        self
+    }
+
+    pub fn env_clear(&mut self) -> &mut Command {
+        self.inner.env_clear();
+        self
+    }
+
+    pub fn current_dir<P: AsRef<Path>>(&mut self, dir: P) -> &mut Command {
+        self.inner.current_dir(dir);
+        self
+    }
+
+    pub fn stdin<T: Into<Stdio>>(&mut self, cfg: T) -> &mut Command {
+        self.inner.stdin(cfg);
+        self.stdin_set = true;
+        self
+    }
+
+    pub fn stdout<T: Into<Stdio>>(&mut self, cfg: T) -> &mut Command {
+        self.inner.stdout(cfg);
+        self.stdout_set = true;
+        self
+    }
+
+    pub fn stderr<T: Into<Stdio>>(&mut self, cfg: T) -> &mut Command {
+        self.inner.stderr(cfg);
+        self.stderr_set = true;
+        self
+    }
+
+    // 子プロセスを起動する
+    // NOTE: 起動(fork/exec)そのものはすぐに終わるので、OSスレッドごと待つ
+    pub fn spawn(&mut self) -> io::Result<Child> {
+        Child::from_std(self.inner.spawn()?)
+    }
+
+    // 子プロセスを起動し、終わるまで現在のスレッドをブロックして終了ステータスを返す
+    pub fn status(&mut self) -> io::Result<ExitStatus> {
+        self.spawn()?.wait()
+    }
+
+    // 子プロセスを起動し、終わるまで現在のスレッドをブロックして、終了ステータスと出力を返す
+    // NOTE: std::process::Command::outputと同じく、指定していなければ標準入力は/dev/nullにし、
+    //       標準出力と標準エラー出力はパイプにして読み込む
+    pub fn output(&mut self) -> io::Result<Output> {
+        if !self.stdin_set {
+            self.inner.stdin(Stdio::null());
+        }
+        if !self.stdout_set {
+            self.inner.stdout(Stdio::piped());
+        }
+        if !self.stderr_set {
+            self.inner.stderr(Stdio::piped());
+        }
+        let child = self.inner.spawn();
+        // NOTE: 指定していなかったものは、spawnやstatusの既定と同じく親から引き継ぐように戻す
+        if !self.stdin_set {
+            self.inner.stdin(Stdio::inherit());
+        }
+        if !self.stdout_set {
+            self.inner.stdout(Stdio::inherit());
+        }
+        if !self.stderr_set {
+            self.inner.stderr(Stdio::inherit());
+        }
+        Child::from_std(child?)?.wait_with_output()
+    }
+}
+
+// 起動した子プロセス
+pub struct Child {
+    inner: process::Child,
+    // NOTE: Noneの場合はスリープしながら終了を確かめる
+    pidfd: Option<OwnedFd>,
+    pub stdin: Option<ChildStdin>,
+    pub stdout: Option<ChildStdout>,
+    pub stderr: Option<ChildStderr>,
+}
+
+impl Child {
+    fn from_std(mut inner: process::Child) -> io::Result<Child> {
+        let pidfd = pidfd_open(inner.id());
+        let stdin = inner.stdin.take().map(ChildStdin::from_std).transpose()?;
+        let stdout = inner.stdout.take().map(ChildStdout::from_std).transpose()?;
+        let stderr = inner.stderr.take().map(ChildStderr::from_std).transpose()?;
+        Ok(Child {
+            inner,
+            pidfd,
+            stdin,
+            stdout,
+            stderr,
+        })
+    }
+
+    pub fn id(&self) -> u32 {
+        self.inner.id()
+    }
+
+    pub fn kill(&mut self) -> io::Result<()> {
+        self.inner.kill()
+    }
+
+    // 子プロセスが終わっていれば終了ステータスを返し、終わっていなければNoneを返す
+    pub fn try_wait(&mut self) -> io::Result<Option<ExitStatus>> {
+        self.inner.try_wait()
+    }
+
+    // 子プロセスが終わるまで現在のスレッドをブロックし、終了ステータスを返す
+    // NOTE: std::process::Child::waitと同じく、子プロセスが入力を待って止まらないように先に標準入力を閉じる
+    //       キャンセルされた場合はErrを返し、子プロセスはそのまま動き続ける
+    pub fn wait(&mut self) -> io::Result<ExitStatus> {
+        drop(self.stdin.take());
+        let mut interval = MIN_POLL_INTERVAL;
+        loop {
+            if let Some(status) = self.inner.try_wait()? {
+                return Ok(status);
+            }
+            match &self.pidfd {
+                Some(pidfd) => crate::wait_io(pidfd.as_raw_fd(), Interest::Readable)?,
+                None => {
+                    crate::sleep(interval).map_err(io::Error::other)?;
+                    interval = (interval * 2).min(MAX_POLL_INTERVAL);
+                }
+            }
+        }
+    }
+
+    // 標準出力と標準エラー出力を最後まで読み込み、子プロセスが終わるまで現在のスレッドをブロックする
+    // NOTE: 片方を読み終わるまでもう片方を読まないと、子プロセスが読まれない方のパイプに書き込めずに止まるので、
+    //       両方を読めるようになった方から読む
+    pub fn wait_with_output(mut self) -> io::Result<Output> {
+        drop(self.stdin.take());
+        let mut stdout = Vec::new();
+        let mut stderr = Vec::new();
+        let mut out = self.stdout.take();
+        let mut err = self.stderr.take();
+        loop {
+            let mut waiting = Vec::new();
+            if let Some(pipe) = &mut out {
+                match read_available(&mut pipe.inner, &mut stdout)? {
+                    true => waiting.push((pipe.as_raw_fd(), Interest::Readable)),
+                    false => out = None,
+                }
+            }
+            if let Some(pipe) = &mut err {
+                match read_available(&mut pipe.inner, &mut stderr)? {
+                    true => waiting.push((pipe.as_raw_fd(), Interest::Readable)),
+                    false => err = None,
+                }
+            }
+            if waiting.is_empty() {
+                break;
+            }
+            crate::wait_io_any(&waiting)?;
+        }
+        let status = self.wait()?;
+        Ok(Output {
+            status,
+            stdout,
+            stderr,
+        })
+    }
+}
+
+impl Drop for Child {
+    fn drop(&mut self) {
+        // pidfdが閉じられる前に監視対象から外す
+        if let Some(pidfd) = &self.pidfd {
+            crate::deregister_io(pidfd.as_raw_fd());
+        }
+    }
+}
+
+// ノンブロッキングのパイプから今読めるだけ読んでbufに足す
+// まだ閉じられていなければtrueを、閉じられていればfalseを返す
+fn read_available(mut pipe: impl Read, buf: &mut Vec<u8>) -> io::Result<bool> {
+    let mut chunk = [0u8; 4096];
+    loop {
+        match pipe.read(&mut chunk) {
+            Ok(0) => return Ok(false),
+            Ok(n) => buf.extend_from_slice(&chunk[..n]),
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock => return Ok(true),
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+// 子プロセスの標準入力
+// NOTE: パイプが満杯の場合は空きができるまで現在のスレッドだけをブロックする
+pub struct ChildStdin {
+    inner: process::ChildStdin,
+}
+
+impl ChildStdin {
+    fn from_std(inner: process::ChildStdin) -> io::Result<ChildStdin> {
+        set_nonblocking(inner.as_raw_fd())?;
+        Ok(ChildStdin { inner })
+    }
+}
+
+impl Write for ChildStdin {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let fd = self.as_raw_fd();
+        retry(fd, Interest::Writable, || self.inner.write(buf))
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl AsRawFd for ChildStdin {
+    fn as_raw_fd(&self) -> RawFd {
+        self.inner.as_raw_fd()
+    }
+}
+
+impl Drop for ChildStdin {
+    fn drop(&mut self) {
+        crate::deregister_io(self.as_raw_fd());
+    }
+}
+
+// 子プロセスの標準出力
+// NOTE: 読み込めるデータがない場合は届くまで現在のスレッドだけをブロックする
+pub struct ChildStdout {
+    inner: process::ChildStdout,
+}
+
+impl ChildStdout {
+    fn from_std(inner: process::ChildStdout) -> io::Result<ChildStdout> {
+        set_nonblocking(inner.as_raw_fd())?;
+        Ok(ChildStdout { inner })
+    }
+}
+
+impl Read for ChildStdout {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let fd = self.as_raw_fd();
+        retry(fd, Interest::Readable, || self.inner.read(buf))
+    }
+}
+
+impl AsRawFd for ChildStdout {
+    fn as_raw_fd(&self) -> RawFd {
+        self.inner.as_raw_fd()
+    }
+}
+
+impl Drop for ChildStdout {
+    fn drop(&mut self) {
+        crate::deregister_io(self.as_raw_fd());
+    }
+}
+
+// 子プロセスの標準エラー出力
+// NOTE: 読み込めるデータがない場合は届くまで現在のスレッドだけをブロックする
+pub struct ChildStderr {
+    inner: process::ChildStderr,
+}
+
+impl ChildStderr {
+    fn from_std(inner: process::ChildStderr) -> io::Result<ChildStderr> {
+        set_nonblocking(inner.as_raw_fd())?;
+        Ok(ChildStderr { inner })
+    }
+}
+
+impl Read for ChildStderr {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let fd = self.as_raw_fd();
+        retry(fd, Interest::Readable, || self.inner.read(buf))
+    }
+}
+
+impl AsRawFd for ChildStderr {
+    fn as_raw_fd(&self) -> RawFd {
+        self.inner.as_raw_fd()
+    }
+}
+
+impl Drop for ChildStderr {
+    fn drop(&mut self) {
+        crate::deregister_io(self.as_raw_fd());
+    }
+}
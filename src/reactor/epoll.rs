@@ -0,0 +1,135 @@
+// Linux向けのepollを使ったI/Oイベントの監視
+use std::io;
+use std::os::raw::c_int;
+use std::os::unix::io::RawFd;
+use std::time::Duration;
+
+use super::Event;
+
+const EPOLL_CLOEXEC: c_int = 0o2000000;
+const EPOLL_CTL_ADD: c_int = 1;
+const EPOLL_CTL_DEL: c_int = 2;
+const EPOLL_CTL_MOD: c_int = 3;
+
+const EPOLLIN: u32 = 0x001;
+const EPOLLOUT: u32 = 0x004;
+const EPOLLERR: u32 = 0x008;
+const EPOLLHUP: u32 = 0x010;
+const EPOLLRDHUP: u32 = 0x2000;
+const EPOLLONESHOT: u32 = 1 << 30;
+
+const EINTR: i32 = 4;
+const MAX_EVENTS: usize = 64;
+
+// NOTE: x86_64ではepoll_eventはpackedな構造体になっている
+#[repr(C)]
+#[cfg_attr(target_arch = "x86_64", repr(packed))]
+#[derive(Clone, Copy)]
+struct EpollEvent {
+    events: u32,
+    data: u64,
+}
+
+extern "C" {
+    fn epoll_create1(flags: c_int) -> c_int;
+    fn epoll_ctl(epfd: c_int, op: c_int, fd: c_int, event: *mut EpollEvent) -> c_int;
+    fn epoll_wait(epfd: c_int, events: *mut EpollEvent, maxevents: c_int, timeout: c_int) -> c_int;
+    fn close(fd: c_int) -> c_int;
+}
+
+pub(super) struct Poller {
+    epfd: RawFd,
+}
+
+impl Poller {
+    pub(super) fn new() -> io::Result<Self> {
+        let epfd = unsafe { epoll_create1(EPOLL_CLOEXEC) };
+        if epfd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(Poller { epfd })
+    }
+
+    // fdの監視内容を設定する
+    // NOTE: EPOLLONESHOTなので一度イベントが届いたら再度armするまで通知されない
+    pub(super) fn arm(
+        &self,
+        fd: RawFd,
+        readable: bool,
+        writable: bool,
+        registered: bool,
+    ) -> io::Result<()> {
+        let mut events = EPOLLONESHOT | EPOLLRDHUP;
+        if readable {
+            events |= EPOLLIN;
+        }
+        if writable {
+            events |= EPOLLOUT;
+        }
+        let mut event = EpollEvent {
+            events,
+            data: fd as u64,
+        };
+        let op = if registered {
+            EPOLL_CTL_MOD
+        } else {
+            EPOLL_CTL_ADD
+        };
+        if unsafe { epoll_ctl(self.epfd, op, fd, &mut event) } < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+
+    pub(super) fn remove(&self, fd: RawFd) {
+        // NOTE: fdがすでに閉じられている場合などは失敗するが無視する
+        unsafe {
+            epoll_ctl(self.epfd, EPOLL_CTL_DEL, fd, std::ptr::null_mut());
+        }
+    }
+
+    // イベントが届くかtimeoutが経過するまで待つ
+    // timeoutがNoneの場合はイベントが届くまで待ち続ける
+    pub(super) fn wait(
+        &self,
+        events: &mut Vec<Event>,
+        timeout: Option<Duration>,
+    ) -> io::Result<()> {
+        let timeout = match timeout {
+            // NOTE: ミリ秒未満を切り捨てると期限前に起きて空回りするので切り上げる
+            Some(timeout) => {
+                let ms = timeout.as_nanos().div_ceil(1_000_000);
+                ms.min(c_int::MAX as u128) as c_int
+            }
+            None => -1,
+        };
+        let mut buf = [EpollEvent { events: 0, data: 0 }; MAX_EVENTS];
+        let n = unsafe { epoll_wait(self.epfd, buf.as_mut_ptr(), MAX_EVENTS as c_int, timeout) };
+        if n < 0 {
+            let err = io::Error::last_os_error();
+            // シグナルで中断された場合はイベントなしとして扱う
+            if err.raw_os_error() == Some(EINTR) {
+                return Ok(());
+            }
+            return Err(err);
+        }
+        for event in &buf[..n as usize] {
+            let flags = event.events;
+            let closed = flags & (EPOLLERR | EPOLLHUP | EPOLLRDHUP) != 0;
+            events.push(Event {
+                fd: event.data as RawFd,
+                readable: flags & EPOLLIN != 0 || closed,
+                writable: flags & EPOLLOUT != 0 || closed,
+            });
+        }
+        Ok(())
+    }
+}
+
+impl Drop for Poller {
+    fn drop(&mut self) {
+        unsafe {
+            close(self.epfd);
+        }
+    }
+}
@@ -0,0 +1,175 @@
+// macOS/BSD向けのkqueueを使ったI/Oイベントの監視
+use std::io;
+use std::os::raw::{c_int, c_long, c_void};
+use std::os::unix::io::RawFd;
+use std::time::Duration;
+
+use super::Event;
+
+const EVFILT_READ: i16 = -1;
+const EVFILT_WRITE: i16 = -2;
+const EV_ADD: u16 = 0x0001;
+const EV_DELETE: u16 = 0x0002;
+const EV_ONESHOT: u16 = 0x0010;
+const EV_ERROR: u16 = 0x4000;
+const EV_EOF: u16 = 0x8000;
+
+const EINTR: i32 = 4;
+const MAX_EVENTS: usize = 64;
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct Kevent {
+    ident: usize,
+    filter: i16,
+    flags: u16,
+    fflags: u32,
+    data: isize,
+    udata: *mut c_void,
+}
+
+#[repr(C)]
+struct Timespec {
+    tv_sec: i64,
+    tv_nsec: c_long,
+}
+
+extern "C" {
+    fn kqueue() -> c_int;
+    fn kevent(
+        kq: c_int,
+        changelist: *const Kevent,
+        nchanges: c_int,
+        eventlist: *mut Kevent,
+        nevents: c_int,
+        timeout: *const Timespec,
+    ) -> c_int;
+    fn close(fd: c_int) -> c_int;
+}
+
+fn kevent_for(fd: RawFd, filter: i16, flags: u16) -> Kevent {
+    Kevent {
+        ident: fd as usize,
+        filter,
+        flags,
+        fflags: 0,
+        data: 0,
+        udata: std::ptr::null_mut(),
+    }
+}
+
+pub(super) struct Poller {
+    kq: RawFd,
+}
+
+impl Poller {
+    pub(super) fn new() -> io::Result<Self> {
+        let kq = unsafe { kqueue() };
+        if kq < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(Poller { kq })
+    }
+
+    // fdの監視内容を設定する
+    // NOTE: EV_ONESHOTなので一度イベントが届いたら再度armするまで通知されない
+    pub(super) fn arm(
+        &self,
+        fd: RawFd,
+        readable: bool,
+        writable: bool,
+        _registered: bool,
+    ) -> io::Result<()> {
+        let mut changes = Vec::with_capacity(2);
+        if readable {
+            changes.push(kevent_for(fd, EVFILT_READ, EV_ADD | EV_ONESHOT));
+        }
+        if writable {
+            changes.push(kevent_for(fd, EVFILT_WRITE, EV_ADD | EV_ONESHOT));
+        }
+        let ret = unsafe {
+            kevent(
+                self.kq,
+                changes.as_ptr(),
+                changes.len() as c_int,
+                std::ptr::null_mut(),
+                0,
+                std::ptr::null(),
+            )
+        };
+        if ret < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+
+    pub(super) fn remove(&self, fd: RawFd) {
+        // NOTE: 登録されていないフィルタの削除は失敗するが無視する
+        for filter in [EVFILT_READ, EVFILT_WRITE] {
+            let change = kevent_for(fd, filter, EV_DELETE);
+            unsafe {
+                kevent(
+                    self.kq,
+                    &change,
+                    1,
+                    std::ptr::null_mut(),
+                    0,
+                    std::ptr::null(),
+                );
+            }
+        }
+    }
+
+    // イベントが届くかtimeoutが経過するまで待つ
+    // timeoutがNoneの場合はイベントが届くまで待ち続ける
+    pub(super) fn wait(
+        &self,
+        events: &mut Vec<Event>,
+        timeout: Option<Duration>,
+    ) -> io::Result<()> {
+        let timeout = timeout.map(|timeout| Timespec {
+            tv_sec: timeout.as_secs() as i64,
+            tv_nsec: timeout.subsec_nanos() as c_long,
+        });
+        let timeout_ptr = match &timeout {
+            Some(timeout) => timeout as *const Timespec,
+            None => std::ptr::null(),
+        };
+        let mut buf = [kevent_for(0, 0, 0); MAX_EVENTS];
+        let n = unsafe {
+            kevent(
+                self.kq,
+                std::ptr::null(),
+                0,
+                buf.as_mut_ptr(),
+                MAX_EVENTS as c_int,
+                timeout_ptr,
+            )
+        };
+        if n < 0 {
+            let err = io::Error::last_os_error();
+            // シグナルで中断された場合はイベントなしとして扱う
+            if err.raw_os_error() == Some(EINTR) {
+                return Ok(());
+            }
+            return Err(err);
+        }
+        for event in &buf[..n as usize] {
+            let closed = event.flags & (EV_ERROR | EV_EOF) != 0;
+            events.push(Event {
+                fd: event.ident as RawFd,
+                readable: event.filter == EVFILT_READ || closed,
+                writable: event.filter == EVFILT_WRITE || closed,
+            });
+        }
+        Ok(())
+    }
+}
+
+impl Drop for Poller {
+    fn drop(&mut self) {
+        unsafe {
+            close(self.kq);
+        }
+    }
+}
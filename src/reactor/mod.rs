@@ -0,0 +1,181 @@
+// ノンブロッキングI/Oの完了を待つスレッドを管理するリアクター
+// NOTE: fdの準備ができるまでスレッドをブロックし、イベントが届いたら再開可能にする
+use std::collections::HashMap;
+use std::io;
+use std::os::unix::io::RawFd;
+use std::time::Duration;
+
+#[cfg(target_os = "linux")]
+mod epoll;
+#[cfg(target_os = "linux")]
+use epoll::Poller;
+
+#[cfg(any(
+    target_os = "macos",
+    target_os = "ios",
+    target_os = "freebsd",
+    target_os = "netbsd",
+    target_os = "openbsd",
+    target_os = "dragonfly"
+))]
+mod kqueue;
+#[cfg(any(
+    target_os = "macos",
+    target_os = "ios",
+    target_os = "freebsd",
+    target_os = "netbsd",
+    target_os = "openbsd",
+    target_os = "dragonfly"
+))]
+use kqueue::Poller;
+
+// I/Oの待ち方
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub(crate) enum Interest {
+    Readable,
+    Writable,
+}
+
+// Pollerから届いたイベント
+struct Event {
+    fd: RawFd,
+    readable: bool,
+    writable: bool,
+}
+
+// fdごとの待ちスレッド
+#[derive(Default)]
+struct Waiters {
+    read: Vec<usize>,
+    write: Vec<usize>,
+    // Pollerに登録済みかどうか
+    registered: bool,
+}
+
+pub(crate) struct Reactor {
+    // NOTE: I/Oを使わないプログラムでfdを作らないように、最初の登録時に作る
+    poller: Option<Poller>,
+    waiters: HashMap<RawFd, Waiters>,
+    events: Vec<Event>,
+}
+
+impl Reactor {
+    pub(crate) fn new() -> Self {
+        Reactor {
+            poller: None,
+            waiters: HashMap::new(),
+            events: Vec::new(),
+        }
+    }
+
+    // I/O待ちのスレッドがいるかどうか
+    pub(crate) fn has_waiters(&self) -> bool {
+        self.waiters
+            .values()
+            .any(|w| !w.read.is_empty() || !w.write.is_empty())
+    }
+
+    // I/O待ちのスレッドのIDを返す
+    // NOTE: 読み込みと書き込みの両方を待っているスレッドは2回返す
+    #[cfg(feature = "metrics")]
+    pub(crate) fn waiters(&self) -> impl Iterator<Item = usize> + '_ {
+        self.waiters
+            .values()
+            .flat_map(|w| w.read.iter().chain(w.write.iter()).copied())
+    }
+
+    // fdの準備ができたらidのスレッドを起こすように登録する
+    pub(crate) fn register(&mut self, fd: RawFd, interest: Interest, id: usize) -> io::Result<()> {
+        if self.poller.is_none() {
+            self.poller = Some(Poller::new()?);
+        }
+        let poller = self.poller.as_ref().unwrap();
+        let waiters = self.waiters.entry(fd).or_default();
+        match interest {
+            Interest::Readable => waiters.read.push(id),
+            Interest::Writable => waiters.write.push(id),
+        }
+        let armed = poller.arm(
+            fd,
+            !waiters.read.is_empty(),
+            !waiters.write.is_empty(),
+            waiters.registered,
+        );
+        if let Err(e) = armed {
+            // 登録に失敗した場合は待ちスレッドから取り除く
+            match interest {
+                Interest::Readable => waiters.read.pop(),
+                Interest::Writable => waiters.write.pop(),
+            };
+            return Err(e);
+        }
+        waiters.registered = true;
+        Ok(())
+    }
+
+    // idのスレッドをfdの待ちスレッドから取り除く
+    // 待ちスレッドにいなかった場合(イベントが届いて取り出された後)はfalseを返す
+    // NOTE: Pollerの登録はそのままにしておき、次にイベントが届いたときに待ちスレッドがいなければ何もしない
+    pub(crate) fn cancel(&mut self, fd: RawFd, interest: Interest, id: usize) -> bool {
+        let waiters = match self.waiters.get_mut(&fd) {
+            Some(waiters) => waiters,
+            None => return false,
+        };
+        let list = match interest {
+            Interest::Readable => &mut waiters.read,
+            Interest::Writable => &mut waiters.write,
+        };
+        let len = list.len();
+        list.retain(|w| *w != id);
+        list.len() != len
+    }
+
+    // fdを監視対象から外す
+    // NOTE: fdを閉じる前に呼ぶこと
+    pub(crate) fn deregister(&mut self, fd: RawFd) {
+        if let Some(waiters) = self.waiters.remove(&fd) {
+            if waiters.registered {
+                if let Some(poller) = &self.poller {
+                    poller.remove(fd);
+                }
+            }
+        }
+    }
+
+    // イベントが届くかtimeoutが経過するまで待ち、準備ができたfdを待っているスレッドのIDをreadyに積む
+    pub(crate) fn poll(
+        &mut self,
+        timeout: Option<Duration>,
+        ready: &mut Vec<usize>,
+    ) -> io::Result<()> {
+        let poller = match &self.poller {
+            Some(poller) => poller,
+            None => return Ok(()),
+        };
+        self.events.clear();
+        poller.wait(&mut self.events, timeout)?;
+
+        for event in &self.events {
+            let waiters = match self.waiters.get_mut(&event.fd) {
+                Some(waiters) => waiters,
+                None => continue,
+            };
+            if event.readable {
+                ready.append(&mut waiters.read);
+            }
+            if event.writable {
+                ready.append(&mut waiters.write);
+            }
+            // 一度イベントが届くと監視が無効になるので、まだ待っているスレッドがいれば再登録する
+            if !waiters.read.is_empty() || !waiters.write.is_empty() {
+                poller.arm(
+                    event.fd,
+                    !waiters.read.is_empty(),
+                    !waiters.write.is_empty(),
+                    waiters.registered,
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
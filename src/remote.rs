@@ -0,0 +1,320 @@
+// 他のOSスレッドからランタイムにタスクを渡したり、スレッドを起こしたりするためのハンドル
+// NOTE: Runtimeは!Sendで他のOSスレッドから触れないので、渡したいものを共有のキュー(インジェクター)に積み、
+//       ソケットに1byte書いてリアクターを起こす
+//       ランタイムは切り替えのたびにキューを確認し、積まれたものを取り出して処理する
+//       RemoteHandleが1つでも残っている間は、他のスレッドがすべて終わってもrunは戻らずに待ち続ける
+use std::collections::VecDeque;
+use std::error::Error;
+use std::fmt;
+use std::io::{self, Read, Write};
+use std::os::unix::io::{AsRawFd, RawFd};
+use std::os::unix::net::UnixStream;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+use crate::executor::ThreadWaker;
+use crate::reactor::Interest;
+use crate::{Context, Runtime, State, ThreadId};
+
+// インジェクターのソケットを待っているのがスレッドではなくインジェクターであることを表すID
+pub(crate) const REMOTE_WAKER: usize = usize::MAX - 2;
+
+type Task = Box<dyn FnOnce() + Send>;
+
+enum Remote {
+    Spawn(Task),
+    Wake(ThreadId),
+    WakeFuture(Arc<ThreadWaker>),
+}
+
+// RuntimeとRemoteHandleで共有する部分
+struct Injector {
+    queue: Mutex<VecDeque<Remote>>,
+    // キューに積まれたものがあるかどうか
+    // NOTE: 切り替えのたびにロックを取らないように、積んだ側が立ててランタイムが下ろす
+    pending: AtomicBool,
+    // Runtimeがドロップされたかどうか
+    closed: AtomicBool,
+    // 残っているRemoteHandleの数
+    // NOTE: Arcの参照の数で数えると、最後のハンドルが知らせてから減るまでの間にランタイムが確かめて見逃すので、別に数える
+    handles: AtomicUsize,
+    // NOTE: 書き込み側はRemoteHandleが、読み込み側はリアクターが使う
+    notify: UnixStream,
+    wakeup: UnixStream,
+}
+
+impl Injector {
+    fn push(&self, remote: Remote) -> Result<(), RuntimeClosed> {
+        {
+            let mut queue = self.queue.lock().unwrap();
+            // NOTE: ロックを持ったまま確かめ、閉じた後に積んだものが取り残されないようにする
+            if self.closed.load(Ordering::Acquire) {
+                return Err(RuntimeClosed);
+            }
+            queue.push_back(remote);
+        }
+        self.pending.store(true, Ordering::Release);
+        self.notify();
+        Ok(())
+    }
+
+    fn notify(&self) {
+        let _ = (&self.notify).write(&[1]);
+    }
+}
+
+// 他のOSスレッドからランタイムを操作するためのハンドル
+// NOTE: Send + Syncなので、クローンして他のOSスレッドに渡してよい
+pub struct RemoteHandle {
+    injector: Arc<Injector>,
+}
+
+impl RemoteHandle {
+    fn new(injector: Arc<Injector>) -> Self {
+        injector.handles.fetch_add(1, Ordering::AcqRel);
+        RemoteHandle { injector }
+    }
+
+    // ランタイムでfを実行するスレッドを生成する
+    // ランタイムがドロップされていた場合はErr(RuntimeClosed)を返す
+    // NOTE: 生成は次にランタイムがスレッドを切り替えるときに行い、利用可能なスレッドがなければ空くまで待つ
+    //       結果を受け取りたい場合は、fの中からstd::sync::mpscなどで送る
+    pub fn spawn<F>(&self, f: F) -> Result<(), RuntimeClosed>
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        self.injector.push(Remote::Spawn(Box::new(f)))
+    }
+
+    // parkで止まっているidのスレッドを再開可能にする
+    // 止まっていない場合は、ThreadHandle::unparkと同じく次のparkがすぐに戻るようにトークンを残す
+    // NOTE: スレッドがすでに終わっている場合は何もしない
+    pub fn wake(&self, id: ThreadId) -> Result<(), RuntimeClosed> {
+        self.injector.push(Remote::Wake(id))
+    }
+}
+
+// ThreadWakerが他のOSスレッドからwakeされたときに、ランタイムに知らせるためのもの
+// NOTE: RemoteHandleと違って持っているだけでは数えないので、runが戻らなくなることはない
+//       ThreadWaker::waitで待っている間だけRuntime::hold_remote_wakerで数え、リアクターに登録しておく
+pub(crate) struct RemoteWaker {
+    injector: Arc<Injector>,
+}
+
+impl RemoteWaker {
+    // ランタイムのOSスレッドでwakerを起こすように頼む
+    // NOTE: ランタイムがドロップされていたら起こすスレッドもないので、何もしない
+    pub(crate) fn wake(&self, waker: Arc<ThreadWaker>) {
+        let _ = self.injector.push(Remote::WakeFuture(waker));
+    }
+}
+
+// Runtime::hold_remote_wakerで数えた分を、ドロップしたときに戻す
+pub(crate) struct RemoteWakerHold<'a> {
+    injector: &'a Injector,
+}
+
+// NOTE: 最後の1つなら、RemoteHandleと同じくリアクターを起こしてrunが戻れるようにする
+impl Drop for RemoteWakerHold<'_> {
+    fn drop(&mut self) {
+        if self.injector.handles.fetch_sub(1, Ordering::AcqRel) == 1 {
+            self.injector.notify();
+        }
+    }
+}
+
+impl Clone for RemoteHandle {
+    fn clone(&self) -> Self {
+        RemoteHandle::new(self.injector.clone())
+    }
+}
+
+// 最後のRemoteHandleがドロップされたら、待っているランタイムを起こしてrunが戻れるようにする
+impl Drop for RemoteHandle {
+    fn drop(&mut self) {
+        if self.injector.handles.fetch_sub(1, Ordering::AcqRel) == 1 {
+            self.injector.notify();
+        }
+    }
+}
+
+impl fmt::Debug for RemoteHandle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RemoteHandle").finish_non_exhaustive()
+    }
+}
+
+// ランタイムがドロップされていて、渡せなかった
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct RuntimeClosed;
+
+impl fmt::Display for RuntimeClosed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        "the runtime has been dropped".fmt(f)
+    }
+}
+
+impl Error for RuntimeClosed {}
+
+pub(crate) struct Remotes {
+    injector: Arc<Injector>,
+    // 読み込み側をリアクターに登録しているかどうか
+    armed: bool,
+    // 利用可能なスレッドがなくて、まだ生成できていないタスク
+    waiting: VecDeque<Task>,
+}
+
+impl Remotes {
+    fn new() -> io::Result<Self> {
+        let (notify, wakeup) = UnixStream::pair()?;
+        notify.set_nonblocking(true)?;
+        wakeup.set_nonblocking(true)?;
+        Ok(Remotes {
+            injector: Arc::new(Injector {
+                queue: Mutex::new(VecDeque::new()),
+                pending: AtomicBool::new(false),
+                closed: AtomicBool::new(false),
+                handles: AtomicUsize::new(0),
+                notify,
+                wakeup,
+            }),
+            armed: false,
+            waiting: VecDeque::new(),
+        })
+    }
+
+    fn wakeup_fd(&self) -> RawFd {
+        self.injector.wakeup.as_raw_fd()
+    }
+
+    // RemoteHandleが残っているかどうか
+    fn has_handles(&self) -> bool {
+        self.injector.handles.load(Ordering::Acquire) > 0
+    }
+}
+
+impl Drop for Remotes {
+    fn drop(&mut self) {
+        let _queue = self.injector.queue.lock().unwrap();
+        self.injector.closed.store(true, Ordering::Release);
+    }
+}
+
+impl Runtime {
+    // 他のOSスレッドからこのランタイムを操作するためのハンドルを返す
+    pub fn handle(self: Pin<&mut Self>) -> io::Result<RemoteHandle> {
+        let this = unsafe { self.get_unchecked_mut() };
+        let handle = RemoteHandle::new(this.injector()?);
+        this.arm_remotes();
+        Ok(handle)
+    }
+
+    // ThreadWakerに持たせるRemoteWakerを返す
+    pub(crate) fn remote_waker(&mut self) -> RemoteWaker {
+        let injector = self
+            .injector()
+            .expect("failed to create the remote injector.");
+        RemoteWaker { injector }
+    }
+
+    // ThreadWakerがwaitで待っている間、RemoteHandleと同じく数えてリアクターに登録しておく
+    // NOTE: 他のスレッドがすべて止まっても、デッドロックとせずに他のOSスレッドから起こされるのを待つ
+    pub(crate) fn hold_remote_waker<'a>(&mut self, waker: &'a RemoteWaker) -> RemoteWakerHold<'a> {
+        waker.injector.handles.fetch_add(1, Ordering::AcqRel);
+        self.arm_remotes();
+        RemoteWakerHold {
+            injector: &waker.injector,
+        }
+    }
+
+    // インジェクターがまだなければ作って返す
+    fn injector(&mut self) -> io::Result<Arc<Injector>> {
+        if self.remotes.is_none() {
+            self.remotes = Some(Remotes::new()?);
+        }
+        Ok(self.remotes.as_ref().unwrap().injector.clone())
+    }
+
+    // RemoteHandleが残っていれば、積まれたときに起こされるようにリアクターに登録する
+    // NOTE: 登録している間はrunが戻らない
+    fn arm_remotes(&mut self) {
+        let remotes = match &mut self.remotes {
+            Some(remotes) => remotes,
+            None => return,
+        };
+        if remotes.armed || !remotes.has_handles() {
+            return;
+        }
+        let fd = remotes.wakeup_fd();
+        self.reactor
+            .register(fd, Interest::Readable, REMOTE_WAKER)
+            .expect("failed to register the remote injector.");
+        self.remotes.as_mut().unwrap().armed = true;
+    }
+
+    // 他のOSスレッドから積まれたものを処理する
+    // NOTE: 切り替え先を選ぶ前に呼ぶ
+    pub(crate) fn drain_remotes(&mut self) {
+        let queue = match &self.remotes {
+            Some(remotes) if remotes.injector.pending.swap(false, Ordering::Acquire) => {
+                std::mem::take(&mut *remotes.injector.queue.lock().unwrap())
+            }
+            Some(_) => VecDeque::new(),
+            None => return,
+        };
+        for remote in queue {
+            match remote {
+                Remote::Spawn(task) => self.remotes.as_mut().unwrap().waiting.push_back(task),
+                Remote::Wake(id) => {
+                    let alive = self
+                        .thread_of(id)
+                        .is_some_and(|t| t.state != State::Available);
+                    if alive {
+                        self.t_unpark(id.index());
+                    }
+                }
+                Remote::WakeFuture(waker) => waker.wake_remote(self),
+            }
+        }
+        self.spawn_remotes();
+    }
+
+    // 生成できていないタスクを、利用可能なスレッドがある分だけ生成する
+    // NOTE: 切り替えの途中なので他のスレッドを実行して空くのを待つことはできず、次の切り替えでまた試す
+    fn spawn_remotes(&mut self) {
+        while self
+            .remotes
+            .as_ref()
+            .is_some_and(|remotes| !remotes.waiting.is_empty())
+        {
+            let id = match self.prepare_thread(false) {
+                Ok(id) => id,
+                Err(_) => return,
+            };
+            let task = self.remotes.as_mut().unwrap().waiting.pop_front().unwrap();
+            // NOTE: JoinHandleは他のOSスレッドに渡せないので手放す
+            drop(self.spawn_on(id, crate::DEFAULT_PRIORITY, None, task));
+            // NOTE: 切り替えの途中で実行中のスレッドとは関係がないので、コンテキストは引き継がない
+            self.threads[id].context = Context::new();
+        }
+    }
+
+    // インジェクターのソケットの準備ができたときに呼ぶ
+    pub(crate) fn wake_remotes(&mut self) {
+        let remotes = match &mut self.remotes {
+            Some(remotes) => remotes,
+            None => return,
+        };
+        remotes.armed = false;
+        // NOTE: 書き込まれた分を読み捨てて、次に積まれたときにまたリアクターが起きるようにする
+        let mut buf = [0u8; 64];
+        while let Ok(n) = (&remotes.injector.wakeup).read(&mut buf) {
+            if n == 0 {
+                break;
+            }
+        }
+        self.drain_remotes();
+        self.arm_remotes();
+    }
+}
@@ -0,0 +1,110 @@
+// ValgrindやAddressSanitizerに自前のスタックとスレッドの切り替えを教える
+// NOTE: sanitizeフィーチャーを有効にしたときだけ使われる
+//       教えないと、ツールはスタックポインタが別の領域に飛んだことを知らないので誤検知が大量に出る
+//       Valgrindのクライアントリクエストは、Valgrindの外で実行した場合は何もしない
+//       AddressSanitizerのフックは--cfg greenthreads_asanを指定してビルドしたときだけ呼ぶ
+//       (nightlyでしか使えないcfg(sanitize)に頼らず、stableでもsanitizeフィーチャーを有効にしてビルドできるようにする)
+use std::arch::asm;
+use std::os::raw::c_void;
+#[cfg(greenthreads_asan)]
+use std::ptr;
+
+use crate::Runtime;
+
+const VG_USERREQ_STACK_REGISTER: usize = 0x1501;
+const VG_USERREQ_STACK_DEREGISTER: usize = 0x1502;
+
+// Valgrindのクライアントリクエストを送る
+// NOTE: Valgrindの外で実行した場合はrdi(x86ではedi)を回転させて元に戻すだけの命令列になり、defaultがそのまま返る
+unsafe fn client_request(default: usize, request: usize, args: [usize; 5]) -> usize {
+    let args = [request, args[0], args[1], args[2], args[3], args[4]];
+    let mut result = default;
+    #[cfg(target_arch = "x86_64")]
+    asm!(
+        "rol rdi, 3",
+        "rol rdi, 13",
+        "rol rdi, 61",
+        "rol rdi, 51",
+        "xchg rbx, rbx",
+        inout("rdx") result,
+        in("rax") args.as_ptr(),
+        options(nostack),
+    );
+    // NOTE: x86ではediを回転させ、合わせて64bit分回して元に戻す
+    #[cfg(target_arch = "x86")]
+    asm!(
+        "rol edi, 3",
+        "rol edi, 13",
+        "rol edi, 29",
+        "rol edi, 19",
+        "xchg ebx, ebx",
+        inout("edx") result,
+        in("eax") args.as_ptr(),
+        options(nostack),
+    );
+    result
+}
+
+// [start, end)をスタックとしてValgrindに登録し、登録IDを返す
+pub(crate) fn stack_register(start: usize, end: usize) -> usize {
+    unsafe { client_request(0, VG_USERREQ_STACK_REGISTER, [start, end, 0, 0, 0]) }
+}
+
+pub(crate) fn stack_deregister(id: usize) {
+    unsafe {
+        client_request(0, VG_USERREQ_STACK_DEREGISTER, [id, 0, 0, 0, 0]);
+    }
+}
+
+#[cfg(greenthreads_asan)]
+extern "C" {
+    fn __sanitizer_start_switch_fiber(
+        fake_stack_save: *mut *mut c_void,
+        bottom: *const c_void,
+        size: usize,
+    );
+    fn __sanitizer_finish_switch_fiber(
+        fake_stack_save: *mut c_void,
+        bottom_old: *mut *const c_void,
+        size_old: *mut usize,
+    );
+}
+
+#[cfg(greenthreads_asan)]
+impl Runtime {
+    // idのスレッドのスタックの一番下のアドレスと大きさ
+    // NOTE: ベーススレッドはOSスレッドのスタックで動くので、最初に切り替えたときにAddressSanitizerから受け取ったものを使う
+    fn sanitizer_stack(&self, id: usize) -> (*const c_void, usize) {
+        match &self.threads[id].stack {
+            Some(stack) => (stack.bottom() as *const c_void, stack.size()),
+            None => (self.main_stack.0 as *const c_void, self.main_stack.1),
+        }
+    }
+}
+
+// posのスレッドに切り替える直前に呼ぶ
+// fake_stack_saveがNULLの場合は、切り替え元のスレッドが終わってもう戻らないことを表す
+#[cfg_attr(not(greenthreads_asan), allow(unused_variables))]
+pub(crate) unsafe fn start_switch(rt: *mut Runtime, pos: usize, fake_stack_save: *mut *mut c_void) {
+    #[cfg(greenthreads_asan)]
+    {
+        let (bottom, size) = (*rt).sanitizer_stack(pos);
+        __sanitizer_start_switch_fiber(fake_stack_save, bottom, size);
+    }
+}
+
+// 切り替わった直後に、切り替え先のスレッドで呼ぶ
+// fake_stackはこのスレッドが切り替えたときにstart_switchで保存したもので、新しく始まったスレッドはNULL
+#[cfg_attr(not(greenthreads_asan), allow(unused_variables))]
+pub(crate) unsafe fn finish_switch(rt: *mut Runtime, fake_stack: *mut c_void) {
+    #[cfg(greenthreads_asan)]
+    {
+        let mut bottom_old = ptr::null();
+        let mut size_old = 0;
+        __sanitizer_finish_switch_fiber(fake_stack, &mut bottom_old, &mut size_old);
+        // NOTE: Runtimeで最初の切り替えは必ずベーススレッドからなので、そのときの切り替え元のスタックの範囲を覚えておく
+        if (*rt).main_stack.0 == 0 && !bottom_old.is_null() {
+            (*rt).main_stack = (bottom_old as usize, size_old);
+        }
+    }
+}
@@ -0,0 +1,127 @@
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::rc::Rc;
+
+use super::Scheduler;
+use crate::{PinnedRuntime, Runtime};
+
+// 再開可能なスレッドの中から、シードで決まる乱数で次に実行するスレッドを選ぶスケジューラ
+// 選んだスレッドのIDを順に記録し、記録した順番どおりに選び直すこともできる
+// NOTE: 同じシードで同じプログラムを動かせば、毎回同じ順番でスレッドが実行される
+//       ただし、スリープやI/Oの待ちが終わる時刻は実際の時間に左右され、プリエンプションはシグナルで起きるので、
+//       それらを使う場合は同じ順番になるとは限らない
+pub struct DeterministicScheduler {
+    ready: Vec<usize>,
+    // xorshift64*の状態
+    state: u64,
+    // 選んだスレッドのIDの記録
+    recorded: Rc<RefCell<Vec<usize>>>,
+    // 記録をなぞって選ぶ場合の、残りの順番
+    replay: Option<VecDeque<usize>>,
+}
+
+impl DeterministicScheduler {
+    pub fn new(seed: u64) -> Self {
+        DeterministicScheduler {
+            ready: Vec::new(),
+            // NOTE: xorshiftは状態が0だと0しか出さないので避ける
+            state: if seed == 0 {
+                0x9E37_79B9_7F4A_7C15
+            } else {
+                seed
+            },
+            recorded: Rc::new(RefCell::new(Vec::new())),
+            replay: None,
+        }
+    }
+
+    // scheduleの順番どおりにスレッドを選ぶスケジューラを作る
+    // NOTE: 記録した順番を使い切ったら、再開可能になった順に選ぶ
+    pub fn replay(schedule: Vec<usize>) -> Self {
+        let mut scheduler = DeterministicScheduler::new(0);
+        scheduler.replay = Some(schedule.into());
+        scheduler
+    }
+
+    fn next_random(&mut self) -> u64 {
+        self.state ^= self.state >> 12;
+        self.state ^= self.state << 25;
+        self.state ^= self.state >> 27;
+        self.state.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+
+    fn pick_index(&mut self) -> usize {
+        let expected = self.replay.as_mut().and_then(|replay| replay.pop_front());
+        match expected {
+            Some(id) => self.ready.iter().position(|r| *r == id).unwrap_or_else(|| {
+                panic!(
+                    "schedule diverged: thread {} is not ready (ready: {:?}).",
+                    id, self.ready
+                )
+            }),
+            None if self.replay.is_some() => 0,
+            None => (self.next_random() % self.ready.len() as u64) as usize,
+        }
+    }
+}
+
+impl Scheduler for DeterministicScheduler {
+    fn ready(&mut self, thread_id: usize) {
+        self.ready.push(thread_id);
+    }
+
+    fn pick_next(&mut self) -> Option<usize> {
+        if self.ready.is_empty() {
+            return None;
+        }
+        let index = self.pick_index();
+        let id = self.ready.remove(index);
+        self.recorded.borrow_mut().push(id);
+        Some(id)
+    }
+
+    // NOTE: yield_toで選ばれたスレッドも記録し、記録をなぞる場合は記録と同じスレッドのときだけ応じる
+    fn take(&mut self, thread_id: usize) -> bool {
+        if let Some(replay) = &mut self.replay {
+            if replay.front() != Some(&thread_id) {
+                return false;
+            }
+            replay.pop_front();
+        }
+        match self.ready.iter().position(|id| *id == thread_id) {
+            Some(pos) => {
+                self.ready.remove(pos);
+                self.recorded.borrow_mut().push(thread_id);
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+impl Runtime {
+    // シードで決まる順番でスレッドを実行するRuntimeを作る
+    // 実行した順番はRuntime::scheduleで取得でき、Runtime::replayでそのとおりに実行し直せる
+    pub fn deterministic(seed: u64) -> PinnedRuntime {
+        Runtime::with_deterministic_scheduler(DeterministicScheduler::new(seed))
+    }
+
+    // Runtime::scheduleで取得した順番どおりにスレッドを実行するRuntimeを作る
+    // NOTE: 記録したときと違うスレッドが再開可能になっていた場合はパニックする
+    pub fn replay(schedule: Vec<usize>) -> PinnedRuntime {
+        Runtime::with_deterministic_scheduler(DeterministicScheduler::replay(schedule))
+    }
+
+    fn with_deterministic_scheduler(scheduler: DeterministicScheduler) -> PinnedRuntime {
+        let recorded = scheduler.recorded.clone();
+        let mut runtime = Runtime::from_scheduler(Box::new(scheduler));
+        runtime.schedule = Some(recorded);
+        runtime.pinned()
+    }
+
+    // これまでに実行したスレッドのIDを順に返す
+    // Runtime::deterministicかRuntime::replayで作った場合のみ記録される
+    pub fn schedule(&self) -> Option<Vec<usize>> {
+        self.schedule.as_ref().map(|s| s.borrow().clone())
+    }
+}
@@ -0,0 +1,127 @@
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+use super::{Scheduler, SwitchReason};
+
+const DEFAULT_LEVELS: usize = 3;
+const DEFAULT_QUANTUM: Duration = Duration::from_millis(10);
+const DEFAULT_BOOST_INTERVAL: Duration = Duration::from_secs(1);
+
+// スレッドごとの状態
+#[derive(Clone, Copy, Default)]
+struct Entry {
+    level: usize,
+    // 今のレベルで使った時間
+    used: Duration,
+}
+
+// 多段フィードバックキュー(Multi-Level Feedback Queue)のスケジューラ
+// レベルが小さいキューのスレッドから順に実行する
+// NOTE:
+//  - 新しいスレッドは一番上(レベル0)から始める
+//  - 今のレベルの持ち時間(quantum)を使い切ったスレッドは1つ下のレベルに下げる
+//    持ち時間はレベルが1つ下がるごとに2倍になる
+//  - 持ち時間を使い切る前にブロックしたスレッド(I/Oなどを待つ対話的なスレッド)は1つ上のレベルに上げる
+//  - 下のレベルのスレッドが飢餓状態にならないように、boost_intervalごとにすべてのスレッドをレベル0に戻す
+pub struct MlfqScheduler {
+    queues: Vec<VecDeque<usize>>,
+    entries: Vec<Entry>,
+    quantum: Duration,
+    boost_interval: Duration,
+    last_boost: Instant,
+}
+
+impl MlfqScheduler {
+    pub fn new() -> Self {
+        MlfqScheduler::with_config(DEFAULT_LEVELS, DEFAULT_QUANTUM, DEFAULT_BOOST_INTERVAL)
+    }
+
+    // levels: キューの数
+    // quantum: レベル0の持ち時間
+    // boost_interval: すべてのスレッドをレベル0に戻す間隔
+    pub fn with_config(levels: usize, quantum: Duration, boost_interval: Duration) -> Self {
+        assert!(levels > 0, "levels must not be zero.");
+        MlfqScheduler {
+            queues: vec![VecDeque::new(); levels],
+            entries: Vec::new(),
+            quantum,
+            boost_interval,
+            last_boost: Instant::now(),
+        }
+    }
+
+    fn entry(&mut self, thread_id: usize) -> &mut Entry {
+        if self.entries.len() <= thread_id {
+            self.entries.resize(thread_id + 1, Entry::default());
+        }
+        &mut self.entries[thread_id]
+    }
+
+    // levelの持ち時間
+    fn quantum_of(&self, level: usize) -> Duration {
+        self.quantum * (1 << level.min(31))
+    }
+
+    // すべてのスレッドをレベル0に戻す
+    fn boost(&mut self) {
+        for entry in &mut self.entries {
+            *entry = Entry::default();
+        }
+        let (top, rest) = self.queues.split_at_mut(1);
+        for queue in rest {
+            top[0].extend(queue.drain(..));
+        }
+        self.last_boost = Instant::now();
+    }
+}
+
+impl Default for MlfqScheduler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Scheduler for MlfqScheduler {
+    fn ready(&mut self, thread_id: usize) {
+        let level = self.entry(thread_id).level;
+        self.queues[level].push_back(thread_id);
+    }
+
+    fn pick_next(&mut self) -> Option<usize> {
+        if self.last_boost.elapsed() >= self.boost_interval {
+            self.boost();
+        }
+        self.queues.iter_mut().find_map(|queue| queue.pop_front())
+    }
+
+    fn take(&mut self, thread_id: usize) -> bool {
+        for queue in &mut self.queues {
+            if let Some(pos) = queue.iter().position(|id| *id == thread_id) {
+                queue.remove(pos);
+                return true;
+            }
+        }
+        false
+    }
+
+    fn ran(&mut self, thread_id: usize, run_time: Duration, reason: SwitchReason) {
+        let lowest = self.queues.len() - 1;
+        let mut entry = *self.entry(thread_id);
+        entry.used += run_time;
+        if reason == SwitchReason::Finished {
+            // 次に同じIDで生成されるスレッドは一番上から始める
+            entry = Entry::default();
+        } else if entry.used >= self.quantum_of(entry.level) {
+            entry.level = (entry.level + 1).min(lowest);
+            entry.used = Duration::ZERO;
+        } else if reason == SwitchReason::Blocked {
+            entry.level = entry.level.saturating_sub(1);
+            entry.used = Duration::ZERO;
+        }
+        *self.entry(thread_id) = entry;
+    }
+
+    fn level(&self, thread_id: usize) -> Option<usize> {
+        Some(self.entries.get(thread_id).map_or(0, |e| e.level))
+    }
+}
@@ -0,0 +1,99 @@
+// 次に実行するスレッドを選ぶスケジューラ
+// NOTE: コンテキストスイッチはRuntimeが行い、スケジューラはどのスレッドを実行するかだけを決める
+//       stdに依存しないので、bareのExecutorでも使える(時間を測るMlfqとRuntimeを使うDeterministicを除く)
+#[cfg(feature = "std")]
+mod deterministic;
+#[cfg(feature = "std")]
+mod mlfq;
+mod priority;
+mod round_robin;
+
+use alloc::boxed::Box;
+use core::time::Duration;
+
+#[cfg(feature = "std")]
+pub use deterministic::DeterministicScheduler;
+#[cfg(feature = "std")]
+pub use mlfq::MlfqScheduler;
+pub use priority::PriorityScheduler;
+pub use round_robin::RoundRobinScheduler;
+
+// 優先度は値が大きいほど先に実行される
+pub const DEFAULT_PRIORITY: u8 = 0;
+
+// スレッドが実行を止めた理由
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum SwitchReason {
+    // 自分からyieldした
+    Yielded,
+    // 持ち時間を過ぎてプリエンプションで切り替えられた
+    Preempted,
+    // ブロックした(スリープやI/O待ち、parkも含む)
+    Blocked,
+    // タスクが終わった
+    Finished,
+}
+
+// スケジューリングアルゴリズムを差し替えるためのトレイト
+// Runtimeはスレッドの状態が変わるたびに対応するメソッドを呼ぶ
+pub trait Scheduler {
+    // スレッドが再開可能になった
+    // 生成されたとき、yieldしたとき、ブロックから起こされたときに呼ばれる
+    fn ready(&mut self, thread_id: usize);
+
+    // 次に実行するスレッドを選ぶ
+    // readyで渡されたスレッドの中から選んで取り除き、ない場合はNoneを返す
+    // NOTE: 実行中のスレッドはyieldしてもpick_nextの後にreadyが呼ばれるので、自分自身が選ばれることはない
+    fn pick_next(&mut self) -> Option<usize>;
+
+    // 再開可能なスレッドの中からthread_idを取り除く
+    // 取り除いた場合はtrueを返し、Runtimeはpick_nextの代わりにそのスレッドに切り替える
+    // NOTE: yield_toで使う
+    //       対応しないスケジューラはfalseを返せばよく、その場合はpick_nextで選んだスレッドに切り替える
+    fn take(&mut self, _thread_id: usize) -> bool {
+        false
+    }
+
+    // 実行中のスレッドがブロックした
+    fn block(&mut self, _thread_id: usize) {}
+
+    // スレッドの優先度が変わった
+    // NOTE: 生成時にも呼ばれるので、優先度を使わないスケジューラは無視してよい
+    fn set_priority(&mut self, _thread_id: usize, _priority: u8) {}
+
+    // スレッドが他のスレッドに切り替わった
+    // run_timeは前に切り替わってから続けて実行していた時間
+    // NOTE: yieldした場合は、readyより前に呼ばれる
+    fn ran(&mut self, _thread_id: usize, _run_time: Duration, _reason: SwitchReason) {}
+
+    // スレッドが今いるキューのレベル
+    // NOTE: キューのレベルを持たないスケジューラはNoneを返す
+    fn level(&self, _thread_id: usize) -> Option<usize> {
+        None
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum SchedulerPolicy {
+    // スレッドを順番に実行する
+    #[default]
+    RoundRobin,
+    // 優先度が高いスレッドから実行する
+    // 待たされているスレッドは優先度が徐々に上がるので、優先度が低くても飢餓状態にはならない
+    Priority,
+    // 多段フィードバックキューで、持ち時間を使い切ったスレッドを下げ、ブロックしたスレッドを上げる
+    // NOTE: 持ち時間を測れるように、Builder::time_sliceでプリエンプションを有効にするとよい
+    #[cfg(feature = "std")]
+    Mlfq,
+}
+
+impl SchedulerPolicy {
+    pub(crate) fn build(self) -> Box<dyn Scheduler> {
+        match self {
+            SchedulerPolicy::RoundRobin => Box::new(RoundRobinScheduler::new()),
+            SchedulerPolicy::Priority => Box::new(PriorityScheduler::new()),
+            #[cfg(feature = "std")]
+            SchedulerPolicy::Mlfq => Box::new(MlfqScheduler::new()),
+        }
+    }
+}
@@ -0,0 +1,91 @@
+use alloc::vec::Vec;
+use core::cmp::Reverse;
+
+use super::{Scheduler, DEFAULT_PRIORITY};
+
+struct Entry {
+    id: usize,
+    priority: u8,
+    // キューに積まれたときのtick
+    since: u64,
+}
+
+impl Entry {
+    // 待った分だけ優先度を上げたもの(エージング)
+    fn effective_priority(&self, tick: u64) -> u64 {
+        self.priority as u64 + (tick - self.since)
+    }
+}
+
+// 優先度が高いスレッドから実行するスケジューラ
+// 待たされているスレッドは選ばれなかった回数だけ優先度が上がるので、飢餓状態にはならない
+#[derive(Default)]
+pub struct PriorityScheduler {
+    entries: Vec<Entry>,
+    // スレッドIDごとの優先度
+    priorities: Vec<u8>,
+    // スレッドを選ぶたびに進むカウンタ
+    tick: u64,
+}
+
+impl PriorityScheduler {
+    pub fn new() -> Self {
+        PriorityScheduler {
+            entries: Vec::new(),
+            priorities: Vec::new(),
+            tick: 0,
+        }
+    }
+
+    fn priority(&self, thread_id: usize) -> u8 {
+        self.priorities
+            .get(thread_id)
+            .copied()
+            .unwrap_or(DEFAULT_PRIORITY)
+    }
+}
+
+impl Scheduler for PriorityScheduler {
+    fn ready(&mut self, thread_id: usize) {
+        self.entries.push(Entry {
+            id: thread_id,
+            priority: self.priority(thread_id),
+            since: self.tick,
+        });
+    }
+
+    // 優先度が一番高いスレッドを取り出す
+    // 同じ優先度の場合は長く待っている方を選ぶ
+    fn pick_next(&mut self) -> Option<usize> {
+        let tick = self.tick;
+        let (pos, _) = self
+            .entries
+            .iter()
+            .enumerate()
+            .max_by_key(|(_, e)| (e.effective_priority(tick), Reverse(e.since)))?;
+        self.tick += 1;
+        Some(self.entries.remove(pos).id)
+    }
+
+    fn take(&mut self, thread_id: usize) -> bool {
+        match self.entries.iter().position(|e| e.id == thread_id) {
+            Some(pos) => {
+                self.entries.remove(pos);
+                self.tick += 1;
+                true
+            }
+            None => false,
+        }
+    }
+
+    // NOTE: キューに積まれている場合もすぐに反映する(Mutexの優先度継承で、ロックを持っているスレッドの優先度を上げるため)
+    fn set_priority(&mut self, thread_id: usize, priority: u8) {
+        if self.priorities.len() <= thread_id {
+            self.priorities.resize(thread_id + 1, DEFAULT_PRIORITY);
+        }
+        self.priorities[thread_id] = priority;
+        if let Some(entry) = self.entries.iter_mut().find(|e| e.id == thread_id) {
+            entry.priority = priority;
+        }
+    }
+}
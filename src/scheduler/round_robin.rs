@@ -0,0 +1,34 @@
+use alloc::collections::VecDeque;
+
+use super::Scheduler;
+
+// 再開可能になった順に実行するスケジューラ
+#[derive(Default)]
+pub struct RoundRobinScheduler {
+    queue: VecDeque<usize>,
+}
+
+impl RoundRobinScheduler {
+    pub fn new() -> Self {
+        RoundRobinScheduler {
+            queue: VecDeque::new(),
+        }
+    }
+}
+
+impl Scheduler for RoundRobinScheduler {
+    fn ready(&mut self, thread_id: usize) {
+        self.queue.push_back(thread_id);
+    }
+
+    fn pick_next(&mut self) -> Option<usize> {
+        self.queue.pop_front()
+    }
+
+    fn take(&mut self, thread_id: usize) -> bool {
+        match self.queue.iter().position(|id| *id == thread_id) {
+            Some(pos) => self.queue.remove(pos).is_some(),
+            None => false,
+        }
+    }
+}
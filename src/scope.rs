@@ -0,0 +1,125 @@
+// 借用したデータを使えるスレッドを生成するスコープ
+// NOTE: スコープを抜ける前に、スコープ内で生成したスレッドがすべて終わるまで待つので、
+//       'staticでないデータを借用してもスレッドより先に解放されることはない
+use std::cell::Cell;
+use std::marker::PhantomData;
+use std::panic::{self, AssertUnwindSafe};
+use std::pin::Pin;
+use std::rc::Rc;
+use std::thread::Result;
+
+use crate::join::{self, JoinHandle};
+use crate::{preempt, RestoreCurrent, Runtime, ThreadHandle, CURRENT, DEFAULT_PRIORITY};
+
+struct ScopeData {
+    // 終わっていないスレッドの数
+    running: Cell<usize>,
+    // joinされていないスレッドも含めて、パニックしたスレッドがあったかどうか
+    a_thread_panicked: Cell<bool>,
+}
+
+pub struct Scope<'scope, 'env: 'scope> {
+    runtime: *mut Runtime,
+    data: Rc<ScopeData>,
+    scope: PhantomData<&'scope mut &'scope ()>,
+    env: PhantomData<&'env mut &'env ()>,
+}
+
+impl<'scope> Scope<'scope, '_> {
+    // スコープ内でスレッドを生成する
+    // fはスコープの外のデータを借用できる
+    pub fn spawn<F, T>(&'scope self, f: F) -> ScopedJoinHandle<'scope, T>
+    where
+        F: FnOnce() -> T + 'scope,
+        T: 'scope,
+    {
+        let _guard = preempt::disable();
+        let data = self.data.clone();
+        data.running.set(data.running.get() + 1);
+        let f = move || {
+            let result = panic::catch_unwind(AssertUnwindSafe(f));
+            if result.is_err() {
+                data.a_thread_panicked.set(true);
+            }
+            data.running.set(data.running.get() - 1);
+            match result {
+                Ok(output) => output,
+                Err(payload) => panic::resume_unwind(payload),
+            }
+        };
+
+        // 利用可能なスレッドがない場合は空くまで待つ
+        // NOTE: 待つ間は他のスレッドに切り替えるので、&mut Runtimeを作るのは待った後にする
+        let id = unsafe { Runtime::t_prepare_thread(self.runtime, true) }
+            .expect("failed to spawn a scoped thread.");
+        let rt = unsafe { &mut *self.runtime };
+        let (task, handle) = join::wrap(f, ThreadHandle::new(rt.thread_id(id)));
+        // NOTE: スコープを抜ける前にスレッドが終わるのを待つので、taskが'scopeより長く実行されることはない
+        let task: Box<dyn FnOnce() + 'static> = unsafe { std::mem::transmute(task) };
+        rt.start_thread(id, DEFAULT_PRIORITY, None, task, handle.token.clone());
+        ScopedJoinHandle {
+            handle,
+            scope: PhantomData,
+        }
+    }
+}
+
+pub struct ScopedJoinHandle<'scope, T> {
+    handle: JoinHandle<T>,
+    scope: PhantomData<&'scope ()>,
+}
+
+impl<T> ScopedJoinHandle<'_, T> {
+    // スレッドが終わるまでブロックし、戻り値を返す
+    pub fn join(self) -> Result<T> {
+        self.handle.join()
+    }
+
+    pub fn thread(&self) -> &ThreadHandle {
+        self.handle.thread()
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.handle.is_finished()
+    }
+}
+
+impl Runtime {
+    // スコープを作ってfを実行し、スコープ内で生成したスレッドがすべて終わるまでRuntimeを動かす
+    // joinされていないスレッドがパニックした場合は、すべて終わった後にパニックする
+    pub fn scope<'env, F, T>(self: Pin<&mut Self>, f: F) -> T
+    where
+        F: for<'scope> FnOnce(&'scope Scope<'scope, 'env>) -> T,
+    {
+        let this = unsafe { self.get_unchecked_mut() };
+        let rt: *mut Runtime = this;
+        // NOTE: runと同じく、戻るとき(パニックで巻き戻るときも)は元のRuntimeに戻す
+        let _restore = RestoreCurrent(CURRENT.with(|current| current.replace(rt)));
+        let scope = Scope {
+            runtime: rt,
+            data: Rc::new(ScopeData {
+                running: Cell::new(0),
+                a_thread_panicked: Cell::new(false),
+            }),
+            scope: PhantomData,
+            env: PhantomData,
+        };
+        let result = panic::catch_unwind(AssertUnwindSafe(|| f(&scope)));
+
+        // fがパニックした場合も、借用がなくなる前にスレッドが終わるのを待つ
+        while scope.data.running.get() > 0 {
+            if !this.run_once() {
+                panic!(
+                    "deadlock: scoped threads can never finish.\n{}",
+                    this.blocked_threads_report()
+                );
+            }
+        }
+
+        match result {
+            Err(payload) => panic::resume_unwind(payload),
+            Ok(_) if scope.data.a_thread_panicked.get() => panic!("a scoped thread panicked"),
+            Ok(output) => output,
+        }
+    }
+}
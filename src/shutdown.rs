@@ -0,0 +1,183 @@
+// Runtimeの終了処理
+use std::io;
+use std::os::raw::c_int;
+use std::panic;
+use std::pin::Pin;
+use std::time::{Duration, Instant};
+
+use crate::cancel::Cancelled;
+use crate::reactor::{Interest, Reactor};
+use crate::signal::{self, Signal, SIGNAL_WAKER};
+use crate::timer::Timers;
+use crate::{preempt, runtime_ptr, Runtime, State, CURRENT};
+
+// run_until_signalでキャンセルしたスレッドが自分で終わるのを待つ時間の既定値
+pub(crate) const DEFAULT_SHUTDOWN_GRACE: Duration = Duration::from_secs(5);
+
+impl Runtime {
+    // runと同じくすべてのスレッドが終わるまで実行するが、signalsのどれかを受け取ったら終了処理をして戻る
+    // 終了処理では残っているスレッドをすべてキャンセルし、Builder::shutdown_graceの間は自分で終わるのを待ち、
+    // それでも終わらないスレッドはshutdownと同じくスタックを巻き戻して終わらせる
+    // 受け取ったシグナルを返し、シグナルを受け取らずにすべてのスレッドが終わった場合はNoneを返す
+    // NOTE: キャンセルされたスレッドでは、sleepやrecvなどのブロックする処理がErr(Cancelled)を返すので、
+    //       それを見て後片付けをして終わること
+    pub fn run_until_signal(self: Pin<&mut Self>, signals: &[c_int]) -> io::Result<Option<c_int>> {
+        let this = unsafe { self.get_unchecked_mut() };
+        let mut handles = signals
+            .iter()
+            .map(|&signum| signal::signal(signum))
+            .collect::<io::Result<Vec<Signal>>>()?;
+        let pipe = signal::local_pipe()?;
+
+        let rt: *mut Runtime = this;
+        let prev = CURRENT.with(|current| current.replace(rt));
+        let _guard = preempt::disable();
+        if let Some(time_slice) = this.time_slice {
+            preempt::start(time_slice).expect("failed to start preemption timer.");
+        }
+        let caught = loop {
+            if let Some(signal) = handles
+                .iter_mut()
+                .find_map(|s| s.try_recv().then(|| s.signum()))
+            {
+                break Some(signal);
+            }
+            if unsafe { Runtime::t_yield(rt) } {
+                continue;
+            }
+            // スリープ中やI/O待ちのスレッドもいなければ、すべて終わっている
+            if this.timers.next_deadline().is_none() && !this.reactor.has_waiters() {
+                break None;
+            }
+            // NOTE: 休止している間にシグナルが届いたら起きるように、休止する間だけパイプをリアクターに登録する
+            this.reactor
+                .register(pipe, Interest::Readable, SIGNAL_WAKER)
+                .expect("failed to register the signal pipe.");
+            this.wait_events();
+            this.reactor.cancel(pipe, Interest::Readable, SIGNAL_WAKER);
+        };
+        if caught.is_some() {
+            this.drain();
+        }
+        if this.time_slice.is_some() {
+            preempt::stop().expect("failed to stop preemption timer.");
+        }
+        CURRENT.with(|current| current.set(prev));
+        this.recycle_stacks();
+        this.report_deadlock();
+        Ok(caught)
+    }
+
+    // 残っているスレッドをすべてキャンセルし、shutdown_graceの間は自分で終わるのを待つ
+    // 期限を過ぎても残っているスレッドはshutdownで終わらせる
+    fn drain(&mut self) {
+        for id in 1..self.threads.len() {
+            if self.threads[id].state != State::Available {
+                self.threads[id].token.clone().cancel();
+            }
+        }
+
+        let rt: *mut Runtime = self;
+        let deadline = Instant::now() + self.shutdown_grace;
+        let max_park = self.max_park;
+        while self.threads[1..]
+            .iter()
+            .any(|t| t.state != State::Available)
+        {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                break;
+            }
+            // NOTE: 期限を過ぎても休止し続けないように、一度に休止する時間を期限までにする
+            self.max_park = Some(max_park.map_or(remaining, |max_park| max_park.min(remaining)));
+            if unsafe { !Runtime::t_yield(rt) && !(*rt).wait_events() } {
+                break;
+            }
+        }
+        self.max_park = max_park;
+
+        if self.threads[1..]
+            .iter()
+            .any(|t| t.state != State::Available)
+        {
+            // NOTE: run_until_signalから呼ばれるので、selfは固定されている
+            unsafe { Pin::new_unchecked(self) }.shutdown();
+        }
+    }
+
+    // 残っているスレッドをすべてキャンセルし、終わるまで待つ
+    // キャンセルされたスレッドは、次にyieldやブロックしたところでスタックを巻き戻して終わる
+    // まだ始まっていないスレッドはタスクを実行せずに終わる
+    // NOTE: yieldもブロックもせずに動き続けるスレッドは止められない
+    pub fn shutdown(self: Pin<&mut Self>) {
+        let this = unsafe { self.get_unchecked_mut() };
+        let _guard = preempt::disable();
+        this.cancelled = true;
+        // スリープ中やI/O待ちのスレッドも含めて、止まっているスレッドをすべて再開可能にする
+        this.timers = Timers::new();
+        this.reactor = Reactor::new();
+        for id in 1..this.threads.len() {
+            if matches!(this.threads[id].state, State::Blocked | State::Parked) {
+                this.make_ready(id);
+            }
+        }
+
+        let rt: *mut Runtime = this;
+        let prev = CURRENT.with(|current| current.replace(rt));
+        unsafe { while Runtime::t_yield(rt) || (*rt).wait_events() {} }
+        CURRENT.with(|current| current.set(prev));
+        this.cancelled = false;
+    }
+
+    // キャンセルされている場合は、スタックを巻き戻して現在のスレッドを終わらせる
+    // NOTE: 巻き戻し中のドロップ処理でブロックした場合は、二重にパニックしないようにそのまま戻る
+    pub(crate) unsafe fn check_cancelled(rt: *mut Runtime) {
+        if (*rt).cancelled && (*rt).current != 0 && !std::thread::panicking() {
+            panic::resume_unwind(Box::new(Cancelled));
+        }
+    }
+
+    // yieldした後に呼び、shutdownに加えてTaskGroupで生成したスレッドがキャンセルされた場合も巻き戻す
+    // NOTE: ブロックする処理はキャンセルされるとErr(Cancelled)を返すので、TaskGroupのキャンセルではyieldでだけ巻き戻す
+    pub(crate) unsafe fn check_cancelled_at_yield(rt: *mut Runtime) {
+        Runtime::check_cancelled(rt);
+        let unwind = {
+            let rt = &*rt;
+            rt.threads[rt.current].token.should_unwind()
+        };
+        if unwind && (*rt).current != 0 && !std::thread::panicking() {
+            panic::resume_unwind(Box::new(Cancelled));
+        }
+    }
+}
+
+pub(crate) fn is_cancelled() -> bool {
+    unsafe { (*runtime_ptr()).cancelled }
+}
+
+impl Drop for Runtime {
+    fn drop(&mut self) {
+        // 終わっていないスレッドのスタックに残っている値をドロップしてから解放する
+        // NOTE: パニック中(デッドロックの報告など)はキャンセルで巻き戻せないので、値はドロップせずに解放する
+        if !std::thread::panicking()
+            && self.threads[1..]
+                .iter()
+                .any(|t| t.state != State::Available)
+        {
+            // NOTE: Runtimeは固定したものしか作れず、ドロップされるまで動かないので、固定した参照に戻してよい
+            unsafe { Pin::new_unchecked(&mut *self) }.shutdown();
+        }
+        // 残っているスタックをStackAllocatorに返す
+        for thread in &mut self.threads {
+            if let Some(stack) = thread.stack.take() {
+                self.stacks.allocator.deallocate(stack);
+            }
+        }
+        let rt: *mut Runtime = self;
+        CURRENT.with(|current| {
+            if current.get() == rt {
+                current.set(std::ptr::null_mut());
+            }
+        });
+    }
+}
@@ -0,0 +1,254 @@
+// Unixのシグナルをグリーンスレッドで受け取る
+// NOTE: シグナルハンドラの中ではメモリの確保やロック、スレッドの切り替えができないので、
+//       ハンドラはシグナルごとの受け取った回数を増やし、パイプ(self-pipe)に1byte書くだけにする
+//       受け取る側はパイプの読み込み側をリアクターで待ち、起きたら回数が増えたかを確かめる
+//       シグナルはプロセスのどのOSスレッドに届くか分からないので、パイプはランタイムを動かすOSスレッドごとに作り、
+//       ハンドラは使われているすべてのパイプに書く
+//       ハンドラが書き込む途中のパイプを閉じないように、パイプは閉じずにプールに戻して使い回す
+//       Builder::dump_on_signalを有効にしている場合、SIGQUITとSIGUSR1はどちらかのハンドラしか動かない
+use std::cell::RefCell;
+use std::io::{self, Read};
+use std::marker::PhantomData;
+use std::os::raw::{c_int, c_void};
+use std::os::unix::io::{AsRawFd, RawFd};
+use std::os::unix::net::UnixStream;
+use std::sync::atomic::{AtomicBool, AtomicI32, AtomicU64, Ordering};
+use std::sync::Mutex;
+
+use crate::preempt::{errno_location, sigaction, SigAction, SA_RESTART};
+use crate::reactor::Interest;
+
+pub const SIGHUP: c_int = 1;
+pub const SIGINT: c_int = 2;
+pub const SIGQUIT: c_int = 3;
+pub const SIGPIPE: c_int = 13;
+pub const SIGTERM: c_int = 15;
+#[cfg(target_os = "linux")]
+pub const SIGUSR1: c_int = 10;
+#[cfg(target_os = "linux")]
+pub const SIGUSR2: c_int = 12;
+#[cfg(target_os = "linux")]
+pub const SIGCHLD: c_int = 17;
+#[cfg(target_os = "linux")]
+pub const SIGWINCH: c_int = 28;
+#[cfg(not(target_os = "linux"))]
+pub const SIGUSR1: c_int = 30;
+#[cfg(not(target_os = "linux"))]
+pub const SIGUSR2: c_int = 31;
+#[cfg(not(target_os = "linux"))]
+pub const SIGCHLD: c_int = 20;
+#[cfg(not(target_os = "linux"))]
+pub const SIGWINCH: c_int = 28;
+
+// シグナル番号の上限
+const NSIG: usize = 65;
+// 同時にシグナルを待てるOSスレッドの数
+const MAX_PIPES: usize = 16;
+
+// 受け取れないか、ランタイムが使っているシグナル
+// NOTE: SIGKILLとSIGSTOPは捕まえられず、SIGSEGVとSIGBUSは伸ばせるスタック、SIGALRMはプリエンプションが使う
+#[cfg(target_os = "linux")]
+const RESERVED: [c_int; 5] = [9, 19, 11, 7, 14];
+#[cfg(not(target_os = "linux"))]
+const RESERVED: [c_int; 5] = [9, 17, 11, 10, 14];
+
+// シグナルごとの受け取った回数
+static COUNTS: [AtomicU64; NSIG] = [const { AtomicU64::new(0) }; NSIG];
+// シグナルごとの、ハンドラを登録したかどうか
+static INSTALLED: [AtomicBool; NSIG] = [const { AtomicBool::new(false) }; NSIG];
+static INSTALL_LOCK: Mutex<()> = Mutex::new(());
+// 使われているパイプの書き込み側(-1は空き)
+static WRITERS: [AtomicI32; MAX_PIPES] = [const { AtomicI32::new(-1) }; MAX_PIPES];
+// 使い終わったパイプ
+static IDLE_PIPES: Mutex<Vec<(UnixStream, UnixStream)>> = Mutex::new(Vec::new());
+
+extern "C" {
+    fn write(fd: c_int, buf: *const c_void, count: usize) -> isize;
+}
+
+// シグナルのパイプを待っているのがスレッドではなくrun_until_signalであることを表すID
+pub(crate) const SIGNAL_WAKER: usize = usize::MAX - 3;
+
+// このOSスレッドのパイプ
+struct Pipe {
+    // NOTE: ドロップするときにプールに戻すためにOptionにする
+    streams: Option<(UnixStream, UnixStream)>,
+    slot: usize,
+}
+
+impl Pipe {
+    fn open() -> io::Result<Pipe> {
+        let (reader, writer) = match IDLE_PIPES.lock().unwrap().pop() {
+            Some(pipe) => pipe,
+            None => {
+                let (reader, writer) = UnixStream::pair()?;
+                reader.set_nonblocking(true)?;
+                writer.set_nonblocking(true)?;
+                (reader, writer)
+            }
+        };
+        let fd = writer.as_raw_fd();
+        let slot = WRITERS.iter().position(|w| {
+            w.compare_exchange(-1, fd, Ordering::AcqRel, Ordering::Acquire)
+                .is_ok()
+        });
+        match slot {
+            Some(slot) => Ok(Pipe {
+                streams: Some((reader, writer)),
+                slot,
+            }),
+            None => {
+                IDLE_PIPES.lock().unwrap().push((reader, writer));
+                Err(io::Error::other(
+                    "too many OS threads are waiting for signals",
+                ))
+            }
+        }
+    }
+
+    fn reader(&self) -> &UnixStream {
+        &self.streams.as_ref().unwrap().0
+    }
+
+    // 溜まっている分を読み捨てる
+    fn drain(&self) {
+        let mut buf = [0u8; 64];
+        while let Ok(n) = self.reader().read(&mut buf) {
+            if n == 0 {
+                break;
+            }
+        }
+    }
+}
+
+// OSスレッドが終わるときに、パイプを閉じずにプールに戻す
+// NOTE: ランタイムはOSスレッドより先に終わっているので、リアクターから外す必要はない
+impl Drop for Pipe {
+    fn drop(&mut self) {
+        WRITERS[self.slot].store(-1, Ordering::Release);
+        if let Some(streams) = self.streams.take() {
+            if let Ok(mut idle) = IDLE_PIPES.lock() {
+                idle.push(streams);
+            }
+        }
+    }
+}
+
+thread_local! {
+    static PIPE: RefCell<Option<Pipe>> = const { RefCell::new(None) };
+}
+
+// このOSスレッドのパイプの読み込み側を返す
+pub(crate) fn local_pipe() -> io::Result<RawFd> {
+    PIPE.with(|pipe| {
+        let mut pipe = pipe.borrow_mut();
+        if pipe.is_none() {
+            *pipe = Some(Pipe::open()?);
+        }
+        Ok(pipe.as_ref().unwrap().reader().as_raw_fd())
+    })
+}
+
+// このOSスレッドのパイプに溜まっている分を読み捨て、次に届いたときにまたリアクターが起きるようにする
+pub(crate) fn drain_local_pipe() {
+    PIPE.with(|pipe| {
+        if let Some(pipe) = &*pipe.borrow() {
+            pipe.drain();
+        }
+    });
+}
+
+extern "C" fn handle_signal(signum: c_int) {
+    // NOTE: writeで割り込まれた処理のerrnoを書き換えないように戻す
+    let errno = unsafe { *errno_location() };
+    COUNTS[signum as usize].fetch_add(1, Ordering::AcqRel);
+    for writer in &WRITERS {
+        let fd = writer.load(Ordering::Acquire);
+        if fd >= 0 {
+            // NOTE: パイプが満杯の場合は書けないが、すでに起こすためのデータがあるので問題ない
+            unsafe { write(fd, [1u8].as_ptr() as *const c_void, 1) };
+        }
+    }
+    unsafe { *errno_location() = errno };
+}
+
+fn install(signum: c_int) -> io::Result<()> {
+    if INSTALLED[signum as usize].load(Ordering::Acquire) {
+        return Ok(());
+    }
+    let _lock = INSTALL_LOCK.lock().unwrap();
+    if INSTALLED[signum as usize].load(Ordering::Acquire) {
+        return Ok(());
+    }
+    let action = SigAction {
+        sa_handler: handle_signal as extern "C" fn(c_int) as usize,
+        sa_mask: Default::default(),
+        sa_flags: SA_RESTART,
+        #[cfg(target_os = "linux")]
+        sa_restorer: 0,
+    };
+    if unsafe { sigaction(signum, &action, std::ptr::null_mut()) } < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    INSTALLED[signum as usize].store(true, Ordering::Release);
+    Ok(())
+}
+
+// シグナルを受け取るためのハンドル
+// NOTE: 作ったOSスレッドのパイプを使うので、他のOSスレッドには渡せない
+pub struct Signal {
+    signum: c_int,
+    // 最後に確かめたときの受け取った回数
+    seen: u64,
+    reader: RawFd,
+    _not_send: PhantomData<*const ()>,
+}
+
+// signumのシグナルを受け取るためのハンドルを返す
+// NOTE: 一度登録したハンドラはプロセスが終わるまで外さないので、Signalをすべてドロップした後に届いたシグナルは無視される
+//       (Ctrl+Cなどで終わらなくなる)
+pub fn signal(signum: c_int) -> io::Result<Signal> {
+    if signum <= 0 || signum as usize >= NSIG || RESERVED.contains(&signum) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("signal {} cannot be handled", signum),
+        ));
+    }
+    let reader = local_pipe()?;
+    install(signum)?;
+    Ok(Signal {
+        signum,
+        seen: COUNTS[signum as usize].load(Ordering::Acquire),
+        reader,
+        _not_send: PhantomData,
+    })
+}
+
+impl Signal {
+    // シグナルが届くまで現在のスレッドをブロックする
+    // キャンセルされた場合はErrを返す
+    // NOTE: 前にrecvしてから何度届いていても1回にまとめる
+    pub fn recv(&mut self) -> io::Result<()> {
+        loop {
+            if self.try_recv() {
+                return Ok(());
+            }
+            crate::wait_io(self.reader, Interest::Readable)?;
+            drain_local_pipe();
+        }
+    }
+
+    // 前に確かめてからシグナルが届いていればtrueを返す
+    pub fn try_recv(&mut self) -> bool {
+        let count = COUNTS[self.signum as usize].load(Ordering::Acquire);
+        if count == self.seen {
+            return false;
+        }
+        self.seen = count;
+        true
+    }
+
+    pub fn signum(&self) -> c_int {
+        self.signum
+    }
+}
@@ -0,0 +1,379 @@
+// 実行中のタスクのスタックを写し、同じところから続きを実行させる実験的な機能
+// fork_taskはスタックを別のスレッドに写して、プロセスのforkのように親と子の両方をそこから続けさせる
+// checkpointはスタックを保存し、restoreで保存したところまで巻き戻して、そこからもう一度実行させる
+// NOTE: switch_toは保存するレジスタをすべてスタックに積むので、止めたスレッドのスタックを写せばその時点の実行を写せる
+//       写している間はそのスタックで動けないので、作業用の小さなスタックに切り替えてから写す
+//       スタックはバイト列として写すだけなので、スタック上の値が持っているヒープのメモリなどは複製されない
+//       (StringやRcを置いたフレームを写すと、両方のコピーがドロップして二重解放になる)
+//       そのためfork_taskとrestoreはunsafeで、写したり巻き戻したりするフレームにはCopyな値だけを置くこと
+//       プロセスのforkやsetjmp/longjmpがどう動くかを試すための教材向けで、AddressSanitizerには対応していない
+//       守るべき条件は、それぞれの関数のコメントに書く
+#![allow(clippy::missing_safety_doc)]
+use std::any::Any;
+use std::mem;
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr::{self, addr_of, addr_of_mut};
+
+use crate::context::{self, ThreadContext};
+use crate::join::JoinHandle;
+use crate::spawn::SpawnError;
+use crate::stack::Stack;
+use crate::{preempt, runtime_ptr, Runtime, ThreadId};
+
+// 作業用スタックの大きさ
+const SCRATCH_STACK_SIZE: usize = 64 * 1024;
+const WORD: usize = mem::size_of::<usize>();
+
+// 作業用スタックでする処理
+enum Job {
+    // parentのスタックをchildのスタックに写す
+    Fork {
+        parent: usize,
+        child: usize,
+    },
+    // threadのスタックを保存する
+    Checkpoint {
+        thread: usize,
+    },
+    // 保存したスタック(lenバイト)をspから上に書き戻す
+    Restore {
+        sp: usize,
+        bytes: *const u8,
+        len: usize,
+    },
+}
+
+// 作業用スタックと、そこでする処理の受け渡し
+// NOTE: Runtimeで最初にfork_taskかcheckpointを使うときに作る
+pub(crate) struct Scratch {
+    stack: Stack,
+    ctx: ThreadContext,
+    job: Option<Job>,
+    // 写せたかどうか
+    forked: bool,
+    // checkpointで保存した(スタックポインタ, スタック)
+    saved: Option<(usize, Vec<u8>)>,
+    // restoreで巻き戻したところかどうか
+    restored: bool,
+    // fork_taskで作った子と、forkableの終わりで実行するタスク(JoinHandleに結果を書く)
+    // NOTE: スレッドのtaskに残すと、まだ始まっていないスレッドと見分けられないので移しておく
+    children: Vec<(usize, Box<dyn FnOnce()>)>,
+    // forkableの中でパニックした子のペイロード
+    child_panic: Option<Box<dyn Any + Send>>,
+}
+
+impl Scratch {
+    fn new() -> Self {
+        Scratch {
+            stack: Stack::map(SCRATCH_STACK_SIZE).expect("failed to allocate the scratch stack."),
+            ctx: ThreadContext::default(),
+            job: None,
+            forked: false,
+            saved: None,
+            restored: false,
+            children: Vec::new(),
+            child_panic: None,
+        }
+    }
+}
+
+// fork_taskの結果
+pub enum Fork {
+    // 親には子のJoinHandleを返す
+    Parent(JoinHandle<()>),
+    Child,
+}
+
+// checkpointの結果
+pub enum Checkpoint {
+    // スタックを保存した
+    Saved(Snapshot),
+    // restoreで巻き戻されて、もう一度checkpointから戻った
+    Restored,
+}
+
+// checkpointで保存したスタック
+pub struct Snapshot {
+    thread: ThreadId,
+    sp: usize,
+    bytes: Vec<u8>,
+}
+
+impl Snapshot {
+    // 保存したスレッド
+    pub fn thread(&self) -> ThreadId {
+        self.thread
+    }
+
+    // 保存したスタックの大きさ(バイト)
+    pub fn size(&self) -> usize {
+        self.bytes.len()
+    }
+}
+
+// fの中でfork_taskを呼べるようにしてfを実行する
+// fork_taskで作った子は、fから戻ったところで(fの戻り値を捨てて)終わる
+// NOTE: forkableより外のフレームも子に写されるが、子はそこまで戻らないので、外のフレームの値が二重にドロップされることはない
+//       子がfの中でパニックした場合は、子のJoinHandle::joinがErrを返す
+pub fn forkable<F, R>(f: F) -> R
+where
+    F: FnOnce() -> R,
+{
+    let owner = {
+        let _guard = preempt::disable();
+        let rt = unsafe { &mut *runtime_ptr() };
+        assert!(
+            rt.current != 0,
+            "forkable can only be used on a green thread."
+        );
+        rt.threads[rt.current].forkable += 1;
+        rt.current
+    };
+    let result = panic::catch_unwind(AssertUnwindSafe(f));
+    let rt_ptr = runtime_ptr();
+    unsafe {
+        if (*rt_ptr).current != owner {
+            exit_child(rt_ptr, result.err());
+        }
+        let rt = &mut *rt_ptr;
+        rt.threads[owner].forkable -= 1;
+    }
+    result.unwrap_or_else(|payload| panic::resume_unwind(payload))
+}
+
+// 現在のタスクのスタックを新しいスレッドに写し、親と子の両方がここから続きを実行する
+// 親にはParent(子のJoinHandle)を、子にはChildを返す
+// 利用可能なスレッドがない場合は空くまで待ち、子のスタックに入りきらない場合はErr(SpawnError::StackAllocation)を返す
+// NOTE: forkableの中でしか呼べず、子は親より先に実行される
+//       写したスタックの中でスタックを指している値(フレームポインタやローカル変数への参照)は、子のスタックを指すように直す
+//       それらしい値をすべて直すので、たまたま親のスタックのアドレスと同じ値の整数も書き換わる
+//       ヒープから親のスタックを指しているポインタは直さない
+//       LocalKeyの値は引き継がず、コンテキストは引き継ぐ
+//       forkableからここまでのフレームの値はバイト列のまま複製されるので、両方でドロップしてよい値だけを置くこと
+pub unsafe fn fork_task() -> Result<Fork, SpawnError> {
+    let rt_ptr = runtime_ptr();
+    let _guard = preempt::disable();
+    let (parent, child, handle) = {
+        let parent = {
+            let rt = &mut *rt_ptr;
+            let parent = rt.current;
+            assert!(
+                parent != 0 && rt.threads[parent].forkable > 0,
+                "fork_task must be called inside forkable."
+            );
+            rt.inject_spawn_failure()?;
+            parent
+        };
+        // NOTE: 空くまで待つ間は他のスレッドに切り替えるので、&mut Runtimeを作るのは待った後にする
+        let child = Runtime::t_prepare_thread(rt_ptr, true)?;
+        let rt = &mut *rt_ptr;
+        let priority = rt.threads[parent].priority;
+        let name = rt.threads[parent].name.clone();
+        let handle = rt.spawn_on(child, priority, name, || {
+            if let Some(payload) = take_child_panic() {
+                panic::resume_unwind(payload);
+            }
+        });
+        rt.threads[child].forkable = rt.threads[parent].forkable;
+        (parent, child, handle)
+    };
+    Runtime::run_job(rt_ptr, Job::Fork { parent, child });
+
+    if (*rt_ptr).current != parent {
+        // NOTE: 子のhandleは親のものを写しただけなので、ドロップしない
+        mem::forget(handle);
+        return Ok(Fork::Child);
+    }
+    let scratch = (*rt_ptr).scratch.as_mut().unwrap();
+    if !mem::take(&mut scratch.forked) {
+        // NOTE: 写せなかった子は、始まるとJoinHandleに結果を書くだけで終わる
+        return Err(SpawnError::StackAllocation);
+    }
+    // 子は親より先に実行する
+    (*rt_ptr).next_hint = Some(child);
+    Runtime::t_yield(rt_ptr);
+    Ok(Fork::Parent(handle))
+}
+
+// 現在のタスクのスタックを保存する
+// 保存したところでSaved(スナップショット)を返し、後でrestoreで巻き戻されると、もう一度ここからRestoredを返す
+// NOTE: 巻き戻すとスタックの上のローカル変数もすべて戻るので、スナップショットやくり返した回数などは
+//       ヒープやスタティック変数に置いておく
+pub fn checkpoint() -> Checkpoint {
+    let rt_ptr = runtime_ptr();
+    let _guard = preempt::disable();
+    unsafe {
+        let thread = (*rt_ptr).current;
+        assert!(
+            thread != 0,
+            "checkpoint can only be used on a green thread."
+        );
+        Runtime::run_job(rt_ptr, Job::Checkpoint { thread });
+
+        let rt = &mut *rt_ptr;
+        let scratch = rt.scratch.as_mut().unwrap();
+        if mem::take(&mut scratch.restored) {
+            return Checkpoint::Restored;
+        }
+        let (sp, bytes) = scratch.saved.take().unwrap();
+        Checkpoint::Saved(Snapshot {
+            thread: rt.thread_id(thread),
+            sp,
+            bytes,
+        })
+    }
+}
+
+// 現在のタスクのスタックをsnapshotを保存したところまで巻き戻し、checkpointからもう一度実行させる
+// NOTE: snapshotを保存したスレッドでしか使えない
+//       checkpointの後に作ったフレームはドロップせずに捨てる(その値が持っているメモリなどはリークする)
+//       checkpointの時点でスタックにあった値をその後にドロップしたりムーブしたりしていると、
+//       巻き戻した後に解放済みの値を使うことになるので、そうしていないことを呼び出し側で保証すること
+pub unsafe fn restore(snapshot: &Snapshot) -> ! {
+    let rt_ptr = runtime_ptr();
+    // NOTE: ガードは戻らないのでドロップされず、巻き戻した先のcheckpointにあるガードが元に戻す
+    let _guard = preempt::disable();
+    {
+        let rt = &*rt_ptr;
+        assert!(
+            rt.current != 0 && rt.thread_id(rt.current) == snapshot.thread,
+            "the snapshot was taken on another thread."
+        );
+    }
+    Runtime::run_job(
+        rt_ptr,
+        Job::Restore {
+            sp: snapshot.sp,
+            bytes: snapshot.bytes.as_ptr(),
+            len: snapshot.bytes.len(),
+        },
+    );
+    unreachable!("restore returned without rewinding the stack.");
+}
+
+impl Runtime {
+    // 現在のスレッドを止めて作業用スタックでjobを実行し、終わったら現在のスレッドに戻る
+    // NOTE: Restoreの場合は巻き戻した先(checkpointの中)に戻り、
+    //       Forkの場合は子もスケジューラに選ばれたときにここから戻る
+    unsafe fn run_job(rt: *mut Runtime, job: Job) {
+        let scratch = (*rt).scratch.get_or_insert_with(Scratch::new);
+        scratch.job = Some(job);
+        // NOTE: 作業用スタックは毎回一番上から使う
+        let top = scratch.stack.top();
+        scratch.ctx.prepare(top, job_main, 0);
+
+        let threads = (*rt).threads.as_mut_ptr();
+        let old: *mut ThreadContext = addr_of_mut!((*threads.add((*rt).current)).ctx);
+        let new: *const ThreadContext = addr_of!((*rt).scratch.as_ref().unwrap().ctx);
+        context::switch_to(old, new);
+        (*rt).reclaim_finished();
+    }
+
+    // 作業用スタックでjobを実行し、次に再開するスタックポインタを返す
+    unsafe fn do_job(&mut self) -> usize {
+        let scratch = self.scratch.as_mut().unwrap();
+        match scratch.job.take().unwrap() {
+            Job::Fork { parent, child } => {
+                let sp = self.threads[parent].ctx.stack_pointer();
+                let from = self.aligned_top(parent);
+                let to = self.aligned_top(child);
+                let len = from - sp;
+                let dst = to - len;
+                // NOTE: 伸ばせるスタックなどで子のスタックに入りきらない場合は写さない
+                if dst < self.threads[child].stack.as_ref().unwrap().limit() {
+                    return sp;
+                }
+                ptr::copy_nonoverlapping(sp as *const u8, dst as *mut u8, len);
+                // 親のスタックを指している値を、子のスタックの同じ位置を指すように直す
+                for addr in (dst..to).step_by(WORD) {
+                    let word = addr as *mut usize;
+                    if (sp..from).contains(&*word) {
+                        *word = *word - sp + dst;
+                    }
+                }
+                self.threads[child].ctx.set_stack_pointer(dst);
+                // NOTE: 子は親と同じところから再開するので、JoinHandleに結果を書くタスクはforkableの終わりで実行する
+                let task = self.threads[child].task.take().unwrap();
+                let scratch = self.scratch.as_mut().unwrap();
+                scratch.children.push((child, task));
+                scratch.forked = true;
+                sp
+            }
+            Job::Checkpoint { thread } => {
+                let sp = self.threads[thread].ctx.stack_pointer();
+                let len = self.aligned_top(thread) - sp;
+                let bytes = std::slice::from_raw_parts(sp as *const u8, len).to_vec();
+                self.scratch.as_mut().unwrap().saved = Some((sp, bytes));
+                sp
+            }
+            Job::Restore { sp, bytes, len } => {
+                ptr::copy_nonoverlapping(bytes, sp as *mut u8, len);
+                scratch.restored = true;
+                sp
+            }
+        }
+    }
+
+    // スレッドのスタックの一番上を、prepareと同じく16byte境界に揃えたアドレス
+    // NOTE: 親と子で揃え方を同じにして、写した後もスタックポインタの16byte境界への揃い方が変わらないようにする
+    fn aligned_top(&self, id: usize) -> usize {
+        let stack = self.threads[id]
+            .stack
+            .as_ref()
+            .expect("stack is not allocated.");
+        stack.top() as usize & !15
+    }
+}
+
+// 作業用スタックで最初に実行する関数
+// jobを実行したら、止めたスレッド(Forkの場合は親)に切り替えて二度と戻らない
+extern "C" fn job_main(_: usize) {
+    unsafe {
+        let rt = runtime_ptr();
+        let mut next = ThreadContext::default();
+        next.set_stack_pointer((*rt).do_job());
+        let mut discard = ThreadContext::default();
+        context::switch_to(&mut discard, &next);
+    }
+    unreachable!("the scratch stack was resumed.");
+}
+
+// forkableの終わりで、fork_taskで作った子を終わらせる
+// NOTE: 親から写したforkableより外のフレームには戻らず、他のスレッドに切り替えて二度と戻らない
+unsafe fn exit_child(rt: *mut Runtime, panic: Option<Box<dyn Any + Send>>) -> ! {
+    let task = {
+        let rt = &mut *rt;
+        let current = rt.current;
+        let scratch = rt.scratch.as_mut().unwrap();
+        scratch.child_panic = panic;
+        let pos = scratch
+            .children
+            .iter()
+            .position(|(id, _)| *id == current)
+            .unwrap();
+        scratch.children.swap_remove(pos).1
+    };
+    task();
+    // タスクが終わったときと同じく、スレッドローカル変数とコンテキストをドロップする
+    let (locals, context) = {
+        let rt = &mut *rt;
+        let thread = &mut rt.threads[rt.current];
+        (
+            mem::take(&mut thread.locals),
+            mem::take(&mut thread.context),
+        )
+    };
+    drop(locals);
+    drop(context);
+    Runtime::t_return(rt);
+    unreachable!("a finished thread was resumed.");
+}
+
+// 子のタスクがJoinHandleに書く前に、forkableの中でパニックしたペイロードを取り出す
+fn take_child_panic() -> Option<Box<dyn Any + Send>> {
+    let _guard = preempt::disable();
+    let rt = unsafe { &mut *runtime_ptr() };
+    rt.scratch
+        .as_mut()
+        .and_then(|scratch| scratch.child_panic.take())
+}
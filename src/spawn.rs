@@ -0,0 +1,152 @@
+// スレッドの生成
+use std::error::Error;
+use std::fmt;
+use std::pin::Pin;
+
+use crate::join::{self, JoinHandle};
+use crate::park::ThreadHandle;
+use crate::{preempt, Runtime, DEFAULT_PRIORITY};
+
+// スレッドを生成できなかった理由
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SpawnError {
+    // 利用可能なスレッドがない
+    PoolExhausted,
+    // スタックを確保できなかった
+    StackAllocation,
+}
+
+impl fmt::Display for SpawnError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SpawnError::PoolExhausted => "no thread is available".fmt(f),
+            SpawnError::StackAllocation => "failed to allocate a thread stack".fmt(f),
+        }
+    }
+}
+
+impl Error for SpawnError {}
+
+impl Runtime {
+    // スレッドを生成する
+    // 利用可能なスレッドがない場合は、他のスレッドを実行して空くまで待つ
+    pub fn spawn<F, T>(self: Pin<&mut Self>, f: F) -> Result<JoinHandle<T>, SpawnError>
+    where
+        F: FnOnce() -> T + 'static,
+        T: 'static,
+    {
+        self.spawn_with_priority(DEFAULT_PRIORITY, f)
+    }
+
+    // 優先度を指定してスレッドを生成する
+    // NOTE: 優先度をどう扱うかはスケジューラによる
+    pub fn spawn_with_priority<F, T>(
+        self: Pin<&mut Self>,
+        priority: u8,
+        f: F,
+    ) -> Result<JoinHandle<T>, SpawnError>
+    where
+        F: FnOnce() -> T + 'static,
+        T: 'static,
+    {
+        unsafe { Runtime::t_spawn(self.get_unchecked_mut(), priority, None, true, f) }
+    }
+
+    // 名前を付けてスレッドを生成する
+    // NOTE: 名前はThreadHandle::nameで取得できる
+    pub fn spawn_named<F, T>(
+        self: Pin<&mut Self>,
+        name: impl Into<String>,
+        f: F,
+    ) -> Result<JoinHandle<T>, SpawnError>
+    where
+        F: FnOnce() -> T + 'static,
+        T: 'static,
+    {
+        let name = Some(name.into());
+        unsafe { Runtime::t_spawn(self.get_unchecked_mut(), DEFAULT_PRIORITY, name, true, f) }
+    }
+
+    // スレッドを生成する
+    // 利用可能なスレッドがない場合は待たずにErr(SpawnError::PoolExhausted)を返す
+    pub fn try_spawn<F, T>(self: Pin<&mut Self>, f: F) -> Result<JoinHandle<T>, SpawnError>
+    where
+        F: FnOnce() -> T + 'static,
+        T: 'static,
+    {
+        unsafe { Runtime::t_spawn(self.get_unchecked_mut(), DEFAULT_PRIORITY, None, false, f) }
+    }
+
+    // スレッドを生成する
+    // waitがtrueの場合は、利用可能なスレッドができるまで他のスレッドを実行して待つ
+    // NOTE: 待つ間は他のスレッドに切り替えるので、&mut Runtimeを持ったまま待たないようにポインタで受け取る
+    //       グリーンスレッドの中から生成する場合はruntime_ptr()を渡す
+    pub(crate) unsafe fn t_spawn<F, T>(
+        rt: *mut Runtime,
+        priority: u8,
+        name: Option<String>,
+        wait: bool,
+        f: F,
+    ) -> Result<JoinHandle<T>, SpawnError>
+    where
+        F: FnOnce() -> T + 'static,
+        T: 'static,
+    {
+        let _guard = preempt::disable();
+        (*rt).inject_spawn_failure()?;
+        let id = Runtime::t_prepare_thread(rt, wait)?;
+        Ok((*rt).spawn_on(id, priority, name, f))
+    }
+
+    pub(crate) fn spawn_on<F, T>(
+        &mut self,
+        id: usize,
+        priority: u8,
+        name: Option<String>,
+        f: F,
+    ) -> JoinHandle<T>
+    where
+        F: FnOnce() -> T + 'static,
+        T: 'static,
+    {
+        // タスク関数はcallから取り出して実行する
+        let (task, handle) = join::wrap(f, ThreadHandle::new(self.thread_id(id)));
+        self.start_thread(id, priority, name, task, handle.token.clone());
+        handle
+    }
+
+    // 利用可能なスレッドを探し、スタックを確保してIDを返す
+    // waitがtrueの場合は、利用可能なスレッドができるまで他のスレッドを実行して待つ
+    // NOTE: 待っても他のスレッドが終わらない場合(すべてブロック中など)はErr(SpawnError::PoolExhausted)を返す
+    pub(crate) fn prepare_thread(&mut self, wait: bool) -> Result<usize, SpawnError> {
+        unsafe { Runtime::t_prepare_thread(self, wait) }
+    }
+
+    // prepare_threadと同じだが、待つ間に切り替えるのでポインタで受け取る
+    pub(crate) unsafe fn t_prepare_thread(
+        rt: *mut Runtime,
+        wait: bool,
+    ) -> Result<usize, SpawnError> {
+        let id = loop {
+            {
+                let rt = &mut *rt;
+                // NOTE: 待っている間に終わったスレッドのスタックもプールに戻す
+                rt.recycle_stacks();
+                if let Some(id) = rt.available_thread() {
+                    break id;
+                }
+            }
+            if !wait || !Runtime::t_run_once(rt) {
+                return Err(SpawnError::PoolExhausted);
+            }
+        };
+        let rt = &mut *rt;
+        // プールに空いているスタックがなければmmapで確保する
+        let stack = rt.stacks.get().map_err(|_| SpawnError::StackAllocation)?;
+        let thread = &mut rt.threads[id];
+        thread.stack = Some(stack);
+        // 新しいタスクに使うので世代を進め、前のタスクのハンドルと見分けられるようにする
+        thread.generation += 1;
+        Ok(id)
+    }
+}
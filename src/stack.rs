@@ -0,0 +1,736 @@
+// スレッドのスタックの確保と使い回し
+// NOTE: スタックはmmapで確保するので、実際に触ったページの分しか物理メモリを使わない
+//       一番下のページはガードページにして、スタックが溢れたらSIGSEGVで止まるようにする
+//
+// 伸ばせるスタック
+// NOTE: 最大の大きさの領域を予約だけしておき、最初は上の方の小さな領域だけを読み書きできるようにする
+//       読み書きできない領域に触れてSIGSEGVが届いたら、シグナルハンドラの中で読み書きできる領域を下に広げ、
+//       同じ命令をもう一度実行させる
+//       アドレスを変えずに伸ばすので、スタックの中を指すポインタやrsp、rbpを書き換える必要はない
+//
+// デバッグビルドでは、プールに戻したスタックの使った領域をFREEDで埋め、読み書きできないようにする
+// NOTE: 終わったスレッドのスタックを指すポインタ(use-after-return)で触れるとSIGSEGVが届くので、
+//       シグナルハンドラで診断を出してabortする
+//       読み書きできなくできないStackAllocatorの領域は、使い回すときとスレッドが終わるときに
+//       FREEDが書き換えられていないかを確かめる
+//
+// スタックの確保方法はStackAllocatorで差し替えられる
+// NOTE: 既定のMmapStackAllocatorは上のとおりmmapで確保する
+//       それ以外(静的なバッファやhugepage、テスト用に数を数えるものなど)はStack::from_memoryで包んで渡す
+use std::cell::Cell;
+use std::io;
+use std::mem::MaybeUninit;
+use std::os::raw::{c_int, c_long, c_void};
+use std::ptr::{self, addr_of, addr_of_mut};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Once;
+
+use crate::preempt::{sigaction, SigAction};
+use crate::CURRENT;
+
+const PROT_NONE: c_int = 0;
+const PROT_READ: c_int = 1;
+const PROT_WRITE: c_int = 2;
+const MAP_PRIVATE: c_int = 0x02;
+#[cfg(target_os = "linux")]
+const MAP_ANONYMOUS: c_int = 0x20;
+#[cfg(not(target_os = "linux"))]
+const MAP_ANONYMOUS: c_int = 0x1000;
+const MAP_FAILED: *mut c_void = !0 as *mut c_void;
+
+// NOTE: LinuxのMADV_DONTNEEDはすぐにページを解放し、次に触ったときはゼロ埋めされたページになる
+//       macOSなどではMADV_DONTNEEDでは解放されないのでMADV_FREEを使う
+#[cfg(target_os = "linux")]
+const MADV_RELEASE: c_int = 4;
+#[cfg(not(target_os = "linux"))]
+const MADV_RELEASE: c_int = 5;
+
+#[cfg(target_os = "linux")]
+const SC_PAGESIZE: c_int = 30;
+#[cfg(not(target_os = "linux"))]
+const SC_PAGESIZE: c_int = 29;
+
+const SIGSEGV: c_int = 11;
+// NOTE: macOSではガードページへのアクセスがSIGBUSで届くことがある
+#[cfg(target_os = "linux")]
+const SIGBUS: c_int = 7;
+#[cfg(not(target_os = "linux"))]
+const SIGBUS: c_int = 10;
+
+#[cfg(target_os = "linux")]
+const SA_SIGINFO: c_int = 0x4;
+#[cfg(target_os = "linux")]
+const SA_ONSTACK: c_int = 0x08000000;
+#[cfg(not(target_os = "linux"))]
+const SA_SIGINFO: c_int = 0x40;
+#[cfg(not(target_os = "linux"))]
+const SA_ONSTACK: c_int = 0x0001;
+
+#[cfg(target_os = "linux")]
+const SS_DISABLE: c_int = 2;
+#[cfg(not(target_os = "linux"))]
+const SS_DISABLE: c_int = 4;
+
+// シグナルハンドラを実行するための代替スタックの大きさ
+const SIGNAL_STACK_SIZE: usize = 64 * 1024;
+// 一度に伸ばすページ数
+const GROW_PAGES: usize = 4;
+
+pub(crate) const DEFAULT_MAX_IDLE_STACKS: usize = 64;
+
+// スレッドが終わってプールに戻すスタックに、前のタスクが書いた値をどう扱うか
+// NOTE: Builder::stack_recycleで指定する
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum StackRecyclePolicy {
+    // 何もせずにそのまま使い回す(一番速い)
+    // NOTE: 次のタスクが初期化していない領域を読むと、前のタスクの値が見える
+    //       デバッグビルドでは、終わったときに使っていた領域をFREEDで埋めるので、その分は前のタスクの値の代わりにFREEDが見える
+    #[default]
+    KeepDirty,
+    // 読み書きできる領域をすべて0で埋める
+    // NOTE: 使ったページの数によらず全体に書き込むので、スタックが大きいと遅く、物理メモリも使う
+    //       poison_stacksを指定した場合は、0の代わりにPOISONで使った分だけを埋め直す
+    Zero,
+    // madvise(MADV_DONTNEED)でページをOSに返す
+    // NOTE: 次に触ったときに0で埋められたページが割り当てられるので、メモリは減るがページフォールトが起きる
+    //       StackAllocatorが用意した領域は返せないので、Zeroと同じく0で埋める
+    Release,
+}
+
+// スタックの使用量を測るために、使っていない領域を埋めておく値
+const POISON: u64 = 0xDEAD_BEEF_DEAD_BEEF;
+
+// デバッグビルドで、プールに戻したスタックを埋めておく値
+#[cfg(debug_assertions)]
+const FREED: u8 = 0xDD;
+
+#[repr(C)]
+struct SigInfo {
+    si_signo: c_int,
+    si_errno: c_int,
+    si_code: c_int,
+    // NOTE: 32bitのLinuxではsi_addrが4byte境界でよいので詰め物はない
+    #[cfg(all(target_os = "linux", target_pointer_width = "64"))]
+    _pad: c_int,
+    #[cfg(not(target_os = "linux"))]
+    _pad: [c_int; 3],
+    si_addr: *mut c_void,
+}
+
+#[cfg(target_os = "linux")]
+#[repr(C)]
+struct SignalStack {
+    ss_sp: *mut c_void,
+    ss_flags: c_int,
+    ss_size: usize,
+}
+
+#[cfg(not(target_os = "linux"))]
+#[repr(C)]
+struct SignalStack {
+    ss_sp: *mut c_void,
+    ss_size: usize,
+    ss_flags: c_int,
+}
+
+extern "C" {
+    fn mmap(
+        addr: *mut c_void,
+        len: usize,
+        prot: c_int,
+        flags: c_int,
+        fd: c_int,
+        offset: c_long,
+    ) -> *mut c_void;
+    fn munmap(addr: *mut c_void, len: usize) -> c_int;
+    fn mprotect(addr: *mut c_void, len: usize, prot: c_int) -> c_int;
+    fn madvise(addr: *mut c_void, len: usize, advice: c_int) -> c_int;
+    fn sysconf(name: c_int) -> c_long;
+    fn sigaltstack(ss: *const SignalStack, old_ss: *mut SignalStack) -> c_int;
+    fn write(fd: c_int, buf: *const c_void, count: usize) -> isize;
+}
+
+// NOTE: シグナルハンドラの中からも使うので、一度取得したら覚えておく
+static PAGE_SIZE: AtomicUsize = AtomicUsize::new(0);
+
+fn page_size() -> usize {
+    let page = PAGE_SIZE.load(Ordering::Relaxed);
+    if page != 0 {
+        return page;
+    }
+    let page = unsafe { sysconf(SC_PAGESIZE) as usize };
+    PAGE_SIZE.store(page, Ordering::Relaxed);
+    page
+}
+
+fn map(len: usize, prot: c_int) -> io::Result<*mut u8> {
+    let addr = unsafe {
+        mmap(
+            ptr::null_mut(),
+            len,
+            prot,
+            MAP_PRIVATE | MAP_ANONYMOUS,
+            -1,
+            0,
+        )
+    };
+    if addr == MAP_FAILED {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(addr as *mut u8)
+}
+
+fn protect(addr: usize, len: usize, prot: c_int) -> io::Result<()> {
+    if unsafe { mprotect(addr as *mut c_void, len, prot) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+pub struct Stack {
+    // ガードページを含めた領域の先頭
+    base: *mut u8,
+    // ガードページを含めた領域の大きさ
+    len: usize,
+    // 読み書きできる領域の一番下のアドレス
+    limit: usize,
+    // 伸ばせるスタックの場合は、最初に読み書きできるようにする大きさ
+    initial: Option<usize>,
+    // POISONで埋めてあるかどうか
+    poisoned: bool,
+    // プールに戻してFREEDで埋めた領域の一番下のアドレス
+    // NOTE: Noneの場合は使われているスタックで、Someの場合はmmapで確保した領域なら読み書きできなくしてある
+    #[cfg(debug_assertions)]
+    freed: Option<usize>,
+    // StackAllocatorが用意した領域
+    // NOTE: Noneの場合はmmapで確保した領域で、ドロップするときにmunmapする
+    memory: Option<Box<dyn AsMut<[u8]>>>,
+    // Valgrindに登録したときのID
+    #[cfg(feature = "sanitize")]
+    valgrind_id: usize,
+}
+
+impl Stack {
+    // sizeの大きさのスタックを確保する
+    // initialを指定した場合は、最初はinitialの分だけ読み書きできるようにし、足りなくなったらsizeまで伸ばす
+    pub(crate) fn new(size: usize, initial: Option<usize>) -> io::Result<Self> {
+        let page = page_size();
+        let len = size.div_ceil(page) * page + page;
+        let initial = initial.map(|initial| initial.div_ceil(page).clamp(1, len / page - 1) * page);
+        let prot = match initial {
+            Some(_) => PROT_NONE,
+            None => PROT_READ | PROT_WRITE,
+        };
+        let base = map(len, prot)?;
+        // NOTE: ここから先で失敗した場合はドロップでmunmapされる
+        let mut stack = Stack {
+            base,
+            len,
+            limit: base as usize + page,
+            initial,
+            poisoned: false,
+            #[cfg(debug_assertions)]
+            freed: None,
+            memory: None,
+            #[cfg(feature = "sanitize")]
+            valgrind_id: 0,
+        };
+        match initial {
+            Some(initial) => {
+                stack.limit = stack.top() as usize - initial;
+                protect(stack.limit, initial, PROT_READ | PROT_WRITE)?;
+            }
+            None => protect(base as usize, page, PROT_NONE)?,
+        }
+        // NOTE: 登録しないと、スタックポインタがこの領域に移ったときにValgrindが別のスタックに移ったと分からない
+        #[cfg(feature = "sanitize")]
+        {
+            stack.valgrind_id =
+                crate::sanitize::stack_register(stack.bottom(), stack.top() as usize);
+        }
+        Ok(stack)
+    }
+
+    // ガードページのあるsizeの大きさのスタックをmmapで確保する
+    pub fn map(size: usize) -> io::Result<Self> {
+        Stack::new(size, None)
+    }
+
+    // StackAllocatorが用意した領域をスタックにする
+    // NOTE: ガードページはないので、溢れた場合は隣のメモリを壊す
+    //       領域はスタックと一緒にドロップされる
+    pub fn from_memory<M>(memory: M) -> Self
+    where
+        M: AsMut<[u8]> + 'static,
+    {
+        // NOTE: Boxに入れてから取り出すので、配列をそのまま渡してもアドレスは変わらない
+        let mut memory: Box<dyn AsMut<[u8]>> = Box::new(memory);
+        let range = (*memory).as_mut().as_mut_ptr_range();
+        #[allow(unused_mut)]
+        let mut stack = Stack {
+            base: range.start,
+            len: range.end as usize - range.start as usize,
+            limit: range.start as usize,
+            initial: None,
+            poisoned: false,
+            #[cfg(debug_assertions)]
+            freed: None,
+            memory: Some(memory),
+            #[cfg(feature = "sanitize")]
+            valgrind_id: 0,
+        };
+        #[cfg(feature = "sanitize")]
+        {
+            stack.valgrind_id =
+                crate::sanitize::stack_register(stack.bottom(), stack.top() as usize);
+        }
+        stack
+    }
+
+    // スタックの一番上のアドレス
+    // NOTE: スタックは上位アドレスから下位アドレスに向かって伸びる
+    pub fn top(&self) -> *mut u8 {
+        unsafe { self.base.add(self.len) }
+    }
+
+    // ガードページの大きさ
+    fn guard(&self) -> usize {
+        if self.memory.is_none() {
+            page_size()
+        } else {
+            0
+        }
+    }
+
+    // ガードページを除いたスタックの一番下のアドレス
+    pub(crate) fn bottom(&self) -> usize {
+        self.base as usize + self.guard()
+    }
+
+    // 今読み書きできる領域の一番下のアドレス
+    // NOTE: 伸ばせるスタックでない場合はbottomと同じ
+    pub(crate) fn limit(&self) -> usize {
+        self.limit
+    }
+
+    // addrにアクセスできるように読み書きできる領域を下に伸ばす
+    // 伸ばせるスタックでない場合や、addrがこのスタックの伸ばせる範囲にない場合はfalseを返す
+    // NOTE: シグナルハンドラから呼ばれるので、メモリの確保やロックをしないこと
+    fn grow(&mut self, addr: usize) -> bool {
+        let page = page_size();
+        let guard_end = self.base as usize + page;
+        if self.initial.is_none() || addr < guard_end || addr >= self.limit {
+            return false;
+        }
+        let limit = (addr & !(page - 1))
+            .saturating_sub(GROW_PAGES * page)
+            .max(guard_end);
+        if protect(limit, self.limit - limit, PROT_READ | PROT_WRITE).is_err() {
+            return false;
+        }
+        if self.poisoned {
+            fill_poison(limit, self.limit);
+        }
+        self.limit = limit;
+        true
+    }
+
+    // ガードページを除いたスタックの大きさ
+    pub fn size(&self) -> usize {
+        self.len - self.guard()
+    }
+
+    // スタックを使った量の最大値を、POISONが書き換えられた一番下のアドレスから求める
+    // POISONで埋めていない場合はNoneを返す
+    // NOTE: 使った領域にたまたまPOISONと同じ値が残っている場合は少なく見積もる
+    pub(crate) fn high_water_mark(&self) -> Option<usize> {
+        if !self.poisoned {
+            return None;
+        }
+        let top = self.top() as usize;
+        let mut addr = self.limit;
+        while addr < top && unsafe { *(addr as *const u64) } == POISON {
+            addr += 8;
+        }
+        Some(top - addr)
+    }
+
+    // 使った領域をPOISONで埋め直す
+    // NOTE: 初めて埋めるときは読み書きできる領域のすべてのページに触れるので、その分の物理メモリが確保される
+    fn poison(&mut self) {
+        let top = self.top() as usize;
+        let from = match self.high_water_mark() {
+            Some(used) => top - used,
+            None => self.limit,
+        };
+        fill_poison(from, top);
+        self.poisoned = true;
+    }
+
+    // 上からusedの分(とその下の1ページ)をFREEDで埋め、mmapで確保した領域なら読み書きできなくする
+    // usedがNoneの場合は埋めずに読み書きできなくするだけにする
+    // NOTE: 使った量は切り替えるときに測った値なので、その間に深くなった分を考えて1ページ余分に埋める
+    //       FREEDで埋めるのは書き換えを見つけるためで、読み書きできなくするのは使った量に関わらず全体
+    #[cfg(debug_assertions)]
+    fn quarantine(&mut self, used: Option<usize>) {
+        let page = page_size();
+        let top = self.top() as usize;
+        let from = match used {
+            Some(used) => top
+                .saturating_sub((used.div_ceil(page) + 1) * page)
+                .max(self.limit),
+            None => top,
+        };
+        unsafe { ptr::write_bytes(from as *mut u8, FREED, top - from) };
+        if self.memory.is_none() {
+            let _ = protect(self.limit, top - self.limit, PROT_NONE);
+        }
+        self.freed = Some(from);
+    }
+
+    // FREEDが書き換えられていないことを確かめてから、読み書きできるように戻す
+    // NOTE: FREEDで埋めるとタスクが残した値は消えるので、デバッグビルドではKeepDirtyでも前のタスクの値は見えず、
+    //       代わりにFREEDが見える(初期化していない領域を読んでいることに気付きやすい)
+    #[cfg(debug_assertions)]
+    fn unquarantine(&mut self, policy: StackRecyclePolicy) {
+        let from = match self.freed {
+            Some(from) => from,
+            None => return,
+        };
+        let top = self.top() as usize;
+        if self.memory.is_none() {
+            protect(self.limit, top - self.limit, PROT_READ | PROT_WRITE)
+                .expect("failed to unprotect a freed stack.");
+        }
+        self.check_freed();
+        self.freed = None;
+        // NOTE: 使用量を測っている場合は、FREEDで上書きした分をPOISONに戻す
+        //       測っていない場合は、ZeroとReleaseでは0に戻し、埋めた0がFREEDのまま残らないようにする
+        //       KeepDirtyではFREEDのまま渡す
+        if self.poisoned {
+            fill_poison(from, top);
+        } else if policy != StackRecyclePolicy::KeepDirty {
+            unsafe { ptr::write_bytes(from as *mut u8, 0, top - from) };
+        }
+    }
+
+    // FREEDで埋めた領域が書き換えられていたらabortする
+    // NOTE: 読み書きできなくしてある場合は書き換えられないので確かめない
+    #[cfg(debug_assertions)]
+    fn check_freed(&self) {
+        let from = match self.freed {
+            Some(from) => from,
+            None => return,
+        };
+        let top = self.top() as usize;
+        let freed = unsafe { std::slice::from_raw_parts(from as *const u8, top - from) };
+        if let Some(offset) = freed.iter().position(|&b| b != FREED) {
+            eprintln!(
+                "fatal: the stack of a finished green thread was modified at {:#x} (use-after-return)",
+                from + offset
+            );
+            std::process::abort();
+        }
+    }
+
+    // addrがプールに戻したスタックの中かどうか
+    #[cfg(debug_assertions)]
+    fn is_freed(&self, addr: usize) -> bool {
+        self.freed.is_some() && self.base as usize <= addr && addr < self.top() as usize
+    }
+
+    // addrがガードページの中かどうか
+    fn is_guard(&self, addr: usize) -> bool {
+        let base = self.base as usize;
+        base <= addr && addr < base + self.guard()
+    }
+
+    // 前のタスクが書いた値が残らないように、読み書きできる領域を0で埋める
+    // NOTE: POISONで埋めてある場合は使った分だけをPOISONで埋め直す
+    fn zero(&mut self) {
+        if self.poisoned {
+            self.poison();
+            return;
+        }
+        let top = self.top() as usize;
+        unsafe { ptr::write_bytes(self.limit as *mut u8, 0, top - self.limit) };
+    }
+
+    // 使ったページをOSに返し、返した場合はtrueを返す
+    // NOTE: 領域はそのまま残るので、次に使うときに改めて確保する必要はない
+    //       伸ばせるスタックは最初の大きさに戻す
+    //       StackAllocatorが用意した領域は返せないので、0で埋めてfalseを返す
+    fn release(&mut self) -> bool {
+        if self.memory.is_some() {
+            self.zero();
+            return false;
+        }
+        let page = page_size();
+        if let Some(initial) = self.initial {
+            let limit = self.top() as usize - initial;
+            if self.limit < limit && protect(self.limit, limit - self.limit, PROT_NONE).is_ok() {
+                self.limit = limit;
+            }
+        }
+        unsafe {
+            madvise(
+                self.base.add(page) as *mut c_void,
+                self.len - page,
+                MADV_RELEASE,
+            );
+        }
+        // NOTE: 返したページはゼロで埋められるので、次に使うときはすべて埋め直す
+        self.poisoned = false;
+        true
+    }
+}
+
+fn fill_poison(from: usize, to: usize) {
+    let mut addr = from;
+    while addr < to {
+        unsafe { (addr as *mut u64).write(POISON) };
+        addr += 8;
+    }
+}
+
+impl Drop for Stack {
+    fn drop(&mut self) {
+        #[cfg(feature = "sanitize")]
+        crate::sanitize::stack_deregister(self.valgrind_id);
+        if self.memory.is_none() {
+            unsafe {
+                munmap(self.base as *mut c_void, self.len);
+            }
+        }
+    }
+}
+
+// スレッドのスタックを確保して解放する方法
+// NOTE: Builder::stack_allocatorでRuntimeに渡す
+pub trait StackAllocator {
+    // sizeの大きさのスタックを確保する
+    fn allocate(&mut self, size: usize) -> io::Result<Stack>;
+
+    // 使い終わったスタックを解放する
+    // NOTE: 渡されるのはallocateで確保したスタックで、もう誰も使っていない
+    fn deallocate(&mut self, stack: Stack) {
+        drop(stack);
+    }
+}
+
+// mmapでガードページ付きのスタックを確保する、既定のStackAllocator
+#[derive(Default)]
+pub struct MmapStackAllocator {
+    // Someの場合は伸ばせるスタックを使い、最初はこの大きさだけ読み書きできるようにする
+    initial: Option<usize>,
+}
+
+impl MmapStackAllocator {
+    pub fn new() -> Self {
+        MmapStackAllocator { initial: None }
+    }
+
+    // 最初はinitialの大きさで始まり、足りなくなったら自動で伸びるスタックを確保する
+    pub fn growable(initial: usize) -> Self {
+        MmapStackAllocator {
+            initial: Some(initial),
+        }
+    }
+}
+
+impl StackAllocator for MmapStackAllocator {
+    fn allocate(&mut self, size: usize) -> io::Result<Stack> {
+        if self.initial.is_some() {
+            install_fault_handler()?;
+        }
+        Stack::new(size, self.initial)
+    }
+}
+
+// 終わったスレッドのスタックを次に生成するスレッドで使い回すためのプール
+pub(crate) struct StackPool {
+    size: usize,
+    pub(crate) allocator: Box<dyn StackAllocator>,
+    idle: Vec<Stack>,
+    // プールに残しておくスタックの最大数、超えた分はmunmapする
+    pub(crate) max_idle: usize,
+    // プールに戻すスタックに残った値の扱い
+    pub(crate) recycle: StackRecyclePolicy,
+    // trueの場合は、使用量を測れるようにスタックをPOISONで埋めてから渡す
+    pub(crate) poison: bool,
+}
+
+impl StackPool {
+    pub(crate) fn new(size: usize) -> Self {
+        StackPool {
+            size,
+            allocator: Box::new(MmapStackAllocator::new()),
+            idle: Vec::new(),
+            max_idle: DEFAULT_MAX_IDLE_STACKS,
+            recycle: StackRecyclePolicy::KeepDirty,
+            poison: false,
+        }
+    }
+
+    // プールにスタックがあれば使い回し、なければ新しく確保する
+    pub(crate) fn get(&mut self) -> io::Result<Stack> {
+        let mut stack = match self.idle.pop() {
+            Some(stack) => stack,
+            None => self.allocator.allocate(self.size)?,
+        };
+        #[cfg(debug_assertions)]
+        stack.unquarantine(self.recycle);
+        if self.poison {
+            stack.poison();
+        }
+        Ok(stack)
+    }
+
+    // 使い終わったスタックをプールに戻す
+    // usedはスタックを使った量で、デバッグビルドではその分をFREEDで埋める
+    // NOTE: 戻すスタックはもう誰も使っていないこと
+    #[cfg_attr(not(debug_assertions), allow(unused_variables))]
+    pub(crate) fn put(&mut self, mut stack: Stack, used: usize) {
+        if self.idle.len() >= self.max_idle {
+            self.allocator.deallocate(stack);
+            return;
+        }
+        let released = match self.recycle {
+            StackRecyclePolicy::KeepDirty => false,
+            StackRecyclePolicy::Zero => {
+                stack.zero();
+                false
+            }
+            StackRecyclePolicy::Release => stack.release(),
+        };
+        // NOTE: OSに返したページはゼロで埋められて物理メモリを使わないので、埋めずに読み書きできなくするだけにする
+        #[cfg(debug_assertions)]
+        {
+            // NOTE: 読み書きできない領域に触れたときに診断を出せるように、シグナルハンドラを登録しておく
+            let _ = install_fault_handler();
+            stack.quarantine((!released).then_some(used));
+        }
+        self.idle.push(stack);
+    }
+
+    // プールにあるスタックのうち、読み書きできなくできなかったもののFREEDが書き換えられていたらabortする
+    #[cfg(debug_assertions)]
+    pub(crate) fn check_freed(&self) {
+        for stack in self.idle.iter().filter(|s| s.memory.is_some()) {
+            stack.check_freed();
+        }
+    }
+}
+
+impl Drop for StackPool {
+    fn drop(&mut self) {
+        for stack in self.idle.drain(..) {
+            // NOTE: StackAllocatorが使い回せるように、読み書きできるように戻してから返す
+            #[cfg(debug_assertions)]
+            let stack = {
+                let mut stack = stack;
+                stack.unquarantine(self.recycle);
+                stack
+            };
+            self.allocator.deallocate(stack);
+        }
+    }
+}
+
+static INSTALL: Once = Once::new();
+// 伸ばせるスタックへのアクセスでなかった場合に戻す、元のSIGSEGVとSIGBUSのハンドラ
+static mut PREV_ACTIONS: [MaybeUninit<SigAction>; 2] =
+    [MaybeUninit::uninit(), MaybeUninit::uninit()];
+
+thread_local! {
+    // このOSスレッドにシグナルハンドラ用の代替スタックを設定したかどうか
+    static SIGNAL_STACK_READY: Cell<bool> = const { Cell::new(false) };
+}
+
+// 伸ばせるスタックのためのSIGSEGVとSIGBUSのハンドラを登録する
+// NOTE: スタックが溢れかけたときにそのスタックの上でハンドラは動かせないので、代替スタックの上で動かす
+//       代替スタックはOSスレッドごとに必要なので、OSスレッドごとに一度だけ設定する
+fn install_fault_handler() -> io::Result<()> {
+    if !SIGNAL_STACK_READY.with(|ready| ready.get()) {
+        set_signal_stack()?;
+        SIGNAL_STACK_READY.with(|ready| ready.set(true));
+    }
+
+    let mut result = Ok(());
+    INSTALL.call_once(|| {
+        let action = SigAction {
+            sa_handler: handle_fault as unsafe extern "C" fn(c_int, *mut SigInfo, *mut c_void)
+                as usize,
+            sa_mask: Default::default(),
+            sa_flags: SA_SIGINFO | SA_ONSTACK,
+            #[cfg(target_os = "linux")]
+            sa_restorer: 0,
+        };
+        for (i, signum) in [SIGSEGV, SIGBUS].into_iter().enumerate() {
+            let prev = unsafe { addr_of_mut!(PREV_ACTIONS[i]) as *mut SigAction };
+            if unsafe { sigaction(signum, &action, prev) } < 0 {
+                result = Err(io::Error::last_os_error());
+                return;
+            }
+        }
+    });
+    result
+}
+
+// 代替スタックがまだ設定されていなければ設定する
+// NOTE: 標準ライブラリがすでに設定している場合はそれを使う
+//       確保した代替スタックはOSスレッドが終わるまで使われるので解放しない
+fn set_signal_stack() -> io::Result<()> {
+    let mut old = MaybeUninit::<SignalStack>::uninit();
+    if unsafe { sigaltstack(ptr::null(), old.as_mut_ptr()) } < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    if unsafe { old.assume_init() }.ss_flags & SS_DISABLE == 0 {
+        return Ok(());
+    }
+    let sp = map(SIGNAL_STACK_SIZE, PROT_READ | PROT_WRITE)?;
+    let stack = SignalStack {
+        ss_sp: sp as *mut c_void,
+        ss_flags: 0,
+        ss_size: SIGNAL_STACK_SIZE,
+    };
+    if unsafe { sigaltstack(&stack, ptr::null_mut()) } < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+unsafe extern "C" fn handle_fault(signum: c_int, info: *mut SigInfo, _ctx: *mut c_void) {
+    let addr = (*info).si_addr as usize;
+    let rt = CURRENT.with(|current| current.get());
+    if !rt.is_null() {
+        // 実行中のスレッドのスタックが足りなくなった場合は伸ばして戻る
+        let threads = (*rt).threads.as_mut_ptr();
+        let stack = &mut (*threads.add((*rt).current)).stack;
+        if let Some(stack) = stack {
+            if stack.grow(addr) {
+                return;
+            }
+            if stack.is_guard(addr) {
+                let msg = "green thread has overflowed its stack\n";
+                write(2, msg.as_ptr() as *const c_void, msg.len());
+            }
+        }
+        // 終わったスレッドのスタックに触れた場合は、そのまま続けても壊れるだけなのでabortする
+        #[cfg(debug_assertions)]
+        if (*rt).stacks.idle.iter().any(|s| s.is_freed(addr)) {
+            let msg =
+                "fatal: green thread touched the stack of a finished thread (use-after-return)\n";
+            write(2, msg.as_ptr() as *const c_void, msg.len());
+            std::process::abort();
+        }
+    }
+    // 元のハンドラに戻してから戻り、同じ命令をもう一度実行させる
+    // NOTE: 本当にスタックが溢れた場合や不正なアクセスは、元のハンドラ(標準ライブラリなど)に任せる
+    let i = if signum == SIGSEGV { 0 } else { 1 };
+    sigaction(
+        signum,
+        addr_of!(PREV_ACTIONS[i]) as *const SigAction,
+        ptr::null_mut(),
+    );
+}
@@ -0,0 +1,92 @@
+// スケジューリングの性能を比べるための統計情報
+use std::time::Duration;
+
+use crate::{Runtime, State};
+
+// Runtime::statsで返す統計情報
+#[derive(Debug, Clone)]
+pub struct Stats {
+    // スレッドを切り替えた回数の合計
+    pub switches: u64,
+    // 再開可能なスレッドの数
+    pub ready: usize,
+    // ブロック中やpark中のスレッドの数
+    pub blocked: usize,
+    // 利用可能なスレッドの数
+    pub available: usize,
+    pub threads: Vec<ThreadStats>,
+}
+
+// スレッドごとの統計情報
+// NOTE: スレッドを生成するたびにリセットされるので、終わったスレッドの値は次に同じIDで生成されるまで残る
+#[derive(Debug, Clone)]
+pub struct ThreadStats {
+    pub id: usize,
+    pub name: Option<String>,
+    // このスレッドに切り替えた回数
+    pub switches: u64,
+    // このスレッドが実行されていた時間
+    // NOTE: ベーススレッドはOSスレッドごと休止している時間も含む
+    pub run_time: Duration,
+    // スタックを使った量の最大値(バイト)
+    // NOTE: スタックをPOISONで埋めている場合はその書き換えから測り、そうでなければ切り替えるときの
+    //       スタックポインタから測るので、切り替えの間に一時的に深くなった分は含まない
+    pub peak_stack: usize,
+    // プリエンプションで強制的に切り替えられた回数
+    pub preemptions: u64,
+    // スケジューラのキューのレベル
+    // NOTE: キューのレベルを持たないスケジューラ(MLFQ以外)ではNone
+    pub queue_level: Option<usize>,
+}
+
+// Threadに持たせるカウンタ
+#[derive(Debug, Default)]
+pub(crate) struct Counters {
+    pub(crate) switches: u64,
+    pub(crate) run_time: Duration,
+    pub(crate) peak_stack: usize,
+    pub(crate) preemptions: u64,
+}
+
+impl Runtime {
+    pub fn stats(&self) -> Stats {
+        let count = |f: fn(&State) -> bool| self.threads.iter().filter(|t| f(&t.state)).count();
+        Stats {
+            switches: self.switches,
+            ready: count(|s| *s == State::Ready),
+            blocked: count(|s| matches!(s, State::Blocked | State::Parked)),
+            available: count(|s| *s == State::Available),
+            threads: self
+                .threads
+                .iter()
+                .map(|t| ThreadStats {
+                    id: t.id,
+                    name: t.name.clone(),
+                    switches: t.counters.switches,
+                    run_time: t.counters.run_time,
+                    peak_stack: t
+                        .stack
+                        .as_ref()
+                        .and_then(|s| s.high_water_mark())
+                        .map_or(t.counters.peak_stack, |hwm| hwm.max(t.counters.peak_stack)),
+                    preemptions: t.counters.preemptions,
+                    queue_level: self.scheduler.level(t.id),
+                })
+                .collect(),
+        }
+    }
+
+    // 切り替え元のスレッドの実行時間とスタックの使用量、切り替え先のスレッドの切り替え回数を記録する
+    // rspは切り替え元のスレッドのスタックポインタ
+    pub(crate) fn record_switch(&mut self, from: usize, to: usize, rsp: usize) {
+        let now = std::time::Instant::now();
+        let thread = &mut self.threads[from];
+        thread.counters.run_time += now - self.running_since;
+        if let Some(stack) = &thread.stack {
+            let usage = (stack.top() as usize).saturating_sub(rsp);
+            thread.counters.peak_stack = thread.counters.peak_stack.max(usage);
+        }
+        self.threads[to].counters.switches += 1;
+        self.running_since = now;
+    }
+}
@@ -0,0 +1,239 @@
+// スレッドを監視し、パニックしたら作り直すスーパーバイザ
+// NOTE: 子はスレッドを作るたびに呼ぶ関数として登録し、パニックで終わったら再起動の方針に従って同じ関数でスレッドを作り直す
+//       パニックせずに戻った子は作り直さず、すべての子が戻ったらスーパーバイザも終わる
+//       子の中でSupervisor::runを呼べば、スーパーバイザを木のように重ねられる
+use std::collections::VecDeque;
+use std::error::Error;
+use std::fmt;
+use std::pin::Pin;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+use crate::sync::mpsc;
+use crate::{runtime_ptr, JoinHandle, Runtime, SpawnError, DEFAULT_PRIORITY};
+
+// 子がパニックしたときに、どの子を作り直すか
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum RestartStrategy {
+    // パニックした子だけを作り直す
+    OneForOne,
+    // 他の子もキャンセルして終わるのを待ち、すべて作り直す
+    // NOTE: 子同士が互いに依存していて、1つだけ作り直しても辻褄が合わない場合に使う
+    OneForAll,
+}
+
+// 再起動の回数の上限の既定値(Erlang/OTPと同じく5秒間に3回まで)
+const DEFAULT_MAX_RESTARTS: usize = 3;
+const DEFAULT_RESTART_WINDOW: Duration = Duration::from_secs(5);
+
+struct Child {
+    name: String,
+    start: Rc<dyn Fn()>,
+    handle: Option<JoinHandle<()>>,
+    // 作り直した回数
+    // NOTE: 作り直す前のスレッドからの終了の知らせを見分けるのに使う
+    generation: u64,
+}
+
+pub struct Supervisor {
+    strategy: RestartStrategy,
+    max_restarts: usize,
+    window: Duration,
+    children: Vec<Child>,
+    // 直近の再起動の時刻
+    restarts: VecDeque<Instant>,
+}
+
+// 子のスレッドが終わったことを、パニックした場合も含めてスーパーバイザに知らせる
+// NOTE: 子のスレッドのスタックに置いておき、ドロップ(巻き戻し中も含む)で送る
+struct ExitNotice {
+    exits: mpsc::Sender<(usize, u64)>,
+    index: usize,
+    generation: u64,
+}
+
+impl Drop for ExitNotice {
+    fn drop(&mut self) {
+        let _ = self.exits.send((self.index, self.generation));
+    }
+}
+
+impl Supervisor {
+    pub fn new(strategy: RestartStrategy) -> Self {
+        Supervisor {
+            strategy,
+            max_restarts: DEFAULT_MAX_RESTARTS,
+            window: DEFAULT_RESTART_WINDOW,
+            children: Vec::new(),
+            restarts: VecDeque::new(),
+        }
+    }
+
+    // windowの間にmax_restarts回より多く再起動が必要になったら、諦めてすべての子を止める
+    pub fn max_restarts(mut self, max_restarts: usize, window: Duration) -> Self {
+        self.max_restarts = max_restarts;
+        self.window = window;
+        self
+    }
+
+    // 子を追加する
+    // startはスレッドを作るたびに呼ばれる
+    pub fn child<F>(mut self, name: impl Into<String>, start: F) -> Self
+    where
+        F: Fn() + 'static,
+    {
+        self.children.push(Child {
+            name: name.into(),
+            start: Rc::new(start),
+            handle: None,
+            generation: 0,
+        });
+        self
+    }
+
+    // 現在のスレッドで子を監視する
+    // すべての子がパニックせずに戻るとOk(())を返す
+    pub fn run(mut self) -> Result<(), SupervisorError> {
+        let (exits, notices) = mpsc::channel();
+        for index in 0..self.children.len() {
+            if let Err(e) = self.start_child(index, &exits) {
+                self.stop_all();
+                return Err(e);
+            }
+        }
+
+        while self.children.iter().any(|c| c.handle.is_some()) {
+            let (index, generation) = match notices.recv() {
+                Ok(notice) => notice,
+                // NOTE: exitsを持っているので、Errになるのはキャンセルされた場合だけ
+                Err(_) => {
+                    self.stop_all();
+                    return Err(SupervisorError::Cancelled);
+                }
+            };
+            if self.children[index].generation != generation {
+                continue;
+            }
+            let handle = match self.children[index].handle.take() {
+                Some(handle) => handle,
+                None => continue,
+            };
+            if handle.join().is_ok() {
+                continue;
+            }
+
+            if !self.record_restart() {
+                self.stop_all();
+                return Err(SupervisorError::TooManyRestarts(
+                    self.children[index].name.clone(),
+                ));
+            }
+            let restart = match self.strategy {
+                RestartStrategy::OneForOne => vec![index],
+                RestartStrategy::OneForAll => {
+                    let running: Vec<usize> = (0..self.children.len())
+                        .filter(|&i| i == index || self.children[i].handle.is_some())
+                        .collect();
+                    self.stop_all();
+                    running
+                }
+            };
+            for index in restart {
+                if let Err(e) = self.start_child(index, &exits) {
+                    self.stop_all();
+                    return Err(e);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn start_child(
+        &mut self,
+        index: usize,
+        exits: &mpsc::Sender<(usize, u64)>,
+    ) -> Result<(), SupervisorError> {
+        let child = &mut self.children[index];
+        child.generation += 1;
+        let start = child.start.clone();
+        let notice = ExitNotice {
+            exits: exits.clone(),
+            index,
+            generation: child.generation,
+        };
+        // NOTE: Scope::spawnと同じく、利用可能なスレッドがなければ他のスレッドを実行して空くまで待つ
+        //       待つ間は他のスレッドに切り替えるので、&mut Runtimeを作らずにポインタのまま渡す
+        let handle = unsafe {
+            Runtime::t_spawn(runtime_ptr(), DEFAULT_PRIORITY, None, true, move || {
+                let _notice = notice;
+                start();
+            })
+        }
+        .map_err(|e| SupervisorError::Spawn(self.children[index].name.clone(), e))?;
+        self.children[index].handle = Some(handle);
+        Ok(())
+    }
+
+    // 動いている子をすべてキャンセルし、終わるまで待つ
+    // NOTE: キャンセルは協調的なので、キャンセルを確認しない子がいると戻らない
+    fn stop_all(&mut self) {
+        for child in &self.children {
+            if let Some(handle) = &child.handle {
+                handle.cancel();
+            }
+        }
+        for child in &mut self.children {
+            if let Some(handle) = child.handle.take() {
+                let _ = handle.join();
+            }
+        }
+    }
+
+    // 再起動を記録し、上限を超えていなければtrueを返す
+    fn record_restart(&mut self) -> bool {
+        let now = Instant::now();
+        while let Some(&oldest) = self.restarts.front() {
+            if now.duration_since(oldest) < self.window {
+                break;
+            }
+            self.restarts.pop_front();
+        }
+        self.restarts.push_back(now);
+        self.restarts.len() <= self.max_restarts
+    }
+}
+
+// スーパーバイザが子の監視をやめた理由
+#[derive(Debug)]
+pub enum SupervisorError {
+    // 名前の子がパニックして、再起動の回数の上限を超えた
+    TooManyRestarts(String),
+    // 名前の子のスレッドを生成できなかった
+    Spawn(String, SpawnError),
+    // スーパーバイザのスレッドがキャンセルされた
+    Cancelled,
+}
+
+impl fmt::Display for SupervisorError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SupervisorError::TooManyRestarts(name) => {
+                write!(f, "child {} restarted too many times", name)
+            }
+            SupervisorError::Spawn(name, e) => write!(f, "failed to spawn child {}: {}", name, e),
+            SupervisorError::Cancelled => "supervisor was cancelled".fmt(f),
+        }
+    }
+}
+
+impl Error for SupervisorError {}
+
+impl Runtime {
+    // スーパーバイザを動かすスレッドを生成する
+    pub fn spawn_supervisor(
+        self: Pin<&mut Self>,
+        supervisor: Supervisor,
+    ) -> Result<JoinHandle<Result<(), SupervisorError>>, SpawnError> {
+        self.spawn(move || supervisor.run())
+    }
+}
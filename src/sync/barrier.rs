@@ -0,0 +1,59 @@
+use std::cell::Cell;
+
+use super::WaitQueue;
+
+// 決まった数のスレッドがそろうまで待ち合わせるバリア
+// 最後に到着したスレッドが待っているスレッドをすべて起こす
+// NOTE: すべてのスレッドがそろうと最初の状態に戻るので、続けて何度でも使える
+pub struct Barrier {
+    n: usize,
+    // 今の回に到着したスレッドの数
+    count: Cell<usize>,
+    // 何回目の待ち合わせか
+    // NOTE: 起こされたスレッドが、自分の回の待ち合わせが終わったかどうかを確かめるのに使う
+    generation: Cell<usize>,
+    waiters: WaitQueue,
+}
+
+// waitの結果
+pub struct BarrierWaitResult {
+    is_leader: bool,
+}
+
+impl BarrierWaitResult {
+    // 最後に到着したスレッドかどうか
+    // NOTE: 1回の待ち合わせでtrueになるのは1つのスレッドだけ
+    pub fn is_leader(&self) -> bool {
+        self.is_leader
+    }
+}
+
+impl Barrier {
+    pub fn new(n: usize) -> Self {
+        Barrier {
+            n,
+            count: Cell::new(0),
+            generation: Cell::new(0),
+            waiters: WaitQueue::new("Barrier::wait"),
+        }
+    }
+
+    // n個のスレッドがwaitを呼ぶまでブロックする
+    pub fn wait(&self) -> BarrierWaitResult {
+        let _guard = crate::preempt::disable();
+        let generation = self.generation.get();
+        let count = self.count.get() + 1;
+        if count < self.n {
+            self.count.set(count);
+            while self.generation.get() == generation {
+                self.waiters.wait();
+            }
+            BarrierWaitResult { is_leader: false }
+        } else {
+            self.count.set(0);
+            self.generation.set(generation.wrapping_add(1));
+            self.waiters.notify_all();
+            BarrierWaitResult { is_leader: true }
+        }
+    }
+}
@@ -0,0 +1,57 @@
+use super::{MutexGuard, WaitQueue};
+
+// グリーンスレッド用の条件変数
+pub struct Condvar {
+    waiters: WaitQueue,
+}
+
+impl Condvar {
+    pub fn new() -> Self {
+        Condvar {
+            waiters: WaitQueue::new("Condvar::wait"),
+        }
+    }
+
+    // ロックを解放してnotifyされるまでブロックし、起こされたらロックを取り直して返す
+    // NOTE: ロックの解放から待ちキューに入るまでの間に他のスレッドへ切り替わることはなく、
+    //       notifyを取りこぼすことはない
+    pub fn wait<'a, T>(&self, guard: MutexGuard<'a, T>) -> MutexGuard<'a, T> {
+        // NOTE: ロックの解放から待ちキューに入るまでの間にプリエンプションで切り替わらないようにする
+        let _guard = crate::preempt::disable();
+        let mutex = guard.mutex;
+        drop(guard);
+        self.waiters.wait();
+        mutex.lock()
+    }
+
+    // conditionがtrueを返す間待ち続ける
+    pub fn wait_while<'a, T, F>(
+        &self,
+        mut guard: MutexGuard<'a, T>,
+        mut condition: F,
+    ) -> MutexGuard<'a, T>
+    where
+        F: FnMut(&mut T) -> bool,
+    {
+        while condition(&mut *guard) {
+            guard = self.wait(guard);
+        }
+        guard
+    }
+
+    // 待っているスレッドを1つ起こす
+    pub fn notify_one(&self) {
+        self.waiters.notify_one();
+    }
+
+    // 待っているスレッドをすべて起こす
+    pub fn notify_all(&self) {
+        self.waiters.notify_all();
+    }
+}
+
+impl Default for Condvar {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,24 @@
+// グリーンスレッド用の同期プリミティブ
+// NOTE: OSスレッドをブロックせず、待ちが発生したらBlocked(ブロック中)にして他のスレッドに切り替える
+mod barrier;
+mod condvar;
+pub mod mpsc;
+mod mutex;
+pub mod oneshot;
+mod pipe;
+mod rwlock;
+mod select;
+mod semaphore;
+mod wait_group;
+mod wait_queue;
+
+pub use barrier::{Barrier, BarrierWaitResult};
+pub use condvar::Condvar;
+pub use mutex::{Mutex, MutexGuard};
+pub use pipe::{pipe, pipe_with_capacity, PipeReader, PipeWriter};
+pub use rwlock::{RwLock, RwLockPreference, RwLockReadGuard, RwLockWriteGuard};
+pub use select::Select;
+pub use semaphore::{Semaphore, SemaphorePermit};
+pub use wait_group::WaitGroup;
+
+pub(crate) use wait_queue::WaitQueue;
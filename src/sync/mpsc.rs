@@ -0,0 +1,354 @@
+// グリーンスレッド間のMPSCチャネル
+// NOTE: 送信側は満杯のとき、受信側は空のときにブロックして他のスレッドに切り替える
+use std::cell::{Cell, RefCell};
+use std::collections::VecDeque;
+use std::error::Error;
+use std::fmt;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+use super::select::Selectable;
+use super::WaitQueue;
+
+struct Shared<T> {
+    queue: RefCell<VecDeque<T>>,
+    // Noneの場合は上限なし
+    bound: Option<usize>,
+    senders: Cell<usize>,
+    receiver_alive: Cell<bool>,
+    // 受信待ちのスレッド
+    recv_waiters: WaitQueue,
+    // 空き待ちのスレッド
+    send_waiters: WaitQueue,
+}
+
+impl<T> Shared<T> {
+    fn is_full(&self) -> bool {
+        match self.bound {
+            Some(bound) => self.queue.borrow().len() >= bound,
+            None => false,
+        }
+    }
+}
+
+// 上限なしのチャネルを作る
+// sendはブロックしない
+pub fn channel<T>() -> (Sender<T>, Receiver<T>) {
+    new_channel(None)
+}
+
+// 最大bound個まで値を溜められるチャネルを作る
+// 満杯の場合、sendは空きができるまでブロックする
+pub fn sync_channel<T>(bound: usize) -> (Sender<T>, Receiver<T>) {
+    assert!(bound > 0, "bound of sync_channel must be greater than 0.");
+    new_channel(Some(bound))
+}
+
+fn new_channel<T>(bound: Option<usize>) -> (Sender<T>, Receiver<T>) {
+    let shared = Rc::new(Shared {
+        queue: RefCell::new(VecDeque::new()),
+        bound,
+        senders: Cell::new(1),
+        receiver_alive: Cell::new(true),
+        recv_waiters: WaitQueue::new("Receiver::recv"),
+        send_waiters: WaitQueue::new("Sender::send"),
+    });
+    (
+        Sender {
+            shared: shared.clone(),
+        },
+        Receiver { shared },
+    )
+}
+
+pub struct Sender<T> {
+    shared: Rc<Shared<T>>,
+}
+
+impl<T> Sender<T> {
+    // 値を送信する
+    // チャネルが満杯の場合は空きができるまでブロックする
+    // 受信側がドロップされている場合は値をそのまま返す
+    pub fn send(&self, mut t: T) -> Result<(), SendError<T>> {
+        crate::consume_budget();
+        // NOTE: 満杯を確認してから待ちキューに入るまでの間に切り替わると、起こされそこねるので止める
+        let _guard = crate::preempt::disable();
+        loop {
+            match self.try_send(t) {
+                Ok(()) => return Ok(()),
+                Err(TrySendError::Disconnected(v)) => return Err(SendError(v)),
+                Err(TrySendError::Full(v)) => {
+                    // 空きができるまで待ってから改めて送信を試みる
+                    t = v;
+                    self.shared.send_waiters.wait();
+                }
+            }
+        }
+    }
+
+    // ブロックせずに値を送信する
+    pub fn try_send(&self, t: T) -> Result<(), TrySendError<T>> {
+        #[cfg(greenthreads_model)]
+        crate::model::yield_point();
+        let _guard = crate::preempt::disable();
+        let shared = &self.shared;
+        if !shared.receiver_alive.get() {
+            return Err(TrySendError::Disconnected(t));
+        }
+        if shared.is_full() {
+            return Err(TrySendError::Full(t));
+        }
+        shared.queue.borrow_mut().push_back(t);
+        #[cfg(greenthreads_model)]
+        assert!(
+            shared
+                .bound
+                .is_none_or(|bound| shared.queue.borrow().len() <= bound),
+            "model: channel holds more values than its bound."
+        );
+        // 受信待ちのスレッドを起こす
+        shared.recv_waiters.notify_one();
+        Ok(())
+    }
+}
+
+impl<T> Clone for Sender<T> {
+    fn clone(&self) -> Self {
+        let _guard = crate::preempt::disable();
+        self.shared.senders.set(self.shared.senders.get() + 1);
+        Sender {
+            shared: self.shared.clone(),
+        }
+    }
+}
+
+impl<T> Drop for Sender<T> {
+    fn drop(&mut self) {
+        let _guard = crate::preempt::disable();
+        let senders = self.shared.senders.get() - 1;
+        self.shared.senders.set(senders);
+        // 最後の送信側がいなくなったら、受信待ちのスレッドにエラーを返させる
+        if senders == 0 {
+            self.shared.recv_waiters.notify_all();
+        }
+    }
+}
+
+pub struct Receiver<T> {
+    shared: Rc<Shared<T>>,
+}
+
+impl<T> Receiver<T> {
+    // 値を受信する
+    // チャネルが空の場合は値が届くまでブロックする
+    // 送信側がすべてドロップされ、値が残っていない場合やキャンセルされた場合はエラーを返す
+    pub fn recv(&self) -> Result<T, RecvError> {
+        crate::consume_budget();
+        // NOTE: 空を確認してから待ちキューに入るまでの間に切り替わると、起こされそこねるので止める
+        let _guard = crate::preempt::disable();
+        loop {
+            match self.try_recv() {
+                Ok(t) => return Ok(t),
+                Err(TryRecvError::Disconnected) => return Err(RecvError::Disconnected),
+                Err(TryRecvError::Empty) => {
+                    if self.shared.recv_waiters.wait_cancellable().is_err() {
+                        return Err(RecvError::Cancelled);
+                    }
+                }
+            }
+        }
+    }
+
+    // recvと同じだが、durが経過しても値が届かない場合はErr(Timeout)を返す
+    pub fn recv_timeout(&self, dur: Duration) -> Result<T, RecvTimeoutError> {
+        self.recv_until(Instant::now() + dur)
+    }
+
+    // recvと同じだが、deadlineになっても値が届かない場合はErr(Timeout)を返す
+    pub fn recv_until(&self, deadline: Instant) -> Result<T, RecvTimeoutError> {
+        let _guard = crate::preempt::disable();
+        loop {
+            match self.try_recv() {
+                Ok(t) => return Ok(t),
+                Err(TryRecvError::Disconnected) => return Err(RecvTimeoutError::Disconnected),
+                Err(TryRecvError::Empty) => {
+                    match self.shared.recv_waiters.wait_cancellable_until(deadline) {
+                        Ok(true) => {}
+                        Ok(false) => return Err(RecvTimeoutError::Timeout),
+                        Err(_) => return Err(RecvTimeoutError::Cancelled),
+                    }
+                }
+            }
+        }
+    }
+
+    // ブロックせずに値を受信する
+    pub fn try_recv(&self) -> Result<T, TryRecvError> {
+        #[cfg(greenthreads_model)]
+        crate::model::yield_point();
+        let _guard = crate::preempt::disable();
+        let shared = &self.shared;
+        let t = shared.queue.borrow_mut().pop_front();
+        match t {
+            Some(t) => {
+                // 空きができたので送信待ちのスレッドを起こす
+                shared.send_waiters.notify_one();
+                Ok(t)
+            }
+            None if shared.senders.get() == 0 => Err(TryRecvError::Disconnected),
+            None => Err(TryRecvError::Empty),
+        }
+    }
+
+    // 送信側がすべてドロップされるまで値を受信し続けるイテレータを返す
+    pub fn iter(&self) -> Iter<'_, T> {
+        Iter { rx: self }
+    }
+}
+
+impl<T> Selectable for Receiver<T> {
+    // 値が届いているか、送信側がすべてドロップされていればrecvはすぐに戻る
+    fn is_ready(&self) -> bool {
+        !self.shared.queue.borrow().is_empty() || self.shared.senders.get() == 0
+    }
+
+    fn waiters(&self) -> &WaitQueue {
+        &self.shared.recv_waiters
+    }
+}
+
+impl<T> Drop for Receiver<T> {
+    fn drop(&mut self) {
+        let _guard = crate::preempt::disable();
+        self.shared.receiver_alive.set(false);
+        // 空き待ちのスレッドにエラーを返させる
+        self.shared.send_waiters.notify_all();
+    }
+}
+
+pub struct Iter<'a, T> {
+    rx: &'a Receiver<T>,
+}
+
+impl<T> Iterator for Iter<'_, T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        self.rx.recv().ok()
+    }
+}
+
+impl<'a, T> IntoIterator for &'a Receiver<T> {
+    type Item = T;
+    type IntoIter = Iter<'a, T>;
+
+    fn into_iter(self) -> Iter<'a, T> {
+        self.iter()
+    }
+}
+
+// 受信側がドロップされていて送信できなかった
+#[derive(PartialEq, Eq, Clone, Copy)]
+pub struct SendError<T>(pub T);
+
+impl<T> fmt::Debug for SendError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SendError").finish_non_exhaustive()
+    }
+}
+
+impl<T> fmt::Display for SendError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        "sending on a closed channel".fmt(f)
+    }
+}
+
+impl<T> Error for SendError<T> {}
+
+#[derive(PartialEq, Eq, Clone, Copy)]
+pub enum TrySendError<T> {
+    // チャネルが満杯
+    Full(T),
+    // 受信側がドロップされている
+    Disconnected(T),
+}
+
+impl<T> fmt::Debug for TrySendError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TrySendError::Full(..) => "Full(..)".fmt(f),
+            TrySendError::Disconnected(..) => "Disconnected(..)".fmt(f),
+        }
+    }
+}
+
+impl<T> fmt::Display for TrySendError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TrySendError::Full(..) => "sending on a full channel".fmt(f),
+            TrySendError::Disconnected(..) => "sending on a closed channel".fmt(f),
+        }
+    }
+}
+
+impl<T> Error for TrySendError<T> {}
+
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum RecvError {
+    // 送信側がすべてドロップされている
+    Disconnected,
+    // 受信を待っている間にキャンセルされた
+    Cancelled,
+}
+
+impl fmt::Display for RecvError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RecvError::Disconnected => "receiving on a closed channel".fmt(f),
+            RecvError::Cancelled => "receiving was cancelled".fmt(f),
+        }
+    }
+}
+
+impl Error for RecvError {}
+
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum TryRecvError {
+    // チャネルが空
+    Empty,
+    // 送信側がすべてドロップされている
+    Disconnected,
+}
+
+impl fmt::Display for TryRecvError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TryRecvError::Empty => "receiving on an empty channel".fmt(f),
+            TryRecvError::Disconnected => "receiving on a closed channel".fmt(f),
+        }
+    }
+}
+
+impl Error for TryRecvError {}
+
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum RecvTimeoutError {
+    // 期限までに値が届かなかった
+    Timeout,
+    // 送信側がすべてドロップされている
+    Disconnected,
+    // 受信を待っている間にキャンセルされた
+    Cancelled,
+}
+
+impl fmt::Display for RecvTimeoutError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RecvTimeoutError::Timeout => "timed out waiting on a channel".fmt(f),
+            RecvTimeoutError::Disconnected => "receiving on a closed channel".fmt(f),
+            RecvTimeoutError::Cancelled => "receiving was cancelled".fmt(f),
+        }
+    }
+}
+
+impl Error for RecvTimeoutError {}
@@ -0,0 +1,134 @@
+use std::cell::{Cell, UnsafeCell};
+use std::ops::{Deref, DerefMut};
+use std::time::{Duration, Instant};
+
+use super::WaitQueue;
+
+// グリーンスレッド用のMutex
+// ロックが取れない場合はOSスレッドをブロックせず、ロックが解放されるまで他のスレッドに切り替える
+// NOTE: 待っている間は、ロックを持っているスレッドに自分の優先度を継承させる(優先度の逆転を防ぐ)
+pub struct Mutex<T> {
+    // ロックを持っているスレッド
+    owner: Cell<Option<usize>>,
+    waiters: WaitQueue,
+    data: UnsafeCell<T>,
+}
+
+impl<T> Mutex<T> {
+    pub fn new(data: T) -> Self {
+        Mutex {
+            owner: Cell::new(None),
+            waiters: WaitQueue::new("Mutex::lock"),
+            data: UnsafeCell::new(data),
+        }
+    }
+
+    // ロックを取得する
+    // 他のスレッドがロックを持っている場合は解放されるまでブロックする
+    pub fn lock(&self) -> MutexGuard<'_, T> {
+        #[cfg(greenthreads_model)]
+        crate::model::yield_point();
+        crate::consume_budget();
+        let _guard = crate::preempt::disable();
+        // 起こされた後に別のスレッドが先にロックを取っている可能性があるのでループで確認する
+        while let Some(owner) = self.owner.get() {
+            crate::inherit_priority(owner, self.waiters.addr());
+            self.waiters.wait_for(Some(owner));
+        }
+        self.acquire();
+        MutexGuard { mutex: self }
+    }
+
+    // ロックを取得する
+    // durが経過してもロックが取れない場合はNoneを返す
+    pub fn lock_timeout(&self, dur: Duration) -> Option<MutexGuard<'_, T>> {
+        self.lock_until(Instant::now() + dur)
+    }
+
+    // ロックを取得する
+    // deadlineになってもロックが取れない場合はNoneを返す
+    pub fn lock_until(&self, deadline: Instant) -> Option<MutexGuard<'_, T>> {
+        #[cfg(greenthreads_model)]
+        crate::model::yield_point();
+        crate::consume_budget();
+        let _guard = crate::preempt::disable();
+        while let Some(owner) = self.owner.get() {
+            // NOTE: 期限が過ぎて諦めた場合も、継承させた優先度はownerがロックを手放すまで残る
+            crate::inherit_priority(owner, self.waiters.addr());
+            if !self.waiters.wait_until(deadline, Some(owner)) {
+                return None;
+            }
+        }
+        self.acquire();
+        Some(MutexGuard { mutex: self })
+    }
+
+    // ロックを取得できる場合のみ取得する
+    pub fn try_lock(&self) -> Option<MutexGuard<'_, T>> {
+        #[cfg(greenthreads_model)]
+        crate::model::yield_point();
+        let _guard = crate::preempt::disable();
+        if self.owner.get().is_some() {
+            return None;
+        }
+        self.acquire();
+        Some(MutexGuard { mutex: self })
+    }
+
+    pub fn get_mut(&mut self) -> &mut T {
+        self.data.get_mut()
+    }
+
+    pub fn into_inner(self) -> T {
+        self.data.into_inner()
+    }
+
+    // 現在のスレッドをロックの持ち主にする
+    // まだ待っているスレッドがいれば、その優先度を継承する
+    fn acquire(&self) {
+        self.owner.set(Some(crate::current_thread()));
+        let waiting = self.waiters.waiting();
+        if !waiting.is_empty() {
+            crate::acquire_priority(self.waiters.addr(), &waiting);
+        }
+    }
+
+    fn unlock(&self) {
+        let _guard = crate::preempt::disable();
+        #[cfg(greenthreads_model)]
+        assert!(
+            self.owner.get().is_some(),
+            "model: Mutex unlocked while not locked."
+        );
+        self.owner.set(None);
+        crate::release_priority(self.waiters.addr());
+        // 待っているスレッドを1つだけ起こす
+        self.waiters.notify_one();
+    }
+}
+
+// ロックを保持している間だけ中身にアクセスできるガード
+// ドロップ時にロックを解放する
+pub struct MutexGuard<'a, T> {
+    pub(super) mutex: &'a Mutex<T>,
+}
+
+impl<T> Deref for MutexGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        unsafe { &*self.mutex.data.get() }
+    }
+}
+
+impl<T> DerefMut for MutexGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { &mut *self.mutex.data.get() }
+    }
+}
+
+impl<T> Drop for MutexGuard<'_, T> {
+    fn drop(&mut self) {
+        self.mutex.unlock();
+    }
+}
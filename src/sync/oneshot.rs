@@ -0,0 +1,141 @@
+// 値を1つだけ送る、グリーンスレッド間のワンショットチャネル
+// NOTE: 送信側は値を置いて受信側を起こすだけなのでブロックしない
+//       受信側は値が届くか送信側がドロップされるまでブロックする
+use std::cell::{Cell, RefCell};
+use std::error::Error;
+use std::fmt;
+use std::rc::Rc;
+
+use super::WaitQueue;
+
+struct Shared<T> {
+    value: RefCell<Option<T>>,
+    sender_alive: Cell<bool>,
+    receiver_alive: Cell<bool>,
+    waiters: WaitQueue,
+}
+
+pub fn channel<T>() -> (Sender<T>, Receiver<T>) {
+    let shared = Rc::new(Shared {
+        value: RefCell::new(None),
+        sender_alive: Cell::new(true),
+        receiver_alive: Cell::new(true),
+        waiters: WaitQueue::new("oneshot::Receiver::recv"),
+    });
+    (
+        Sender {
+            shared: shared.clone(),
+        },
+        Receiver { shared },
+    )
+}
+
+pub struct Sender<T> {
+    shared: Rc<Shared<T>>,
+}
+
+impl<T> Sender<T> {
+    // 値を送信して受信側を起こす
+    // 受信側がドロップされている場合は値をそのまま返す
+    pub fn send(self, t: T) -> Result<(), T> {
+        let _guard = crate::preempt::disable();
+        if !self.shared.receiver_alive.get() {
+            return Err(t);
+        }
+        *self.shared.value.borrow_mut() = Some(t);
+        // NOTE: 受信側はドロップで起こす
+        Ok(())
+    }
+
+    // 受信側がドロップされたかどうか
+    pub fn is_closed(&self) -> bool {
+        !self.shared.receiver_alive.get()
+    }
+}
+
+impl<T> Drop for Sender<T> {
+    // 値を送った場合も送らずにドロップされた場合も、受信を待っているスレッドを起こす
+    fn drop(&mut self) {
+        let _guard = crate::preempt::disable();
+        self.shared.sender_alive.set(false);
+        self.shared.waiters.notify_all();
+    }
+}
+
+pub struct Receiver<T> {
+    shared: Rc<Shared<T>>,
+}
+
+impl<T> Receiver<T> {
+    // 値を受信する
+    // 値が届いていない場合は届くまでブロックする
+    // 送信側が値を送らずにドロップされた場合やキャンセルされた場合はエラーを返す
+    pub fn recv(self) -> Result<T, RecvError> {
+        let _guard = crate::preempt::disable();
+        loop {
+            match self.try_recv() {
+                Ok(t) => return Ok(t),
+                Err(TryRecvError::Disconnected) => return Err(RecvError::Disconnected),
+                Err(TryRecvError::Empty) => {
+                    if self.shared.waiters.wait_cancellable().is_err() {
+                        return Err(RecvError::Cancelled);
+                    }
+                }
+            }
+        }
+    }
+
+    // ブロックせずに値を受信する
+    pub fn try_recv(&self) -> Result<T, TryRecvError> {
+        let _guard = crate::preempt::disable();
+        match self.shared.value.borrow_mut().take() {
+            Some(t) => Ok(t),
+            None if !self.shared.sender_alive.get() => Err(TryRecvError::Disconnected),
+            None => Err(TryRecvError::Empty),
+        }
+    }
+}
+
+impl<T> Drop for Receiver<T> {
+    fn drop(&mut self) {
+        self.shared.receiver_alive.set(false);
+    }
+}
+
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum RecvError {
+    // 送信側が値を送らずにドロップされた
+    Disconnected,
+    // 受信を待っている間にキャンセルされた
+    Cancelled,
+}
+
+impl fmt::Display for RecvError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RecvError::Disconnected => "the sender was dropped without sending".fmt(f),
+            RecvError::Cancelled => "receiving was cancelled".fmt(f),
+        }
+    }
+}
+
+impl Error for RecvError {}
+
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum TryRecvError {
+    // まだ値が届いていない
+    Empty,
+    // 送信側が値を送らずにドロップされた、または値を受信済み
+    Disconnected,
+}
+
+impl fmt::Display for TryRecvError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TryRecvError::Empty => "no value has been sent yet".fmt(f),
+            TryRecvError::Disconnected => "the sender was dropped without sending".fmt(f),
+        }
+    }
+}
+
+impl Error for TryRecvError {}
@@ -0,0 +1,195 @@
+// グリーンスレッドの間でバイト列を受け渡すパイプ
+// NOTE: OSのパイプと違ってシステムコールを使わず、Runtimeの中のリングバッファでやり取りする
+//       書く側はバッファが満杯なら空くまで、読む側は空なら書かれるまでブロックし、他のスレッドに切り替える
+//       std::io::ReadとWriteを実装するので、io::copyやシリアライザ、圧縮などのRead/Writeを受け取る処理を
+//       そのまま2つのスレッドにまたがって使える
+//       PipeReaderはBufReadも実装していて、fill_bufでリングバッファの中を直接読めるので、読む側で一度バッファに写さずに済む
+use std::cell::Cell;
+use std::io::{self, BufRead, Read, Write};
+use std::ptr;
+use std::rc::Rc;
+use std::slice;
+
+use super::WaitQueue;
+
+// pipeで作るリングバッファの大きさ
+const DEFAULT_CAPACITY: usize = 64 * 1024;
+
+struct Shared {
+    // リングバッファ
+    // NOTE: 読む側にはたまっている部分のスライスを貸し出し、その間も書く側は空いている部分に書くので、
+    //       全体への&mutを作らないようにポインタで持つ
+    buf: *mut u8,
+    capacity: usize,
+    // 次に読む位置と、たまっているバイト数
+    head: Cell<usize>,
+    len: Cell<usize>,
+    reader_alive: Cell<bool>,
+    writer_alive: Cell<bool>,
+    read_waiters: WaitQueue,
+    write_waiters: WaitQueue,
+}
+
+impl Drop for Shared {
+    fn drop(&mut self) {
+        unsafe {
+            drop(Box::from_raw(ptr::slice_from_raw_parts_mut(
+                self.buf,
+                self.capacity,
+            )));
+        }
+    }
+}
+
+// 64KiBのバッファを持つパイプを作る
+pub fn pipe() -> (PipeReader, PipeWriter) {
+    pipe_with_capacity(DEFAULT_CAPACITY)
+}
+
+// capacityバイトのバッファを持つパイプを作る
+pub fn pipe_with_capacity(capacity: usize) -> (PipeReader, PipeWriter) {
+    assert!(capacity > 0, "capacity of pipe must be greater than 0.");
+    let buf = Box::into_raw(vec![0u8; capacity].into_boxed_slice()) as *mut u8;
+    let shared = Rc::new(Shared {
+        buf,
+        capacity,
+        head: Cell::new(0),
+        len: Cell::new(0),
+        reader_alive: Cell::new(true),
+        writer_alive: Cell::new(true),
+        read_waiters: WaitQueue::new("PipeReader::read"),
+        write_waiters: WaitQueue::new("PipeWriter::write"),
+    });
+    (
+        PipeReader {
+            shared: shared.clone(),
+        },
+        PipeWriter { shared },
+    )
+}
+
+pub struct PipeReader {
+    shared: Rc<Shared>,
+}
+
+impl PipeReader {
+    // ブロックせずに読めるバイト数
+    pub fn available(&self) -> usize {
+        self.shared.len.get()
+    }
+}
+
+impl Read for PipeReader {
+    // たまっているバイト列を読む
+    // 空の場合は書かれるまでブロックし、書く側がドロップされていれば0を返す
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        let data = self.fill_buf()?;
+        let n = data.len().min(buf.len());
+        buf[..n].copy_from_slice(&data[..n]);
+        self.consume(n);
+        Ok(n)
+    }
+}
+
+impl BufRead for PipeReader {
+    // リングバッファの中でたまっている部分を返す
+    // NOTE: バッファの終わりで折り返している場合は終わりまでを返し、残りは次に呼んだときに返す
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        crate::consume_budget();
+        // NOTE: 空を確認してから待ちキューに入るまでの間に切り替わると、起こされそこねるので止める
+        let _guard = crate::preempt::disable();
+        let shared = &*self.shared;
+        while shared.len.get() == 0 {
+            if !shared.writer_alive.get() {
+                return Ok(&[]);
+            }
+            shared
+                .read_waiters
+                .wait_cancellable()
+                .map_err(io::Error::other)?;
+        }
+        let head = shared.head.get();
+        let len = shared.len.get().min(shared.capacity - head);
+        Ok(unsafe { slice::from_raw_parts(shared.buf.add(head), len) })
+    }
+
+    fn consume(&mut self, amt: usize) {
+        let _guard = crate::preempt::disable();
+        let shared = &*self.shared;
+        let amt = amt.min(shared.len.get());
+        if amt == 0 {
+            return;
+        }
+        shared.head.set((shared.head.get() + amt) % shared.capacity);
+        shared.len.set(shared.len.get() - amt);
+        // 空きを待っている書く側を起こす
+        shared.write_waiters.notify_one();
+    }
+}
+
+impl Drop for PipeReader {
+    // 空きを待っている書く側に、エラーを返させる
+    fn drop(&mut self) {
+        let _guard = crate::preempt::disable();
+        self.shared.reader_alive.set(false);
+        self.shared.write_waiters.notify_all();
+    }
+}
+
+pub struct PipeWriter {
+    shared: Rc<Shared>,
+}
+
+impl Write for PipeWriter {
+    // 空いている分だけ書き、書いたバイト数を返す
+    // 満杯の場合は空くまでブロックし、読む側がドロップされていればErr(BrokenPipe)を返す
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        crate::consume_budget();
+        let _guard = crate::preempt::disable();
+        let shared = &*self.shared;
+        loop {
+            if !shared.reader_alive.get() {
+                return Err(io::ErrorKind::BrokenPipe.into());
+            }
+            if shared.len.get() < shared.capacity {
+                break;
+            }
+            shared
+                .write_waiters
+                .wait_cancellable()
+                .map_err(io::Error::other)?;
+        }
+        let n = buf.len().min(shared.capacity - shared.len.get());
+        // 空いている部分の先頭から書き、バッファの終わりを越える分は先頭に折り返す
+        let tail = (shared.head.get() + shared.len.get()) % shared.capacity;
+        let first = n.min(shared.capacity - tail);
+        unsafe {
+            ptr::copy_nonoverlapping(buf.as_ptr(), shared.buf.add(tail), first);
+            ptr::copy_nonoverlapping(buf[first..].as_ptr(), shared.buf, n - first);
+        }
+        shared.len.set(shared.len.get() + n);
+        // 書かれるのを待っている読む側を起こす
+        shared.read_waiters.notify_one();
+        Ok(n)
+    }
+
+    // NOTE: 書いた時点で読む側から読めるので、何もしない
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Drop for PipeWriter {
+    // 書かれるのを待っている読む側に、終わり(0バイト)を返させる
+    fn drop(&mut self) {
+        let _guard = crate::preempt::disable();
+        self.shared.writer_alive.set(false);
+        self.shared.read_waiters.notify_all();
+    }
+}
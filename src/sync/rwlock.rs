@@ -0,0 +1,205 @@
+use std::cell::{Cell, UnsafeCell};
+use std::ops::{Deref, DerefMut};
+
+use super::WaitQueue;
+
+// 読み込みと書き込みが競合したときにどちらを優先するか
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RwLockPreference {
+    // 書き込み中でなければ読み込みのロックを取れる
+    // NOTE: 読み込みが途切れないと、書き込みのロックがいつまでも取れないことがある
+    #[default]
+    Read,
+    // 書き込みのロックを待っているスレッドがいる間は、新しく読み込みのロックを取らせない
+    Write,
+}
+
+// グリーンスレッド用のRwLock
+// 読み込みのロックは複数のスレッドが同時に持て、書き込みのロックは1つのスレッドだけが持てる
+// ロックが取れない場合はOSスレッドをブロックせず、解放されるまで他のスレッドに切り替える
+pub struct RwLock<T> {
+    preference: RwLockPreference,
+    // 読み込みのロックを持っているスレッドの数
+    readers: Cell<usize>,
+    // 書き込みのロックを持っているスレッド
+    writer: Cell<Option<usize>>,
+    // 書き込みのロックを待っているスレッドの数
+    waiting_writers: Cell<usize>,
+    read_waiters: WaitQueue,
+    write_waiters: WaitQueue,
+    data: UnsafeCell<T>,
+}
+
+impl<T> RwLock<T> {
+    pub fn new(data: T) -> Self {
+        RwLock::with_preference(data, RwLockPreference::default())
+    }
+
+    pub fn with_preference(data: T, preference: RwLockPreference) -> Self {
+        RwLock {
+            preference,
+            readers: Cell::new(0),
+            writer: Cell::new(None),
+            waiting_writers: Cell::new(0),
+            read_waiters: WaitQueue::new("RwLock::read"),
+            write_waiters: WaitQueue::new("RwLock::write"),
+            data: UnsafeCell::new(data),
+        }
+    }
+
+    // 読み込みのロックを取得する
+    // 他のスレッドが書き込みのロックを持っている場合は解放されるまでブロックする
+    pub fn read(&self) -> RwLockReadGuard<'_, T> {
+        #[cfg(greenthreads_model)]
+        crate::model::yield_point();
+        crate::consume_budget();
+        let _guard = crate::preempt::disable();
+        // 起こされた後に別のスレッドが先に書き込みのロックを取っている可能性があるのでループで確認する
+        while !self.can_read() {
+            self.read_waiters.wait_for(self.writer.get());
+        }
+        self.readers.set(self.readers.get() + 1);
+        #[cfg(greenthreads_model)]
+        self.check_invariants();
+        RwLockReadGuard { lock: self }
+    }
+
+    // 読み込みのロックを取得できる場合のみ取得する
+    pub fn try_read(&self) -> Option<RwLockReadGuard<'_, T>> {
+        #[cfg(greenthreads_model)]
+        crate::model::yield_point();
+        let _guard = crate::preempt::disable();
+        if !self.can_read() {
+            return None;
+        }
+        self.readers.set(self.readers.get() + 1);
+        #[cfg(greenthreads_model)]
+        self.check_invariants();
+        Some(RwLockReadGuard { lock: self })
+    }
+
+    // 書き込みのロックを取得する
+    // 他のスレッドがロックを持っている場合は、すべて解放されるまでブロックする
+    pub fn write(&self) -> RwLockWriteGuard<'_, T> {
+        #[cfg(greenthreads_model)]
+        crate::model::yield_point();
+        crate::consume_budget();
+        let _guard = crate::preempt::disable();
+        self.waiting_writers.set(self.waiting_writers.get() + 1);
+        while !self.can_write() {
+            self.write_waiters.wait_for(self.writer.get());
+        }
+        self.waiting_writers.set(self.waiting_writers.get() - 1);
+        self.writer.set(Some(crate::current_thread()));
+        #[cfg(greenthreads_model)]
+        self.check_invariants();
+        RwLockWriteGuard { lock: self }
+    }
+
+    // 書き込みのロックを取得できる場合のみ取得する
+    pub fn try_write(&self) -> Option<RwLockWriteGuard<'_, T>> {
+        #[cfg(greenthreads_model)]
+        crate::model::yield_point();
+        let _guard = crate::preempt::disable();
+        if !self.can_write() {
+            return None;
+        }
+        self.writer.set(Some(crate::current_thread()));
+        #[cfg(greenthreads_model)]
+        self.check_invariants();
+        Some(RwLockWriteGuard { lock: self })
+    }
+
+    pub fn get_mut(&mut self) -> &mut T {
+        self.data.get_mut()
+    }
+
+    pub fn into_inner(self) -> T {
+        self.data.into_inner()
+    }
+
+    fn can_read(&self) -> bool {
+        self.writer.get().is_none()
+            && (self.preference == RwLockPreference::Read || self.waiting_writers.get() == 0)
+    }
+
+    fn can_write(&self) -> bool {
+        self.writer.get().is_none() && self.readers.get() == 0
+    }
+
+    // 書き込みのロックは他のロックと同時に持てない
+    #[cfg(greenthreads_model)]
+    fn check_invariants(&self) {
+        assert!(
+            self.writer.get().is_none() || self.readers.get() == 0,
+            "model: RwLock is locked for reading and writing at the same time."
+        );
+    }
+
+    fn read_unlock(&self) {
+        let _guard = crate::preempt::disable();
+        self.readers.set(self.readers.get() - 1);
+        // 最後の読み込みのロックが解放されたら、書き込みを待っているスレッドを1つ起こす
+        if self.readers.get() == 0 {
+            self.write_waiters.notify_one();
+        }
+    }
+
+    fn write_unlock(&self) {
+        let _guard = crate::preempt::disable();
+        self.writer.set(None);
+        // NOTE: 書き込み優先の場合は、書き込みを待っているスレッドがいればそちらだけを起こす
+        if self.preference == RwLockPreference::Write && self.waiting_writers.get() > 0 {
+            self.write_waiters.notify_one();
+        } else {
+            self.read_waiters.notify_all();
+            self.write_waiters.notify_one();
+        }
+    }
+}
+
+// 読み込みのロックを保持している間だけ中身を読めるガード
+// ドロップ時にロックを解放する
+pub struct RwLockReadGuard<'a, T> {
+    lock: &'a RwLock<T>,
+}
+
+impl<T> Deref for RwLockReadGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        unsafe { &*self.lock.data.get() }
+    }
+}
+
+impl<T> Drop for RwLockReadGuard<'_, T> {
+    fn drop(&mut self) {
+        self.lock.read_unlock();
+    }
+}
+
+// 書き込みのロックを保持している間だけ中身にアクセスできるガード
+// ドロップ時にロックを解放する
+pub struct RwLockWriteGuard<'a, T> {
+    lock: &'a RwLock<T>,
+}
+
+impl<T> Deref for RwLockWriteGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        unsafe { &*self.lock.data.get() }
+    }
+}
+
+impl<T> DerefMut for RwLockWriteGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { &mut *self.lock.data.get() }
+    }
+}
+
+impl<T> Drop for RwLockWriteGuard<'_, T> {
+    fn drop(&mut self) {
+        self.lock.write_unlock();
+    }
+}
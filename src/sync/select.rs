@@ -0,0 +1,169 @@
+// 複数のチャネルやタイマー、I/Oのうち、最初に準備ができたものを待つ
+// NOTE: 現在のスレッドをすべての待ち先に登録してからブロックし、起こされたらすべての登録を取り消す
+//       起こしたもの以外の待ち先でnotifyを受け取っていた場合は、そのnotifyを他の待ちスレッドに譲る
+use std::io;
+use std::os::unix::io::RawFd;
+use std::time::{Duration, Instant};
+
+use super::mpsc::Receiver;
+use super::WaitQueue;
+use crate::deadlock::BlockedOn;
+use crate::reactor::Interest;
+use crate::{preempt, runtime_ptr, Runtime};
+
+// Selectで待てる受信側
+pub(crate) trait Selectable {
+    // 待たずに受信できるかどうか
+    fn is_ready(&self) -> bool;
+    // 受信を待つスレッドのキュー
+    fn waiters(&self) -> &WaitQueue;
+}
+
+enum Operation<'a> {
+    Recv(&'a dyn Selectable),
+    Deadline(Instant),
+    Io(RawFd, Interest),
+}
+
+// 待つものを登録して、waitで最初に準備ができたものを待つ
+// 登録するメソッドが返す番号とwaitが返す番号を比べて、どれの準備ができたかを判断する
+#[derive(Default)]
+pub struct Select<'a> {
+    operations: Vec<Operation<'a>>,
+}
+
+impl<'a> Select<'a> {
+    pub fn new() -> Self {
+        Select {
+            operations: Vec::new(),
+        }
+    }
+
+    // rxで値を受信できるのを待つ
+    // 登録した順番を返す
+    // NOTE: 送信側がすべてドロップされた場合も準備ができたものとして扱う
+    pub fn recv<T>(&mut self, rx: &'a Receiver<T>) -> usize {
+        self.push(Operation::Recv(rx))
+    }
+
+    // deadlineになるのを待つ
+    pub fn deadline(&mut self, deadline: Instant) -> usize {
+        self.push(Operation::Deadline(deadline))
+    }
+
+    // 今からdurが経過するのを待つ
+    pub fn timeout(&mut self, dur: Duration) -> usize {
+        self.deadline(Instant::now() + dur)
+    }
+
+    // fdを読み込めるようになるのを待つ
+    pub fn readable(&mut self, fd: RawFd) -> usize {
+        self.push(Operation::Io(fd, Interest::Readable))
+    }
+
+    // fdに書き込めるようになるのを待つ
+    pub fn writable(&mut self, fd: RawFd) -> usize {
+        self.push(Operation::Io(fd, Interest::Writable))
+    }
+
+    fn push(&mut self, operation: Operation<'a>) -> usize {
+        self.operations.push(operation);
+        self.operations.len() - 1
+    }
+
+    // 登録したもののうち、最初に準備ができたものの番号を返す
+    // すでに準備ができているものがあれば、待たずに一番先に登録したものを返す
+    // キャンセルされた場合はErr(Cancelled)を包んだエラーを返す
+    pub fn wait(&self) -> io::Result<usize> {
+        assert!(!self.operations.is_empty(), "nothing to select.");
+        unsafe { Runtime::t_select(runtime_ptr(), self) }
+    }
+
+    // 待たずに準備ができているものの番号を返す
+    // NOTE: I/Oは待ってみないと分からないので含めない
+    pub fn try_select(&self) -> Option<usize> {
+        let now = Instant::now();
+        self.operations.iter().position(|op| match op {
+            Operation::Recv(rx) => rx.is_ready(),
+            Operation::Deadline(deadline) => *deadline <= now,
+            Operation::Io(..) => false,
+        })
+    }
+}
+
+impl Runtime {
+    unsafe fn t_select(rt: *mut Runtime, select: &Select) -> io::Result<usize> {
+        let _guard = preempt::disable();
+        loop {
+            if let Some(index) = select.try_select() {
+                return Ok(index);
+            }
+
+            // すべての待ち先に現在のスレッドを登録する
+            let current = (*rt).current;
+            for (i, op) in select.operations.iter().enumerate() {
+                match op {
+                    Operation::Recv(rx) => rx.waiters().register(current),
+                    Operation::Deadline(deadline) => (*rt).timers.add(*deadline, current),
+                    Operation::Io(fd, interest) => {
+                        if let Err(e) = (*rt).reactor.register(*fd, *interest, current) {
+                            (*rt).unregister_select(&select.operations[..i], current);
+                            return Err(e);
+                        }
+                    }
+                }
+            }
+
+            let result = crate::block_thread_cancellable(BlockedOn {
+                what: "Select::wait",
+                addr: select as *const Select as usize,
+                holder: None,
+            });
+            let (fired, notified) = (*rt).unregister_select(&select.operations, current);
+
+            // 起こされた理由になったものを選ぶ
+            let now = Instant::now();
+            let chosen = match result {
+                Ok(()) => select
+                    .operations
+                    .iter()
+                    .enumerate()
+                    .position(|(i, op)| match op {
+                        Operation::Recv(rx) => rx.is_ready(),
+                        Operation::Deadline(deadline) => *deadline <= now,
+                        Operation::Io(..) => fired[i],
+                    }),
+                Err(_) => None,
+            };
+            // 選ばなかった受信側でnotifyを受け取っていた場合は、他の待ちスレッドに譲る
+            for (i, op) in select.operations.iter().enumerate() {
+                if let Operation::Recv(rx) = op {
+                    if notified[i] && chosen != Some(i) {
+                        rx.waiters().notify_one();
+                    }
+                }
+            }
+            if let Err(e) = result {
+                return Err(io::Error::other(e));
+            }
+            if let Some(index) = chosen {
+                return Ok(index);
+            }
+        }
+    }
+
+    // operationsに登録した現在のスレッドを取り除く
+    // (I/Oのイベントが届いたかどうか, 受信側でnotifyを受け取ったかどうか)をそれぞれ返す
+    fn unregister_select(&mut self, operations: &[Operation], id: usize) -> (Vec<bool>, Vec<bool>) {
+        let mut fired = vec![false; operations.len()];
+        let mut notified = vec![false; operations.len()];
+        for (i, op) in operations.iter().enumerate() {
+            match op {
+                Operation::Recv(rx) => notified[i] = !rx.waiters().remove(id),
+                Operation::Deadline(_) => self.timers.remove(id),
+                Operation::Io(fd, interest) => fired[i] = !self.reactor.cancel(*fd, *interest, id),
+            }
+        }
+        (fired, notified)
+    }
+}
@@ -0,0 +1,82 @@
+use std::cell::Cell;
+
+use super::WaitQueue;
+
+// グリーンスレッド用のカウンティングセマフォ
+// 許可の数が0の場合はOSスレッドをブロックせず、許可が返されるまで他のスレッドに切り替える
+pub struct Semaphore {
+    // 残っている許可の数
+    permits: Cell<usize>,
+    waiters: WaitQueue,
+}
+
+impl Semaphore {
+    pub fn new(permits: usize) -> Self {
+        Semaphore {
+            permits: Cell::new(permits),
+            waiters: WaitQueue::new("Semaphore::acquire"),
+        }
+    }
+
+    // 許可を1つ取得する
+    // 残っていない場合は返されるまでブロックする
+    pub fn acquire(&self) -> SemaphorePermit<'_> {
+        #[cfg(greenthreads_model)]
+        crate::model::yield_point();
+        crate::consume_budget();
+        let _guard = crate::preempt::disable();
+        // 起こされた後に別のスレッドが先に取っている可能性があるのでループで確認する
+        while self.permits.get() == 0 {
+            self.waiters.wait();
+        }
+        self.permits.set(self.permits.get() - 1);
+        SemaphorePermit { semaphore: self }
+    }
+
+    // 許可が残っている場合のみ取得する
+    pub fn try_acquire(&self) -> Option<SemaphorePermit<'_>> {
+        #[cfg(greenthreads_model)]
+        crate::model::yield_point();
+        let _guard = crate::preempt::disable();
+        if self.permits.get() == 0 {
+            return None;
+        }
+        self.permits.set(self.permits.get() - 1);
+        Some(SemaphorePermit { semaphore: self })
+    }
+
+    // 許可をn個増やす
+    pub fn add_permits(&self, n: usize) {
+        let _guard = crate::preempt::disable();
+        self.permits.set(self.permits.get() + n);
+        for _ in 0..n {
+            if !self.waiters.notify_one() {
+                break;
+            }
+        }
+    }
+
+    pub fn available_permits(&self) -> usize {
+        self.permits.get()
+    }
+}
+
+// 取得した許可
+// ドロップ時に許可を返す
+pub struct SemaphorePermit<'a> {
+    semaphore: &'a Semaphore,
+}
+
+impl SemaphorePermit<'_> {
+    // 許可を返さずに手放す
+    // NOTE: 許可の数はその分だけ減ったままになる
+    pub fn forget(self) {
+        std::mem::forget(self);
+    }
+}
+
+impl Drop for SemaphorePermit<'_> {
+    fn drop(&mut self) {
+        self.semaphore.add_permits(1);
+    }
+}
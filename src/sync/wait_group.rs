@@ -0,0 +1,64 @@
+use std::cell::Cell;
+use std::rc::Rc;
+
+use super::WaitQueue;
+
+struct Inner {
+    // 終わっていないタスクの数
+    count: Cell<usize>,
+    waiters: WaitQueue,
+}
+
+// 複数のタスクがすべて終わるのを待つためのカウンタ
+// addで待つタスクの数を増やし、各タスクが終わったらdoneを呼ぶ
+// waitはカウンタが0になるまでブロックする
+#[derive(Clone)]
+pub struct WaitGroup {
+    inner: Rc<Inner>,
+}
+
+impl WaitGroup {
+    pub fn new() -> Self {
+        WaitGroup {
+            inner: Rc::new(Inner {
+                count: Cell::new(0),
+                waiters: WaitQueue::new("WaitGroup::wait"),
+            }),
+        }
+    }
+
+    pub fn add(&self, n: usize) {
+        let _guard = crate::preempt::disable();
+        self.inner.count.set(self.inner.count.get() + n);
+    }
+
+    // タスクが1つ終わったことを知らせる
+    // カウンタが0になったら待っているスレッドをすべて起こす
+    pub fn done(&self) {
+        let _guard = crate::preempt::disable();
+        let count = self
+            .inner
+            .count
+            .get()
+            .checked_sub(1)
+            .expect("WaitGroup::done called more times than add.");
+        self.inner.count.set(count);
+        if count == 0 {
+            self.inner.waiters.notify_all();
+        }
+    }
+
+    // カウンタが0になるまでブロックする
+    pub fn wait(&self) {
+        let _guard = crate::preempt::disable();
+        while self.inner.count.get() > 0 {
+            self.inner.waiters.wait();
+        }
+    }
+}
+
+impl Default for WaitGroup {
+    fn default() -> Self {
+        Self::new()
+    }
+}
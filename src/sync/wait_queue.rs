@@ -0,0 +1,154 @@
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::time::Instant;
+
+use crate::deadlock::BlockedOn;
+use crate::Cancelled;
+
+// 待ち状態のスレッドIDを待った順に保持するキュー
+pub(crate) struct WaitQueue {
+    waiters: RefCell<VecDeque<usize>>,
+    // 何を待つキューか(デッドロックの診断に使う)
+    what: &'static str,
+}
+
+impl WaitQueue {
+    pub(crate) fn new(what: &'static str) -> Self {
+        WaitQueue {
+            waiters: RefCell::new(VecDeque::new()),
+            what,
+        }
+    }
+
+    // idのスレッドをキューの最後に積む
+    // NOTE: 同じスレッドが2回積まれると、notifyで1回分が無駄になるのでモデル検査では確認する
+    fn push(&self, id: usize) {
+        let mut waiters = self.waiters.borrow_mut();
+        #[cfg(greenthreads_model)]
+        assert!(
+            !waiters.contains(&id),
+            "model: thread {} is already waiting on {}.",
+            id,
+            self.what
+        );
+        waiters.push_back(id);
+    }
+
+    // 現在のスレッドをキューに積んでブロックする
+    // notify_one/notify_allで起こされるまで戻らない
+    pub(crate) fn wait(&self) {
+        self.wait_for(None);
+    }
+
+    // waitと同じだが、待っているものを持っているスレッドが分かる場合はholderに指定する
+    // NOTE: holderはデッドロックしたときに、どのスレッドがどのスレッドを待っているかを報告するのに使う
+    pub(crate) fn wait_for(&self, holder: Option<usize>) {
+        let _guard = crate::preempt::disable();
+        self.push(crate::current_thread());
+        crate::block_thread(self.blocked_on(holder));
+    }
+
+    fn blocked_on(&self, holder: Option<usize>) -> BlockedOn {
+        BlockedOn {
+            what: self.what,
+            addr: self.addr(),
+            holder,
+        }
+    }
+
+    // キューを見分けるためのアドレス
+    // NOTE: BlockedOnのaddrと同じ値になる
+    pub(crate) fn addr(&self) -> usize {
+        self as *const WaitQueue as usize
+    }
+
+    // 待っているスレッドのIDを待った順に返す
+    pub(crate) fn waiting(&self) -> Vec<usize> {
+        self.waiters.borrow().iter().copied().collect()
+    }
+
+    // waitと同じだが、キャンセルされた場合は起こされるのを待たずにErr(Cancelled)を返す
+    pub(crate) fn wait_cancellable(&self) -> Result<(), Cancelled> {
+        let _guard = crate::preempt::disable();
+        let id = crate::current_thread();
+        self.push(id);
+        let result = crate::block_thread_cancellable(self.blocked_on(None));
+        // NOTE: キューにいなかった場合はnotifyで起こされた後なので、代わりに他のスレッドを起こす
+        if result.is_err() && !self.remove(id) {
+            self.notify_one();
+        }
+        result
+    }
+
+    // wait_forと同じだが、deadlineを過ぎたら起こされるのを待たずにfalseを返す
+    // notify_one/notify_allで起こされた場合はtrueを返す
+    pub(crate) fn wait_until(&self, deadline: Instant, holder: Option<usize>) -> bool {
+        let _guard = crate::preempt::disable();
+        if deadline <= Instant::now() {
+            return false;
+        }
+        let id = crate::current_thread();
+        self.push(id);
+        crate::block_thread_until(self.blocked_on(holder), deadline);
+        // NOTE: キューに残っていればタイマーで起きたので、自分で取り除く
+        !self.remove(id)
+    }
+
+    // wait_cancellableと同じだが、deadlineを過ぎたら起こされるのを待たずにOk(false)を返す
+    pub(crate) fn wait_cancellable_until(&self, deadline: Instant) -> Result<bool, Cancelled> {
+        let _guard = crate::preempt::disable();
+        if deadline <= Instant::now() {
+            return Ok(false);
+        }
+        let id = crate::current_thread();
+        self.push(id);
+        let result = crate::block_thread_cancellable_until(self.blocked_on(None), deadline);
+        let notified = !self.remove(id);
+        if let Err(e) = result {
+            // NOTE: notifyで起こされた後だった場合は、代わりに他のスレッドを起こす
+            if notified {
+                self.notify_one();
+            }
+            return Err(e);
+        }
+        Ok(notified)
+    }
+
+    // idのスレッドをブロックせずにキューに積む
+    // NOTE: 複数のキューで同時に待つ場合(Select)に使い、起こされたらremoveで取り除く
+    pub(crate) fn register(&self, id: usize) {
+        self.push(id);
+    }
+
+    // idのスレッドをキューから取り除く
+    // キューにいなかった場合(notifyで起こされた後)はfalseを返す
+    pub(crate) fn remove(&self, id: usize) -> bool {
+        let mut waiters = self.waiters.borrow_mut();
+        let len = waiters.len();
+        waiters.retain(|w| *w != id);
+        waiters.len() != len
+    }
+
+    // 一番長く待っているスレッドを起こす
+    // 起こすスレッドがいなかった場合はfalseを返す
+    pub(crate) fn notify_one(&self) -> bool {
+        let _guard = crate::preempt::disable();
+        let id = self.waiters.borrow_mut().pop_front();
+        match id {
+            Some(id) => {
+                crate::wake_thread(id);
+                true
+            }
+            None => false,
+        }
+    }
+
+    // 待っているスレッドをすべて起こす
+    pub(crate) fn notify_all(&self) {
+        let _guard = crate::preempt::disable();
+        let waiters: Vec<usize> = self.waiters.borrow_mut().drain(..).collect();
+        for id in waiters {
+            crate::wake_thread(id);
+        }
+    }
+}
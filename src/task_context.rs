@@ -0,0 +1,147 @@
+// スレッドの処理に付けて伝える、型付きのキーと値の組(コンテキスト)
+// リクエストIDなどをwith_valueで付けると、その中で呼んだ関数や生成したスレッドから参照できる
+// NOTE: LocalKeyはスレッドごとに別の値を持つが、コンテキストは生成したスレッドに引き継がれる
+//       値を書き換えることはできず、with_valueで上書きした値はfの中でだけ見え、fを抜けると元に戻る
+//       コンテキストはRcでつないだリストで、引き継ぐときや保存するときはRcを複製するだけなので、値はコピーされない
+//       チャネルで他のスレッドに処理を渡す場合は、Context::currentで取り出して一緒に送り、受け取った側でenterする
+use std::any::Any;
+use std::fmt;
+use std::marker::PhantomData;
+use std::rc::Rc;
+
+use crate::{preempt, runtime_ptr};
+
+// コンテキストの値を取り出すキー
+// staticで宣言し、そのアドレスでキーを見分ける
+pub struct ContextKey<T: 'static> {
+    name: &'static str,
+    _marker: PhantomData<fn() -> T>,
+}
+
+impl<T: 'static> ContextKey<T> {
+    pub const fn new(name: &'static str) -> Self {
+        ContextKey {
+            name,
+            _marker: PhantomData,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    // 現在のスレッドのコンテキストで、このキーに付けた値を複製して返す
+    pub fn get(&'static self) -> Option<T>
+    where
+        T: Clone,
+    {
+        Context::current().get(self).cloned()
+    }
+
+    fn id(&self) -> usize {
+        self as *const ContextKey<T> as usize
+    }
+}
+
+struct Node {
+    key: usize,
+    name: &'static str,
+    value: Rc<dyn Any>,
+    parent: Option<Rc<Node>>,
+}
+
+// キーと値の組の集まり
+// NOTE: 同じキーに複数の値を付けた場合は、後から付けた値が見える
+#[derive(Clone, Default)]
+pub struct Context {
+    head: Option<Rc<Node>>,
+}
+
+impl Context {
+    // 何も付いていないコンテキストを作る
+    pub fn new() -> Self {
+        Context::default()
+    }
+
+    // 現在のスレッドのコンテキストを返す
+    pub fn current() -> Self {
+        let _guard = preempt::disable();
+        let rt = unsafe { &*runtime_ptr() };
+        rt.threads[rt.current].context.clone()
+    }
+
+    // keyにvalueを付けたコンテキストを返す
+    // NOTE: selfは変わらない
+    pub fn with_value<T: 'static>(&self, key: &'static ContextKey<T>, value: T) -> Self {
+        Context {
+            head: Some(Rc::new(Node {
+                key: key.id(),
+                name: key.name,
+                value: Rc::new(value),
+                parent: self.head.clone(),
+            })),
+        }
+    }
+
+    // keyに付けた値を返す
+    pub fn get<T: 'static>(&self, key: &'static ContextKey<T>) -> Option<&T> {
+        self.nodes()
+            .find(|node| node.key == key.id())
+            .and_then(|node| node.value.downcast_ref::<T>())
+    }
+
+    // このコンテキストを現在のスレッドのコンテキストにしてfを実行し、fを抜けたら元に戻す
+    // NOTE: fがパニックした場合も元に戻す
+    pub fn enter<F, R>(&self, f: F) -> R
+    where
+        F: FnOnce() -> R,
+    {
+        let _restore = Restore(replace_current(self.clone()));
+        f()
+    }
+
+    fn nodes(&self) -> impl Iterator<Item = &Node> {
+        std::iter::successors(self.head.as_deref(), |node| node.parent.as_deref())
+    }
+}
+
+// 見えている値のキーの名前を、新しく付けた順に並べる
+impl fmt::Debug for Context {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut keys = Vec::new();
+        let mut names = Vec::new();
+        for node in self.nodes() {
+            if !keys.contains(&node.key) {
+                keys.push(node.key);
+                names.push(node.name);
+            }
+        }
+        f.debug_struct("Context").field("keys", &names).finish()
+    }
+}
+
+// 現在のスレッドのコンテキストをcontextに置き換え、前のコンテキストを返す
+fn replace_current(context: Context) -> Context {
+    let _guard = preempt::disable();
+    let rt = unsafe { &mut *runtime_ptr() };
+    std::mem::replace(&mut rt.threads[rt.current].context, context)
+}
+
+// ドロップしたときに現在のスレッドのコンテキストを元に戻す
+struct Restore(Context);
+
+impl Drop for Restore {
+    fn drop(&mut self) {
+        replace_current(std::mem::take(&mut self.0));
+    }
+}
+
+// 現在のスレッドのコンテキストのkeyにvalueを付けてfを実行する
+// fの中で生成したスレッドもvalueを引き継ぐ
+pub fn with_value<T, F, R>(key: &'static ContextKey<T>, value: T, f: F) -> R
+where
+    T: 'static,
+    F: FnOnce() -> R,
+{
+    Context::current().with_value(key, value).enter(f)
+}
@@ -0,0 +1,141 @@
+// まとめて待ち、まとめてキャンセルできるスレッドのグループ
+// NOTE: グループで生成したスレッドのトークンは、グループを作ったスレッドのトークンの子になるので、
+//       グループを作ったスレッドがキャンセルされると、その子孫のスレッドもすべてキャンセルされる
+//       キャンセルは協調的で、スレッドは次にyieldしたところで巻き戻して終わり、ブロックする処理はErr(Cancelled)を返す
+use std::any::Any;
+use std::error::Error;
+use std::fmt;
+
+use crate::{
+    runtime_ptr, CancellationToken, Cancelled, JoinHandle, Runtime, SpawnError, DEFAULT_PRIORITY,
+};
+
+pub struct TaskGroup<E = ()> {
+    handles: Vec<JoinHandle<Result<(), E>>>,
+    // グループを作ったスレッドのトークン
+    parent: CancellationToken,
+}
+
+impl<E: 'static> TaskGroup<E> {
+    pub fn new() -> Self {
+        TaskGroup {
+            handles: Vec::new(),
+            parent: crate::current_token(),
+        }
+    }
+
+    // グループにスレッドを追加する
+    // 利用可能なスレッドがない場合は、他のスレッドを実行して空くまで待つ
+    pub fn spawn<F>(&mut self, f: F) -> Result<(), SpawnError>
+    where
+        F: FnOnce() -> Result<(), E> + 'static,
+    {
+        // NOTE: 空くまで待つ間は他のスレッドに切り替えるので、&mut Runtimeを作らずにポインタのまま渡す
+        let handle = unsafe { Runtime::t_spawn(runtime_ptr(), DEFAULT_PRIORITY, None, true, f)? };
+        handle.token.set_unwind();
+        self.parent.add_child(&handle.token);
+        self.handles.push(handle);
+        Ok(())
+    }
+
+    // グループのスレッドをすべてキャンセルする
+    pub fn cancel(&self) {
+        for handle in &self.handles {
+            handle.cancel();
+        }
+    }
+
+    // まだjoinしていないスレッドの数
+    pub fn len(&self) -> usize {
+        self.handles.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.handles.is_empty()
+    }
+
+    // グループのスレッドがすべて終わるまで待つ
+    // Errを返したりパニックしたりしたスレッドがあれば、それらをまとめてErrで返す
+    pub fn join(mut self) -> Result<(), GroupError<E>> {
+        let mut errors = Vec::new();
+        for handle in std::mem::take(&mut self.handles) {
+            match handle.join() {
+                Ok(Ok(())) => {}
+                Ok(Err(e)) => errors.push(TaskError::Failed(e)),
+                Err(payload) if payload.is::<Cancelled>() => errors.push(TaskError::Cancelled),
+                Err(payload) => errors.push(TaskError::Panicked(payload)),
+            }
+        }
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(GroupError { errors })
+        }
+    }
+}
+
+impl<E: 'static> Default for TaskGroup<E> {
+    fn default() -> Self {
+        TaskGroup::new()
+    }
+}
+
+// joinせずにドロップした場合は、残っているスレッドをキャンセルする
+// NOTE: 終わるのは待たない(待つ場合はcancelしてからjoinする)
+impl<E> Drop for TaskGroup<E> {
+    fn drop(&mut self) {
+        for handle in &self.handles {
+            handle.cancel();
+        }
+    }
+}
+
+// グループのスレッドが正常に終わらなかった理由
+pub enum TaskError<E> {
+    // タスクがErrを返した
+    Failed(E),
+    // タスクがパニックした
+    Panicked(Box<dyn Any + Send>),
+    // キャンセルされて巻き戻された
+    Cancelled,
+}
+
+impl<E: fmt::Debug> fmt::Debug for TaskError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TaskError::Failed(e) => f.debug_tuple("Failed").field(e).finish(),
+            TaskError::Panicked(..) => f.write_str("Panicked(..)"),
+            TaskError::Cancelled => f.write_str("Cancelled"),
+        }
+    }
+}
+
+impl<E: fmt::Display> fmt::Display for TaskError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TaskError::Failed(e) => e.fmt(f),
+            TaskError::Panicked(..) => "the task panicked".fmt(f),
+            TaskError::Cancelled => "the task was cancelled".fmt(f),
+        }
+    }
+}
+
+// joinで返す、正常に終わらなかったスレッドのエラーをまとめたもの
+// NOTE: 並びは生成した順
+#[derive(Debug)]
+pub struct GroupError<E> {
+    pub errors: Vec<TaskError<E>>,
+}
+
+impl<E: fmt::Display> fmt::Display for GroupError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} task(s) failed", self.errors.len())?;
+        for (i, e) in self.errors.iter().enumerate() {
+            let sep = if i == 0 { ": " } else { ", " };
+            write!(f, "{}{}", sep, e)?;
+        }
+        Ok(())
+    }
+}
+
+impl<E: fmt::Debug + fmt::Display> Error for GroupError<E> {}
@@ -0,0 +1,296 @@
+// スリープ中のスレッドを管理するタイマー
+// NOTE: 階層型のタイマーホイールで、1msを1tickとして期限をtick単位に切り上げて管理する
+//       レベル0は1tickずつ、レベル1は64tickずつ、...と64個のスロットを持つレベルを重ね、
+//       期限が遠いものほど上のレベルに入れ、時間が進んだら下のレベルに入れ直す
+//       追加と期限切れの取り出しはタイマーの数によらず、ほぼ一定の時間で済む
+use std::collections::VecDeque;
+use std::error::Error;
+use std::fmt;
+use std::time::{Duration, Instant};
+
+use crate::Cancelled;
+
+// 1tickの長さ
+const TICK_NANOS: u128 = 1_000_000;
+// 1レベルのスロットの数のビット数
+const SLOT_BITS: u32 = 6;
+const SLOTS: usize = 1 << SLOT_BITS;
+const SLOT_MASK: u64 = SLOTS as u64 - 1;
+// NOTE: 64^4tick(約4.6時間)より先の期限はoverflowに入れ、近づいてからホイールに入れる
+const LEVELS: usize = 4;
+
+struct Entry {
+    // 期限(startからのtick数)
+    tick: u64,
+    id: usize,
+}
+
+pub(crate) struct Timers {
+    start: Instant,
+    // ホイールを進めた時刻(startからのtick数)
+    elapsed: u64,
+    // levels[レベル][スロット]
+    levels: Vec<Vec<Vec<Entry>>>,
+    // レベルごとの、タイマーが入っているスロットのビットマスク
+    occupied: [u64; LEVELS],
+    overflow: Vec<Entry>,
+    // 期限が来てまだ取り出されていないスレッド
+    expired: VecDeque<usize>,
+}
+
+impl Timers {
+    pub(crate) fn new() -> Self {
+        Timers {
+            start: Instant::now(),
+            elapsed: 0,
+            levels: (0..LEVELS)
+                .map(|_| (0..SLOTS).map(|_| Vec::new()).collect())
+                .collect(),
+            occupied: [0; LEVELS],
+            overflow: Vec::new(),
+            expired: VecDeque::new(),
+        }
+    }
+
+    pub(crate) fn add(&mut self, deadline: Instant, id: usize) {
+        // NOTE: 切り上げるので、期限より早く起こすことはない
+        let nanos = deadline.saturating_duration_since(self.start).as_nanos();
+        let tick = nanos.div_ceil(TICK_NANOS) as u64;
+        self.insert(Entry {
+            tick: tick.max(self.elapsed),
+            id,
+        });
+    }
+
+    // idのスレッドのタイマーを取り除く
+    pub(crate) fn remove(&mut self, id: usize) {
+        for (level, slots) in self.levels.iter_mut().enumerate() {
+            let mut occupied = self.occupied[level];
+            while occupied != 0 {
+                let slot = occupied.trailing_zeros() as usize;
+                occupied &= occupied - 1;
+                slots[slot].retain(|e| e.id != id);
+                if slots[slot].is_empty() {
+                    self.occupied[level] &= !(1 << slot);
+                }
+            }
+        }
+        self.overflow.retain(|e| e.id != id);
+        self.expired.retain(|t| *t != id);
+    }
+
+    // 登録されているタイマーの数(期限が来てまだ取り出されていないものも含む)
+    #[cfg(feature = "metrics")]
+    pub(crate) fn count(&self) -> usize {
+        let wheel: usize = self.levels.iter().flatten().map(|slot| slot.len()).sum();
+        wheel + self.overflow.len() + self.expired.len()
+    }
+
+    // 一番近い期限を返す
+    pub(crate) fn next_deadline(&self) -> Option<Instant> {
+        if !self.expired.is_empty() {
+            return Some(self.instant(self.elapsed));
+        }
+        let tick = match self.next_slot() {
+            // NOTE: 一番近いスロットには、他のスロットのどのタイマーよりも近い期限だけが入っている
+            Some((level, slot, _)) => self.levels[level][slot].iter().map(|e| e.tick).min(),
+            None => self.overflow.iter().map(|e| e.tick).min(),
+        }?;
+        Some(self.instant(tick))
+    }
+
+    // 期限が来たスレッドを1つ取り出す
+    pub(crate) fn pop_expired(&mut self, now: Instant) -> Option<usize> {
+        if self.expired.is_empty() {
+            let now = (now.saturating_duration_since(self.start).as_nanos() / TICK_NANOS) as u64;
+            self.advance(now);
+        }
+        self.expired.pop_front()
+    }
+
+    fn instant(&self, tick: u64) -> Instant {
+        self.start + Duration::from_millis(tick)
+    }
+
+    // 期限がtickのタイマーを入れるレベル
+    // NOTE: elapsedと期限で異なる一番上のビットがどのレベルの範囲にあるかで決める
+    //       こうすると、同じレベルでは今のスロットより後ろにしか入らず、下のレベルほど期限が近くなる
+    fn level_for(&self, tick: u64) -> usize {
+        let masked = (self.elapsed ^ tick) | SLOT_MASK;
+        let significant = 63 - masked.leading_zeros();
+        (significant / SLOT_BITS) as usize
+    }
+
+    fn insert(&mut self, entry: Entry) {
+        let level = self.level_for(entry.tick);
+        if level >= LEVELS {
+            self.overflow.push(entry);
+            return;
+        }
+        let slot = ((entry.tick >> (level as u32 * SLOT_BITS)) & SLOT_MASK) as usize;
+        self.levels[level][slot].push(entry);
+        self.occupied[level] |= 1 << slot;
+    }
+
+    // タイマーが入っている一番近いスロットの(レベル, スロット, スロットの始まりのtick)を返す
+    fn next_slot(&self) -> Option<(usize, usize, u64)> {
+        (0..LEVELS).find_map(|level| {
+            if self.occupied[level] == 0 {
+                return None;
+            }
+            let shift = level as u32 * SLOT_BITS;
+            let slot = self.occupied[level].trailing_zeros() as usize;
+            let level_start = self.elapsed & !((1u64 << (shift + SLOT_BITS)) - 1);
+            Some((level, slot, level_start + ((slot as u64) << shift)))
+        })
+    }
+
+    // ホイールをnowまで進め、期限が来たタイマーをexpiredに移す
+    fn advance(&mut self, now: u64) {
+        loop {
+            match self.next_slot() {
+                Some((level, slot, start)) if start <= now => {
+                    self.elapsed = self.elapsed.max(start);
+                    self.occupied[level] &= !(1 << slot);
+                    for entry in std::mem::take(&mut self.levels[level][slot]) {
+                        if entry.tick <= now {
+                            self.expired.push_back(entry.id);
+                        } else {
+                            // 下のレベルに入れ直す
+                            self.insert(entry);
+                        }
+                    }
+                }
+                _ => {
+                    self.elapsed = self.elapsed.max(now);
+                    // 近づいたoverflowのタイマーをホイールに入れ、期限が来ていないか確認し直す
+                    if !self.refill_overflow() {
+                        return;
+                    }
+                }
+            }
+        }
+    }
+
+    // ホイールに入るようになったoverflowのタイマーを移し、移したものがあればtrueを返す
+    fn refill_overflow(&mut self) -> bool {
+        let (fits, rest): (Vec<Entry>, Vec<Entry>) = std::mem::take(&mut self.overflow)
+            .into_iter()
+            .partition(|e| self.level_for(e.tick) < LEVELS);
+        self.overflow = rest;
+        let moved = !fits.is_empty();
+        for entry in fits {
+            self.insert(entry);
+        }
+        moved
+    }
+}
+
+// 現在のスレッドを指定した時間だけスリープさせる
+// スリープ中は他のスレッドに切り替わり、期限が来るまでスケジュールされない
+// キャンセルされた場合は期限を待たずにErr(Cancelled)を返す
+pub fn sleep(dur: Duration) -> Result<(), Cancelled> {
+    sleep_until(Instant::now() + dur)
+}
+
+// 現在のスレッドを指定した時刻までスリープさせる
+pub fn sleep_until(deadline: Instant) -> Result<(), Cancelled> {
+    if deadline <= Instant::now() {
+        return Ok(());
+    }
+    crate::sleep_thread_until(deadline)
+}
+
+// 一定の周期で処理するためのティッカー
+pub struct Interval {
+    period: Duration,
+    // 次の周期の予定時刻
+    next: Instant,
+}
+
+// periodごとにtickが戻るティッカーを作る
+// NOTE: 最初のtickはすぐに戻る
+pub fn interval(period: Duration) -> Interval {
+    interval_at(Instant::now(), period)
+}
+
+// startから始めてperiodごとにtickが戻るティッカーを作る
+pub fn interval_at(start: Instant, period: Duration) -> Interval {
+    assert!(!period.is_zero(), "period of interval must be non-zero.");
+    Interval {
+        period,
+        next: start,
+    }
+}
+
+impl Interval {
+    // 次の周期の予定時刻までスリープし、その予定時刻を返す
+    // キャンセルされた場合はErr(Cancelled)を返す
+    // NOTE: 次の予定は起きた時刻ではなく今回の予定時刻から数えるので、起きるのが遅れても周期がずれていかない
+    //       1周期以上遅れた場合は、遅れた分をまとめて返さずに次の予定まで飛ばす
+    pub fn tick(&mut self) -> Result<Instant, Cancelled> {
+        let scheduled = self.next;
+        sleep_until(scheduled)?;
+        let now = Instant::now();
+        let behind = now.saturating_duration_since(scheduled).as_nanos() / self.period.as_nanos();
+        self.next = scheduled + self.period * (behind as u32 + 1);
+        Ok(scheduled)
+    }
+
+    // 次の周期を今からperiod後にする
+    pub fn reset(&mut self) {
+        self.next = Instant::now() + self.period;
+    }
+
+    pub fn period(&self) -> Duration {
+        self.period
+    }
+}
+
+// timeoutの期限が過ぎたことを表すエラー
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Elapsed;
+
+impl fmt::Display for Elapsed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        "deadline has elapsed".fmt(f)
+    }
+}
+
+impl Error for Elapsed {}
+
+// fを実行し、durが経過したら中で待っている処理を中断させる
+// 期限までにfが戻った場合はその値を、期限を過ぎた場合はErr(Elapsed)を返す
+// NOTE: 期限を過ぎると、sleepやチャネルのrecv、I/O待ちなどキャンセルできる待ちがErr(Cancelled)を返すので、
+//       f側でエラーを見て戻ること
+//       Mutex::lockのようなキャンセルできない待ちは中断しない
+pub fn timeout<F, T>(dur: Duration, f: F) -> Result<T, Elapsed>
+where
+    F: FnOnce() -> T,
+{
+    timeout_at(Instant::now() + dur, f)
+}
+
+// fを実行し、deadlineになったら中で待っている処理を中断させる
+// NOTE: 入れ子にした場合は、近い方の期限で中断する
+pub fn timeout_at<F, T>(deadline: Instant, f: F) -> Result<T, Elapsed>
+where
+    F: FnOnce() -> T,
+{
+    // fから戻るかパニックで抜けたら、外側の期限に戻す
+    struct Restore(Option<Instant>);
+    impl Drop for Restore {
+        fn drop(&mut self) {
+            crate::replace_deadline(self.0);
+        }
+    }
+
+    let outer = crate::replace_deadline(None);
+    let _restore = Restore(outer);
+    crate::replace_deadline(Some(outer.map_or(deadline, |outer| outer.min(deadline))));
+    let value = f();
+    if deadline <= Instant::now() {
+        return Err(Elapsed);
+    }
+    Ok(value)
+}
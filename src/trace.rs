@@ -0,0 +1,49 @@
+// スレッドの生成や切り替えなどのイベントを記録するためのフック
+// NOTE: traceフィーチャーを有効にしたときだけ使える
+use std::pin::Pin;
+use std::time::Instant;
+
+use crate::{Runtime, State};
+
+// Runtimeの中で起きたイベント
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TraceEvent {
+    // スレッドが生成された
+    // NOTE: nameはspawn_namedで付けた名前
+    Spawn { id: usize, name: Option<String> },
+    // fromのスレッドからtoのスレッドに切り替えた
+    Switch { from: usize, to: usize },
+    // スレッドがブロックした(parkも含む)
+    Block { id: usize },
+    // ブロックしていたスレッドが再開可能になった
+    Wake { id: usize },
+    // スレッドのタスクが終わった
+    Exit { id: usize },
+}
+
+pub(crate) type Tracer = Box<dyn FnMut(Instant, TraceEvent)>;
+
+impl Runtime {
+    // イベントが起きるたびに、起きた時刻とイベントを渡してfを呼ぶ
+    // NOTE: fはRuntimeの処理の途中で呼ばれるので、中でスレッドを生成したりブロックしたりしないこと
+    pub fn on_trace<F>(self: Pin<&mut Self>, f: F)
+    where
+        F: FnMut(Instant, TraceEvent) + 'static,
+    {
+        let this = unsafe { self.get_unchecked_mut() };
+        this.tracer = Some(Box::new(f));
+    }
+
+    pub(crate) fn trace(&mut self, event: TraceEvent) {
+        if let Some(tracer) = &mut self.tracer {
+            tracer(Instant::now(), event);
+        }
+    }
+
+    // 止まっていたスレッドを再開可能にする場合だけWakeを記録する
+    pub(crate) fn trace_wake(&mut self, id: usize) {
+        if matches!(self.threads[id].state, State::Blocked | State::Parked) {
+            self.trace(TraceEvent::Wake { id });
+        }
+    }
+}
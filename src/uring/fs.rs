@@ -0,0 +1,92 @@
+use std::ffi::CString;
+use std::io::{self, Read, Write};
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::io::{AsRawFd, FromRawFd, OwnedFd, RawFd};
+use std::path::Path;
+
+use super::{read, submit, write, Sqe, IORING_OP_OPENAT};
+
+const AT_FDCWD: i32 = -100;
+const O_RDONLY: u32 = 0;
+const O_WRONLY: u32 = 0o1;
+const O_CREAT: u32 = 0o100;
+const O_TRUNC: u32 = 0o1000;
+const O_CLOEXEC: u32 = 0o2000000;
+
+// io_uringで読み書きするファイル
+// NOTE: 読み書きしている間は現在のスレッドだけがブロックし、他のスレッドは動き続ける
+//       閉じるのはドロップしたときにOSスレッドで行う
+pub struct File {
+    fd: OwnedFd,
+    // Read/Writeで次に読み書きする位置
+    pos: u64,
+}
+
+impl File {
+    // 読み込み用に開く
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<File> {
+        File::open_with(path.as_ref(), O_RDONLY)
+    }
+
+    // 書き込み用に開く
+    // ファイルがなければ作り、あれば空にする
+    pub fn create<P: AsRef<Path>>(path: P) -> io::Result<File> {
+        File::open_with(path.as_ref(), O_WRONLY | O_CREAT | O_TRUNC)
+    }
+
+    fn open_with(path: &Path, flags: u32) -> io::Result<File> {
+        let path = CString::new(path.as_os_str().as_bytes())
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+        let sqe = Sqe {
+            opcode: IORING_OP_OPENAT,
+            fd: AT_FDCWD,
+            addr: path.as_ptr() as u64,
+            len: 0o666,
+            op_flags: flags | O_CLOEXEC,
+            ..Sqe::default()
+        };
+        let fd = submit(sqe, "File::open")?;
+        Ok(File {
+            fd: unsafe { OwnedFd::from_raw_fd(fd) },
+            pos: 0,
+        })
+    }
+
+    // offsetの位置から読み込む
+    // NOTE: Readで使う位置は変わらない
+    pub fn read_at(&self, buf: &mut [u8], offset: u64) -> io::Result<usize> {
+        read(self.as_raw_fd(), buf, offset, "File::read")
+    }
+
+    // offsetの位置に書き込む
+    // NOTE: Writeで使う位置は変わらない
+    pub fn write_at(&self, buf: &[u8], offset: u64) -> io::Result<usize> {
+        write(self.as_raw_fd(), buf, offset, "File::write")
+    }
+}
+
+impl Read for File {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.read_at(buf, self.pos)?;
+        self.pos += n as u64;
+        Ok(n)
+    }
+}
+
+impl Write for File {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = self.write_at(buf, self.pos)?;
+        self.pos += n as u64;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl AsRawFd for File {
+    fn as_raw_fd(&self) -> RawFd {
+        self.fd.as_raw_fd()
+    }
+}
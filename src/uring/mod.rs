@@ -0,0 +1,376 @@
+// io_uringを使ったファイルとソケットのI/O
+// NOTE: 読み書きをRuntimeが持つリングに投入してスレッドをブロックし、完了したら再開可能にする
+//       epollと違い、準備ができるのを待つのではなく処理そのものをカーネルに任せるので、
+//       ファイルのように準備という考え方がないものでも他のスレッドを止めずに待てる
+//       リングのfdはリアクターに登録し、完了が届いたらepollから起こしてもらう
+use std::collections::HashMap;
+use std::io;
+use std::os::raw::{c_int, c_long, c_uint, c_void};
+use std::os::unix::io::RawFd;
+use std::ptr;
+use std::sync::atomic::{AtomicU32, Ordering};
+
+use crate::deadlock::BlockedOn;
+use crate::reactor::Interest;
+use crate::{preempt, runtime_ptr, Runtime};
+
+pub mod fs;
+pub mod net;
+
+const SYS_IO_URING_SETUP: c_long = 425;
+const SYS_IO_URING_ENTER: c_long = 426;
+
+const IORING_OFF_SQ_RING: i64 = 0;
+const IORING_OFF_CQ_RING: i64 = 0x8000000;
+const IORING_OFF_SQES: i64 = 0x10000000;
+
+const IORING_OP_ACCEPT: u8 = 13;
+const IORING_OP_CONNECT: u8 = 16;
+const IORING_OP_OPENAT: u8 = 18;
+const IORING_OP_READ: u8 = 22;
+const IORING_OP_WRITE: u8 = 23;
+const IORING_OP_SEND: u8 = 26;
+const IORING_OP_RECV: u8 = 27;
+
+const PROT_READ: c_int = 1;
+const PROT_WRITE: c_int = 2;
+const MAP_SHARED: c_int = 0x01;
+const MAP_POPULATE: c_int = 0x8000;
+const MAP_FAILED: *mut c_void = !0 as *mut c_void;
+
+// リングの大きさ
+// NOTE: 1つのスレッドが同時に投入する処理は1つだけなので、スレッドの数より十分大きければよい
+const RING_ENTRIES: u32 = 64;
+
+// 完了を知らせるリングのfdを待っているのがスレッドではなくリングであることを表すID
+pub(crate) const URING_WAKER: usize = usize::MAX - 1;
+
+extern "C" {
+    fn syscall(num: c_long, ...) -> c_long;
+    fn mmap(
+        addr: *mut c_void,
+        len: usize,
+        prot: c_int,
+        flags: c_int,
+        fd: c_int,
+        offset: i64,
+    ) -> *mut c_void;
+    fn munmap(addr: *mut c_void, len: usize) -> c_int;
+    fn close(fd: c_int) -> c_int;
+}
+
+#[repr(C)]
+#[derive(Default)]
+struct SqringOffsets {
+    head: u32,
+    tail: u32,
+    ring_mask: u32,
+    ring_entries: u32,
+    flags: u32,
+    dropped: u32,
+    array: u32,
+    resv1: u32,
+    user_addr: u64,
+}
+
+#[repr(C)]
+#[derive(Default)]
+struct CqringOffsets {
+    head: u32,
+    tail: u32,
+    ring_mask: u32,
+    ring_entries: u32,
+    overflow: u32,
+    cqes: u32,
+    flags: u32,
+    resv1: u32,
+    user_addr: u64,
+}
+
+#[repr(C)]
+#[derive(Default)]
+struct Params {
+    sq_entries: u32,
+    cq_entries: u32,
+    flags: u32,
+    sq_thread_cpu: u32,
+    sq_thread_idle: u32,
+    features: u32,
+    wq_fd: u32,
+    resv: [u32; 3],
+    sq_off: SqringOffsets,
+    cq_off: CqringOffsets,
+}
+
+// 投入する処理(Submission Queue Entry)
+#[repr(C)]
+#[derive(Default)]
+pub(crate) struct Sqe {
+    opcode: u8,
+    flags: u8,
+    ioprio: u16,
+    fd: i32,
+    off: u64,
+    addr: u64,
+    len: u32,
+    op_flags: u32,
+    user_data: u64,
+    buf_index: u16,
+    personality: u16,
+    splice_fd_in: i32,
+    addr3: u64,
+    _pad: u64,
+}
+
+// 完了した処理(Completion Queue Entry)
+#[repr(C)]
+struct Cqe {
+    user_data: u64,
+    res: i32,
+    flags: u32,
+}
+
+// mmapした領域
+struct Mapping {
+    ptr: *mut u8,
+    len: usize,
+}
+
+impl Mapping {
+    fn new(fd: RawFd, len: usize, offset: i64) -> io::Result<Self> {
+        let ptr = unsafe {
+            mmap(
+                ptr::null_mut(),
+                len,
+                PROT_READ | PROT_WRITE,
+                MAP_SHARED | MAP_POPULATE,
+                fd,
+                offset,
+            )
+        };
+        if ptr == MAP_FAILED {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(Mapping {
+            ptr: ptr as *mut u8,
+            len,
+        })
+    }
+
+    fn at<T>(&self, offset: u32) -> *mut T {
+        unsafe { self.ptr.add(offset as usize) as *mut T }
+    }
+}
+
+impl Drop for Mapping {
+    fn drop(&mut self) {
+        unsafe {
+            munmap(self.ptr as *mut c_void, self.len);
+        }
+    }
+}
+
+pub(crate) struct Ring {
+    fd: RawFd,
+    sq: Mapping,
+    cq: Mapping,
+    sqes: Mapping,
+    params: Params,
+    // 完了した処理の結果(スレッドのID -> 結果)
+    results: HashMap<usize, i32>,
+    // 投入してまだ完了していない処理の数
+    in_flight: usize,
+    // リングのfdをリアクターに登録しているかどうか
+    armed: bool,
+}
+
+impl Ring {
+    fn new() -> io::Result<Self> {
+        let mut params = Params::default();
+        let fd = unsafe { syscall(SYS_IO_URING_SETUP, RING_ENTRIES as c_uint, &mut params) };
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let fd = fd as RawFd;
+        let mapped = (|| {
+            let sq_len = params.sq_off.array as usize + params.sq_entries as usize * 4;
+            let cq_len = params.cq_off.cqes as usize
+                + params.cq_entries as usize * std::mem::size_of::<Cqe>();
+            let sqes_len = params.sq_entries as usize * std::mem::size_of::<Sqe>();
+            Ok::<_, io::Error>((
+                Mapping::new(fd, sq_len, IORING_OFF_SQ_RING)?,
+                Mapping::new(fd, cq_len, IORING_OFF_CQ_RING)?,
+                Mapping::new(fd, sqes_len, IORING_OFF_SQES)?,
+            ))
+        })();
+        let (sq, cq, sqes) = match mapped {
+            Ok(mapped) => mapped,
+            Err(e) => {
+                unsafe { close(fd) };
+                return Err(e);
+            }
+        };
+        Ok(Ring {
+            fd,
+            sq,
+            cq,
+            sqes,
+            params,
+            results: HashMap::new(),
+            in_flight: 0,
+            armed: false,
+        })
+    }
+
+    // sqeをSubmission Queueに積んでカーネルに渡す
+    fn submit(&mut self, mut sqe: Sqe, id: usize) -> io::Result<()> {
+        let off = &self.params.sq_off;
+        unsafe {
+            let head = &*self.sq.at::<AtomicU32>(off.head);
+            let tail = &*self.sq.at::<AtomicU32>(off.tail);
+            let mask = *self.sq.at::<u32>(off.ring_mask);
+            let t = tail.load(Ordering::Relaxed);
+            // NOTE: 積むたびにio_uring_enterでカーネルに取り込ませるので、満杯になることはない
+            debug_assert!(t.wrapping_sub(head.load(Ordering::Acquire)) < self.params.sq_entries);
+            let index = t & mask;
+            sqe.user_data = id as u64;
+            self.sqes.at::<Sqe>(0).add(index as usize).write(sqe);
+            *self.sq.at::<u32>(off.array).add(index as usize) = index;
+            tail.store(t.wrapping_add(1), Ordering::Release);
+            if syscall(
+                SYS_IO_URING_ENTER,
+                self.fd,
+                1 as c_uint,
+                0 as c_uint,
+                0 as c_uint,
+                0usize,
+                0usize,
+            ) < 0
+            {
+                // 取り込まれなかったので積んだものを取り消す
+                tail.store(t, Ordering::Release);
+                return Err(io::Error::last_os_error());
+            }
+        }
+        self.in_flight += 1;
+        Ok(())
+    }
+
+    // Completion Queueから完了した処理を取り出し、投入したスレッドのIDを返す
+    fn reap(&mut self) -> Vec<usize> {
+        let off = &self.params.cq_off;
+        let mut completed = Vec::new();
+        unsafe {
+            let head = &*self.cq.at::<AtomicU32>(off.head);
+            let tail = &*self.cq.at::<AtomicU32>(off.tail);
+            let mask = *self.cq.at::<u32>(off.ring_mask);
+            let mut h = head.load(Ordering::Relaxed);
+            while h != tail.load(Ordering::Acquire) {
+                let cqe = &*self.cq.at::<Cqe>(off.cqes).add((h & mask) as usize);
+                let id = cqe.user_data as usize;
+                self.results.insert(id, cqe.res);
+                completed.push(id);
+                h = h.wrapping_add(1);
+            }
+            head.store(h, Ordering::Release);
+        }
+        self.in_flight -= completed.len();
+        completed
+    }
+}
+
+impl Drop for Ring {
+    fn drop(&mut self) {
+        // NOTE: 完了していない処理があれば、リングを閉じたときにカーネルが取り消す
+        unsafe {
+            close(self.fd);
+        }
+    }
+}
+
+impl Runtime {
+    // sqeを投入し、完了するまで現在のスレッドをブロックして結果を返す
+    // NOTE: カーネルが読み書きするバッファはスレッドのスタックなどにあるので、
+    //       完了する前に戻ってはいけない(キャンセルでは起こさない)
+    unsafe fn t_submit(rt: *mut Runtime, sqe: Sqe, what: &'static str) -> io::Result<i32> {
+        let _guard = preempt::disable();
+        let current = (*rt).current;
+        {
+            let rt = &mut *rt;
+            if rt.uring.is_none() {
+                rt.uring = Some(Ring::new()?);
+            }
+            let ring = rt.uring.as_mut().unwrap();
+            ring.submit(sqe, current)?;
+            if !ring.armed {
+                rt.reactor
+                    .register(ring.fd, Interest::Readable, URING_WAKER)?;
+                ring.armed = true;
+            }
+        }
+        loop {
+            if let Some(res) = (*rt).uring.as_mut().unwrap().results.remove(&current) {
+                if res < 0 {
+                    return Err(io::Error::from_raw_os_error(-res));
+                }
+                return Ok(res);
+            }
+            crate::block_thread(BlockedOn {
+                what,
+                addr: 0,
+                holder: None,
+            });
+        }
+    }
+
+    // 完了した処理を投入したスレッドを再開可能にする
+    // NOTE: リアクターがリングのfdの準備ができたと返したときに呼ぶ
+    pub(crate) fn reap_uring(&mut self) {
+        let ring = match &mut self.uring {
+            Some(ring) => ring,
+            None => return,
+        };
+        ring.armed = false;
+        let completed = ring.reap();
+        // まだ完了していない処理があれば、また起こされるように登録し直す
+        if ring.in_flight > 0 {
+            let fd = ring.fd;
+            self.reactor
+                .register(fd, Interest::Readable, URING_WAKER)
+                .expect("failed to register io_uring.");
+            self.uring.as_mut().unwrap().armed = true;
+        }
+        for id in completed {
+            self.t_wake(id);
+        }
+    }
+}
+
+// sqeを投入し、完了するまで現在のスレッドをブロックして結果を返す
+fn submit(sqe: Sqe, what: &'static str) -> io::Result<i32> {
+    unsafe { Runtime::t_submit(runtime_ptr(), sqe, what) }
+}
+
+fn read(fd: RawFd, buf: &mut [u8], offset: u64, what: &'static str) -> io::Result<usize> {
+    let sqe = Sqe {
+        opcode: IORING_OP_READ,
+        fd,
+        off: offset,
+        addr: buf.as_mut_ptr() as u64,
+        len: buf.len().min(u32::MAX as usize) as u32,
+        ..Sqe::default()
+    };
+    submit(sqe, what).map(|n| n as usize)
+}
+
+fn write(fd: RawFd, buf: &[u8], offset: u64, what: &'static str) -> io::Result<usize> {
+    let sqe = Sqe {
+        opcode: IORING_OP_WRITE,
+        fd,
+        off: offset,
+        addr: buf.as_ptr() as u64,
+        len: buf.len().min(u32::MAX as usize) as u32,
+        ..Sqe::default()
+    };
+    submit(sqe, what).map(|n| n as usize)
+}
@@ -0,0 +1,212 @@
+use std::io::{self, Read, Write};
+use std::net::{self, Shutdown, SocketAddr, ToSocketAddrs};
+use std::os::raw::c_int;
+use std::os::unix::io::{AsRawFd, FromRawFd, RawFd};
+
+use super::{submit, Sqe, IORING_OP_ACCEPT, IORING_OP_CONNECT, IORING_OP_RECV, IORING_OP_SEND};
+
+const AF_INET: c_int = 2;
+const AF_INET6: c_int = 10;
+const SOCK_STREAM: c_int = 1;
+const SOCK_CLOEXEC: c_int = 0o2000000;
+const MSG_NOSIGNAL: u32 = 0x4000;
+
+extern "C" {
+    fn socket(domain: c_int, ty: c_int, protocol: c_int) -> c_int;
+}
+
+#[repr(C)]
+struct SockaddrIn {
+    sin_family: u16,
+    sin_port: u16,
+    sin_addr: [u8; 4],
+    sin_zero: [u8; 8],
+}
+
+#[repr(C)]
+struct SockaddrIn6 {
+    sin6_family: u16,
+    sin6_port: u16,
+    sin6_flowinfo: u32,
+    sin6_addr: [u8; 16],
+    sin6_scope_id: u32,
+}
+
+// io_uringで送受信するTCPのリスナー
+pub struct TcpListener {
+    inner: net::TcpListener,
+}
+
+impl TcpListener {
+    pub fn bind<A: ToSocketAddrs>(addr: A) -> io::Result<TcpListener> {
+        Ok(TcpListener {
+            inner: net::TcpListener::bind(addr)?,
+        })
+    }
+
+    // 接続を受け付ける
+    // 接続要求が来ていない場合は来るまで現在のスレッドをブロックする
+    pub fn accept(&self) -> io::Result<(TcpStream, SocketAddr)> {
+        let sqe = Sqe {
+            opcode: IORING_OP_ACCEPT,
+            fd: self.as_raw_fd(),
+            op_flags: SOCK_CLOEXEC as u32,
+            ..Sqe::default()
+        };
+        let fd = submit(sqe, "TcpListener::accept")?;
+        let inner = unsafe { net::TcpStream::from_raw_fd(fd) };
+        let addr = inner.peer_addr()?;
+        Ok((TcpStream { inner }, addr))
+    }
+
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.inner.local_addr()
+    }
+}
+
+impl AsRawFd for TcpListener {
+    fn as_raw_fd(&self) -> RawFd {
+        self.inner.as_raw_fd()
+    }
+}
+
+// io_uringで送受信するTCPのストリーム
+pub struct TcpStream {
+    inner: net::TcpStream,
+}
+
+impl TcpStream {
+    // 接続する
+    // 接続が確立するまで現在のスレッドだけをブロックする
+    pub fn connect<A: ToSocketAddrs>(addr: A) -> io::Result<TcpStream> {
+        let mut last_err = None;
+        for addr in addr.to_socket_addrs()? {
+            match TcpStream::connect_addr(&addr) {
+                Ok(stream) => return Ok(stream),
+                Err(e) => last_err = Some(e),
+            }
+        }
+        Err(last_err.unwrap_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "could not resolve to any addresses",
+            )
+        }))
+    }
+
+    fn connect_addr(addr: &SocketAddr) -> io::Result<TcpStream> {
+        let domain = match addr {
+            SocketAddr::V4(_) => AF_INET,
+            SocketAddr::V6(_) => AF_INET6,
+        };
+        let fd = unsafe { socket(domain, SOCK_STREAM | SOCK_CLOEXEC, 0) };
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        // NOTE: 失敗したときにfdを閉じるように先に包んでおく
+        let inner = unsafe { net::TcpStream::from_raw_fd(fd) };
+        // NOTE: カーネルが読むのは完了するまでなので、submitから戻るまで生きていればよい
+        let (v4, v6);
+        let (ptr, len) = match addr {
+            SocketAddr::V4(a) => {
+                v4 = SockaddrIn {
+                    sin_family: AF_INET as u16,
+                    sin_port: a.port().to_be(),
+                    sin_addr: a.ip().octets(),
+                    sin_zero: [0; 8],
+                };
+                (&v4 as *const _ as u64, std::mem::size_of::<SockaddrIn>())
+            }
+            SocketAddr::V6(a) => {
+                v6 = SockaddrIn6 {
+                    sin6_family: AF_INET6 as u16,
+                    sin6_port: a.port().to_be(),
+                    sin6_flowinfo: a.flowinfo(),
+                    sin6_addr: a.ip().octets(),
+                    sin6_scope_id: a.scope_id(),
+                };
+                (&v6 as *const _ as u64, std::mem::size_of::<SockaddrIn6>())
+            }
+        };
+        let sqe = Sqe {
+            opcode: IORING_OP_CONNECT,
+            fd,
+            addr: ptr,
+            off: len as u64,
+            ..Sqe::default()
+        };
+        submit(sqe, "TcpStream::connect")?;
+        Ok(TcpStream { inner })
+    }
+
+    pub fn peer_addr(&self) -> io::Result<SocketAddr> {
+        self.inner.peer_addr()
+    }
+
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.inner.local_addr()
+    }
+
+    pub fn shutdown(&self, how: Shutdown) -> io::Result<()> {
+        self.inner.shutdown(how)
+    }
+
+    pub fn set_nodelay(&self, nodelay: bool) -> io::Result<()> {
+        self.inner.set_nodelay(nodelay)
+    }
+}
+
+// 読み込めるデータがない場合は届くまで現在のスレッドをブロックする
+impl Read for &TcpStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let sqe = Sqe {
+            opcode: IORING_OP_RECV,
+            fd: self.as_raw_fd(),
+            addr: buf.as_mut_ptr() as u64,
+            len: buf.len().min(u32::MAX as usize) as u32,
+            ..Sqe::default()
+        };
+        submit(sqe, "TcpStream::read").map(|n| n as usize)
+    }
+}
+
+// 送信バッファが満杯の場合は空きができるまで現在のスレッドをブロックする
+impl Write for &TcpStream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let sqe = Sqe {
+            opcode: IORING_OP_SEND,
+            fd: self.as_raw_fd(),
+            addr: buf.as_ptr() as u64,
+            len: buf.len().min(u32::MAX as usize) as u32,
+            op_flags: MSG_NOSIGNAL,
+            ..Sqe::default()
+        };
+        submit(sqe, "TcpStream::write").map(|n| n as usize)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Read for TcpStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        (&*self).read(buf)
+    }
+}
+
+impl Write for TcpStream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        (&*self).write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl AsRawFd for TcpStream {
+    fn as_raw_fd(&self) -> RawFd {
+        self.inner.as_raw_fd()
+    }
+}
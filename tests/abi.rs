@@ -0,0 +1,90 @@
+// グリーンスレッドからSSEの16byte境界を前提にするCの関数を呼んでも落ちず、スタックの形がABIどおりかを確かめる
+#![cfg(feature = "std")]
+
+use std::os::raw::{c_char, c_int};
+
+use greenthreads::{current, debug_verify_stack, yield_thread, Runtime};
+
+extern "C" {
+    fn snprintf(buf: *mut c_char, len: usize, fmt: *const c_char, ...) -> c_int;
+}
+
+// 16byte境界に揃える必要がある値
+#[repr(align(16))]
+struct Aligned([f64; 2]);
+
+// 可変長引数にdoubleを渡すと、glibcのsnprintfはxmmレジスタをmovapsでスタックに退避するので、
+// スタックポインタが16byte境界からずれていると落ちる
+fn format_float(value: f64) -> String {
+    let mut buf = [0u8; 32];
+    let len = unsafe {
+        snprintf(
+            buf.as_mut_ptr() as *mut c_char,
+            buf.len(),
+            c"%.3f".as_ptr(),
+            value,
+        )
+    };
+    String::from_utf8_lossy(&buf[..len as usize]).into_owned()
+}
+
+// スタック上の値が16byte境界に揃っているか
+#[inline(never)]
+extern "C" fn aligned_local(value: f64) -> bool {
+    let local = std::hint::black_box(Aligned([value, value]));
+    (&local as *const Aligned as usize).is_multiple_of(16) && local.0[1] == value
+}
+
+#[test]
+fn sse_aligned_ffi_call_from_task() {
+    let mut runtime = Runtime::builder().strict_abi(true).build();
+    let mut handles = Vec::new();
+    for i in 0..4 {
+        let handle = runtime
+            .as_mut()
+            .spawn(move || {
+                let value = i as f64 * 1.5;
+                assert_eq!(debug_verify_stack(current().thread_id()), Ok(()));
+                assert_eq!(format_float(value), format!("{:.3}", value));
+                assert!(aligned_local(value));
+                // 一度切り替えてから戻った後も揃っている
+                yield_thread();
+                assert_eq!(format_float(value), format!("{:.3}", value));
+                assert!(aligned_local(value));
+                assert_eq!(debug_verify_stack(current().thread_id()), Ok(()));
+            })
+            .unwrap();
+        handles.push(handle);
+    }
+    runtime.as_mut().run();
+    for handle in handles {
+        handle.join().unwrap();
+    }
+}
+
+#[test]
+fn verify_suspended_and_finished_threads() {
+    let mut runtime = Runtime::builder().strict_abi(true).build();
+    let worker = runtime
+        .as_mut()
+        .spawn(|| {
+            format_float(0.25);
+            yield_thread();
+            format_float(0.5);
+        })
+        .unwrap();
+    let id = worker.thread().thread_id();
+    let checker = runtime
+        .as_mut()
+        .spawn(move || {
+            // workerはyieldして止まっている
+            assert_eq!(debug_verify_stack(id), Ok(()));
+            yield_thread();
+            // workerは終わっている
+            assert!(debug_verify_stack(id).is_err());
+        })
+        .unwrap();
+    runtime.as_mut().run();
+    worker.join().unwrap();
+    checker.join().unwrap();
+}
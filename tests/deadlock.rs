@@ -0,0 +1,67 @@
+// デッドロックしたときに、runがパニックせずに報告してから戻るかを確かめる
+#![cfg(feature = "std")]
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use greenthreads::sync::Mutex;
+use greenthreads::{park, yield_thread, PinnedRuntime, Runtime};
+
+// 2つのスレッドが逆の順番でロックを取る
+fn spawn_lock_cycle(runtime: &mut PinnedRuntime) {
+    let a = Rc::new(Mutex::new(()));
+    let b = Rc::new(Mutex::new(()));
+    let (a1, b1) = (a.clone(), b.clone());
+    runtime
+        .as_mut()
+        .spawn(move || {
+            let _a = a1.lock();
+            yield_thread();
+            let _b = b1.lock();
+        })
+        .unwrap();
+    runtime
+        .as_mut()
+        .spawn(move || {
+            let _b = b.lock();
+            yield_thread();
+            let _a = a.lock();
+        })
+        .unwrap();
+}
+
+#[test]
+fn run_returns_and_reports_deadlock() {
+    let reports = Rc::new(RefCell::new(Vec::new()));
+    let seen = reports.clone();
+    let mut runtime = Runtime::builder()
+        .on_deadlock(move |deadlock| seen.borrow_mut().push(deadlock.report().to_string()))
+        .build();
+    spawn_lock_cycle(&mut runtime);
+    runtime.as_mut().run();
+    let reports = reports.borrow();
+    assert_eq!(reports.len(), 1);
+    assert!(reports[0].contains("blocked on Mutex::lock"));
+    assert!(reports[0].contains("cycle: thread 1 -> thread 2 -> thread 1"));
+}
+
+#[test]
+fn runtime_is_usable_after_deadlock() {
+    let mut runtime = Runtime::builder().on_deadlock(|_| {}).build();
+    let parked = runtime.as_mut().spawn(park).unwrap();
+    runtime.as_mut().run();
+    // 止まったままのスレッドを終わらせてから、続けて使える
+    runtime.as_mut().shutdown();
+    assert!(parked.join().is_err());
+    let handle = runtime.as_mut().spawn(|| 42).unwrap();
+    runtime.as_mut().run();
+    assert_eq!(handle.join().unwrap(), 42);
+}
+
+#[test]
+#[should_panic(expected = "deadlock: no thread is ready to run.")]
+fn panic_on_deadlock() {
+    let mut runtime = Runtime::builder().panic_on_deadlock(true).build();
+    spawn_lock_cycle(&mut runtime);
+    runtime.as_mut().run();
+}
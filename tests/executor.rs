@@ -0,0 +1,74 @@
+// 別のOSスレッドからwakeされたFutureが、ランタイムのOSスレッドで再開されるかを確かめる
+#![cfg(feature = "std")]
+
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Waker};
+use std::thread;
+use std::time::Duration;
+
+use greenthreads::Runtime;
+
+// 別のOSスレッドがdoneを立ててwakeするまでPendingを返すFuture
+#[derive(Default)]
+struct Shared {
+    done: bool,
+    waker: Option<Waker>,
+}
+
+struct WaitForeign(Arc<Mutex<Shared>>);
+
+impl Future for WaitForeign {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        let mut shared = self.0.lock().unwrap();
+        if shared.done {
+            return Poll::Ready(());
+        }
+        shared.waker = Some(cx.waker().clone());
+        Poll::Pending
+    }
+}
+
+// wakerが登録されるのを待ってから、別のOSスレッドでdoneを立ててwakeする
+fn wake_from_foreign_thread(shared: Arc<Mutex<Shared>>) -> thread::JoinHandle<()> {
+    thread::spawn(move || loop {
+        thread::sleep(Duration::from_millis(10));
+        let waker = {
+            let mut shared = shared.lock().unwrap();
+            match shared.waker.take() {
+                Some(waker) => {
+                    shared.done = true;
+                    waker
+                }
+                None => continue,
+            }
+        };
+        waker.wake();
+        return;
+    })
+}
+
+// 他に実行するスレッドがなくても、デッドロックとせずに別のOSスレッドからのwakeを待つ
+#[test]
+fn block_on_is_woken_from_a_foreign_thread() {
+    let mut runtime = Runtime::new();
+    let shared = Arc::new(Mutex::new(Shared::default()));
+    let waker = wake_from_foreign_thread(shared.clone());
+    runtime.as_mut().block_on(WaitForeign(shared));
+    waker.join().unwrap();
+}
+
+// runも、Futureが別のOSスレッドから起こされて完了した後に戻る
+#[test]
+fn run_returns_after_a_foreign_wake() {
+    let mut runtime = Runtime::new();
+    let shared = Arc::new(Mutex::new(Shared::default()));
+    let waker = wake_from_foreign_thread(shared.clone());
+    let handle = runtime.as_mut().spawn_async(WaitForeign(shared)).unwrap();
+    runtime.as_mut().run();
+    assert!(handle.is_finished());
+    waker.join().unwrap();
+}
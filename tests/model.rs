@@ -0,0 +1,47 @@
+// モデル検査で、切り替えの順番をすべて試して競合を見つけられるかを確かめる
+// RUSTFLAGS="--cfg greenthreads_model" cargo test --test model
+#![cfg(all(feature = "std", greenthreads_model))]
+
+use std::rc::Rc;
+
+use greenthreads::model::model;
+use greenthreads::sync::Mutex;
+use greenthreads::PinnedRuntime;
+
+// 2つのスレッドでcounterを1ずつ増やし、両方が終わってから2になっているかを確かめる
+fn check_two_increments<F>(increment: F) -> usize
+where
+    F: Fn(&Mutex<i32>) + Copy + 'static,
+{
+    model(move |runtime: &mut PinnedRuntime| {
+        let counter = Rc::new(Mutex::new(0));
+        let handles: Vec<_> = (0..2)
+            .map(|_| {
+                let counter = counter.clone();
+                runtime.as_mut().spawn(move || increment(&counter)).unwrap()
+            })
+            .collect();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+        assert_eq!(*counter.lock(), 2, "lost update");
+    })
+}
+
+// 1回のロックの中で読んで書けば、どの順番で切り替わっても結果は2になる
+#[test]
+fn atomic_increment() {
+    let iterations = check_two_increments(|counter| *counter.lock() += 1);
+    // NOTE: ロックの前の切り替えで順番が分かれるので、1通りではない
+    assert!(iterations > 1);
+}
+
+// 読むロックと書くロックを分けると、間に他のスレッドが割り込む順番で更新が失われる
+#[test]
+#[should_panic(expected = "lost update")]
+fn split_increment_loses_update() {
+    check_two_increments(|counter| {
+        let value = *counter.lock();
+        *counter.lock() = value + 1;
+    });
+}
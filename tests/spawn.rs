@@ -0,0 +1,62 @@
+// スレッドの中から、利用可能なスレッドが空くのを待って生成できるかを確かめる
+#![cfg(feature = "std")]
+
+use std::cell::Cell;
+use std::rc::Rc;
+
+use greenthreads::{spawner, yield_thread, Runtime, TaskGroup};
+
+// 利用可能なスレッドより多く生成し、空くのを待つ間に他のスレッドに切り替わっても正しく生成できる
+#[test]
+fn spawn_waits_for_a_free_thread_inside_a_task() {
+    let mut runtime = Runtime::builder().max_threads(2).build();
+    let count = Rc::new(Cell::new(0));
+    let counted = count.clone();
+    let parent = runtime
+        .as_mut()
+        .spawn(move || {
+            let mut group = TaskGroup::new();
+            for _ in 0..3 {
+                let counted = counted.clone();
+                group
+                    .spawn(move || {
+                        yield_thread();
+                        counted.set(counted.get() + 1);
+                        Ok::<(), ()>(())
+                    })
+                    .unwrap();
+            }
+            for _ in 0..3 {
+                let counted = counted.clone();
+                spawner()
+                    .spawn(async move {
+                        counted.set(counted.get() + 1);
+                    })
+                    .unwrap();
+            }
+            group.join().unwrap();
+        })
+        .unwrap();
+    runtime.as_mut().run();
+    parent.join().unwrap();
+    assert_eq!(count.get(), 6);
+}
+
+// scopeから戻った後は、呼び出す前のRuntimeが現在のRuntimeに戻る
+#[test]
+fn scope_restores_the_current_runtime() {
+    let mut outer = Runtime::new();
+    outer.as_mut().init();
+    {
+        let mut inner = Runtime::new();
+        inner.as_mut().scope(|s| {
+            s.spawn(yield_thread);
+        });
+    }
+    let ran = Rc::new(Cell::new(false));
+    let flag = ran.clone();
+    // NOTE: spawnerは現在のRuntimeに生成するので、innerを指したままならドロップしたRuntimeに生成してしまう
+    spawner().spawn(async move { flag.set(true) }).unwrap();
+    outer.as_mut().run();
+    assert!(ran.get());
+}